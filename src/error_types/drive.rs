@@ -15,7 +15,9 @@ pub enum DriveError {
     #[error("The operation failed for non-critical reasons, but no corruption occurred, and the operation can be retried with the same arguments.")]
     Retry,
     #[error("An operation on this disk is taking too long..")]
-    TakingTooLong
+    TakingTooLong,
+    #[error("The pool is out of room, and isn't allowed to add any more disks.")]
+    NoSpace
 }
 
 #[derive(Debug, Clone, Copy, Error, PartialEq)]
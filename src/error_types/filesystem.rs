@@ -30,9 +30,21 @@ pub(in super::super) const IS_A_DIRECTORY: c_int = libc::EISDIR;
 // /// Function not implemented.
 // pub(in super::super) const UNIMPLEMENTED: c_int = libc::ENOSYS;
 /// This operation is not supported in this filesystem.
-// pub(in super::super) const UNSUPPORTED: c_int = libc::ENOTSUP; 
+pub(in super::super) const UNSUPPORTED: c_int = libc::ENOTSUP;
+/// That's not a floppy we can read.
+pub(in super::super) const WRONG_KIND_OF_DISK: c_int = libc::EMEDIUMTYPE;
+/// You can look, but you can't touch.
+pub(in super::super) const NOT_PERMITTED: c_int = libc::EPERM;
+/// Frozen in time.
+pub(in super::super) const READ_ONLY: c_int = libc::EROFS;
+/// No room at the inn.
+pub(in super::super) const NO_SPACE: c_int = libc::ENOSPC;
 /// Access denied / files does not exist.
 pub(in super::super) const NO_SUCH_ITEM: c_int = libc::ENOENT;
+/// Asked for an extended attribute that isn't there.
+pub(in super::super) const NO_SUCH_ATTRIBUTE: c_int = libc::ENODATA;
+/// The buffer you gave us is too small.
+pub(in super::super) const OUT_OF_RANGE: c_int = libc::ERANGE;
 /// Tried to seek to an invalid file position.
 // pub(in super::super) const INVALID_SEEK: c_int = libc::ESPIPE;
 /// Tried to use a filehandle that is stale. New one is required.
@@ -59,6 +71,7 @@ impl From<DriveError> for c_int {
             },
             DriveError::Retry => TRY_AGAIN,
             DriveError::TakingTooLong => BUSY,
+            DriveError::NoSpace => NO_SPACE,
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,354 @@
+// Knobs and dials.
+
+use std::{
+    ffi::OsStr,
+    fmt::Write,
+    path::Path,
+    time::SystemTime
+};
+
+use fuse_mt::{
+    DirectoryEntry,
+    FileAttr,
+    FileType
+};
+use libc::c_int;
+use log::{debug, info};
+
+use crate::{
+    error_types::filesystem::*,
+    filesystem::drive_scheduler::drive_scheduler_struct::DriveScheduler,
+    pool::{
+        disk::{
+            generic::{
+                generic_structs::pointer_struct::DiskPointer,
+                io::cache::cache_io::CachedBlockIO
+            },
+            standard_disk::block::header::header_struct::{
+                StandardDiskHeader,
+                StandardHeaderFlags
+            }
+        },
+        pool_actions::pool_struct::{
+            Pool,
+            GLOBAL_POOL
+        }
+    },
+    pool::manifest::{
+        manifest_methods::count_used,
+        manifest_struct::PoolManifest
+    },
+    pool::fat12::fat12_struct::{
+        ImportReport,
+        Imported
+    },
+    pool::scrub::scrub_struct::ScrubReport,
+    pool::trash::trash_struct::{
+        Restored,
+        Trash
+    },
+    tui::notify::NotifyTui
+};
+
+use super::control_dir_struct::{
+    ControlItem,
+    CONTROL_DIR_NAME,
+    CONTROL_FILES,
+    FOPEN_DIRECT_IO
+};
+
+impl ControlItem {
+    /// Is this path the control directory, or something inside of it?
+    pub(crate) fn is_control_path(path: &Path) -> bool {
+        path.starts_with(Path::new("/").join(CONTROL_DIR_NAME))
+    }
+
+    /// Work out what a path in the control directory points at.
+    ///
+    /// Returns NO_SUCH_ITEM for names that aren't in there.
+    pub(crate) fn from_path(path: &Path) -> Result<ControlItem, c_int> {
+        let inner = path
+            .strip_prefix(Path::new("/").join(CONTROL_DIR_NAME))
+            .map_err(|_| NO_SUCH_ITEM)?;
+        if inner.as_os_str().is_empty() {
+            return Ok(ControlItem::Root);
+        }
+        CONTROL_FILES
+            .into_iter()
+            .find(|item| OsStr::new(item.name()) == inner.as_os_str())
+            .ok_or(NO_SUCH_ITEM)
+    }
+
+    /// Check that something new can be made at this path.
+    ///
+    /// Nothing can be made in the control directory, and the control directory can't be made on disk.
+    pub(crate) fn check_creatable(path: &Path) -> Result<(), c_int> {
+        if !Self::is_control_path(path) {
+            return Ok(());
+        }
+        match Self::from_path(path) {
+            Ok(_) => Err(ITEM_ALREADY_EXISTS),
+            Err(_) => Err(NOT_PERMITTED),
+        }
+    }
+
+    /// The name of this item.
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            ControlItem::Root => CONTROL_DIR_NAME,
+            ControlItem::Stats => "stats",
+            ControlItem::Cache => "cache",
+            ControlItem::Pool => "pool",
+            ControlItem::Disks => "disks",
+            ControlItem::Manifest => "manifest",
+            ControlItem::Flush => "flush",
+            ControlItem::Trash => "trash",
+            ControlItem::Restore => "restore",
+            ControlItem::Scrub => "scrub",
+            ControlItem::Import => "import",
+        }
+    }
+
+    /// Attributes to hand back to the kernel.
+    ///
+    /// Sizes are always zero, since the contents are made when they are read.
+    pub(crate) fn attributes(&self) -> FileAttr {
+        let (kind, perm) = match self {
+            ControlItem::Root => (FileType::Directory, 0o555),
+            ControlItem::Flush | ControlItem::Restore => (FileType::RegularFile, 0o222),
+            ControlItem::Scrub | ControlItem::Import => (FileType::RegularFile, 0o666),
+            _ => (FileType::RegularFile, 0o444),
+        };
+        let now = SystemTime::now();
+        FileAttr {
+            size: 0,
+            blocks: 0,
+            atime: now,
+            mtime: now,
+            ctime: now,
+            crtime: SystemTime::UNIX_EPOCH,
+            kind,
+            perm,
+            nlink: 2,
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+            flags: 0,
+        }
+    }
+
+    /// Flags to open this item with.
+    pub(crate) fn open_flags(&self) -> u32 {
+        match self {
+            ControlItem::Root => 0,
+            _ => FOPEN_DIRECT_IO,
+        }
+    }
+
+    /// Everything in the control directory.
+    pub(crate) fn list(&self) -> Result<Vec<DirectoryEntry>, c_int> {
+        if *self != ControlItem::Root {
+            return Err(NOT_A_DIRECTORY);
+        }
+        let mut entries: Vec<DirectoryEntry> = CONTROL_FILES
+            .iter()
+            .map(|item| DirectoryEntry {
+                name: item.name().into(),
+                kind: FileType::RegularFile,
+            })
+            .collect();
+        entries.push(DirectoryEntry {
+            name: OsStr::new(".").into(),
+            kind: FileType::Directory,
+        });
+        Ok(entries)
+    }
+
+    /// Read part of a control file, made fresh every time.
+    pub(crate) fn read(&self, offset: u64, size: u32) -> Result<Vec<u8>, c_int> {
+        let contents: String = match self {
+            ControlItem::Root => return Err(IS_A_DIRECTORY),
+            ControlItem::Stats => stats_report(),
+            ControlItem::Cache => cache_report(),
+            ControlItem::Pool => pool_report(),
+            ControlItem::Disks => disks_report(),
+            ControlItem::Manifest => PoolManifest::build()?.report(),
+            ControlItem::Trash => trash_report()?,
+            ControlItem::Scrub => match ScrubReport::last() {
+                Some(last) => last.report(),
+                None => String::from("No scrub has been run since mounting.\n"),
+            },
+            ControlItem::Import => match ImportReport::last() {
+                Some(last) => last.report(),
+                None => String::from("No disk has been imported since mounting.\n"),
+            },
+            ControlItem::Flush | ControlItem::Restore => String::new(),
+        };
+        let bytes = contents.into_bytes();
+        let start = (offset as usize).min(bytes.len());
+        let end = start.saturating_add(size as usize).min(bytes.len());
+        Ok(bytes[start..end].to_vec())
+    }
+
+    /// Write to a control file, which does whatever that file does.
+    ///
+    /// What was written only matters for `restore`, which wants a trash entry number, and `import`, which
+    /// wants a directory. Everything else just cares that something was. Writing to `scrub` or `import`
+    /// doesn't return until it's done.
+    pub(crate) fn write(&self, data: &[u8]) -> Result<u32, c_int> {
+        match self {
+            ControlItem::Root => Err(IS_A_DIRECTORY),
+            ControlItem::Flush => {
+                info!("Flush requested through the control directory.");
+                CachedBlockIO::flush()?;
+                Pool::flush()?;
+                debug!("Done.");
+                Ok(data.len() as u32)
+            },
+            ControlItem::Restore => {
+                let id: u64 = std::str::from_utf8(data)
+                    .ok()
+                    .and_then(|text| text.trim().parse().ok())
+                    .ok_or(INVALID_ARGUMENT)?;
+                info!("Restore of trash entry {id} requested through the control directory.");
+                match Trash::restore(id)? {
+                    Restored::Done(_) => Ok(data.len() as u32),
+                    Restored::NotInTrash => Err(NO_SUCH_ITEM),
+                    Restored::PathTaken => Err(ITEM_ALREADY_EXISTS),
+                }
+            },
+            ControlItem::Scrub => {
+                info!("Scrub requested through the control directory.");
+                let report = ScrubReport::run()?;
+                debug!("Done.\n{}", report.report());
+                Ok(data.len() as u32)
+            },
+            ControlItem::Import => {
+                let text: &str = std::str::from_utf8(data).map_err(|_| INVALID_ARGUMENT)?.trim();
+                let (join, destination) = match text.strip_prefix("grow ") {
+                    Some(rest) => (true, Path::new(rest.trim_start())),
+                    None => (false, Path::new(text)),
+                };
+                if !destination.is_absolute() || Self::is_control_path(destination) {
+                    return Err(INVALID_ARGUMENT);
+                }
+                info!("Import into `{}` requested through the control directory.", destination.display());
+                match ImportReport::run(destination, join)? {
+                    Imported::Done(report) => {
+                        debug!("Done.\n{}", report.report());
+                        Ok(data.len() as u32)
+                    },
+                    Imported::NoDestination => Err(NO_SUCH_ITEM),
+                    Imported::NotFat12 => Err(WRONG_KIND_OF_DISK),
+                }
+            },
+            _ => Err(NOT_PERMITTED),
+        }
+    }
+
+    /// Truncating only makes sense on files that can be written, and does nothing.
+    ///
+    /// Shells truncate before writing with `>`, so this has to work on those.
+    pub(crate) fn truncate(&self) -> Result<(), c_int> {
+        match self {
+            ControlItem::Root => Err(IS_A_DIRECTORY),
+            ControlItem::Flush | ControlItem::Restore | ControlItem::Scrub | ControlItem::Import => Ok(()),
+            _ => Err(NOT_PERMITTED),
+        }
+    }
+}
+
+fn stats_report() -> String {
+    let mut report = NotifyTui::report();
+    let _ = writeln!(report, "swaps_avoided_by_scheduling: {}", DriveScheduler::swaps_avoided());
+    report
+}
+
+/// One line per thing in the trash, oldest first, `number deleted_at original_path`.
+///
+/// Deletion times are in seconds since the epoch.
+fn trash_report() -> Result<String, c_int> {
+    let mut report = String::from("number deleted_at original_path\n");
+    for entry in Trash::list()? {
+        let _ = writeln!(report, "{} {} {}", entry.id, entry.deleted.seconds, entry.original.display());
+    }
+    Ok(report)
+}
+
+fn cache_report() -> String {
+    format!(
+        "hit_rate: {:.4}\npressure: {:.4}\n",
+        CachedBlockIO::hit_rate(),
+        CachedBlockIO::pressure()
+    )
+}
+
+fn pool_report() -> String {
+    let pool = GLOBAL_POOL
+        .get()
+        .expect("Pool must exist to report on it.")
+        .lock()
+        .expect("Other mutex holders should not panic.");
+    let header = &pool.header;
+    format!(
+        "highest_known_disk: {}\n\
+        disk_with_next_free_block: {}\n\
+        pool_standard_blocks_free: {}\n\
+        dense_disks: {}\n\
+        dedup_index_blocks: {}\n\
+        snapshots: {}\n\
+        files: {}\n\
+        directories: {}\n",
+        header.highest_known_disk,
+        header.disk_with_next_free_block,
+        header.pool_standard_blocks_free,
+        header.dense_disks.iter().filter(|disk| **disk != 0).count(),
+        header.dedup_index_blocks,
+        header.snapshots,
+        header.items.files,
+        header.items.directories,
+    )
+}
+
+/// One line per disk, `disk kind used free`.
+///
+/// Only disks with their header in the cache are counted, since going to the drive for every
+/// disk would mean swapping through all of them. The rest are listed with `?`s.
+fn disks_report() -> String {
+    let (highest, pool_used) = {
+        let pool = GLOBAL_POOL
+            .get()
+            .expect("Pool must exist to report on it.")
+            .lock()
+            .expect("Other mutex holders should not panic.");
+        (pool.header.highest_known_disk, count_used(&pool.header.block_usage_map))
+    };
+
+    let mut report = String::from("disk kind used free\n");
+    let _ = writeln!(report, "0 pool {pool_used} {}", 2880 - pool_used);
+    for disk in 1..=highest {
+        if Pool::is_dense_disk(disk) {
+            let _ = writeln!(report, "{disk} dense 2880 0");
+            continue;
+        }
+        let header_pointer = DiskPointer {
+            disk,
+            block: 0,
+        };
+        let header: Option<StandardDiskHeader> = if CachedBlockIO::is_block_cached(header_pointer) {
+            CachedBlockIO::read_block(header_pointer).ok().map(|block| StandardDiskHeader::from_block(&block))
+        } else {
+            None
+        };
+        match header {
+            Some(header) if header.flags.contains(StandardHeaderFlags::Marker) => {
+                let used = count_used(&header.block_usage_map);
+                let _ = writeln!(report, "{disk} standard {used} {}", 2880 - used);
+            },
+            _ => {
+                let _ = writeln!(report, "{disk} ? ? ?");
+            },
+        }
+    }
+    report
+}
@@ -0,0 +1,62 @@
+//
+//
+// ======
+// Control directory
+// ======
+//
+//
+
+// A directory that isn't really there.
+// `/.fluster/` is made up on the spot by the FUSE layer, so a running mount can be poked at with
+// plain old `cat` and `echo`. Nothing in here is ever stored on a floppy, and it never shows up in a listing
+// of the root, you have to know it's there.
+
+/// What the control directory is called. It always lives in the root.
+pub(crate) const CONTROL_DIR_NAME: &str = ".fluster";
+
+/// Tells the kernel to ignore the size of a file, and always ask us for its contents.
+/// Control files don't know how big they are until they're read.
+pub(crate) const FOPEN_DIRECT_IO: u32 = 1;
+
+/// Everything in the control directory, including itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ControlItem {
+    /// The directory itself.
+    Root,
+    /// Counters the TUI keeps.
+    Stats,
+    /// Cache hit rate and pressure.
+    Cache,
+    /// Summary of the pool header.
+    Pool,
+    /// How full each disk is.
+    Disks,
+    /// Everything on every disk. Unlike the rest, this can swap disks.
+    Manifest,
+    /// Write anything to this to flush the cache and pool to disk.
+    Flush,
+    /// Everything in the trash. Can swap disks.
+    Trash,
+    /// Write the number of something in the trash to this to put it back.
+    Restore,
+    /// Write anything to this to check every block in the pool, and read it for how that went.
+    /// Swaps through every disk.
+    Scrub,
+    /// Write a directory in the pool to this to copy a FAT12 floppy into it, and read it for how that went.
+    /// Starting with `grow ` wipes the floppy afterwards, and adds it to the pool.
+    Import,
+}
+
+/// The files in the control directory, in the order they are listed.
+pub(crate) const CONTROL_FILES: [ControlItem; 10] = [
+    ControlItem::Stats,
+    ControlItem::Cache,
+    ControlItem::Pool,
+    ControlItem::Disks,
+    ControlItem::Manifest,
+    ControlItem::Flush,
+    ControlItem::Trash,
+    ControlItem::Restore,
+    ControlItem::Scrub,
+    ControlItem::Import,
+];
@@ -0,0 +1,4 @@
+pub(crate) mod control_dir_methods;
+pub(crate) mod control_dir_struct;
+#[cfg(test)]
+mod tests;
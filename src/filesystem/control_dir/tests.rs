@@ -0,0 +1,146 @@
+// Poking the knobs.
+// Unwrapping is okay here, since we want unexpected outcomes to fail tests.
+#![allow(clippy::unwrap_used)]
+
+use std::{ffi::OsStr, path::Path};
+
+use fuse_mt::{FileType, FilesystemMT, RequestInfo};
+use test_log::test;
+
+use crate::{
+    error_types::filesystem::*,
+    filesystem::filesystem_struct::ENABLE_TRASH,
+    pool::disk::standard_disk::block::io::directory::tests::get_filesystem
+};
+
+use super::control_dir_struct::ControlItem;
+
+fn request() -> RequestInfo {
+    RequestInfo {
+        unique: 0,
+        uid: 0,
+        gid: 0,
+        pid: 0,
+    }
+}
+
+#[test]
+fn paths_resolve() {
+    assert_eq!(ControlItem::from_path(Path::new("/.fluster")), Ok(ControlItem::Root));
+    assert_eq!(ControlItem::from_path(Path::new("/.fluster/pool")), Ok(ControlItem::Pool));
+    assert_eq!(ControlItem::from_path(Path::new("/.fluster/nope")), Err(NO_SUCH_ITEM));
+    assert!(!ControlItem::is_control_path(Path::new("/.flusterish")));
+    assert!(!ControlItem::is_control_path(Path::new("/stuff/.fluster")));
+}
+
+/// The control directory can be found, but isn't listed.
+#[test]
+fn hidden_but_there() {
+    let fs = get_filesystem();
+    let (_, attributes) = fs.getattr(request(), Path::new("/.fluster"), None).unwrap();
+    assert_eq!(attributes.kind, FileType::Directory);
+
+    let (root, _) = fs.opendir(request(), Path::new("/"), 0).unwrap();
+    let listed = fs.readdir(request(), Path::new("/"), root).unwrap();
+    assert!(listed.iter().all(|entry| entry.name != ".fluster"));
+
+    let (control, _) = fs.opendir(request(), Path::new("/.fluster"), 0).unwrap();
+    let listed = fs.readdir(request(), Path::new("/.fluster"), control).unwrap();
+    assert!(listed.iter().any(|entry| entry.name == "pool"));
+}
+
+/// Reading gives live info, writing to flush flushes.
+#[test]
+fn read_and_flush() {
+    let fs = get_filesystem();
+    let path = Path::new("/.fluster/pool");
+    let (handle, _) = fs.open(request(), path, 0).unwrap();
+    let read = String::from_utf8(fs.read_bytes(path, handle, 0, 4096).unwrap()).unwrap();
+    assert!(read.contains("highest_known_disk: "));
+
+    let path = Path::new("/.fluster/disks");
+    let (handle, _) = fs.open(request(), path, 0).unwrap();
+    let read = String::from_utf8(fs.read_bytes(path, handle, 0, 4096).unwrap()).unwrap();
+    assert!(read.starts_with("disk kind used free\n0 pool "));
+
+    let path = Path::new("/.fluster/flush");
+    let (handle, _) = fs.open(request(), path, 0).unwrap();
+    assert_eq!(fs.write(request(), path, handle, 0, b"1\n".to_vec(), 0), Ok(2));
+    // Everything else is read only.
+    let path = Path::new("/.fluster/stats");
+    let (handle, _) = fs.open(request(), path, 0).unwrap();
+    let read = String::from_utf8(fs.read_bytes(path, handle, 0, 4096).unwrap()).unwrap();
+    assert!(read.contains("disk_swap_count: "));
+    assert_eq!(fs.write(request(), path, handle, 0, b"1\n".to_vec(), 0), Err(NOT_PERMITTED));
+}
+
+/// Nothing about the control directory ever makes it to disk.
+#[test]
+fn cannot_be_made() {
+    let fs = get_filesystem();
+    let root = Path::new("/");
+    assert_eq!(fs.mkdir(request(), root, OsStr::new(".fluster"), 0).err(), Some(ITEM_ALREADY_EXISTS));
+    assert_eq!(fs.create(request(), root, OsStr::new(".fluster"), 0, 0).err(), Some(ITEM_ALREADY_EXISTS));
+    assert_eq!(fs.create(request(), Path::new("/.fluster"), OsStr::new("new"), 0, 0).err(), Some(NOT_PERMITTED));
+    assert_eq!(fs.rmdir(request(), root, OsStr::new(".fluster")), Err(NOT_PERMITTED));
+}
+
+/// Deleted things can be found in the trash listing, and put back.
+#[test]
+fn trash_and_restore() {
+    let fs = get_filesystem();
+    let _ = ENABLE_TRASH.set(true);
+    let root = Path::new("/");
+    let _ = fs.mkdir(request(), root, OsStr::new("folder"), 0).unwrap();
+    let _ = fs.create(request(), Path::new("/folder"), OsStr::new("oops.txt"), 0, 0).unwrap();
+    assert_eq!(fs.unlink(request(), Path::new("/folder"), OsStr::new("oops.txt")), Ok(()));
+    assert_eq!(fs.rmdir(request(), root, OsStr::new("folder")), Ok(()));
+    assert_eq!(fs.getattr(request(), Path::new("/folder"), None).err(), Some(NO_SUCH_ITEM));
+
+    // The trash itself never shows up.
+    let (listing, _) = fs.opendir(request(), root, 0).unwrap();
+    let listed = fs.readdir(request(), root, listing).unwrap();
+    assert_eq!(listed.len(), 1);
+
+    let path = Path::new("/.fluster/trash");
+    let (handle, _) = fs.open(request(), path, 0).unwrap();
+    let read = String::from_utf8(fs.read_bytes(path, handle, 0, 4096).unwrap()).unwrap();
+    let lines: Vec<&str> = read.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[1].starts_with("1 ") && lines[1].ends_with(" /folder/oops.txt"));
+    assert!(lines[2].starts_with("2 ") && lines[2].ends_with(" /folder"));
+
+    let path = Path::new("/.fluster/restore");
+    let (handle, _) = fs.open(request(), path, 0).unwrap();
+    assert_eq!(fs.write(request(), path, handle, 0, b"1\n".to_vec(), 0), Ok(2));
+    assert_eq!(fs.write(request(), path, handle, 0, b"1\n".to_vec(), 0), Err(NO_SUCH_ITEM));
+    assert_eq!(fs.write(request(), path, handle, 0, b"one".to_vec(), 0), Err(INVALID_ARGUMENT));
+    let (_, attributes) = fs.getattr(request(), Path::new("/folder/oops.txt"), None).unwrap();
+    assert_eq!(attributes.kind, FileType::RegularFile);
+    // The folder it was in got made again, so the old one can't go back.
+    assert_eq!(fs.write(request(), path, handle, 0, b"2".to_vec(), 0), Err(ITEM_ALREADY_EXISTS));
+}
+
+/// Without the trash, removed directories are really gone.
+#[test]
+fn rmdir_without_trash() {
+    let fs = get_filesystem();
+    let root = Path::new("/");
+    let _ = fs.mkdir(request(), root, OsStr::new("folder"), 0).unwrap();
+    assert_eq!(fs.rmdir(request(), root, OsStr::new("folder")), Ok(()));
+    let (listing, _) = fs.opendir(request(), root, 0).unwrap();
+    let listed = fs.readdir(request(), root, listing).unwrap();
+    assert!(listed.iter().all(|entry| entry.name != "folder"));
+}
+
+/// Writing to scrub runs one, reading it says how it went.
+#[test]
+fn scrub_on_demand() {
+    let fs = get_filesystem();
+    let path = Path::new("/.fluster/scrub");
+    let (handle, _) = fs.open(request(), path, 0).unwrap();
+    assert_eq!(fs.write(request(), path, handle, 0, b"1\n".to_vec(), 0), Ok(2));
+    let read = String::from_utf8(fs.read_bytes(path, handle, 0, 4096).unwrap()).unwrap();
+    assert!(read.starts_with("disk checked corrected uncorrectable skipped\n"));
+    assert!(read.lines().any(|line| line.starts_with("total ") && line.ends_with(" 0 0 0")));
+}
@@ -7,7 +7,21 @@ use std::{fs::File, io::{
 
 use log::{debug, error, warn};
 
-use crate::{filesystem::filesystem_struct::FLOPPY_PATH, tui::{notify::NotifyTui, prompts::TuiPrompt, tasks::TaskType}};
+use crate::{filesystem::filesystem_struct::FLOPPY_PATH, pool::disk::generic::{block::block_structs::RawBlock, generic_structs::pointer_struct::DiskPointer}, tui::{notify::NotifyTui, prompts::TuiPrompt, tasks::TaskType}};
+
+/// Pull a single block out of a disk's backup.
+/// 
+/// Returns None if there is no backup of that disk, or it doesn't go out that far.
+/// The block is not checked in any way.
+pub(crate) fn read_backup_block(pointer: DiskPointer) -> Option<RawBlock> {
+    let backed_up = std::fs::File::open(format!("/var/fluster/disk_{}.fluster_backup", pointer.disk)).ok()?;
+    let mut data: [u8; 512] = [0u8; 512];
+    backed_up.read_exact_at(&mut data, pointer.block as u64 * 512).ok()?;
+    Some(RawBlock {
+        block_origin: pointer,
+        data,
+    })
+}
 
 /// Returns true if the entire disk was re-created successfully.
 /// 
@@ -0,0 +1,128 @@
+// Please take a number.
+
+use log::trace;
+
+use crate::{
+    pool::disk::drive_struct::FloppyDrive,
+    tui::notify::NotifyTui
+};
+
+use super::drive_scheduler_struct::{
+    DriveQueue,
+    DriveScheduler,
+    DriveTurn,
+    DriveWaiter,
+    DRIVE_FREED,
+    DRIVE_QUEUE,
+    MAX_SKIPS
+};
+
+impl DriveScheduler {
+    /// Wait in line for the drive.
+    ///
+    /// `disk` is the disk the caller is about to need, if it knows. Waiters that need the disk that is
+    /// already in the drive go first, then whichever disk has the most work waiting on it.
+    ///
+    /// Blocks until it is our turn, and holds the drive until the returned turn is dropped.
+    pub(crate) fn wait_for_drive(disk: Option<u16>) -> DriveTurn {
+        go_wait_for_drive(disk)
+    }
+
+    /// How many disk swaps have been skipped by serving waiters out of order.
+    pub(crate) fn swaps_avoided() -> u64 {
+        DRIVE_QUEUE.lock().expect("Other mutex holders should not panic.").swaps_avoided
+    }
+}
+
+impl Drop for DriveTurn {
+    fn drop(&mut self) {
+        let mut queue = DRIVE_QUEUE.lock().expect("Other mutex holders should not panic.");
+        queue.owned = false;
+        // Everyone checks if they're next, only one of them will be.
+        DRIVE_FREED.notify_all();
+    }
+}
+
+impl DriveQueue {
+    /// Empty line, nobody on the drive.
+    pub(super) fn new() -> Self {
+        DriveQueue {
+            owned: false,
+            waiting: Vec::new(),
+            next_ticket: 0,
+            skips: 0,
+            swaps_avoided: 0,
+        }
+    }
+
+    /// Work out who gets the drive next, given the disk that's in it.
+    ///
+    /// Returns the ticket of the next waiter, or None if nobody is waiting.
+    pub(super) fn pick_next(&self, in_drive: u16) -> Option<u64> {
+        let oldest = self.waiting.first()?;
+
+        // Been waiting long enough, no more cutting in front of them.
+        if self.skips >= MAX_SKIPS {
+            return Some(oldest.ticket);
+        }
+
+        // Anyone who can use what's already in the drive.
+        if let Some(waiter) = self.waiting.iter().find(|waiter| waiter.disk.is_none_or(|disk| disk == in_drive)) {
+            return Some(waiter.ticket);
+        }
+
+        // Somebody has to swap, so make it count. Go to the disk with the most work waiting on it,
+        // ties go to whoever got there first.
+        let mut best: Option<(usize, u64)> = None;
+        for waiter in &self.waiting {
+            let pending = self.waiting.iter().filter(|other| other.disk == waiter.disk).count();
+            if best.is_none_or(|(most, _)| pending > most) {
+                best = Some((pending, waiter.ticket));
+            }
+        }
+        best.map(|(_, ticket)| ticket)
+    }
+
+    /// Take a waiter out of line and give them the drive.
+    fn serve(&mut self, ticket: u64, in_drive: u16) {
+        let position = self.waiting.iter().position(|waiter| waiter.ticket == ticket).expect("Only waiters can be served.");
+        let served = self.waiting.remove(position);
+        self.owned = true;
+
+        if position == 0 {
+            // In order.
+            self.skips = 0;
+            return;
+        }
+
+        self.skips += 1;
+        // Cut in line. If the one we skipped would have swapped but this one doesn't, we saved a swap.
+        let skipped_would_swap = self.waiting[0].disk.is_some_and(|disk| disk != in_drive);
+        let served_swaps = served.disk.is_some_and(|disk| disk != in_drive);
+        if skipped_would_swap && !served_swaps {
+            self.swaps_avoided += 1;
+            NotifyTui::swap_saved();
+        }
+    }
+}
+
+fn go_wait_for_drive(disk: Option<u16>) -> DriveTurn {
+    let mut queue = DRIVE_QUEUE.lock().expect("Other mutex holders should not panic.");
+    let ticket = queue.next_ticket;
+    queue.next_ticket += 1;
+    queue.waiting.push(DriveWaiter { ticket, disk });
+
+    loop {
+        if !queue.owned {
+            let in_drive = FloppyDrive::currently_inserted_disk_number();
+            if queue.pick_next(in_drive) == Some(ticket) {
+                queue.serve(ticket, in_drive);
+                trace!("Got the drive, ticket {ticket}.");
+                return DriveTurn {};
+            }
+            // The drive is free but it's someone else's turn, make sure they know.
+            DRIVE_FREED.notify_all();
+        }
+        queue = DRIVE_FREED.wait(queue).expect("Other mutex holders should not panic.");
+    }
+}
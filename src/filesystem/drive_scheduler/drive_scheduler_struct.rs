@@ -0,0 +1,63 @@
+//
+//
+// ======
+// Drive scheduler
+// ======
+//
+//
+
+// Swapping disks is the slowest thing we do by a mile, since a human has to do it.
+// So when a bunch of operations want the drive at once, we let the ones that can use the disk
+// that's already in there go first, instead of ping-ponging between disks in arrival order.
+
+use std::sync::{Condvar, Mutex};
+
+use lazy_static::lazy_static;
+
+lazy_static! {
+    /// Everyone waiting for the drive, and who has it right now.
+    pub(super) static ref DRIVE_QUEUE: Mutex<DriveQueue> = Mutex::new(DriveQueue::new());
+    /// Poked whenever the drive is handed back, so the waiters can check if it's their turn.
+    pub(super) static ref DRIVE_FREED: Condvar = Condvar::new();
+}
+
+/// How many times the oldest waiter can be skipped over before it goes next no matter what.
+/// Without this, a steady stream of work on one disk could starve everyone else.
+pub(super) const MAX_SKIPS: u32 = 16;
+
+/// Someone in line for the drive.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct DriveWaiter {
+    /// Place in line, lower came first.
+    pub(super) ticket: u64,
+    /// The disk this waiter needs, if it knows.
+    /// Waiters that don't know are happy with whatever disk is in the drive.
+    pub(super) disk: Option<u16>,
+}
+
+/// The line for the drive.
+#[derive(Debug)]
+pub(super) struct DriveQueue {
+    /// Is someone using the drive right now?
+    pub(super) owned: bool,
+    /// Everyone waiting, in arrival order.
+    pub(super) waiting: Vec<DriveWaiter>,
+    /// The next ticket to hand out.
+    pub(super) next_ticket: u64,
+    /// How many times in a row the oldest waiter has been skipped.
+    pub(super) skips: u32,
+    /// How many times we served someone out of order to avoid a swap.
+    pub(super) swaps_avoided: u64,
+}
+
+/// Struct for implementing scheduler methods on.
+/// Holds no information, this is just for calling.
+pub(crate) struct DriveScheduler {
+    // next!
+}
+
+/// Proof that you own the drive. The drive is handed to the next waiter when this is dropped.
+#[must_use = "The drive is released as soon as this is dropped."]
+pub(crate) struct DriveTurn {
+    // only one at a time
+}
@@ -0,0 +1,4 @@
+pub(crate) mod drive_scheduler_methods;
+pub(crate) mod drive_scheduler_struct;
+#[cfg(test)]
+mod tests;
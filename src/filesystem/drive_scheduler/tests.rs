@@ -0,0 +1,51 @@
+// Cutting in line, but only when it helps everyone.
+// Unwrapping is okay here, since we want unexpected outcomes to fail tests.
+#![allow(clippy::unwrap_used)]
+
+use test_log::test;
+
+use super::drive_scheduler_struct::{
+    DriveQueue,
+    DriveWaiter,
+    MAX_SKIPS
+};
+
+fn queue_of(disks: &[Option<u16>]) -> DriveQueue {
+    let mut queue = DriveQueue::new();
+    for disk in disks {
+        let ticket = queue.next_ticket;
+        queue.next_ticket += 1;
+        queue.waiting.push(DriveWaiter { ticket, disk: *disk });
+    }
+    queue
+}
+
+/// Work for the disk already in the drive goes first.
+#[test]
+fn current_disk_first() {
+    let queue = queue_of(&[Some(5), Some(2), Some(5), Some(2)]);
+    assert_eq!(queue.pick_next(2), Some(1));
+    assert_eq!(queue.pick_next(5), Some(0));
+    // Waiters that don't care about the disk don't need a swap either.
+    let queue = queue_of(&[Some(5), None]);
+    assert_eq!(queue.pick_next(2), Some(1));
+}
+
+/// If we have to swap, swap to the disk with the most waiting on it.
+#[test]
+fn busiest_disk_next() {
+    let queue = queue_of(&[Some(5), Some(2), Some(2), Some(7)]);
+    assert_eq!(queue.pick_next(1), Some(1));
+    // Ties go to whoever showed up first.
+    let queue = queue_of(&[Some(5), Some(2)]);
+    assert_eq!(queue.pick_next(1), Some(0));
+    assert_eq!(queue_of(&[]).pick_next(1), None);
+}
+
+/// Nobody waits forever.
+#[test]
+fn oldest_is_not_starved() {
+    let mut queue = queue_of(&[Some(5), Some(2)]);
+    queue.skips = MAX_SKIPS;
+    assert_eq!(queue.pick_next(2), Some(0));
+}
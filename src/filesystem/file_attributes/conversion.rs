@@ -48,7 +48,8 @@ fn go_get_metadata(item: DirectoryItem) -> Result<FileAttr, DriveError> {
     
     // How big is it
     debug!("Getting size...");
-    let size: u64 = item.get_size()?;
+    // Buffered writes might have made it bigger than the pool knows about yet.
+    let size: u64 = item.get_size()?.max(FileHandle::buffered_end(&item).unwrap_or(0));
     NotifyTui::complete_task_step(&handle);
     
     // extract the times
@@ -1,10 +1,10 @@
 // Make the handle do things.
 
-use std::{collections::HashMap, sync::{Arc, Mutex}};
+use std::{collections::{HashMap, HashSet}, sync::{Arc, Mutex}};
 
 use lazy_static::lazy_static;
 use libc::c_int;
-use log::{debug, error};
+use log::{debug, error, warn};
 
 //
 // Global info about open files
@@ -16,7 +16,14 @@ struct LoveHandles {
     /// Highest allocated number (is kept up to date internally)
     highest: u64,
     /// Recently freed handles (ie open space in the hashmap)
-    free: Vec<u64>
+    free: Vec<u64>,
+    /// How many handles are open on each inode, keyed by where the inode lives.
+    open: HashMap<(DiskPointer, u16), u32>,
+    /// Files that were deleted while they were open. They get freed once the last handle on them is released.
+    unlinked: HashMap<(DiskPointer, u16), DirectoryItem>,
+    /// Handles that were released before their buffered writes made it out. They stay allocated until
+    /// a later flush gets the writes in.
+    closed: HashSet<u64>,
 }
 
 impl LoveHandles {
@@ -27,6 +34,9 @@ impl LoveHandles {
             allocated: HashMap::new(),
             highest: 0,
             free: Vec::new(),
+            open: HashMap::new(),
+            unlinked: HashMap::new(),
+            closed: HashSet::new(),
         }
     }
 
@@ -35,6 +45,11 @@ impl LoveHandles {
         // Get a number
         let num = self.next_free();
 
+        // Keep track of who has what open.
+        if let Some(item) = &item.item {
+            *self.open.entry(inode_key(item)).or_default() += 1;
+        }
+
         // Put it in the hashmap.
         // We also assert that we have not already used this number.
         assert!(self.allocated.insert(num, item).is_none(), "We already used this handle number, even though we thought it was free!");
@@ -71,10 +86,15 @@ impl LoveHandles {
     }
 
     /// You need to let go...
-    fn release_handle(&mut self, number: u64) {
+    ///
+    /// If this was the last handle on a file that has already been deleted, the file is handed back so
+    /// it can finally be freed.
+    fn release_handle(&mut self, number: u64) -> Option<DirectoryItem> {
         // Handles are only ever freed once. Freeing an empty handle is undefined behavior, thus we
         // cant do anything but give up.
-        if self.allocated.remove(&number).is_none() {
+        let released: FileHandle = if let Some(handle) = self.allocated.remove(&number) {
+            handle
+        } else {
             // Bad!
             error!("Tried to free a handle that was not allocated!");
             panic!("Double free on handle.");
@@ -85,6 +105,26 @@ impl LoveHandles {
             // Yep! Reduce highest.
             self.highest -= 1;
         }
+
+        // Was anyone else still using it?
+        let key = inode_key(&released.item?);
+        let count = self.open.get_mut(&key).expect("Open items should be counted.");
+        *count -= 1;
+        if *count != 0 {
+            return None;
+        }
+        let _ = self.open.remove(&key);
+        self.unlinked.remove(&key)
+    }
+
+    /// Hold onto a deleted file if it's still open. Gives it back if nobody has it open.
+    fn defer_delete(&mut self, item: DirectoryItem) -> Option<DirectoryItem> {
+        let key = inode_key(&item);
+        if !self.open.contains_key(&key) {
+            return Some(item);
+        }
+        let _ = self.unlinked.insert(key, item);
+        None
     }
 }
 
@@ -92,8 +132,25 @@ impl LoveHandles {
 
 lazy_static! {
     static ref LOANED_HANDLES: Arc<Mutex<LoveHandles>> = Arc::new(Mutex::new(LoveHandles::new()));
+    /// How each open handle has been reading. Only handles that have read something are in here.
+    static ref READ_PATTERNS: Mutex<HashMap<u64, ReadPattern>> = Mutex::new(HashMap::new());
+    /// Checksums of handles that are reading a file from start to end.
+    static ref READ_CHECKSUMS: Mutex<HashMap<u64, RunningChecksum>> = Mutex::new(HashMap::new());
+    /// Buffered writes, one run per handle. Only handles that are holding onto something are in here.
+    static ref WRITE_RUNS: Mutex<HashMap<u64, WriteRun>> = Mutex::new(HashMap::new());
+    /// Errors from writing out a run when the handle wasn't the one asking, saved for the next time it is.
+    static ref WRITE_ERRORS: Mutex<HashMap<u64, c_int>> = Mutex::new(HashMap::new());
 }
 
+/// How many back-to-back reads it takes before we start reading ahead.
+const SEQUENTIAL_STREAK: u32 = 2;
+
+/// The most a handle will buffer before writing it out. Same as the biggest write we let through at once.
+const WRITE_RUN_LIMIT: usize = 1024 * 1024;
+
+/// Extra blocks a run might need for its extents, on top of the blocks for the data itself.
+const WRITE_RUN_EXTENT_SLACK: u32 = 4;
+
 
 
 
@@ -102,17 +159,31 @@ lazy_static! {
 // The actual handles
 //
 
+use std::path::Path;
+
 use crate::{
     error_types::filesystem::*,
-    filesystem::file_handle::file_handle_struct::FileHandle,
-    pool::disk::{
-        standard_disk::block::{
-            directory::directory_struct::{
-                DirectoryBlock,
-                DirectoryItem
-            },
-            io::directory::types::NamedItem
-        }
+    filesystem::file_handle::file_handle_struct::{
+        FileHandle,
+        ReadPattern,
+        WriteRun
+    },
+    pool::{
+        disk::{
+            generic::generic_structs::pointer_struct::DiskPointer,
+            standard_disk::block::{
+                directory::directory_struct::{
+                    DirectoryBlock,
+                    DirectoryItem
+                },
+                io::{
+                    directory::types::NamedItem,
+                    file::checksum::RunningChecksum
+                }
+            }
+        },
+        placement::placement_struct::PlacementGuard,
+        pool_actions::pool_struct::Pool
     }
 };
 
@@ -152,11 +223,224 @@ impl FileHandle {
 
     /// Release a handle.
     /// 
-    /// Will block.
-    pub fn drop_handle(handle: u64) {
-        // This is blocking
-        let read_handles = &mut LOANED_HANDLES.lock().expect("Other mutex holders should not panic.");
-        read_handles.release_handle(handle);
+    /// Anything still buffered on the handle is written out first. If that fails, the error is returned,
+    /// but the handle hangs onto its run, and is only let go of once a later flush gets it out. Unmounting
+    /// tries one last time.
+    /// 
+    /// If this was the last handle on a file that was deleted while open, the file is freed now.
+    /// 
+    /// Will block. May swap disks.
+    pub fn drop_handle(handle: u64) -> Result<(), c_int> {
+        if let Err(error) = write_out(handle) {
+            let held: usize = WRITE_RUNS.lock().expect("Other mutex holders should not panic.").get(&handle).map_or(0, |run| run.data.len());
+            error!("Handle `{handle}` was released before it could write out its buffered bytes! `{held}` bytes will be lost if no later flush gets them in. Error: {error}");
+            let _ = LOANED_HANDLES.lock().expect("Other mutex holders should not panic.").closed.insert(handle);
+            return Err(error);
+        }
+        let saved: Option<c_int> = take_write_error(handle);
+        release(handle)?;
+        match saved {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    /// Write to a file through this handle, holding onto the bytes if we can.
+    /// 
+    /// Writes that land inside of, or right after, what the handle is already holding are added to it,
+    /// so a file written from start to end turns into a few big writes instead of lots of little ones.
+    /// Anything else writes out what the handle was holding first. Other handles on the same file are
+    /// written out too, so the newest bytes always land last.
+    /// 
+    /// Writes go straight through when the pool might not have room for them, so running out of space is
+    /// reported by the write that did it. Errors from writing out earlier buffered bytes are reported here too.
+    /// 
+    /// Returns how many bytes were written, which is always all of them.
+    /// 
+    /// May swap disks.
+    pub fn buffer_write(handle: u64, offset: u64, data: &[u8]) -> Result<u32, c_int> {
+        if let Some(error) = take_write_error(handle) {
+            return Err(error);
+        }
+        flush_other_handles(handle);
+
+        let mut runs = WRITE_RUNS.lock().expect("Other mutex holders should not panic.");
+        let held: usize = runs.get(&handle).map_or(0, |run| run.data.len());
+        let roomy: bool = Pool::has_room_for((held + data.len()).div_ceil(507) as u32 + WRITE_RUN_EXTENT_SLACK);
+
+        if roomy && let Some(run) = runs.get_mut(&handle) {
+            let run_end: u64 = run.offset + run.data.len() as u64;
+            if offset >= run.offset && offset <= run_end && (offset - run.offset) as usize + data.len() <= WRITE_RUN_LIMIT {
+                // Fits right into what we have.
+                let start: usize = (offset - run.offset) as usize;
+                let end: usize = start + data.len();
+                if end > run.data.len() {
+                    run.data.resize(end, 0);
+                }
+                run.data[start..end].copy_from_slice(data);
+                return Ok(data.len() as u32);
+            }
+        }
+
+        // Can't add onto the old run, so it goes out first.
+        drop(runs);
+        write_out(handle)?;
+
+        let run: WriteRun = WriteRun {
+            offset,
+            data: data.to_vec(),
+        };
+        if !roomy || data.len() >= WRITE_RUN_LIMIT {
+            // No point holding onto it.
+            write_run(handle, &run)?;
+        } else {
+            let _ = WRITE_RUNS.lock().expect("Other mutex holders should not panic.").insert(handle, run);
+        }
+        Ok(data.len() as u32)
+    }
+
+    /// Write out everything this handle is holding onto.
+    /// 
+    /// Also reports any error from an earlier attempt at writing it out. If writing it out fails, the
+    /// handle keeps holding onto it, and it is tried again next time.
+    /// 
+    /// Only call this while changing the filesystem. May swap disks.
+    pub fn flush_writes(handle: u64) -> Result<(), c_int> {
+        write_out(handle)?;
+        match take_write_error(handle) {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    /// Write out everything every handle is holding onto, so the pool is up to date.
+    /// 
+    /// Call this before changing files without going through a handle's writes. Lookups should use
+    /// `buffered_end()` and `overlay_buffered()` instead, since they can't write anything. Errors are saved
+    /// for the handle they happened on, and reported the next time it writes, flushes, or is released.
+    /// 
+    /// Only call this while changing the filesystem. May swap disks.
+    pub fn flush_all_writes() {
+        let handles: Vec<u64> = WRITE_RUNS.lock().expect("Other mutex holders should not panic.").keys().copied().collect();
+        for handle in handles {
+            flush_saving_error(handle);
+        }
+    }
+
+    /// Where the buffered writes on a file end, if any handle is holding onto some.
+    /// 
+    /// The file is at least this big, even if the pool doesn't know it yet.
+    pub(crate) fn buffered_end(item: &DirectoryItem) -> Option<u64> {
+        let handles: Vec<u64> = buffered_handles_of(item);
+        let runs = WRITE_RUNS.lock().expect("Other mutex holders should not panic.");
+        handles
+            .iter()
+            .filter_map(|handle| runs.get(handle))
+            .map(|run| run.offset + run.data.len() as u64)
+            .max()
+    }
+
+    /// Lay anything buffered for a file over bytes that were read out of the pool, starting at `offset`.
+    pub(crate) fn overlay_buffered(item: &DirectoryItem, offset: u64, bytes: &mut [u8]) {
+        let handles: Vec<u64> = buffered_handles_of(item);
+        let runs = WRITE_RUNS.lock().expect("Other mutex holders should not panic.");
+        let read_end: u64 = offset + bytes.len() as u64;
+        for run in handles.iter().filter_map(|handle| runs.get(handle)) {
+            let run_end: u64 = run.offset + run.data.len() as u64;
+            let start: u64 = run.offset.max(offset);
+            let end: u64 = run_end.min(read_end);
+            if start >= end {
+                continue;
+            }
+            bytes[(start - offset) as usize..(end - offset) as usize]
+                .copy_from_slice(&run.data[(start - run.offset) as usize..(end - run.offset) as usize]);
+        }
+    }
+
+    /// Roughly how many blocks everything buffered will take up once it's written out.
+    pub(crate) fn buffered_blocks() -> u64 {
+        WRITE_RUNS
+            .lock()
+            .expect("Other mutex holders should not panic.")
+            .values()
+            .map(|run| run.data.len().div_ceil(507) as u64)
+            .sum()
+    }
+
+    /// Does anyone have this item open?
+    pub(crate) fn is_open(item: &DirectoryItem) -> bool {
+        LOANED_HANDLES
+            .lock()
+            .expect("Other mutex holders should not panic.")
+            .open
+            .contains_key(&inode_key(item))
+    }
+
+    /// Files that are deleted while open stay readable and writable through their handles, and are only
+    /// freed once the last one is released.
+    /// 
+    /// Takes a file that was just taken out of its directory. Returns it if it isn't open, and should be
+    /// freed right away. Otherwise it's held onto, and None is returned.
+    /// 
+    /// If fluster goes down before the file is closed, its blocks are never freed.
+    pub(crate) fn defer_delete(item: DirectoryItem) -> Option<DirectoryItem> {
+        LOANED_HANDLES.lock().expect("Other mutex holders should not panic.").defer_delete(item)
+    }
+
+    /// Feed a finished read into the handle's running checksum.
+    /// 
+    /// Reading from the start of the file begins a new checksum, and reading anywhere but right
+    /// after the last read gives up on it. Once the handle has read up to `file_size`, returns
+    /// the checksum of everything it read.
+    pub fn note_checksummed_read(handle: u64, offset: u64, bytes: &[u8], file_size: u64) -> Option<u64> {
+        let mut checksums = READ_CHECKSUMS.lock().expect("Other mutex holders should not panic.");
+        if offset == 0 {
+            let _ = checksums.insert(handle, RunningChecksum::default());
+        }
+        let running = checksums.get_mut(&handle)?;
+        if !running.feed(offset, bytes) {
+            // Skipped around, can't check this one.
+            let _ = checksums.remove(&handle);
+            return None;
+        }
+        if running.next_offset() < file_size {
+            return None;
+        }
+        let finished: u64 = running.finish();
+        let _ = checksums.remove(&handle);
+        Some(finished)
+    }
+
+    /// Record a finished read on a handle.
+    /// 
+    /// If the handle has been reading sequentially, and this read went past what was already read
+    /// ahead, returns the offset readahead should start from.
+    pub fn note_read(handle: u64, offset: u64, length: u64) -> Option<u64> {
+        let mut patterns = READ_PATTERNS.lock().expect("Other mutex holders should not panic.");
+        let pattern = patterns.entry(handle).or_default();
+
+        if offset == pattern.next_offset {
+            pattern.streak = pattern.streak.saturating_add(1);
+        } else {
+            // Jumped somewhere else, start over.
+            pattern.streak = 0;
+            pattern.prefetched_until = 0;
+        }
+        pattern.next_offset = offset + length;
+
+        if pattern.streak >= SEQUENTIAL_STREAK && pattern.next_offset >= pattern.prefetched_until {
+            Some(pattern.next_offset)
+        } else {
+            None
+        }
+    }
+
+    /// Record how far readahead got on a handle, so we don't redo it on every read.
+    pub fn note_prefetched(handle: u64, until: u64) {
+        let mut patterns = READ_PATTERNS.lock().expect("Other mutex holders should not panic.");
+        if let Some(pattern) = patterns.get_mut(&handle) {
+            pattern.prefetched_until = until;
+        }
     }
 
     /// Check if this handle is a file or a directory by attempting to read it from disk, otherwise
@@ -207,7 +491,13 @@ impl FileHandle {
 
     
     /// Loads in and returns the directory item if it exists.
+    /// 
+    /// Handles that were opened on an item always return that item, even if it has since moved.
     pub fn get_directory_item(&self) -> Result<DirectoryItem, c_int> {
+        if let Some(item) = &self.item {
+            return Ok(item.clone());
+        }
+
         // Open the containing folder
         let block = match DirectoryBlock::try_find_directory(self.path.parent())? {
             Some(ok) => ok,
@@ -262,4 +552,105 @@ impl FileHandle {
 
         
     }
-}
\ No newline at end of file
+}
+
+/// Write a run out to the file a handle points at, following the placement of where the handle was opened.
+fn write_run(handle: u64, run: &WriteRun) -> Result<(), c_int> {
+    let got_handle = FileHandle::read(handle);
+    debug!("Writing out `{}` buffered bytes to `{}`...", run.data.len(), got_handle.path.display());
+    let _placement = PlacementGuard::enter(got_handle.path.parent().unwrap_or(Path::new("/")))?;
+    let file = got_handle.get_directory_item()?;
+    let _ = file.write_file(&run.data, run.offset)?;
+    Ok(())
+}
+
+/// Write out a handle's run, if it has one.
+/// 
+/// The run is only let go of once it's in the file. Writing it again after a failure just lands the
+/// same bytes in the same spots, so a run that only made it partway is finished off by the next try.
+/// 
+/// Handles that were already released are let go of once their run is out.
+fn write_out(handle: u64) -> Result<(), c_int> {
+    let run: Option<WriteRun> = WRITE_RUNS.lock().expect("Other mutex holders should not panic.").get(&handle).cloned();
+    let Some(run) = run else {
+        return Ok(());
+    };
+    write_run(handle, &run)?;
+    let _ = WRITE_RUNS.lock().expect("Other mutex holders should not panic.").remove(&handle);
+
+    let closed: bool = LOANED_HANDLES.lock().expect("Other mutex holders should not panic.").closed.remove(&handle);
+    if closed {
+        debug!("Released handle `{handle}` finally wrote out its buffered bytes, letting go of it.");
+        // Whoever released it already heard about the write failing, this has nowhere to go.
+        if let Err(error) = release(handle) {
+            error!("Failed to let go of released handle `{handle}`. Error: {error}");
+        }
+    }
+    Ok(())
+}
+
+/// Let go of a handle that has nothing left buffered.
+/// 
+/// If this was the last handle on a file that was deleted while open, the file is freed now.
+fn release(handle: u64) -> Result<(), c_int> {
+    // This is blocking
+    let orphan = LOANED_HANDLES.lock().expect("Other mutex holders should not panic.").release_handle(handle);
+    // The number might get handed out again, and the new owner reads however it likes.
+    let _ = READ_PATTERNS.lock().expect("Other mutex holders should not panic.").remove(&handle);
+    let _ = READ_CHECKSUMS.lock().expect("Other mutex holders should not panic.").remove(&handle);
+    let _ = WRITE_ERRORS.lock().expect("Other mutex holders should not panic.").remove(&handle);
+
+    if let Some(orphan) = orphan {
+        debug!("Last handle on deleted file `{}` released, freeing it...", orphan.name);
+        orphan.delete_unlinked()?;
+    }
+    Ok(())
+}
+
+/// Write out a handle's run, holding onto the error for later if it fails.
+fn flush_saving_error(handle: u64) {
+    if let Err(error) = write_out(handle) {
+        warn!("Failed to write out buffered bytes, telling the handle next time it writes. Error: {error}");
+        let _ = WRITE_ERRORS.lock().expect("Other mutex holders should not panic.").insert(handle, error);
+    }
+}
+
+/// Every handle holding onto writes for the same file as this item.
+fn buffered_handles_of(item: &DirectoryItem) -> Vec<u64> {
+    let key = inode_key(item);
+    let handles: Vec<u64> = WRITE_RUNS.lock().expect("Other mutex holders should not panic.").keys().copied().collect();
+    handles
+        .into_iter()
+        .filter(|handle| FileHandle::read(*handle).item.is_some_and(|other| inode_key(&other) == key))
+        .collect()
+}
+
+/// Write out the runs of every other handle on the same file as this one.
+fn flush_other_handles(handle: u64) {
+    let Some(item) = FileHandle::read(handle).item else {
+        return;
+    };
+    let key = inode_key(&item);
+    let others: Vec<u64> = WRITE_RUNS
+        .lock()
+        .expect("Other mutex holders should not panic.")
+        .keys()
+        .copied()
+        .filter(|other| *other != handle)
+        .collect();
+    for other in others {
+        if FileHandle::read(other).item.is_some_and(|other_item| inode_key(&other_item) == key) {
+            flush_saving_error(other);
+        }
+    }
+}
+
+/// Take the saved error for a handle, if there is one.
+fn take_write_error(handle: u64) -> Option<c_int> {
+    WRITE_ERRORS.lock().expect("Other mutex holders should not panic.").remove(&handle)
+}
+
+/// Inodes never move, so where one lives is as good as a name for it.
+fn inode_key(item: &DirectoryItem) -> (DiskPointer, u16) {
+    (item.location.pointer, item.location.offset)
+}
@@ -10,11 +10,37 @@
 // We are in charge of our own file handle management. Fun! (lie)
 // So we need a way to hand out and retrieve them.
 
+use crate::pool::disk::standard_disk::block::directory::directory_struct::DirectoryItem;
 
 
 /// Handle for any type of item (file or directory).
 #[derive(Debug, Clone)]
 pub(crate) struct FileHandle {
-    /// The path of this file/folder.
+    /// The path of this file/folder, as of when it was opened.
     pub path: Box<std::path::Path>, // Non-static size, thus boxed.
-}
\ No newline at end of file
+    /// The item this handle was opened on, if it's in the pool.
+    ///
+    /// Once this is set, the path is only a name. The handle keeps pointing at the same inode if the item is
+    /// renamed, moved, or even unlinked. Temporary handles that are only used to look something up leave this empty.
+    pub item: Option<DirectoryItem>,
+}
+
+/// How a handle has been reading its file, so we can guess where it'll read next.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct ReadPattern {
+    /// Where the last read on this handle ended.
+    pub next_offset: u64,
+    /// How many reads in a row have started where the previous one ended.
+    pub streak: u32,
+    /// Everything before this offset was already pulled into the cache by readahead.
+    pub prefetched_until: u64,
+}
+
+/// Writes on a handle that haven't made it into the file yet.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct WriteRun {
+    /// Where in the file the run starts.
+    pub offset: u64,
+    /// Everything written since, with no gaps.
+    pub data: Vec<u8>,
+}
@@ -1,2 +1,4 @@
-pub(super) mod file_handle_methods;
-pub(super) mod file_handle_struct;
\ No newline at end of file
+pub(crate) mod file_handle_methods;
+pub(crate) mod file_handle_struct;
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,303 @@
+// Don't pull the rug out.
+// Unwrapping is okay here, since we want unexpected outcomes to fail tests.
+#![allow(clippy::unwrap_used)]
+
+use std::{ffi::OsStr, path::Path};
+
+use fuse_mt::{FilesystemMT, RequestInfo};
+use test_log::test;
+
+use crate::{
+    error_types::filesystem::*,
+    filesystem::{
+        file_handle::file_handle_struct::FileHandle,
+        filesystem_struct::{FlusterFS, AUTO_GROW}
+    },
+    pool::{
+        disk::{
+            generic::{
+                generic_structs::pointer_struct::DiskPointer,
+                io::cache::cache_io::CachedBlockIO
+            },
+            standard_disk::block::{
+                header::header_struct::StandardDiskHeader,
+                io::directory::{
+                    tests::get_filesystem,
+                    types::NamedItem
+                }
+            }
+        },
+        manifest::manifest_methods::count_used,
+        pool_actions::pool_struct::Pool
+    }
+};
+
+fn request() -> RequestInfo {
+    RequestInfo {
+        unique: 0,
+        uid: 0,
+        gid: 0,
+        pid: 0,
+    }
+}
+
+/// How big the file is in the pool, ignoring anything still buffered.
+fn size_in_pool(name: &str) -> u64 {
+    let root = Pool::get_root_directory().unwrap();
+    root.find_item(&NamedItem::File(name.to_string())).unwrap().unwrap().get_size().unwrap()
+}
+
+fn used_blocks() -> u32 {
+    let header = StandardDiskHeader::from_block(&CachedBlockIO::read_block(DiskPointer { disk: 1, block: 0 }).unwrap());
+    count_used(&header.block_usage_map)
+}
+
+/// Handles keep working after what they point at is moved somewhere else.
+#[test]
+fn renamed_while_open() {
+    let fs = get_filesystem();
+    let root = Path::new("/");
+    let _ = fs.create(request(), root, OsStr::new("before.txt"), 0, 0).unwrap();
+    let _ = fs.mkdir(request(), root, OsStr::new("folder"), 0).unwrap();
+    let (handle, _) = fs.open(request(), Path::new("/before.txt"), libc::O_RDWR as u32).unwrap();
+    let _ = fs.write(request(), Path::new("/before.txt"), handle, 0, b"hello".to_vec(), 0).unwrap();
+
+    fs.rename(request(), root, OsStr::new("before.txt"), Path::new("/folder"), OsStr::new("after.txt")).unwrap();
+    let after = Path::new("/folder/after.txt");
+    let _ = fs.write(request(), after, handle, 5, b" there".to_vec(), 0).unwrap();
+    assert_eq!(fs.read_bytes(after, handle, 0, 100).unwrap(), b"hello there");
+    let (_, attributes) = fs.getattr(request(), after, Some(handle)).unwrap();
+    assert_eq!(attributes.size, 11);
+
+    // Directories too.
+    let (listing, _) = fs.opendir(request(), Path::new("/folder"), 0).unwrap();
+    fs.rename(request(), root, OsStr::new("folder"), root, OsStr::new("moved")).unwrap();
+    let listed = fs.readdir(request(), Path::new("/moved"), listing).unwrap();
+    assert!(listed.iter().any(|entry| entry.name == "after.txt"));
+
+    fs.releasedir(request(), Path::new("/moved"), listing, 0).unwrap();
+    fs.release(request(), Path::new("/moved/after.txt"), handle, 0, 0, false).unwrap();
+}
+
+/// Deleting an open file hides it, but it's only freed once the last handle goes away.
+#[test]
+fn unlinked_while_open() {
+    let fs = get_filesystem();
+    let root = Path::new("/");
+    let path = Path::new("/doomed.bin");
+    let first = fs.create(request(), root, OsStr::new("doomed.bin"), 0, 0).unwrap().fh;
+    let (second, _) = fs.open(request(), path, libc::O_RDONLY as u32).unwrap();
+    let contents: Vec<u8> = (0..20_000_u32).map(|number| number as u8).collect();
+    let _ = fs.write(request(), path, first, 0, contents.clone(), 0).unwrap();
+    fs.flush(request(), path, first, 0).unwrap();
+
+    let before = used_blocks();
+    fs.unlink(request(), root, OsStr::new("doomed.bin")).unwrap();
+    assert_eq!(fs.getattr(request(), path, None).err(), Some(NO_SUCH_ITEM));
+    assert_eq!(used_blocks(), before);
+
+    // Something new in the same spot doesn't get mixed up with it.
+    let _ = fs.create(request(), root, OsStr::new("doomed.bin"), 0, 0).unwrap();
+    assert_eq!(fs.read_bytes(path, second, 0, 20_000).unwrap(), contents);
+    let _ = fs.write(request(), path, first, 0, b"still here".to_vec(), 0).unwrap();
+    assert_eq!(fs.read_bytes(path, second, 0, 10).unwrap(), b"still here");
+
+    fs.release(request(), path, first, 0, 0, false).unwrap();
+    assert_eq!(fs.read_bytes(path, second, 0, 10).unwrap(), b"still here");
+    let still_open = used_blocks();
+    fs.release(request(), path, second, 0, 0, false).unwrap();
+    assert!(used_blocks() < still_open);
+}
+
+/// Open directories can't be deleted or replaced.
+#[test]
+fn busy_directories() {
+    let fs = get_filesystem();
+    let root = Path::new("/");
+    let _ = fs.mkdir(request(), root, OsStr::new("open"), 0).unwrap();
+    let _ = fs.mkdir(request(), root, OsStr::new("other"), 0).unwrap();
+    let _ = fs.mkdir(request(), Path::new("/other"), OsStr::new("inner"), 0).unwrap();
+    let (listing, _) = fs.opendir(request(), Path::new("/open"), 0).unwrap();
+
+    assert_eq!(fs.rmdir(request(), root, OsStr::new("open")), Err(BUSY));
+    assert_eq!(
+        fs.rename(request(), Path::new("/other"), OsStr::new("inner"), root, OsStr::new("open")),
+        Err(BUSY)
+    );
+
+    fs.releasedir(request(), Path::new("/open"), listing, 0).unwrap();
+    fs.rmdir(request(), root, OsStr::new("open")).unwrap();
+}
+
+/// Small writes in a row should be held onto, and land in the pool all at once.
+#[test]
+fn writes_are_coalesced() {
+    let fs = get_filesystem();
+    let path = Path::new("/streamed.bin");
+    let handle = fs.create(request(), Path::new("/"), OsStr::new("streamed.bin"), 0, 0).unwrap().fh;
+    let contents: Vec<u8> = (0..200_000_u32).map(|number| (number % 251) as u8).collect();
+
+    let before = used_blocks();
+    for (index, chunk) in contents.chunks(4096).enumerate() {
+        assert_eq!(fs.write(request(), path, handle, index as u64 * 4096, chunk.to_vec(), 0), Ok(chunk.len() as u32));
+    }
+    // Going back over something we just wrote stays in the buffer too.
+    let _ = fs.write(request(), path, handle, 10, b"patched".to_vec(), 0).unwrap();
+    let mut expected = contents.clone();
+    expected[10..17].copy_from_slice(b"patched");
+
+    // Nothing has touched the pool yet.
+    assert_eq!(size_in_pool("streamed.bin"), 0);
+    assert_eq!(used_blocks(), before);
+
+    // But anyone looking at the file sees everything, without it being written out.
+    let (_, attributes) = fs.getattr(request(), path, None).unwrap();
+    assert_eq!(attributes.size, contents.len() as u64);
+    assert_eq!(fs.read_bytes(path, handle, 0, 200_000).unwrap(), expected);
+    assert_eq!(fs.read_bytes(path, handle, 199_990, 100).unwrap(), expected[199_990..]);
+    let _ = fs.statfs(request(), Path::new("/")).unwrap();
+    assert_eq!(size_in_pool("streamed.bin"), 0);
+    assert_eq!(used_blocks(), before);
+
+    // Flushing the handle puts it in the pool.
+    fs.flush(request(), path, handle, 0).unwrap();
+    assert_eq!(size_in_pool("streamed.bin"), contents.len() as u64);
+    assert_eq!(fs.read_bytes(path, handle, 0, 200_000).unwrap(), expected);
+
+    // Jumping somewhere else writes out the old run first, and closing writes out the rest.
+    let _ = fs.write(request(), path, handle, 500_000, b"far away".to_vec(), 0).unwrap();
+    let _ = fs.write(request(), path, handle, 0, b"start".to_vec(), 0).unwrap();
+    assert_eq!(size_in_pool("streamed.bin"), 500_008);
+    fs.flush(request(), path, handle, 0).unwrap();
+    fs.release(request(), path, handle, 0, 0, false).unwrap();
+    let (reader, _) = fs.open(request(), path, libc::O_RDONLY as u32).unwrap();
+    assert_eq!(fs.read_bytes(path, reader, 0, 5).unwrap(), b"start");
+    assert_eq!(fs.read_bytes(path, reader, 500_000, 100).unwrap(), b"far away");
+    fs.release(request(), path, reader, 0, 0, false).unwrap();
+}
+
+/// Two handles writing over each other should end up with whichever wrote last.
+#[test]
+fn buffered_handles_keep_order() {
+    let fs = get_filesystem();
+    let path = Path::new("/shared.txt");
+    let first = fs.create(request(), Path::new("/"), OsStr::new("shared.txt"), 0, 0).unwrap().fh;
+    let (second, _) = fs.open(request(), path, libc::O_RDWR as u32).unwrap();
+
+    let _ = fs.write(request(), path, first, 0, b"aaaaaaaaaa".to_vec(), 0).unwrap();
+    let _ = fs.write(request(), path, second, 2, b"bbbb".to_vec(), 0).unwrap();
+    let _ = fs.write(request(), path, first, 10, b"cc".to_vec(), 0).unwrap();
+    assert_eq!(fs.read_bytes(path, second, 0, 100).unwrap(), b"aabbbbaaaacc");
+
+    // Truncating can't be undone by something that was still buffered.
+    let _ = fs.write(request(), path, first, 12, b"dd".to_vec(), 0).unwrap();
+    fs.truncate(request(), path, Some(second), 4).unwrap();
+    fs.release(request(), path, first, 0, 0, false).unwrap();
+    assert_eq!(fs.read_bytes(path, second, 0, 100).unwrap(), b"aabb");
+    fs.release(request(), path, second, 0, 0, false).unwrap();
+}
+
+/// Buffered writes that can't be written out yet are held onto until they can, instead of being lost.
+#[test]
+fn failed_flushes_keep_the_run() {
+    let fs = get_filesystem();
+    let _ = AUTO_GROW.set(false);
+    let path = Path::new("/held.txt");
+    let handle = fs.create(request(), Path::new("/"), OsStr::new("held.txt"), 0, 0).unwrap().fh;
+    let _ = fs.write(request(), path, handle, 0, vec![b'h'; 100], 0).unwrap();
+
+    // Fill up the pool behind the handle's back.
+    let filler = Pool::get_root_directory().unwrap().new_file("filler.bin".to_string()).unwrap();
+    let mut filled: u64 = 0;
+    for chunk in [vec![1; 507 * 200], vec![1; 507]] {
+        while filler.write_file(&chunk, filled).is_ok() {
+            filled += chunk.len() as u64;
+        }
+    }
+
+    // Can't go anywhere, but it's still there.
+    assert_eq!(fs.flush(request(), path, handle, 0), Err(NO_SPACE));
+    assert_eq!(size_in_pool("held.txt"), 0);
+    assert_eq!(fs.getattr(request(), path, Some(handle)).unwrap().1.size, 100);
+    assert_eq!(fs.read_bytes(path, handle, 0, 1000).unwrap(), vec![b'h'; 100]);
+
+    // Once there's room, it makes it in.
+    fs.unlink(request(), Path::new("/"), OsStr::new("filler.bin")).unwrap();
+    fs.flush(request(), path, handle, 0).unwrap();
+    assert_eq!(size_in_pool("held.txt"), 100);
+    fs.release(request(), path, handle, 0, 0, false).unwrap();
+}
+
+/// Releasing a handle that can't write out what it's holding keeps it around for a later flush.
+#[test]
+fn released_runs_are_kept() {
+    let fs = get_filesystem();
+    let _ = AUTO_GROW.set(false);
+    let path = Path::new("/closed.txt");
+    let handle = fs.create(request(), Path::new("/"), OsStr::new("closed.txt"), 0, 0).unwrap().fh;
+    let _ = fs.write(request(), path, handle, 0, vec![b'c'; 100], 0).unwrap();
+
+    let filler = Pool::get_root_directory().unwrap().new_file("filler.bin".to_string()).unwrap();
+    let mut filled: u64 = 0;
+    for chunk in [vec![1; 507 * 200], vec![1; 507]] {
+        while filler.write_file(&chunk, filled).is_ok() {
+            filled += chunk.len() as u64;
+        }
+    }
+
+    // The release fails, but the bytes are still there for anyone looking.
+    assert_eq!(fs.release(request(), path, handle, 0, 0, false), Err(NO_SPACE));
+    assert_eq!(size_in_pool("closed.txt"), 0);
+    assert_eq!(fs.getattr(request(), path, None).unwrap().1.size, 100);
+
+    // And they make it in once there's room.
+    fs.unlink(request(), Path::new("/"), OsStr::new("filler.bin")).unwrap();
+    FileHandle::flush_all_writes();
+    assert_eq!(size_in_pool("closed.txt"), 100);
+    let (reader, _) = fs.open(request(), path, libc::O_RDONLY as u32).unwrap();
+    assert_eq!(fs.read_bytes(path, reader, 0, 1000).unwrap(), vec![b'c'; 100]);
+    fs.release(request(), path, reader, 0, 0, false).unwrap();
+}
+
+/// fallocate goes through the handle, and lands on top of anything that was still buffered.
+#[test]
+fn fallocate_through_a_handle() {
+    let fs = get_filesystem();
+    let path = Path::new("/allocated.bin");
+    let handle = fs.create(request(), Path::new("/"), OsStr::new("allocated.bin"), 0, 0).unwrap().fh;
+    let _ = fs.write(request(), path, handle, 0, vec![9; 507 * 4], 0).unwrap();
+
+    let punch = libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE;
+    FlusterFS::fallocate(handle, 507, 507 * 2, punch).unwrap();
+    let mut expected = vec![9; 507 * 4];
+    expected[507..507 * 3].fill(0);
+    assert_eq!(fs.read_bytes(path, handle, 0, 507 * 4).unwrap(), expected);
+
+    // Growing the file, or not.
+    FlusterFS::fallocate(handle, 0, 507 * 8, libc::FALLOC_FL_KEEP_SIZE).unwrap();
+    assert_eq!(size_in_pool("allocated.bin"), 507 * 4);
+    FlusterFS::fallocate(handle, 0, 507 * 8, 0).unwrap();
+    assert_eq!(size_in_pool("allocated.bin"), 507 * 8);
+
+    // Things we don't do.
+    assert_eq!(FlusterFS::fallocate(handle, 0, 10, libc::FALLOC_FL_PUNCH_HOLE), Err(UNSUPPORTED));
+    assert_eq!(FlusterFS::fallocate(handle, 0, 10, libc::FALLOC_FL_ZERO_RANGE), Err(UNSUPPORTED));
+    assert_eq!(FlusterFS::fallocate(handle, 0, 0, 0), Err(INVALID_ARGUMENT));
+    fs.release(request(), path, handle, 0, 0, false).unwrap();
+}
+
+/// A read that had to be looked up twice should only count once towards spotting sequential reads.
+#[test]
+fn retried_reads_count_once() {
+    let fs = get_filesystem();
+    let path = Path::new("/retried.bin");
+    let handle = fs.create(request(), Path::new("/"), OsStr::new("retried.bin"), 0, 0).unwrap().fh;
+    let _ = fs.write(request(), path, handle, 0, vec![7; 4096], 0).unwrap();
+    assert_eq!(fs.read_bytes(path, handle, 0, 100).unwrap().len(), 100);
+
+    // Like a cache-only attempt that missed, then the real one.
+    let _ = fs.fetch_bytes(path, handle, 100, 100).unwrap();
+    let mut read = fs.fetch_bytes(path, handle, 100, 100).unwrap();
+    FlusterFS::note_finished_read(handle, 100, &mut read);
+    assert_eq!(read.ahead, Some(200));
+}
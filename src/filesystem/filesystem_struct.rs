@@ -2,10 +2,13 @@
 
 // Imports
 
+use crate::pool::disk::standard_disk::block::directory::directory_struct::DirectoryItem;
 use crate::pool::pool_actions::pool_struct::Pool;
+use crate::tui::prompt_script::prompt_script_struct::PromptSource;
 use std::{
     path::PathBuf,
     sync::{Arc, Mutex, OnceLock},
+    time::Duration,
 };
 // Structs, Enums, Flags
 
@@ -31,6 +34,46 @@ lazy_static! {
 pub(crate) static WRITE_BACKUPS: OnceLock<bool> = OnceLock::new();
 // TUI cannot be disabled mid run.
 pub(crate) static USE_TUI: OnceLock<bool> = OnceLock::new();
+// Mounting never formats anything, only `mkfs` sets this.
+/// Allowed to turn a blank disk into a brand new pool.
+pub(crate) static FORMAT_POOL: OnceLock<bool> = OnceLock::new();
+// Dedup is set on mount, or by the pool if it already uses it. Already shared blocks are respected even when this is off.
+/// Look for identical data blocks when writing files.
+pub(crate) static ENABLE_DEDUP: OnceLock<bool> = OnceLock::new();
+// Trash is set on mount, or by the pool if it already uses it. Whatever is already in the trash can still be restored when this is off.
+/// Move deleted files and directories into the trash instead of freeing them.
+pub(crate) static ENABLE_TRASH: OnceLock<bool> = OnceLock::new();
+// Checksums are picked when a file is made, and default to on if the pool already uses them. Files that already have one keep it up to date either way.
+/// Give new files a whole-file checksum.
+pub(crate) static ENABLE_CHECKSUMS: OnceLock<bool> = OnceLock::new();
+// Read from way down in the block reading code, which has no idea what options it was started with.
+/// Fix one or two flipped bits in blocks that fail their CRC check, instead of giving up on them.
+pub(crate) static ENABLE_ERROR_CORRECTION: OnceLock<bool> = OnceLock::new();
+// The cache is built the first time it's used, so it can't be resized afterwards.
+/// How many blocks the cache can hold across all of its tiers.
+pub(crate) static CACHE_BLOCKS: OnceLock<usize> = OnceLock::new();
+/// How the cache is split between tiers 0, 1 and 2.
+pub(crate) static CACHE_TIER_RATIOS: OnceLock<[usize; 3]> = OnceLock::new();
+/// How the cache picks which blocks to throw out.
+pub(crate) static CACHE_POLICY: OnceLock<CachePolicyKind> = OnceLock::new();
+/// Where to write down every block the cache is asked for.
+pub(crate) static CACHE_TRACE: OnceLock<PathBuf> = OnceLock::new();
+/// Write every block to disk as soon as it changes, instead of waiting for a flush.
+pub(crate) static WRITE_THROUGH: OnceLock<bool> = OnceLock::new();
+/// Flush the cache once it has held unwritten data for this long.
+pub(crate) static MAX_DIRTY_AGE: OnceLock<Duration> = OnceLock::new();
+/// Where to keep the cache between mounts.
+pub(crate) static CACHE_FILE: OnceLock<PathBuf> = OnceLock::new();
+/// What the pool is called on its disk labels.
+pub(crate) static POOL_NAME: OnceLock<String> = OnceLock::new();
+/// Where to write the disk manifest and labels on unmount.
+pub(crate) static MANIFEST_DIRECTORY: OnceLock<PathBuf> = OnceLock::new();
+/// How long a prompt script gets to answer before we move on without it.
+pub(crate) static PROMPT_TIMEOUT: OnceLock<Duration> = OnceLock::new();
+/// Add new disks to the pool when it runs out of room.
+pub(crate) static AUTO_GROW: OnceLock<bool> = OnceLock::new();
+/// The most disks the pool can have, not counting the pool disk.
+pub(crate) static MAX_DISKS: OnceLock<u16> = OnceLock::new();
 
 /// Options availble at time of pool creation / filesystem load
 pub struct FilesystemOptions {
@@ -46,5 +89,99 @@ pub struct FilesystemOptions {
     pub(super) enable_backup: bool,
     /// Enable the TUI
     #[allow(dead_code)] // it's lying.
-    pub(super) enable_tui: bool
+    pub(super) enable_tui: bool,
+    /// Deduplicate identical data blocks on write.
+    #[allow(dead_code)] // it's lying.
+    pub(super) enable_dedup: bool,
+    /// Send deleted items to the trash.
+    #[allow(dead_code)] // it's lying.
+    pub(super) enable_trash: bool,
+    /// Give new files checksums.
+    #[allow(dead_code)] // it's lying.
+    pub(super) enable_checksums: bool,
+    /// Fix blocks with a few flipped bits.
+    #[allow(dead_code)] // it's lying.
+    pub(super) enable_error_correction: bool,
+    /// Total cache size in blocks, if not the default.
+    #[allow(dead_code)] // it's lying.
+    pub(super) cache_blocks: Option<usize>,
+    /// Write blocks to disk immediately instead of holding them in the cache.
+    #[allow(dead_code)] // it's lying.
+    pub(super) write_through: bool,
+    /// Longest unwritten data can sit in the cache, if there is a limit.
+    #[allow(dead_code)] // it's lying.
+    pub(super) max_dirty_age: Option<Duration>,
+    /// Where the cache is saved between mounts, if anywhere.
+    #[allow(dead_code)] // it's lying.
+    pub(super) cache_file: Option<PathBuf>,
+    /// How the cache picks which blocks to throw out.
+    #[allow(dead_code)] // it's lying.
+    pub(super) cache_policy: CachePolicyKind,
+    /// Where cache accesses are recorded, if anywhere.
+    #[allow(dead_code)] // it's lying.
+    pub(super) cache_trace: Option<PathBuf>,
+    /// What the pool is called on its disk labels, if not the default.
+    #[allow(dead_code)] // it's lying.
+    pub(super) pool_name: Option<String>,
+    /// Where the disk manifest and labels are written on unmount, if anywhere.
+    #[allow(dead_code)] // it's lying.
+    pub(super) manifest_directory: Option<PathBuf>,
+    /// Where prompts are sent instead of the TUI, if anywhere.
+    #[allow(dead_code)] // it's lying.
+    pub(super) prompt_script: Option<PromptSource>,
+    /// How long the prompt script gets to answer, if there is a limit.
+    #[allow(dead_code)] // it's lying.
+    pub(super) prompt_timeout: Option<Duration>,
+    /// Add disks when the pool fills up.
+    #[allow(dead_code)] // it's lying.
+    pub(super) auto_grow: bool,
+    /// The most disks the pool can grow to, if there is a limit.
+    #[allow(dead_code)] // it's lying.
+    pub(super) max_disks: Option<u16>,
+}
+
+/// The ways the cache can pick which blocks to throw out when it's full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CachePolicyKind {
+    /// Three tiers, blocks move up a tier when they're read again, and all of tier 0 is thrown out at once.
+    #[default]
+    Tiered,
+    /// Adaptive replacement (ARC). Keeps blocks read once apart from blocks read a lot, and remembers
+    /// recently thrown out blocks to work out how much room each side should get.
+    Adaptive,
+}
+
+/// How a cache policy did when replaying a recorded trace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachePolicyReport {
+    /// The policy that was replayed.
+    pub policy: CachePolicyKind,
+    /// Reads answered by the cache.
+    pub hits: u64,
+    /// Reads that had to go to a disk.
+    pub misses: u64,
+    /// Hits out of all reads, from 0 to 1.
+    pub hit_rate: f64,
+    /// How many times the disk in the drive had to be changed, including writing out whatever
+    /// was left at the end.
+    pub swaps: u64,
+}
+
+/// A read that came back, along with whatever it still needs done afterwards.
+///
+/// Reads can be retried inside the IO gate, so a handle's read pattern and running checksum are only
+/// fed once the attempt that actually gets returned is known.
+pub(crate) struct FinishedRead {
+    /// What was read.
+    pub(crate) bytes: Vec<u8>,
+    /// The file it came out of. None for control items.
+    pub(crate) file: Option<DirectoryItem>,
+    /// How big the file was when it was read.
+    pub(crate) file_size: u64,
+    /// The checksum the file should have, if it has one.
+    pub(crate) expected_checksum: Option<u64>,
+    /// The checksum of the whole file, if this read finished reading all of it in order.
+    pub(crate) whole_checksum: Option<u64>,
+    /// Where readahead should start from, if it should.
+    pub(crate) ahead: Option<u64>,
 }
@@ -10,14 +10,25 @@
 
 use std::{ffi::OsStr, path::Path, time::Duration};
 
-use fuse_mt::{DirectoryEntry, FileAttr, FileType, FilesystemMT, Statfs};
+use fuse_mt::{DirectoryEntry, FileAttr, FileType, FilesystemMT, Statfs, Xattr};
 use log::{debug, error, info, warn};
 use rand::Rng;
 
 use crate::{
     filesystem::{
-        filesystem_struct::FlusterFS,
-        item_flag::flag_struct::ItemFlag
+        control_dir::control_dir_struct::ControlItem,
+        drive_scheduler::drive_scheduler_struct::DriveScheduler,
+        filesystem_struct::{
+            FinishedRead,
+            FlusterFS,
+            CACHE_FILE,
+            MANIFEST_DIRECTORY
+        },
+        item_flag::flag_struct::ItemFlag,
+        snapshot_dir::snapshot_dir_struct::{
+            SnapshotDir,
+            SnapshotView
+        }
     },
     pool::{disk::{
         generic::io::cache::cache_io::CachedBlockIO,
@@ -25,15 +36,23 @@ use crate::{
             directory::directory_struct::{
                 DirectoryBlock, DirectoryItem, DirectoryItemFlags
             },
-            io::directory::types::NamedItem
+            io::{
+                directory::types::NamedItem,
+                file::checksum::CHECKSUM_XATTR
+            }
         }
-    }, pool_actions::pool_struct::{Pool, GLOBAL_POOL}}, tui::{notify::NotifyTui, prompts::TuiPrompt, tasks::TaskType}
+    }, manifest::manifest_methods::update_manifest, placement::placement_struct::{
+        Placement,
+        PlacementGuard,
+        PLACEMENT_XATTR
+    }, pool_actions::pool_struct::{Pool, GLOBAL_POOL}, trash::trash_struct::Trash}, tui::{notify::NotifyTui, prompts::TuiPrompt, tasks::TaskType}
 };
 
 use super::file_handle::file_handle_struct::FileHandle;
 use crate::error_types::filesystem::*;
 
 use fuse_mt::CreatedEntry;
+use libc::c_int;
 
 
 
@@ -106,6 +125,16 @@ impl FilesystemMT for FlusterFS {
             true
         );
         info!("Shutting down filesystem...");
+        // Nobody is left to hear about errors at this point, but they're logged.
+        FileHandle::flush_all_writes();
+        // Keep the cache around for next time if we were asked to. This flushes it too.
+        if let Some(path) = CACHE_FILE.get() {
+            info!("Saving cache...");
+            match CachedBlockIO::save_to_file(path) {
+                Ok(saved) => info!("Saved {saved} blocks to the cache file."),
+                Err(error) => warn!("Couldn't save the cache, next mount will start cold. Error: {error}"),
+            }
+        }
         // Flush all of the tiers of cache.
         info!("Flushing cache...");
         // We dont retry flushing the cache, since if the flushing fails, that means it went all the way through
@@ -116,6 +145,12 @@ impl FilesystemMT for FlusterFS {
         info!("Flushing pool info...");
         // Same story here.
         Pool::flush().expect("I sure hope pool flushing works!");
+        // Nothing moves after this, so the manifest is as up to date as it'll get.
+        if let Some(directory) = MANIFEST_DIRECTORY.get() {
+            info!("Writing the disk manifest...");
+            update_manifest(directory);
+        }
+        info!("Scheduling saved `{}` disk swaps this session.", DriveScheduler::swaps_avoided());
         info!("Goodbye! .o/");
     }
 
@@ -128,6 +163,18 @@ impl FilesystemMT for FlusterFS {
     ) -> fuse_mt::ResultEntry {
         debug!("Getting attributes of `{}`...", path.display());
 
+        // The control directory isn't on disk, and changes constantly, so it is never cached.
+        if ControlItem::is_control_path(path) {
+            return Ok((Duration::ZERO, ControlItem::from_path(path)?.attributes()));
+        }
+        // Neither is the snapshot directory, and snapshots come and go.
+        if SnapshotDir::is_snapshot_dir(path) {
+            return Ok((Duration::ZERO, SnapshotDir::attributes()));
+        }
+        // Anything inside of a snapshot is looked up from that snapshot's root.
+        let view = SnapshotView::enter(path)?;
+        let path: &Path = view.path();
+
         // I already wrote a method for this yay
         // but that assumes we have a handle.
         if let Some(handle) = fh {
@@ -149,6 +196,7 @@ impl FilesystemMT for FlusterFS {
         // We cant just use the TryInto FileAttr since we dont know for sure if the item exists yet.
         let temp_handle: FileHandle = FileHandle {
             path: path.into(),
+            item: None,
         };
 
         // Go get the item.
@@ -202,6 +250,14 @@ impl FilesystemMT for FlusterFS {
         size: u64,
     ) -> fuse_mt::ResultEmpty {
         debug!("Truncating `{}` to be `{}` bytes long...", path.display(), size);
+        if ControlItem::is_control_path(path) {
+            return ControlItem::from_path(path)?.truncate();
+        }
+        SnapshotDir::check_writable(path)?;
+        // Buffered writes past the new end would grow the file right back.
+        FileHandle::flush_all_writes();
+        // Growing a file allocates, which should follow the placement of its directory.
+        let _placement = PlacementGuard::enter(path.parent().unwrap_or(Path::new("/")))?;
         let task_handle = NotifyTui::start_task(
             TaskType::FilesystemTruncateFile(
                 path.file_name().unwrap_or(OsStr::new("?")).display().to_string()
@@ -219,6 +275,7 @@ impl FilesystemMT for FlusterFS {
             // Temp handle that we will not allocate.
             FileHandle {
                 path: path.into(),
+                item: None,
             }
         };
 
@@ -293,6 +350,14 @@ impl FilesystemMT for FlusterFS {
         _mode: u32, // Permission bit related. Do not need.
     ) -> fuse_mt::ResultEntry {
         debug!("Creating new directory in `{}` named `{}`.", parent.display(), name.display());
+        // Making a directory in the snapshot directory takes a snapshot.
+        if SnapshotDir::is_snapshot_dir(parent) {
+            FileHandle::flush_all_writes();
+            return Ok((Duration::ZERO, SnapshotDir::take(name)?));
+        }
+        ControlItem::check_creatable(&parent.join(name))?;
+        SnapshotDir::check_creatable(&parent.join(name))?;
+        let _placement = PlacementGuard::enter(parent)?;
         let handle = NotifyTui::start_task(TaskType::FilesystemMakeDirectory(name.display().to_string()), 3);
         // Make sure the name isn't too long
         if name.len() > 255 {
@@ -352,12 +417,17 @@ impl FilesystemMT for FlusterFS {
         name: &std::ffi::OsStr,
     ) -> fuse_mt::ResultEmpty {
         debug!("Deleting file `{}` from directory `{}`...", name.display(), parent.display());
+        if ControlItem::is_control_path(&parent.join(name)) {
+            return Err(NOT_PERMITTED);
+        }
+        SnapshotDir::check_writable(&parent.join(name))?;
 
         let handle = NotifyTui::start_task(TaskType::FilesystemDeleteFile(name.display().to_string()), 3);
 
         // Make a fake handle to lookup the file we are looking for
         let temp_handle: FileHandle = FileHandle {
             path: parent.join(name).into(),
+            item: None,
         };
 
         // This will return properly if the item did not exist.
@@ -371,6 +441,18 @@ impl FilesystemMT for FlusterFS {
             NotifyTui::cancel_task(handle);
             return Err(NOT_A_DIRECTORY);
         };
+
+        // Keep it around instead, if we're doing that.
+        if Trash::enabled() {
+            debug!("Moving file to the trash...");
+            if Trash::throw_away(parent, file.into())?.is_some() {
+                NotifyTui::complete_multiple_task_steps(&handle, 2);
+                NotifyTui::finish_task(handle);
+                return Ok(());
+            }
+            NotifyTui::cancel_task(handle);
+            return Err(NO_SUCH_ITEM);
+        }
         
         // Now we need the parent directory block to perform the removal
         debug!("Looking for file...");
@@ -409,16 +491,24 @@ impl FilesystemMT for FlusterFS {
         name: &std::ffi::OsStr,
     ) -> fuse_mt::ResultEmpty {
         debug!("Attempting to remove directory `{}` from `{}`...", name.display(), parent.display());
+        if ControlItem::is_control_path(&parent.join(name)) {
+            return Err(NOT_PERMITTED);
+        }
+        // Removing a directory from the snapshot directory deletes that snapshot.
+        if SnapshotDir::is_snapshot_dir(parent) {
+            return SnapshotDir::delete(name);
+        }
+        SnapshotDir::check_writable(&parent.join(name))?;
 
         let handle = NotifyTui::start_task(TaskType::FilesystemRemoveDirectory(name.display().to_string()), 4);
 
         let string_name: String = name.to_str().expect("Should be valid utf8").to_string();
 
         // Open the parent directory
-        if let Some(parent_dir) = DirectoryBlock::try_find_directory(Some(parent))? {
+        if let Some(mut parent_dir) = DirectoryBlock::try_find_directory(Some(parent))? {
             NotifyTui::complete_task_step(&handle);
             // Parent exists, get the child
-            if let Some(child_dir) = parent_dir.find_item(&NamedItem::Directory(string_name))? {
+            if let Some(child_dir) = parent_dir.find_item(&NamedItem::Directory(string_name.clone()))? {
                 NotifyTui::complete_task_step(&handle);
                 // Directory exists.
                 
@@ -442,9 +532,33 @@ impl FilesystemMT for FlusterFS {
                     return Err(DIRECTORY_NOT_EMPTY);
                 }
                 
+                // Keep it around instead, if we're doing that.
+                if Trash::enabled() {
+                    debug!("Moving directory to the trash...");
+                    drop(parent_dir);
+                    if Trash::throw_away(parent, child_dir.into())?.is_none() {
+                        NotifyTui::cancel_task(handle);
+                        return Err(NO_SUCH_ITEM);
+                    }
+                    NotifyTui::complete_task_step(&handle);
+                    NotifyTui::finish_task(handle);
+                    return Ok(());
+                }
+
+                // Open directories can't be freed out from under their handles, and POSIX lets us refuse.
+                if FileHandle::is_open(&child_dir) {
+                    debug!("Directory is open, cannot delete.");
+                    NotifyTui::cancel_task(handle);
+                    return Err(BUSY);
+                }
+
                 // Run the deletion.
+                // The directory has to come out of the parent first, or the parent would still point at it.
                 debug!("Deleting directory...");
-                block_to_delete.delete_self(child_dir)?;
+                let extracted: DirectoryItem = parent_dir
+                    .find_and_extract_item(&NamedItem::Directory(string_name))?
+                    .ok_or(NO_SUCH_ITEM)?;
+                block_to_delete.delete_self(extracted)?;
                 NotifyTui::complete_task_step(&handle);
                 NotifyTui::finish_task(handle);
                 debug!("Done.");
@@ -487,6 +601,13 @@ impl FilesystemMT for FlusterFS {
     ) -> fuse_mt::ResultEmpty {
         debug!("Renaming a item from `{}` to `{}`,", name.display(), newname.display());
         debug!("and moving from `{}` to `{}`.", parent.display(), newparent.display());
+        if ControlItem::is_control_path(&parent.join(name)) || ControlItem::is_control_path(&newparent.join(newname)) {
+            return Err(NOT_PERMITTED);
+        }
+        SnapshotDir::check_writable(&parent.join(name))?;
+        SnapshotDir::check_writable(&newparent.join(newname))?;
+        // The new parent might need to grow to fit the item.
+        let _placement = PlacementGuard::enter(newparent)?;
 
         // According to the man pages, we should get some flags here. but we dont.
         // I assume things like RENAME_NOREPLACE are being handled for us then.
@@ -509,9 +630,8 @@ impl FilesystemMT for FlusterFS {
         // why word it like this lmao
         // if the destination already exists, but the move fails, keep what was already at the destination.
 
-        // Also in theory, we should be checking if anyone is reading this item and if they are, return busy.
-        // but there isnt any infra for that yet, and with the one year timeouts, you would have to wait a while.
-        // fun. we will ignore it until something explodes.
+        // Handles follow the inode, not the path, so open items can be moved freely. Files replaced while open
+        // stick around until they're closed, but a directory being replaced while it's open gets EBUSY.
 
 
 
@@ -531,12 +651,14 @@ impl FilesystemMT for FlusterFS {
         // Where we're coming from (including name of file/folder)
         let source_full_temp_handle: FileHandle = FileHandle {
             path: parent.join(name).into(),
+            item: None,
         };
 
         // Where we want to go
         // Where we're coming from (including name of file/folder)
         let destination_full_temp_handle: FileHandle = FileHandle {
             path: newparent.join(newname).into(),
+            item: None,
         };
 
         // If they are the same, we dont need to do anything at all.
@@ -945,7 +1067,11 @@ impl FilesystemMT for FlusterFS {
             if let Some(item) = maybe_destination_directory_item {
                 // Destination has to be empty
                 debug!("Destination already existed, making sure its empty...");
-                if item.clone().get_directory_block()?.is_empty()? {
+                if FileHandle::is_open(&item) {
+                    // Someone is looking at it, and directories can't be kept around after deletion.
+                    warn!("Destination directory is open, cannot replace it.");
+                    return Err(BUSY);
+                } else if item.clone().get_directory_block()?.is_empty()? {
                     // All good, we will delete the directory since we are going to replace it.
                     debug!("It's empty, it will be deleted soon");
                 } else {
@@ -975,9 +1101,9 @@ impl FilesystemMT for FlusterFS {
             // We have to tread lightly at this point. If the swap fails, we would lose data.
             // 🤓 erm actually the data would still be there, just not referenced- SHUT UP
 
-            // Extract, and delete. Extraction cleans up anything this used to point to for us.
+            // Extract it now, and delete it once the source is safely in its spot.
             debug!("Extracting destination...");
-            let _extracted_dest = match destination_parent_dir.find_and_extract_item(&NamedItem::Directory(destination_item_name.clone())) {
+            let extracted_dest = match destination_parent_dir.find_and_extract_item(&NamedItem::Directory(destination_item_name.clone())) {
                 Ok(ok) => {
                     // Directory had to've been there, right?
                     if let Some(worked) = ok {
@@ -1038,6 +1164,11 @@ impl FilesystemMT for FlusterFS {
                 },
             }
             debug!("Insertion succeeded.");
+
+            // Nothing points at the old destination anymore, and it's empty, so it can go.
+            debug!("Deleting old destination...");
+            let old_destination: DirectoryBlock = extracted_dest.get_directory_block()?;
+            old_destination.delete_self(extracted_dest)?;
             
             // Now that the data has been safely pointed at from the new location, we will remove the old reference to it.
             debug!("Removing old source...");
@@ -1109,6 +1240,28 @@ impl FilesystemMT for FlusterFS {
         flags: u32,
     ) -> fuse_mt::ResultOpen {
         debug!("Opening item at path `{}`...", path.display());
+        // Control items still get a handle, since the kernel wants one.
+        if ControlItem::is_control_path(path) {
+            let item = ControlItem::from_path(path)?;
+            let new_handle: u64 = FileHandle {
+                path: path.into(),
+                item: None,
+            }.allocate();
+            return Ok((new_handle, item.open_flags()));
+        }
+        if SnapshotDir::is_snapshot_dir(path) {
+            let new_handle: u64 = FileHandle {
+                path: path.into(),
+                item: None,
+            }.allocate();
+            return Ok((new_handle, 0));
+        }
+        // Snapshots can only be read.
+        if SnapshotDir::is_snapshot_path(path) && flags as i32 & libc::O_ACCMODE != libc::O_RDONLY {
+            return Err(READ_ONLY);
+        }
+        let view = SnapshotView::enter(path)?;
+        let path: &Path = view.path();
         let task_handle = NotifyTui::start_task(TaskType::FilesystemOpenFile(
             path.file_name().unwrap_or(OsStr::new("?")).display().to_string()
         ), 4);
@@ -1128,6 +1281,7 @@ impl FilesystemMT for FlusterFS {
         // already open somewhere else.
         let mut handle: FileHandle = FileHandle {
             path: path.into(),
+            item: None,
         };
 
         // We do not allocate the file handle until we are sure we will use it.
@@ -1214,7 +1368,9 @@ impl FilesystemMT for FlusterFS {
         }
         
         // We are done creating/loading the file, its time to get a handle.
+        // From here on, the handle follows this item wherever it goes.
         debug!("Getting a handle on things...");
+        handle.item = Some(found_item);
         let new_handle: u64 = handle.allocate();
 
         NotifyTui::complete_task_step(&task_handle);
@@ -1240,104 +1396,9 @@ impl FilesystemMT for FlusterFS {
         size: u32,
         callback: impl FnOnce(fuse_mt::ResultSlice<'_>) -> fuse_mt::CallbackResult,
     ) -> fuse_mt::CallbackResult {
-        debug!("Reading `{}` bytes from file `{}`", size, path.display());
-
-        let task_handle = NotifyTui::start_task(
-            TaskType::FilesystemReadFile(
-                path.file_name()
-                .unwrap_or(OsStr::new("?")).display().to_string()
-            ),
-            3
-        );
-
-        // Open the file handle
-        let got_handle = FileHandle::read(fh);
-
-        // Still not sure if we need to check this, but whatever.
-        if got_handle.path != path.into() {
-            // They aren't the same? not sure what to do with that
-            error!("readdir() tried to read a path, but provided a handle to a different path.");
-            error!("fh: `{}` | path: `{}`", got_handle.path.display(), path.display());
-            error!("Not sure what to do here, giving up.");
-            NotifyTui::cancel_task(task_handle);
-            return callback(Err(GENERIC_FAILURE));
-        }
-        
-        // Try finding the directory item
-        let file = match got_handle.get_directory_item() {
-            Ok(ok) => ok,
-            Err(err) => {
-                // Getting the item failed, maybe it wasn't there.
-                NotifyTui::cancel_task(task_handle);
-                return callback(Err(err))
-            },
-        };
-        NotifyTui::complete_task_step(&task_handle);
-        
-        // Make sure that it's a file.
-        if file.flags.contains(DirectoryItemFlags::IsDirectory) {
-            // Can't read a directory!
-            warn!("Tried to read a directory as a file. Ignoring...");
-            return callback(Err(IS_A_DIRECTORY));
-        }
-        
-        // Found a file!
-        // We need to bound our read by the size of the file, since the read() filesystem call can
-        // try to read past the end.
-        let file_size = match file.get_size() {
-            Ok(ok) => ok,
-            Err(error) => {
-                // Lower level error
-                NotifyTui::cancel_task(task_handle);
-                warn!("Failed to get size of file! Giving up...");
-                return callback(Err(error.into()))
-            },
-        };
-
-        NotifyTui::complete_task_step(&task_handle);
-        
-        // Subtract the offset to idk man why am i explaining this im sure you understand.
-        // Reads are limited to 4GB long, which should be way above our max read size anyways.
-
-        // If a read bigger than that comes in, we'll ignore it.
-        let checking_size = std::cmp::min(size as u64, file_size - offset);
-        let bounded_read_length = if checking_size > u32::MAX.into() {
-            // Cant do that.
-
-            // Also if you're here, that means the file you're trying to read is actually >4GB.
-            // You are insane.
-
-            warn!("Tried to read more than 4GB at once!");
-            NotifyTui::cancel_task(task_handle);
-            return callback(Err(INVALID_ARGUMENT));
-        } else {
-            // Size checked, this cast is safe.
-            checking_size as u32
-        };
-
-        if bounded_read_length != size {
-            // size did change.
-            debug!("Read was too large, truncated to `{bounded_read_length}` bytes.");
-        }
-
-        // Do the read.
-        // This vec might be HUGE, this is why we need to limit the read size on the filesystem.
-        debug!("Starting read...");
-        let read_buffer: Vec<u8> = match file.read_file(offset, bounded_read_length) {
-            Ok(ok) => ok,
-            Err(error) => {
-                // Lower level error
-                warn!("Failed while reading the file! Giving up...");
-                NotifyTui::cancel_task(task_handle);
-                return callback(Err(error.into()))
-            },
-        };
-        NotifyTui::complete_task_step(&task_handle);
-        debug!("Read finished.");
-        NotifyTui::finish_task(task_handle);
-
-        // All done!
-        callback(Ok(&read_buffer))
+        // The actual reading happens elsewhere, so it can be retried without the callback.
+        let read_buffer = self.read_bytes(path, fh, offset, size);
+        callback(read_buffer.as_deref().map_err(|error| *error))
     }
 
     // Write data to a file using a file handle.
@@ -1351,6 +1412,12 @@ impl FilesystemMT for FlusterFS {
         _flags: u32, // hehe
     ) -> fuse_mt::ResultWrite {
         debug!("Writing `{}` bytes to file `{}`...", data.len(), path.display());
+        if ControlItem::is_control_path(path) {
+            // Control items can look at the whole pool.
+            FileHandle::flush_all_writes();
+            return ControlItem::from_path(path)?.write(&data);
+        }
+        SnapshotDir::check_writable(path)?;
         let task_handle = NotifyTui::start_task(
             TaskType::FilesystemWriteFile(
                 path.file_name()
@@ -1359,21 +1426,12 @@ impl FilesystemMT for FlusterFS {
             2
         );
 
-        // Open the file handle
+        // Open the file handle.
+        // The path may not match the one it was opened with anymore, but the handle knows what it's pointing at.
         let got_handle = FileHandle::read(fh);
 
-        // Still not sure if we need to check this, but whatever.
-        if got_handle.path != path.into() {
-            // They aren't the same? not sure what to do with that
-            error!("readdir() tried to read a path, but provided a handle to a different path.");
-            error!("fh: `{}` | path: `{}`", got_handle.path.display(), path.display());
-            error!("Not sure what to do here, giving up.");
-            NotifyTui::cancel_task(task_handle);
-            return Err(GENERIC_FAILURE);
-        }
-
-        // Try finding the directory item
-        let file = got_handle.get_directory_item()?;
+        // Make sure the item is still there.
+        let _ = got_handle.get_directory_item()?;
         NotifyTui::complete_task_step(&task_handle);
         
         // man page:
@@ -1396,8 +1454,10 @@ impl FilesystemMT for FlusterFS {
 
 
         // Now write to the file!
+        // Writes are buffered on the handle, and only hit the pool in big runs. The allocation and
+        // inode update happen then, not on every little write from the kernel.
         debug!("Starting write...");
-        let bytes_written = file.write_file(&data, offset)?;
+        let bytes_written = FileHandle::buffer_write(fh, offset, &data)?;
         NotifyTui::complete_task_step(&task_handle);
         debug!("Write completed.");
 
@@ -1410,12 +1470,12 @@ impl FilesystemMT for FlusterFS {
         Ok(bytes_written)
     }
 
-    // Flushing does not do anything, since we manually handle our caching.
+    // Flushing only writes out what the handle has buffered, since we manually handle our caching.
     fn flush(
         &self,
         _req: fuse_mt::RequestInfo,
         _path: &std::path::Path,
-        _fh: u64,
+        fh: u64,
         _lock_owner: u64,
     ) -> fuse_mt::ResultEmpty {
 
@@ -1423,8 +1483,10 @@ impl FilesystemMT for FlusterFS {
         // We are responsible for tracking how stale the cache is.
 
         // The only time we will flush the cache from this level is on shutdown.
-        
-        Ok(())
+
+        // Buffered writes still need to make it into the cache though, and this is called on every close(),
+        // so it's the last chance to tell the caller that their writes didn't work.
+        FileHandle::flush_writes(fh)
     }
 
     // Releasing a file handle.
@@ -1437,8 +1499,7 @@ impl FilesystemMT for FlusterFS {
         _lock_owner: u64,
         _flush: bool,
     ) -> fuse_mt::ResultEmpty {
-        FileHandle::drop_handle(fh);
-        Ok(())
+        FileHandle::drop_handle(fh)
     }
 
     // See flush()
@@ -1446,10 +1507,10 @@ impl FilesystemMT for FlusterFS {
         &self,
         _req: fuse_mt::RequestInfo,
         _path: &std::path::Path,
-        _fh: u64,
+        fh: u64,
         _datasync: bool,
     ) -> fuse_mt::ResultEmpty {
-        Ok(())
+        FileHandle::flush_writes(fh)
     }
 
     // Open a directory and get a handle to it
@@ -1483,6 +1544,16 @@ impl FilesystemMT for FlusterFS {
         fh: u64,
     ) -> fuse_mt::ResultReaddir {
         debug!("Getting contents of directory `{}`...", path.display());
+        // The root never lists the control directory, since it isn't in the root's directory block.
+        if ControlItem::is_control_path(path) {
+            return ControlItem::from_path(path)?.list();
+        }
+        // Same goes for the snapshot directory.
+        if SnapshotDir::is_snapshot_dir(path) {
+            return Ok(SnapshotDir::list());
+        }
+        let view = SnapshotView::enter(path)?;
+        let path: &Path = view.path();
 
         let task_handle = NotifyTui::start_task(TaskType::FilesystemReadDirectory(
             path.file_name().unwrap_or(OsStr::new("?")).display().to_string()
@@ -1490,19 +1561,9 @@ impl FilesystemMT for FlusterFS {
             4
         );
 
-        // Make sure the file handle and the incoming path are the same. I assume they should be, but
-        // cant hurt to check.
+        // The directory may have been moved since it was opened, but the handle still points at it.
         let got_handle = FileHandle::read(fh);
         
-        if got_handle.path != path.into() {
-            // They aren't the same? not sure what to do with that
-            error!("readdir() tried to read a path, but provided a handle to a different path.");
-            error!("fh: `{}` | path: `{}`", got_handle.path.display(), path.display());
-            error!("Not sure what to do here, giving up.");
-            NotifyTui::cancel_task(task_handle);
-            return Err(GENERIC_FAILURE);
-        }
-        
         // Since we have a handle, getting the directory is easy.
         debug!("Getting the directory item from handle...");
         let dir_item: DirectoryItem = if let Ok(exists) = got_handle.get_directory_item() {
@@ -1538,8 +1599,8 @@ impl FilesystemMT for FlusterFS {
         let items = dir_block.list()?;
         NotifyTui::complete_task_step(&task_handle);
         
-        // Now pull out the names and types
-        let mut listed_items: Vec<DirectoryEntry> = items.iter().map(|item| {
+        // Now pull out the names and types, skipping the trash.
+        let mut listed_items: Vec<DirectoryEntry> = items.iter().filter(|item| !Trash::is_trash(item)).map(|item| {
             let kind = if item.flags.contains(DirectoryItemFlags::IsDirectory) {
                 FileType::Directory
             } else {
@@ -1576,8 +1637,7 @@ impl FilesystemMT for FlusterFS {
         fh: u64,
         _flags: u32,
     ) -> fuse_mt::ResultEmpty {
-        FileHandle::drop_handle(fh);
-        Ok(())
+        FileHandle::drop_handle(fh)
     }
 
     // See flush()
@@ -1599,7 +1659,7 @@ impl FilesystemMT for FlusterFS {
         // Get the pool header, we need it for disk counts and such.
         // If this doesnt work, just tell the caller to try again later.
 
-        let pool = if let Ok(pool_inner) = GLOBAL_POOL.get().expect("Global pool should be created at this stage!").try_lock() {
+        let pool = if let Ok(pool_inner) = GLOBAL_POOL.get().expect("Global pool should be created at this stage!").lock() {
             pool_inner.header
         } else {
             // Lock failed.
@@ -1607,24 +1667,28 @@ impl FilesystemMT for FlusterFS {
             return Err(TRY_AGAIN)
         };
 
-        // Number of blocks on the device is just the highest disk*2880
-        let blocks: u64 = pool.highest_known_disk as u64 * 2880;
+        // Only standard disks hold everything else, dense disks are taken up by a single file each.
+        // Each of those loses its header block, and the rest of the metadata comes out of the same free space.
+        let dense_disks: u64 = pool.dense_disks.iter().filter(|disk| **disk != 0).count() as u64;
+        let standard_disks: u64 = u64::from(pool.highest_known_disk).saturating_sub(dense_disks);
+        let overhead: u64 = pool.metadata_blocks();
+        let blocks: u64 = (standard_disks * 2879).saturating_sub(overhead);
 
         // We know how many blocks are free.
         // Not sure how Linux reacts to disks that grow on the fly, but
         // it seems like a likely enough use-case that this should be fine...
-        let bfree: u64 = pool.pool_standard_blocks_free.into();
+        // Anything still buffered is counted as if it was already written, so it doesn't look like there's
+        // more room than there is.
+        let bfree: u64 = u64::from(pool.pool_standard_blocks_free).min(blocks).saturating_sub(FileHandle::buffered_blocks());
 
         // The number of available blocks is the same as the number of free blocks.
         let bavail = bfree;
 
-        // Knowing how many files exist is a process that would take a VERY long time to figure out
-        // typically. This seems like a bad idea to actually look up on the fly.
-        // Just say there's a few.
-        let files: u64 = 985;
+        // Every file and directory has an inode.
+        let files: u64 = pool.items.files + pool.items.directories;
 
-        // We don't have a limit to file inodes, so we always have a lot free.
-        let ffree: u64 = 12345;
+        // There's no limit on inodes, but every new file or directory needs at least one block of its own.
+        let ffree: u64 = bfree;
 
         // Blocks are 512 bytes, technically, but innards of those blocks are limited...
         // Not that huge of a deal for transfers to be aligned, so we'll just say 512 still.
@@ -1650,49 +1714,111 @@ impl FilesystemMT for FlusterFS {
         Ok(stat)
     }
 
-    // Extended attributes are not supported.
-    // fn setxattr(
-    //     &self,
-    //     _req: fuse_mt::RequestInfo,
-    //     _path: &std::path::Path,
-    //     _name: &std::ffi::OsStr,
-    //     _value: &[u8],
-    //     _flags: u32,
-    //     _position: u32,
-    // ) -> fuse_mt::ResultEmpty {
-    //     Err(libc::ENOSYS)
-    // }
+    // The only extended attribute that can be set is the placement of a directory.
+    // Files with a checksum also have that, but it can only be read.
+    fn setxattr(
+        &self,
+        _req: fuse_mt::RequestInfo,
+        path: &std::path::Path,
+        name: &std::ffi::OsStr,
+        value: &[u8],
+        flags: u32,
+        _position: u32, // macOS only.
+    ) -> fuse_mt::ResultEmpty {
+        debug!("Setting extended attribute `{}` on `{}`...", name.display(), path.display());
+        SnapshotDir::check_writable(path)?;
+        if name != PLACEMENT_XATTR {
+            debug!("Unknown attribute.");
+            return Err(UNSUPPORTED);
+        }
+        let placement: Placement = if let Some(parsed) = std::str::from_utf8(value).ok().and_then(Placement::parse) {
+            parsed
+        } else {
+            debug!("Not a valid placement.");
+            return Err(INVALID_ARGUMENT);
+        };
 
-    // Extended attributes are not supported.
-    // fn getxattr(
-    //     &self,
-    //     _req: fuse_mt::RequestInfo,
-    //     _path: &std::path::Path,
-    //     _name: &std::ffi::OsStr,
-    //     _size: u32,
-    // ) -> fuse_mt::ResultXattr {
-    //     Err(libc::ENOSYS)
-    // }
+        // Respect create / replace only.
+        let existing: Option<Placement> = Placement::of_directory(path)?;
+        if flags as i32 & libc::XATTR_CREATE != 0 && existing.is_some() {
+            return Err(ITEM_ALREADY_EXISTS);
+        }
+        if flags as i32 & libc::XATTR_REPLACE != 0 && existing.is_none() {
+            return Err(NO_SUCH_ATTRIBUTE);
+        }
 
-    // Extended attributes are not supported.
-    // fn listxattr(
-    //     &self,
-    //     _req: fuse_mt::RequestInfo,
-    //     _path: &std::path::Path,
-    //     _size: u32,
-    // ) -> fuse_mt::ResultXattr {
-    //     Err(libc::ENOSYS)
-    // }
+        if Placement::set_on_directory(path, Some(placement))? {
+            debug!("Placement set to `{placement}`.");
+            Ok(())
+        } else {
+            // Files and the root directory can't be placed.
+            debug!("Only directories below the root can have a placement.");
+            Err(UNSUPPORTED)
+        }
+    }
 
-    // Extended attributes are not supported.
-    // fn removexattr(
-    //     &self,
-    //     _req: fuse_mt::RequestInfo,
-    //     _path: &std::path::Path,
-    //     _name: &std::ffi::OsStr,
-    // ) -> fuse_mt::ResultEmpty {
-    //     Err(libc::ENOSYS)
-    // }
+    fn getxattr(
+        &self,
+        _req: fuse_mt::RequestInfo,
+        path: &std::path::Path,
+        name: &std::ffi::OsStr,
+        size: u32,
+    ) -> fuse_mt::ResultXattr {
+        debug!("Getting extended attribute `{}` on `{}`...", name.display(), path.display());
+        let view = SnapshotView::enter(path)?;
+        let path: &Path = view.path();
+        if name == CHECKSUM_XATTR {
+            // The checksum has to include anything still buffered. The gate runs this one as a change,
+            // since it writes.
+            FileHandle::flush_all_writes();
+            return checksum_xattr(path, size);
+        }
+        if name != PLACEMENT_XATTR {
+            return Err(NO_SUCH_ATTRIBUTE);
+        }
+        if let Some(placement) = Placement::of_directory(path)? {
+            xattr_reply(placement.to_string().into_bytes(), size)
+        } else {
+            Err(NO_SUCH_ATTRIBUTE)
+        }
+    }
+
+    fn listxattr(
+        &self,
+        _req: fuse_mt::RequestInfo,
+        path: &std::path::Path,
+        size: u32,
+    ) -> fuse_mt::ResultXattr {
+        debug!("Listing extended attributes on `{}`...", path.display());
+        let view = SnapshotView::enter(path)?;
+        let path: &Path = view.path();
+        // Names are null terminated.
+        let mut names: Vec<u8> = Vec::new();
+        if Placement::of_directory(path)?.is_some() {
+            names.extend(PLACEMENT_XATTR.as_bytes());
+            names.push(0);
+        }
+        if let Some(file) = find_file(path)? && file.get_checksum()?.is_some() {
+            names.extend(CHECKSUM_XATTR.as_bytes());
+            names.push(0);
+        }
+        xattr_reply(names, size)
+    }
+
+    fn removexattr(
+        &self,
+        _req: fuse_mt::RequestInfo,
+        path: &std::path::Path,
+        name: &std::ffi::OsStr,
+    ) -> fuse_mt::ResultEmpty {
+        debug!("Removing extended attribute `{}` from `{}`...", name.display(), path.display());
+        SnapshotDir::check_writable(path)?;
+        if name != PLACEMENT_XATTR || Placement::of_directory(path)?.is_none() {
+            return Err(NO_SUCH_ATTRIBUTE);
+        }
+        let _ = Placement::set_on_directory(path, None)?;
+        Ok(())
+    }
 
     // "This call is not required but is highly recommended." Okay then we wont do it muhahaha
     // fn access(
@@ -1714,6 +1840,9 @@ impl FilesystemMT for FlusterFS {
         flags: u32,
     ) -> fuse_mt::ResultCreate {
         debug!("Creating new file named `{}` in `{}`...", name.display(), parent.display());
+        ControlItem::check_creatable(&parent.join(name))?;
+        SnapshotDir::check_creatable(&parent.join(name))?;
+        let _placement = PlacementGuard::enter(parent)?;
 
         let task_handle = NotifyTui::start_task(TaskType::FilesystemCreateFile(name.display().to_string()), 5);
 
@@ -1811,6 +1940,7 @@ impl FilesystemMT for FlusterFS {
         // Construct and return the handle to the new file
         let new_handle: FileHandle = FileHandle {
             path: constructed_path.into(),
+            item: Some(resulting_item.clone()),
         };
 
         // We can get attributes directly from the directory item we just made
@@ -1832,3 +1962,310 @@ impl FilesystemMT for FlusterFS {
         })
     }
 }
+
+// Helpers for the FUSE calls above that don't fit in the trait.
+impl FlusterFS {
+    /// Read bytes out of a file, bounded by the size of the file.
+    ///
+    /// Split out of `read()`, since the callback can only be called once.
+    pub(crate) fn read_bytes(
+        &self,
+        path: &std::path::Path,
+        fh: u64,
+        offset: u64,
+        size: u32,
+    ) -> Result<Vec<u8>, c_int> {
+        let mut read: FinishedRead = self.fetch_bytes(path, fh, offset, size)?;
+        FlusterFS::note_finished_read(fh, offset, &mut read);
+        self.follow_up_read(path, fh, &read)?;
+        Ok(read.bytes)
+    }
+
+    /// The reading part of `read_bytes()`, without updating anything about the handle.
+    ///
+    /// Only looks, so it's safe to retry.
+    pub(crate) fn fetch_bytes(
+        &self,
+        path: &std::path::Path,
+        fh: u64,
+        offset: u64,
+        size: u32,
+    ) -> Result<FinishedRead, c_int> {
+        debug!("Reading `{}` bytes from file `{}`", size, path.display());
+        if ControlItem::is_control_path(path) {
+            return Ok(FinishedRead {
+                bytes: ControlItem::from_path(path)?.read(offset, size)?,
+                file: None,
+                file_size: 0,
+                expected_checksum: None,
+                whole_checksum: None,
+                ahead: None,
+            });
+        }
+        let view = SnapshotView::enter(path)?;
+        let path: &Path = view.path();
+
+        let task_handle = NotifyTui::start_task(
+            TaskType::FilesystemReadFile(
+                path.file_name()
+                .unwrap_or(OsStr::new("?")).display().to_string()
+            ),
+            3
+        );
+
+        // Open the file handle.
+        // The path may not match the one it was opened with anymore, but the handle knows what it's pointing at.
+        let got_handle = FileHandle::read(fh);
+        
+        // Try finding the directory item
+        let file = match got_handle.get_directory_item() {
+            Ok(ok) => ok,
+            Err(err) => {
+                // Getting the item failed, maybe it wasn't there.
+                NotifyTui::cancel_task(task_handle);
+                return Err(err)
+            },
+        };
+        NotifyTui::complete_task_step(&task_handle);
+        
+        // Make sure that it's a file.
+        if file.flags.contains(DirectoryItemFlags::IsDirectory) {
+            // Can't read a directory!
+            warn!("Tried to read a directory as a file. Ignoring...");
+            return Err(IS_A_DIRECTORY);
+        }
+        
+        // Found a file!
+        // We need to bound our read by the size of the file, since the read() filesystem call can
+        // try to read past the end.
+        let stored_size = match file.get_size() {
+            Ok(ok) => ok,
+            Err(error) => {
+                // Lower level error
+                NotifyTui::cancel_task(task_handle);
+                warn!("Failed to get size of file! Giving up...");
+                return Err(error.into())
+            },
+        };
+        // Buffered writes might go past the end of what the pool has.
+        let file_size: u64 = stored_size.max(FileHandle::buffered_end(&file).unwrap_or(0));
+
+        NotifyTui::complete_task_step(&task_handle);
+        
+        // Subtract the offset to idk man why am i explaining this im sure you understand.
+        // Reads are limited to 4GB long, which should be way above our max read size anyways.
+
+        // If a read bigger than that comes in, we'll ignore it.
+        let checking_size = std::cmp::min(size as u64, file_size - offset);
+        let bounded_read_length = if checking_size > u32::MAX.into() {
+            // Cant do that.
+
+            // Also if you're here, that means the file you're trying to read is actually >4GB.
+            // You are insane.
+
+            warn!("Tried to read more than 4GB at once!");
+            NotifyTui::cancel_task(task_handle);
+            return Err(INVALID_ARGUMENT);
+        } else {
+            // Size checked, this cast is safe.
+            checking_size as u32
+        };
+
+        if bounded_read_length != size {
+            // size did change.
+            debug!("Read was too large, truncated to `{bounded_read_length}` bytes.");
+        }
+
+        // Do the read.
+        // This vec might be HUGE, this is why we need to limit the read size on the filesystem.
+        debug!("Starting read...");
+        // Only the part the pool has can come out of it, the rest is buffered.
+        let stored_length: u32 = std::cmp::min(u64::from(bounded_read_length), stored_size.saturating_sub(offset)) as u32;
+        let mut read_buffer: Vec<u8> = if stored_length == 0 {
+            Vec::new()
+        } else {
+            match file.read_file(offset, stored_length) {
+                Ok(ok) => ok,
+                Err(error) => {
+                    // Lower level error
+                    warn!("Failed while reading the file! Giving up...");
+                    NotifyTui::cancel_task(task_handle);
+                    return Err(error.into())
+                },
+            }
+        };
+        // Anything buffered goes on top, since it's newer.
+        read_buffer.resize(bounded_read_length as usize, 0);
+        FileHandle::overlay_buffered(&file, offset, &mut read_buffer);
+        NotifyTui::complete_task_step(&task_handle);
+        debug!("Read finished.");
+        NotifyTui::finish_task(task_handle);
+
+        // All done!
+        Ok(FinishedRead {
+            bytes: read_buffer,
+            expected_checksum: file.get_checksum()?,
+            file: Some(file),
+            file_size,
+            whole_checksum: None,
+            ahead: None,
+        })
+    }
+
+    /// Feed a read that is actually being returned into its handle's read pattern and running checksum.
+    ///
+    /// Must only be called once per read, so never from inside the IO gate.
+    pub(crate) fn note_finished_read(fh: u64, offset: u64, read: &mut FinishedRead) {
+        if read.file.is_none() {
+            // Control items don't get any of this.
+            return;
+        }
+        // Reading the whole file in order is a free chance to check it against its checksum.
+        if read.expected_checksum.is_some() {
+            read.whole_checksum = FileHandle::note_checksummed_read(fh, offset, &read.bytes, read.file_size);
+        }
+        read.ahead = FileHandle::note_read(fh, offset, read.bytes.len() as u64);
+    }
+
+    /// Check the file against its checksum and read ahead, if the read that finished calls for it.
+    ///
+    /// Only looks, so it's safe to retry.
+    pub(crate) fn follow_up_read(&self, path: &std::path::Path, fh: u64, read: &FinishedRead) -> Result<(), c_int> {
+        let Some(file) = &read.file else {
+            return Ok(());
+        };
+
+        if let Some(expected) = read.expected_checksum
+            && let Some(actual) = read.whole_checksum
+            && actual != expected {
+            // The file might have just changed under this handle, so make sure before complaining.
+            let view = SnapshotView::enter(path)?;
+            if file.verify_checksum()? == Some(false) {
+                error!("`{}` does not match its checksum! It has been corrupted.", view.path().display());
+                return Err(GENERIC_FAILURE);
+            }
+        }
+
+        // If this handle is streaming through the file, grab the rest of what's on this disk
+        // now, rather than one small read at a time.
+        if let Some(ahead) = read.ahead && ahead < read.file_size {
+            match file.prefetch(ahead) {
+                Ok(until) => FileHandle::note_prefetched(fh, until),
+                Err(error) => {
+                    // The read itself already worked, readahead is just a bonus.
+                    warn!("Readahead failed: {error:?}");
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// Preallocate space in a file, or punch holes in it.
+    ///
+    /// Supports plain preallocation, `FALLOC_FL_KEEP_SIZE`, and `FALLOC_FL_PUNCH_HOLE` (which
+    /// must be paired with `FALLOC_FL_KEEP_SIZE`, as on Linux). Other modes are not supported.
+    ///
+    /// fuse_mt doesn't pass fallocate along, so this is called from the `FallocateShim` instead, which
+    /// only knows the handle.
+    pub fn fallocate(fh: u64, offset: u64, length: u64, mode: i32) -> fuse_mt::ResultEmpty {
+        let got_handle = FileHandle::read(fh);
+        let path: &Path = &got_handle.path;
+        debug!("Fallocate on `{}` with mode `{mode:#x}`, `{length}` bytes at `{offset}`...", path.display());
+        if ControlItem::is_control_path(path) {
+            return Err(NOT_PERMITTED);
+        }
+        SnapshotDir::check_writable(path)?;
+
+        let keep_size: bool = mode & libc::FALLOC_FL_KEEP_SIZE != 0;
+        let punch_hole: bool = mode & libc::FALLOC_FL_PUNCH_HOLE != 0;
+
+        // Anything other than those two flags, we don't do.
+        if mode & !(libc::FALLOC_FL_KEEP_SIZE | libc::FALLOC_FL_PUNCH_HOLE) != 0 {
+            warn!("Unsupported fallocate mode `{mode:#x}`.");
+            return Err(UNSUPPORTED);
+        }
+
+        // Punching holes is not allowed to change the size.
+        if punch_hole && !keep_size {
+            warn!("Tried to punch a hole without keeping the size.");
+            return Err(UNSUPPORTED);
+        }
+
+        // Zero length is invalid.
+        if length == 0 {
+            return Err(INVALID_ARGUMENT);
+        }
+
+        // Buffered writes have to land first, or they would undo a punched hole.
+        FileHandle::flush_all_writes();
+        let _placement = PlacementGuard::enter(path.parent().unwrap_or(Path::new("/")))?;
+
+        let task_handle = NotifyTui::start_task(
+            TaskType::FilesystemAllocateFile(
+                path.file_name().unwrap_or(OsStr::new("?")).display().to_string()
+            ),
+            2
+        );
+
+        let file = got_handle.get_directory_item()?;
+        if file.flags.contains(DirectoryItemFlags::IsDirectory) {
+            NotifyTui::cancel_task(task_handle);
+            return Err(IS_A_DIRECTORY);
+        }
+        NotifyTui::complete_task_step(&task_handle);
+
+        if punch_hole {
+            debug!("Punching hole...");
+            file.punch_hole(offset, length)?;
+        } else {
+            debug!("Preallocating...");
+            file.preallocate(offset, length, keep_size)?;
+        }
+        NotifyTui::complete_task_step(&task_handle);
+
+        debug!("Fallocate finished.");
+        NotifyTui::finish_task(task_handle);
+        Ok(())
+    }
+}
+
+/// Find a file from its path, if it's there.
+fn find_file(path: &Path) -> Result<Option<DirectoryItem>, c_int> {
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        // The root isn't a file.
+        return Ok(None);
+    };
+    let Some(directory) = DirectoryBlock::try_find_directory(Some(parent))? else {
+        return Ok(None);
+    };
+    Ok(directory.find_item(&NamedItem::File(name.to_string_lossy().to_string()))?)
+}
+
+/// Check a file against its checksum, and hand the checksum back as hex if it matched.
+fn checksum_xattr(path: &Path, size: u32) -> fuse_mt::ResultXattr {
+    let Some(file) = find_file(path)? else {
+        return Err(NO_SUCH_ATTRIBUTE);
+    };
+    match file.verify_checksum()? {
+        None => Err(NO_SUCH_ATTRIBUTE),
+        Some(false) => {
+            error!("`{}` does not match its checksum! It has been corrupted.", path.display());
+            Err(GENERIC_FAILURE)
+        },
+        Some(true) => {
+            let checksum: u64 = file.get_checksum()?.expect("Verified files have a checksum.");
+            xattr_reply(format!("{checksum:016x}").into_bytes(), size)
+        },
+    }
+}
+
+/// Asking with a size of zero only wants to know how big the buffer needs to be.
+fn xattr_reply(data: Vec<u8>, size: u32) -> fuse_mt::ResultXattr {
+    if size == 0 {
+        Ok(Xattr::Size(data.len() as u32))
+    } else if data.len() > size as usize {
+        Err(OUT_OF_RANGE)
+    } else {
+        Ok(Xattr::Data(data))
+    }
+}
@@ -8,13 +8,40 @@
 //
 //
 
-use std::path::PathBuf;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
 
-use log::debug;
+use log::{debug, error, info, warn};
 
+use crate::filesystem::filesystem_struct::AUTO_GROW;
+use crate::filesystem::filesystem_struct::CACHE_BLOCKS;
+use crate::filesystem::filesystem_struct::CACHE_FILE;
+use crate::filesystem::filesystem_struct::CACHE_POLICY;
+use crate::filesystem::filesystem_struct::CACHE_TRACE;
+use crate::filesystem::filesystem_struct::CACHE_TIER_RATIOS;
+use crate::filesystem::filesystem_struct::ENABLE_CHECKSUMS;
+use crate::filesystem::filesystem_struct::ENABLE_DEDUP;
+use crate::filesystem::filesystem_struct::ENABLE_ERROR_CORRECTION;
+use crate::filesystem::filesystem_struct::ENABLE_TRASH;
+use crate::filesystem::filesystem_struct::FORMAT_POOL;
+use crate::filesystem::filesystem_struct::MAX_DIRTY_AGE;
+use crate::filesystem::filesystem_struct::MAX_DISKS;
+use crate::filesystem::filesystem_struct::POOL_NAME;
+use crate::filesystem::filesystem_struct::PROMPT_TIMEOUT;
+use crate::filesystem::filesystem_struct::MANIFEST_DIRECTORY;
+use crate::filesystem::filesystem_struct::WRITE_THROUGH;
 use crate::filesystem::filesystem_struct::USE_TUI;
 use crate::filesystem::filesystem_struct::WRITE_BACKUPS;
+use crate::pool::disk::generic::io::cache::cache_io::CachedBlockIO;
+use crate::pool::disk::standard_disk::standard_disk_struct::StandardDisk;
+use crate::pool::format::format_struct::Feature;
 use crate::pool::pool_actions::pool_struct::Pool;
+use crate::tui::prompt_script::prompt_script_methods::attach_prompt_script;
+use crate::tui::prompt_script::prompt_script_struct::PromptSource;
+use crate::filesystem::filesystem_struct::CachePolicyKind;
+use crate::filesystem::filesystem_struct::CachePolicyReport;
 use crate::filesystem::filesystem_struct::FilesystemOptions;
 use crate::filesystem::filesystem_struct::FlusterFS;
 use crate::filesystem::filesystem_struct::FLOPPY_PATH;
@@ -82,8 +109,259 @@ impl FilesystemOptions {
             floppy_drive,
             enable_backup,
             enable_tui,
+            enable_dedup: false,
+            enable_trash: false,
+            enable_checksums: false,
+            enable_error_correction: false,
+            cache_blocks: None,
+            write_through: false,
+            max_dirty_age: None,
+            cache_file: None,
+            cache_policy: CachePolicyKind::Tiered,
+            cache_trace: None,
+            pool_name: None,
+            manifest_directory: None,
+            prompt_script: None,
+            prompt_timeout: None,
+            auto_grow: true,
+            max_disks: None,
         }
     }
+
+    /// Name the pool, for printing on disk labels.
+    /// 
+    /// None keeps the default.
+    pub fn with_pool_name(mut self, name: Option<String>) -> Self {
+        if let Some(name) = name.clone() {
+            debug!("Setting POOL_NAME...");
+            POOL_NAME.set(name).expect("This should only ever be called once.");
+            debug!("Done.");
+        }
+        self.pool_name = name;
+        self
+    }
+
+    /// Write a list of what's on every disk, and printable labels for them, into this directory on unmount.
+    /// 
+    /// Building them reads the whole pool, so unmounting will swap through every disk. None (the default)
+    /// writes nothing.
+    pub fn with_manifest(mut self, directory: Option<PathBuf>) -> Self {
+        if let Some(directory) = directory.clone() {
+            debug!("Setting MANIFEST_DIRECTORY...");
+            MANIFEST_DIRECTORY.set(directory).expect("This should only ever be called once.");
+            debug!("Done.");
+        }
+        self.manifest_directory = directory;
+        self
+    }
+
+    /// Send every prompt to another process instead of the TUI, for running Fluster without a human around.
+    /// 
+    /// Prompts go out as one line events, and the first line that comes back is the answer.
+    /// Disk swaps are answered with `ok` once the disk is in the drive. Stdio can't be used with the TUI.
+    /// 
+    /// Panics if the script can't be reached. None (the default) leaves prompts to the user.
+    pub fn with_prompt_script(mut self, source: Option<PromptSource>) -> Self {
+        if let Some(source) = source.clone() {
+            if source == PromptSource::Stdio && self.enable_tui {
+                panic!("The TUI draws on stdout, so prompts can't be answered over stdio with it enabled.");
+            }
+            debug!("Attaching prompt script...");
+            attach_prompt_script(&source);
+            debug!("Done.");
+        }
+        self.prompt_script = source;
+        self
+    }
+
+    /// How long the prompt script gets to answer each prompt.
+    /// 
+    /// When it runs out, input prompts get an empty answer, disk swaps fail like nobody swapped
+    /// the disk, and everything else carries on. None (the default) waits forever.
+    pub fn with_prompt_timeout(mut self, timeout: Option<Duration>) -> Self {
+        if let Some(timeout) = timeout {
+            debug!("Setting PROMPT_TIMEOUT...");
+            PROMPT_TIMEOUT.set(timeout).expect("This should only ever be called once.");
+            debug!("Done.");
+        }
+        self.prompt_timeout = timeout;
+        self
+    }
+
+    /// Add a new disk to the pool whenever it runs out of room, after purging the trash.
+    /// 
+    /// When off, writes that don't fit fail with "No space left on device" instead of asking for
+    /// a blank floppy. On by default.
+    pub fn with_auto_grow(mut self, enable: bool) -> Self {
+        debug!("Setting AUTO_GROW...");
+        AUTO_GROW.set(enable).expect("This should only ever be called once.");
+        debug!("Done.");
+        self.auto_grow = enable;
+        self
+    }
+
+    /// Stop adding disks once the pool has this many, not counting the pool disk.
+    /// 
+    /// Dense disks count too. Pools that already have more disks than this keep them, they just
+    /// can't get any more. None (the default) has no limit.
+    pub fn with_max_disks(mut self, max_disks: Option<u16>) -> Self {
+        if let Some(max) = max_disks {
+            debug!("Setting MAX_DISKS...");
+            MAX_DISKS.set(max).expect("This should only ever be called once.");
+            debug!("Done.");
+        }
+        self.max_disks = max_disks;
+        self
+    }
+
+    /// Turn on block level deduplication of file data.
+    /// 
+    /// Blocks that were already shared on a previous mount are always handled correctly, this only
+    /// controls if new writes look for duplicates. None (the default) leaves it on if the pool already
+    /// shares blocks, and off otherwise.
+    pub fn with_dedup(mut self, enable: Option<bool>) -> Self {
+        if let Some(enable) = enable {
+            debug!("Setting ENABLE_DEDUP...");
+            ENABLE_DEDUP.set(enable).expect("This should only ever be called once.");
+            debug!("Done.");
+        }
+        self.enable_dedup = enable.unwrap_or(false);
+        self
+    }
+
+    /// Move deleted files and directories into a hidden trash directory instead of freeing them, so they
+    /// can be restored through `/.fluster/restore`.
+    /// 
+    /// The trash is purged, oldest first, whenever the pool would otherwise need another disk.
+    /// None (the default) leaves it on if the pool already has a trash, and off otherwise.
+    pub fn with_trash(mut self, enable: Option<bool>) -> Self {
+        if let Some(enable) = enable {
+            debug!("Setting ENABLE_TRASH...");
+            ENABLE_TRASH.set(enable).expect("This should only ever be called once.");
+            debug!("Done.");
+        }
+        self.enable_trash = enable.unwrap_or(false);
+        self
+    }
+
+    /// Keep a checksum of the whole contents of every new file, which is checked when the file is read
+    /// from start to end, and whenever the `user.fluster.checksum` attribute is read.
+    /// 
+    /// Costs a read of every block a write touches, to update the checksum. None (the default) leaves
+    /// it on if the pool already has files with checksums, and off otherwise.
+    pub fn with_checksums(mut self, enable: Option<bool>) -> Self {
+        if let Some(enable) = enable {
+            debug!("Setting ENABLE_CHECKSUMS...");
+            ENABLE_CHECKSUMS.set(enable).expect("This should only ever be called once.");
+            debug!("Done.");
+        }
+        self.enable_checksums = enable.unwrap_or(false);
+        self
+    }
+
+    /// When a block keeps failing its CRC check, try flipping one or two bits back before giving up on it.
+    /// The fix is never written back, since it might be wrong. Scrubbing repairs the block from its backup.
+    /// 
+    /// Off by default. Very rarely, a badly mangled block can get "fixed" into the wrong thing.
+    pub fn with_error_correction(mut self, enable: bool) -> Self {
+        debug!("Setting ENABLE_ERROR_CORRECTION...");
+        ENABLE_ERROR_CORRECTION.set(enable).expect("This should only ever be called once.");
+        debug!("Done.");
+        self.enable_error_correction = enable;
+        self
+    }
+
+    /// Save the clean part of the cache to this file on unmount, and load it back in on the next mount.
+    /// 
+    /// Saves swapping through every disk just to look around on startup. Saved blocks are dropped if
+    /// their disk was written to since. None (the default) starts with an empty cache every time.
+    pub fn with_cache_file(mut self, path: Option<PathBuf>) -> Self {
+        if let Some(path) = path.clone() {
+            debug!("Setting CACHE_FILE...");
+            CACHE_FILE.set(path).expect("This should only ever be called once.");
+            debug!("Done.");
+        }
+        self.cache_file = path;
+        self
+    }
+
+    /// Set how many blocks the cache can hold across all of its tiers.
+    /// 
+    /// None keeps the default.
+    pub fn with_cache_blocks(mut self, blocks: Option<usize>) -> Self {
+        if let Some(blocks) = blocks {
+            debug!("Setting CACHE_BLOCKS...");
+            CACHE_BLOCKS.set(blocks).expect("This should only ever be called once.");
+            debug!("Done.");
+        }
+        self.cache_blocks = blocks;
+        self
+    }
+
+    /// Set how the cache is split between tiers 0, 1 and 2, as weights.
+    /// 
+    /// None keeps the default of `[2, 1, 1]`. Every tier must end up with at least one block, check
+    /// with `FlusterFS::cache_tier_sizes()` first.
+    pub fn with_cache_tier_ratios(self, tier_ratios: Option<[usize; 3]>) -> Self {
+        if let Some(ratios) = tier_ratios {
+            debug!("Setting CACHE_TIER_RATIOS...");
+            CACHE_TIER_RATIOS.set(ratios).expect("This should only ever be called once.");
+            debug!("Done.");
+        }
+        self
+    }
+
+    /// Pick how the cache decides which blocks to throw out when it's full.
+    /// 
+    /// The tier ratios only matter for the tiered policy. Defaults to tiered.
+    pub fn with_cache_policy(mut self, policy: Option<CachePolicyKind>) -> Self {
+        if let Some(policy) = policy {
+            debug!("Setting CACHE_POLICY...");
+            CACHE_POLICY.set(policy).expect("This should only ever be called once.");
+            debug!("Done.");
+        }
+        self.cache_policy = policy.unwrap_or_default();
+        self
+    }
+
+    /// Write down every block the cache is asked to read or write to this file, so the policies can
+    /// be compared against it later with `FlusterFS::compare_cache_policies()`.
+    /// 
+    /// The file is replaced if it already exists. None (the default) records nothing.
+    pub fn with_cache_trace(mut self, path: Option<PathBuf>) -> Self {
+        if let Some(path) = path.clone() {
+            debug!("Setting CACHE_TRACE...");
+            CACHE_TRACE.set(path).expect("This should only ever be called once.");
+            debug!("Done.");
+        }
+        self.cache_trace = path;
+        self
+    }
+
+    /// Write every changed block straight to disk, instead of holding onto it until the cache is flushed.
+    /// 
+    /// Much safer if the machine goes down, much slower if the writes are spread across disks.
+    /// Off by default.
+    pub fn with_write_through(mut self, enable: bool) -> Self {
+        debug!("Setting WRITE_THROUGH...");
+        WRITE_THROUGH.set(enable).expect("This should only ever be called once.");
+        debug!("Done.");
+        self.write_through = enable;
+        self
+    }
+
+    /// Flush the whole cache once it has been holding unwritten blocks for this long.
+    /// 
+    /// No limit by default, the cache is flushed whenever it fills up, or on unmount.
+    pub fn with_max_dirty_age(mut self, max_age: Option<Duration>) -> Self {
+        if let Some(age) = max_age {
+            debug!("Setting MAX_DIRTY_AGE...");
+            MAX_DIRTY_AGE.set(age).expect("This should only ever be called once.");
+            debug!("Done.");
+        }
+        self.max_dirty_age = max_age;
+        self
+    }
 }
 
 // Starting the filesystem.
@@ -96,8 +374,103 @@ impl FlusterFS {
         #[allow(dead_code)]
         #[allow(unused_variables)]
         let unused = options;
+        // Warm up the cache before the pool starts looking at disks.
+        if let Some(path) = CACHE_FILE.get() && path.exists() {
+            match CachedBlockIO::load_from_file(path) {
+                Ok(loaded) => info!("Loaded {loaded} blocks from the cache file."),
+                Err(error) => warn!("Couldn't load the cache file, starting cold. Error: {error}"),
+            }
+        }
         let fs = FlusterFS { pool: Pool::load() };
         debug!("Done starting filesystem.");
         fs
     }
+
+    /// Make a brand new pool on a blank pool disk, with this many standard disks already set up.
+    ///
+    /// Features turned on in the options are marked on the pool, so they stay on for every mount after this one.
+    /// Panics if the pool disk isn't blank, or if any of the disks can't be made.
+    pub fn mkfs(options: &FilesystemOptions, disks: u16) -> Self {
+        go_mkfs(options, disks)
+    }
+
+    /// How many blocks each tier of the tiered cache would get, with the same defaults as a mount.
+    /// 
+    /// The cache can't start if any of them is zero.
+    pub fn cache_tier_sizes(cache_blocks: Option<usize>, tier_ratios: Option<[usize; 3]>) -> [usize; 3] {
+        CachedBlockIO::tier_sizes(cache_blocks, tier_ratios)
+    }
+
+    /// Replay a trace recorded with `FilesystemOptions::with_cache_trace()` through every cache policy,
+    /// without touching any disks.
+    /// 
+    /// The cache size and tier ratios default to the same as a mount. Returns how each policy did.
+    pub fn compare_cache_policies(trace: &Path, cache_blocks: Option<usize>, tier_ratios: Option<[usize; 3]>) -> std::io::Result<Vec<CachePolicyReport>> {
+        CachedBlockIO::compare_policies(trace, cache_blocks, tier_ratios)
+    }
+}
+
+// Picking a cache policy by name.
+impl FromStr for CachePolicyKind {
+    type Err = String;
+
+    /// `tiered`, or `adaptive` (also `arc`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "tiered" => Ok(CachePolicyKind::Tiered),
+            "adaptive" | "arc" => Ok(CachePolicyKind::Adaptive),
+            other => Err(format!("There's no cache policy called `{other}`, try `tiered` or `adaptive`.")),
+        }
+    }
+}
+
+impl Display for CachePolicyKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CachePolicyKind::Tiered => write!(f, "tiered"),
+            CachePolicyKind::Adaptive => write!(f, "adaptive"),
+        }
+    }
+}
+
+//
+//
+// ======
+// Functions
+// ======
+//
+//
+
+fn go_mkfs(options: &FilesystemOptions, disks: u16) -> FlusterFS {
+    assert!(disks >= 1, "A pool needs at least one standard disk.");
+    info!("Making a new pool...");
+    debug!("Setting FORMAT_POOL...");
+    FORMAT_POOL.set(true).expect("This should only ever be called once.");
+    debug!("Done.");
+
+    // Loading makes the pool disk and disk 1.
+    let pool = Pool::load();
+    for _ in 1..disks {
+        if let Err(error) = Pool::new_disk::<StandardDisk>() {
+            error!("Failed to make the rest of the disks.");
+            error!("Reason: {error}");
+            panic!("Failed to make disks! {error}");
+        }
+    }
+
+    // Nothing has used these yet, so they have to be noted by hand.
+    if options.enable_trash {
+        Pool::use_feature(Feature::Trash);
+    }
+    if options.enable_dedup {
+        Pool::use_feature(Feature::Dedup);
+    }
+    if options.enable_checksums {
+        Pool::use_feature(Feature::Checksums);
+    }
+
+    CachedBlockIO::flush().expect("I sure hope cache flushing works!");
+    Pool::flush().expect("I sure hope pool flushing works!");
+    info!("Made a new pool with {disks} disks, plus the pool disk.");
+    FlusterFS { pool }
 }
\ No newline at end of file
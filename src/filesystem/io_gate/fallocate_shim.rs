@@ -0,0 +1,213 @@
+// Sneaking fallocate past fuse_mt.
+// fuse_mt only passes along the calls it knows about, and fallocate isn't one of them. This sits between
+// it and fuser, handing everything else straight through.
+
+use std::{ffi::OsStr, path::Path, time::SystemTime};
+
+use fuse_mt::FuseMT;
+use fuser::{
+    Filesystem,
+    KernelConfig,
+    ReplyAttr,
+    ReplyCreate,
+    ReplyData,
+    ReplyDirectory,
+    ReplyEmpty,
+    ReplyEntry,
+    ReplyOpen,
+    ReplyStatfs,
+    ReplyWrite,
+    ReplyXattr,
+    Request,
+    TimeOrNow
+};
+use libc::c_int;
+
+use crate::error_types::filesystem::INVALID_ARGUMENT;
+
+use super::gated_filesystem::GatedFlusterFS;
+
+/// Wraps fuse_mt so the kernel's fallocate calls make it to Fluster!.
+///
+/// fallocate is answered right here, on the thread reading requests from the kernel, since it only needs
+/// the handle. The gate still makes it wait for the drive like every other change.
+pub struct FallocateShim {
+    inner: FuseMT<GatedFlusterFS>,
+}
+
+impl FallocateShim {
+    /// Put the shim in front of fuse_mt.
+    pub fn new(inner: FuseMT<GatedFlusterFS>) -> Self {
+        Self {
+            inner
+        }
+    }
+}
+
+impl Filesystem for FallocateShim {
+    fn fallocate(&mut self, _req: &Request<'_>, _ino: u64, fh: u64, offset: i64, length: i64, mode: i32, reply: ReplyEmpty) {
+        // The kernel already turns these away, but just in case.
+        let (Ok(offset), Ok(length)) = (u64::try_from(offset), u64::try_from(length)) else {
+            reply.error(INVALID_ARGUMENT);
+            return;
+        };
+        match GatedFlusterFS::fallocate(fh, offset, length, mode) {
+            Ok(()) => reply.ok(),
+            Err(error) => reply.error(error),
+        }
+    }
+
+    // Everything else goes straight through.
+
+    fn init(&mut self, req: &Request<'_>, config: &mut KernelConfig) -> Result<(), c_int> {
+        self.inner.init(req, config)
+    }
+
+    fn destroy(&mut self) {
+        self.inner.destroy();
+    }
+
+    fn lookup(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        self.inner.lookup(req, parent, name, reply);
+    }
+
+    fn forget(&mut self, req: &Request<'_>, ino: u64, nlookup: u64) {
+        self.inner.forget(req, ino, nlookup);
+    }
+
+    fn getattr(&mut self, req: &Request<'_>, ino: u64, reply: ReplyAttr) {
+        self.inner.getattr(req, ino, reply);
+    }
+
+    fn setattr(
+        &mut self,
+        req: &Request<'_>,
+        ino: u64,
+        mode: Option<u32>,
+        uid: Option<u32>,
+        gid: Option<u32>,
+        size: Option<u64>,
+        atime: Option<TimeOrNow>,
+        mtime: Option<TimeOrNow>,
+        ctime: Option<SystemTime>,
+        fh: Option<u64>,
+        crtime: Option<SystemTime>,
+        chgtime: Option<SystemTime>,
+        bkuptime: Option<SystemTime>,
+        flags: Option<u32>,
+        reply: ReplyAttr,
+    ) {
+        self.inner.setattr(req, ino, mode, uid, gid, size, atime, mtime, ctime, fh, crtime, chgtime, bkuptime, flags, reply);
+    }
+
+    fn readlink(&mut self, req: &Request<'_>, ino: u64, reply: ReplyData) {
+        self.inner.readlink(req, ino, reply);
+    }
+
+    fn mknod(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, mode: u32, umask: u32, rdev: u32, reply: ReplyEntry) {
+        self.inner.mknod(req, parent, name, mode, umask, rdev, reply);
+    }
+
+    fn mkdir(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, mode: u32, umask: u32, reply: ReplyEntry) {
+        self.inner.mkdir(req, parent, name, mode, umask, reply);
+    }
+
+    fn unlink(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        self.inner.unlink(req, parent, name, reply);
+    }
+
+    fn rmdir(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        self.inner.rmdir(req, parent, name, reply);
+    }
+
+    fn symlink(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, link: &Path, reply: ReplyEntry) {
+        self.inner.symlink(req, parent, name, link, reply);
+    }
+
+    fn rename(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, newparent: u64, newname: &OsStr, flags: u32, reply: ReplyEmpty) {
+        self.inner.rename(req, parent, name, newparent, newname, flags, reply);
+    }
+
+    fn link(&mut self, req: &Request<'_>, ino: u64, newparent: u64, newname: &OsStr, reply: ReplyEntry) {
+        self.inner.link(req, ino, newparent, newname, reply);
+    }
+
+    fn open(&mut self, req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
+        self.inner.open(req, ino, flags, reply);
+    }
+
+    fn read(&mut self, req: &Request<'_>, ino: u64, fh: u64, offset: i64, size: u32, flags: i32, lock_owner: Option<u64>, reply: ReplyData) {
+        self.inner.read(req, ino, fh, offset, size, flags, lock_owner, reply);
+    }
+
+    fn write(
+        &mut self,
+        req: &Request<'_>,
+        ino: u64,
+        fh: u64,
+        offset: i64,
+        data: &[u8],
+        write_flags: u32,
+        flags: i32,
+        lock_owner: Option<u64>,
+        reply: ReplyWrite,
+    ) {
+        self.inner.write(req, ino, fh, offset, data, write_flags, flags, lock_owner, reply);
+    }
+
+    fn flush(&mut self, req: &Request<'_>, ino: u64, fh: u64, lock_owner: u64, reply: ReplyEmpty) {
+        self.inner.flush(req, ino, fh, lock_owner, reply);
+    }
+
+    fn release(&mut self, req: &Request<'_>, ino: u64, fh: u64, flags: i32, lock_owner: Option<u64>, flush: bool, reply: ReplyEmpty) {
+        self.inner.release(req, ino, fh, flags, lock_owner, flush, reply);
+    }
+
+    fn fsync(&mut self, req: &Request<'_>, ino: u64, fh: u64, datasync: bool, reply: ReplyEmpty) {
+        self.inner.fsync(req, ino, fh, datasync, reply);
+    }
+
+    fn opendir(&mut self, req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
+        self.inner.opendir(req, ino, flags, reply);
+    }
+
+    fn readdir(&mut self, req: &Request<'_>, ino: u64, fh: u64, offset: i64, reply: ReplyDirectory) {
+        self.inner.readdir(req, ino, fh, offset, reply);
+    }
+
+    fn releasedir(&mut self, req: &Request<'_>, ino: u64, fh: u64, flags: i32, reply: ReplyEmpty) {
+        self.inner.releasedir(req, ino, fh, flags, reply);
+    }
+
+    fn fsyncdir(&mut self, req: &Request<'_>, ino: u64, fh: u64, datasync: bool, reply: ReplyEmpty) {
+        self.inner.fsyncdir(req, ino, fh, datasync, reply);
+    }
+
+    fn statfs(&mut self, req: &Request<'_>, ino: u64, reply: ReplyStatfs) {
+        self.inner.statfs(req, ino, reply);
+    }
+
+    fn setxattr(&mut self, req: &Request<'_>, ino: u64, name: &OsStr, value: &[u8], flags: i32, position: u32, reply: ReplyEmpty) {
+        self.inner.setxattr(req, ino, name, value, flags, position, reply);
+    }
+
+    fn getxattr(&mut self, req: &Request<'_>, ino: u64, name: &OsStr, size: u32, reply: ReplyXattr) {
+        self.inner.getxattr(req, ino, name, size, reply);
+    }
+
+    fn listxattr(&mut self, req: &Request<'_>, ino: u64, size: u32, reply: ReplyXattr) {
+        self.inner.listxattr(req, ino, size, reply);
+    }
+
+    fn removexattr(&mut self, req: &Request<'_>, ino: u64, name: &OsStr, reply: ReplyEmpty) {
+        self.inner.removexattr(req, ino, name, reply);
+    }
+
+    fn access(&mut self, req: &Request<'_>, ino: u64, mask: i32, reply: ReplyEmpty) {
+        self.inner.access(req, ino, mask, reply);
+    }
+
+    fn create(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, mode: u32, umask: u32, flags: i32, reply: ReplyCreate) {
+        self.inner.create(req, parent, name, mode, umask, flags, reply);
+    }
+}
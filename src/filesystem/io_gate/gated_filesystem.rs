@@ -0,0 +1,188 @@
+// Bouncer for the FUSE layer.
+// Every call goes through the IO gate before it reaches Fluster! itself, so the filesystem can be
+// handed to more than one thread.
+
+use std::{ffi::OsStr, path::Path, thread, time::Duration};
+
+use fuse_mt::{FilesystemMT, RequestInfo};
+use libc::c_int;
+use log::warn;
+
+use crate::{
+    filesystem::{
+        filesystem_struct::{
+            FinishedRead,
+            FlusterFS,
+            MAX_DIRTY_AGE
+        },
+        io_gate::io_gate_struct::IoGate
+    },
+    pool::disk::{
+        generic::io::cache::cache_io::CachedBlockIO,
+        standard_disk::block::io::file::checksum::CHECKSUM_XATTR
+    }
+};
+
+/// Wraps the filesystem so FUSE can call it from multiple threads.
+///
+/// Lookups (getattr, readdir, statfs and reads) are answered straight from the cache when possible, and
+/// can run alongside each other. Everything else waits its turn, since it either changes the filesystem,
+/// or needs the drive.
+pub struct GatedFlusterFS {
+    inner: FlusterFS,
+}
+
+impl GatedFlusterFS {
+    /// Put the filesystem behind the gate.
+    pub fn new(filesystem: FlusterFS) -> Self {
+        Self {
+            inner: filesystem
+        }
+    }
+
+    /// See `FlusterFS::fallocate()`.
+    pub fn fallocate(fh: u64, offset: u64, length: u64, mode: i32) -> fuse_mt::ResultEmpty {
+        IoGate::change(|| FlusterFS::fallocate(fh, offset, length, mode))
+    }
+}
+
+/// Keep an eye on how long the cache has been sitting on unwritten blocks, and flush it once
+/// that's been too long. Runs until the process exits.
+fn start_dirty_flusher(max_age: Duration) {
+    // Check a few times per max age, so we never go too far past it.
+    let interval = (max_age / 4).clamp(Duration::from_millis(100), Duration::from_secs(1));
+    let _ = thread::spawn(move || loop {
+        thread::sleep(interval);
+        // Flushing needs the drive, so wait in line like everyone else.
+        if let Err(error) = IoGate::change(|| CachedBlockIO::flush_if_older_than(max_age).map_err(c_int::from)) {
+            warn!("Timed cache flush failed! Error: {error}");
+        }
+    });
+}
+
+impl FilesystemMT for GatedFlusterFS {
+    fn init(&self, req: RequestInfo) -> fuse_mt::ResultEmpty {
+        let result = IoGate::change(|| self.inner.init(req));
+        if let Some(max_age) = MAX_DIRTY_AGE.get() {
+            start_dirty_flusher(*max_age);
+        }
+        result
+    }
+
+    fn destroy(&self) {
+        let _ = IoGate::change(|| {
+            self.inner.destroy();
+            Ok(())
+        });
+    }
+
+    fn getattr(&self, req: RequestInfo, path: &Path, fh: Option<u64>) -> fuse_mt::ResultEntry {
+        IoGate::look(|| self.inner.getattr(req, path, fh))
+    }
+
+    fn truncate(&self, req: RequestInfo, path: &Path, fh: Option<u64>, size: u64) -> fuse_mt::ResultEmpty {
+        IoGate::change(|| self.inner.truncate(req, path, fh, size))
+    }
+
+    fn mkdir(&self, req: RequestInfo, parent: &Path, name: &OsStr, mode: u32) -> fuse_mt::ResultEntry {
+        IoGate::change(|| self.inner.mkdir(req, parent, name, mode))
+    }
+
+    fn unlink(&self, req: RequestInfo, parent: &Path, name: &OsStr) -> fuse_mt::ResultEmpty {
+        IoGate::change(|| self.inner.unlink(req, parent, name))
+    }
+
+    fn rmdir(&self, req: RequestInfo, parent: &Path, name: &OsStr) -> fuse_mt::ResultEmpty {
+        IoGate::change(|| self.inner.rmdir(req, parent, name))
+    }
+
+    fn rename(&self, req: RequestInfo, parent: &Path, name: &OsStr, newparent: &Path, newname: &OsStr) -> fuse_mt::ResultEmpty {
+        IoGate::change(|| self.inner.rename(req, parent, name, newparent, newname))
+    }
+
+    // Opening hands out a handle, which would leak if a lookup had to retry it.
+    fn open(&self, req: RequestInfo, path: &Path, flags: u32) -> fuse_mt::ResultOpen {
+        IoGate::change(|| self.inner.open(req, path, flags))
+    }
+
+    fn read(
+        &self,
+        _req: RequestInfo,
+        path: &Path,
+        fh: u64,
+        offset: u64,
+        size: u32,
+        callback: impl FnOnce(fuse_mt::ResultSlice<'_>) -> fuse_mt::CallbackResult,
+    ) -> fuse_mt::CallbackResult {
+        let read_buffer = IoGate::look(|| self.inner.fetch_bytes(path, fh, offset, size)).and_then(|mut read: FinishedRead| {
+            // The lookup may have run twice, only the attempt that came back counts.
+            FlusterFS::note_finished_read(fh, offset, &mut read);
+            IoGate::look(|| self.inner.follow_up_read(path, fh, &read))?;
+            Ok(read.bytes)
+        });
+        callback(read_buffer.as_deref().map_err(|error| *error))
+    }
+
+    fn write(&self, req: RequestInfo, path: &Path, fh: u64, offset: u64, data: Vec<u8>, flags: u32) -> fuse_mt::ResultWrite {
+        IoGate::change(|| self.inner.write(req, path, fh, offset, data, flags))
+    }
+
+    // Flushing and syncing write out whatever the handle is holding onto.
+    fn flush(&self, req: RequestInfo, path: &Path, fh: u64, lock_owner: u64) -> fuse_mt::ResultEmpty {
+        IoGate::change(|| self.inner.flush(req, path, fh, lock_owner))
+    }
+
+    // Handles have their own lock.
+    fn release(&self, req: RequestInfo, path: &Path, fh: u64, flags: u32, lock_owner: u64, flush: bool) -> fuse_mt::ResultEmpty {
+        // The last handle on a deleted file frees it.
+        IoGate::change(|| self.inner.release(req, path, fh, flags, lock_owner, flush))
+    }
+
+    fn fsync(&self, req: RequestInfo, path: &Path, fh: u64, datasync: bool) -> fuse_mt::ResultEmpty {
+        IoGate::change(|| self.inner.fsync(req, path, fh, datasync))
+    }
+
+    fn opendir(&self, req: RequestInfo, path: &Path, flags: u32) -> fuse_mt::ResultOpen {
+        IoGate::change(|| self.inner.opendir(req, path, flags))
+    }
+
+    fn readdir(&self, req: RequestInfo, path: &Path, fh: u64) -> fuse_mt::ResultReaddir {
+        IoGate::look(|| self.inner.readdir(req, path, fh))
+    }
+
+    fn releasedir(&self, req: RequestInfo, path: &Path, fh: u64, flags: u32) -> fuse_mt::ResultEmpty {
+        self.inner.releasedir(req, path, fh, flags)
+    }
+
+    fn fsyncdir(&self, req: RequestInfo, path: &Path, fh: u64, datasync: bool) -> fuse_mt::ResultEmpty {
+        self.inner.fsyncdir(req, path, fh, datasync)
+    }
+
+    fn statfs(&self, req: RequestInfo, path: &Path) -> fuse_mt::ResultStatfs {
+        IoGate::look(|| self.inner.statfs(req, path))
+    }
+
+    fn create(&self, req: RequestInfo, parent: &Path, name: &OsStr, mode: u32, flags: u32) -> fuse_mt::ResultCreate {
+        IoGate::change(|| self.inner.create(req, parent, name, mode, flags))
+    }
+
+    fn setxattr(&self, req: RequestInfo, path: &Path, name: &OsStr, value: &[u8], flags: u32, position: u32) -> fuse_mt::ResultEmpty {
+        IoGate::change(|| self.inner.setxattr(req, path, name, value, flags, position))
+    }
+
+    fn getxattr(&self, req: RequestInfo, path: &Path, name: &OsStr, size: u32) -> fuse_mt::ResultXattr {
+        // The checksum needs buffered writes in the file first.
+        if name == CHECKSUM_XATTR {
+            return IoGate::change(|| self.inner.getxattr(req, path, name, size));
+        }
+        IoGate::look(|| self.inner.getxattr(req, path, name, size))
+    }
+
+    fn listxattr(&self, req: RequestInfo, path: &Path, size: u32) -> fuse_mt::ResultXattr {
+        IoGate::look(|| self.inner.listxattr(req, path, size))
+    }
+
+    fn removexattr(&self, req: RequestInfo, path: &Path, name: &OsStr) -> fuse_mt::ResultEmpty {
+        IoGate::change(|| self.inner.removexattr(req, path, name))
+    }
+}
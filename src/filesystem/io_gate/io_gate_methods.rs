@@ -0,0 +1,94 @@
+// Who goes there?
+
+use std::{cell::Cell, thread::LocalKey};
+
+use libc::c_int;
+use log::trace;
+
+use crate::filesystem::drive_scheduler::drive_scheduler_struct::DriveScheduler;
+
+use super::io_gate_struct::{
+    FlagGuard,
+    IoGate,
+    CACHE_MISSED,
+    CACHE_ONLY,
+    FILESYSTEM_STATE,
+    INSIDE_GATE
+};
+
+impl IoGate {
+    /// Run an operation that only looks at the filesystem.
+    ///
+    /// The operation is first attempted using only the cache, alongside any other lookups. If it needs
+    /// a block that isn't cached, it is run again once this thread owns the drive.
+    ///
+    /// Since the operation may run twice, it must not change anything.
+    pub(crate) fn look<T>(operation: impl Fn() -> Result<T, c_int>) -> Result<T, c_int> {
+        if INSIDE_GATE.get() {
+            // Already got in.
+            return operation();
+        }
+        let _state = FILESYSTEM_STATE.read().expect("Other lock holders should not panic.");
+        let _inside = FlagGuard::raise(&INSIDE_GATE);
+
+        // Try the cache.
+        let attempt = {
+            let _cache_only = FlagGuard::raise(&CACHE_ONLY);
+            CACHE_MISSED.set(None);
+            operation()
+        };
+
+        let Some(disk) = CACHE_MISSED.get() else {
+            // Never needed the drive.
+            return attempt;
+        };
+
+        // Have to go to the drive.
+        trace!("Cache miss on disk {disk}, waiting for the drive...");
+        let _drive = DriveScheduler::wait_for_drive(Some(disk));
+        operation()
+    }
+
+    /// Run an operation that changes the filesystem.
+    ///
+    /// Waits for every other operation to finish, and owns the drive while it runs.
+    pub(crate) fn change<T>(operation: impl FnOnce() -> Result<T, c_int>) -> Result<T, c_int> {
+        if INSIDE_GATE.get() {
+            // Already got in.
+            return operation();
+        }
+        let _state = FILESYSTEM_STATE.write().expect("Other lock holders should not panic.");
+        // No idea which disks this will need, so it'll take whatever is in the drive.
+        let _drive = DriveScheduler::wait_for_drive(None);
+        let _inside = FlagGuard::raise(&INSIDE_GATE);
+        operation()
+    }
+
+    /// Is this thread only allowed to use the cache right now?
+    ///
+    /// If so, the caller must not touch the drive, and should call `missed()` and bail instead.
+    pub(crate) fn cache_only() -> bool {
+        CACHE_ONLY.get()
+    }
+
+    /// Note that a cache-only attempt needed a block from this disk that wasn't cached.
+    pub(crate) fn missed(disk: u16) {
+        CACHE_MISSED.set(Some(disk));
+    }
+}
+
+impl FlagGuard {
+    /// Raise a flag on this thread until the guard is dropped.
+    pub(super) fn raise(flag: &'static LocalKey<Cell<bool>>) -> Self {
+        FlagGuard {
+            flag,
+            previous: flag.replace(true),
+        }
+    }
+}
+
+impl Drop for FlagGuard {
+    fn drop(&mut self) {
+        self.flag.set(self.previous);
+    }
+}
@@ -0,0 +1,48 @@
+//
+//
+// ======
+// IO gate
+// ======
+//
+//
+
+// Floppies are slow, RAM is not. Operations that the cache can answer on its own should not
+// have to wait in line behind a read that is busy spinning up the drive.
+
+use std::{cell::Cell, sync::RwLock, thread::LocalKey};
+
+use lazy_static::lazy_static;
+
+lazy_static! {
+    /// Guards the shape of the filesystem.
+    ///
+    /// Anything that changes the filesystem holds this exclusively, so nobody can see a half
+    /// finished update. Anything that only looks at the filesystem shares it.
+    pub(super) static ref FILESYSTEM_STATE: RwLock<()> = RwLock::new(());
+}
+
+// Only one thread ever talks to the drive at a time, the DriveScheduler decides who.
+
+thread_local! {
+    /// Set while this thread is inside the gate, so operations that call other operations don't lock themselves out.
+    pub(super) static INSIDE_GATE: Cell<bool> = const { Cell::new(false) };
+    /// Set while this thread is trying to answer something purely from the cache.
+    pub(super) static CACHE_ONLY: Cell<bool> = const { Cell::new(false) };
+    /// Set to the disk a cache-only attempt needed, if it needed a block that wasn't cached.
+    pub(super) static CACHE_MISSED: Cell<Option<u16>> = const { Cell::new(None) };
+}
+
+/// Struct for implementing gate methods on.
+/// Holds no information, this is just for calling.
+pub(crate) struct IoGate {
+    // gate keeping
+}
+
+/// Raises one of the flags above on this thread until dropped, then puts back whatever was there before.
+///
+/// Dropped on the way out of a panic too, so a crashed operation can't leave the next one on this
+/// thread thinking it is already inside the gate.
+pub(super) struct FlagGuard {
+    pub(super) flag: &'static LocalKey<Cell<bool>>,
+    pub(super) previous: bool,
+}
@@ -0,0 +1,6 @@
+pub(crate) mod io_gate_methods;
+pub(crate) mod io_gate_struct;
+pub mod gated_filesystem;
+pub mod fallocate_shim;
+#[cfg(test)]
+mod tests;
\ No newline at end of file
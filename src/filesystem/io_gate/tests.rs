@@ -0,0 +1,73 @@
+// Testing the gate, mind the gap.
+// Unwrapping is okay here, since we want unexpected outcomes to fail tests.
+#![allow(clippy::unwrap_used)]
+
+use std::{cell::Cell, panic, sync::mpsc, thread, time::Duration};
+
+use test_log::test;
+
+use super::io_gate_struct::{FlagGuard, IoGate, CACHE_ONLY};
+
+/// A lookup that misses the cache should be run again, this time allowed to use the drive.
+#[test]
+fn look_retries_after_miss() {
+    let attempts: Cell<u8> = Cell::new(0);
+    let result = IoGate::look(|| {
+        attempts.set(attempts.get() + 1);
+        if IoGate::cache_only() {
+            // Pretend the block wasn't there.
+            IoGate::missed(1);
+            return Err(libc::ERESTART);
+        }
+        Ok(42)
+    });
+    assert_eq!(result, Ok(42));
+    assert_eq!(attempts.get(), 2);
+    // Should be back to normal afterwards.
+    assert!(!IoGate::cache_only());
+}
+
+/// Cache hits should not have to wait for whoever is using the drive.
+#[test]
+fn hits_skip_the_drive_line() {
+    let (holding_tx, holding_rx) = mpsc::channel::<()>();
+    let (release_tx, release_rx) = mpsc::channel::<()>();
+
+    // Go hog the drive.
+    let hog = thread::spawn(move || {
+        IoGate::look(|| {
+            if IoGate::cache_only() {
+                IoGate::missed(1);
+                return Err(libc::ERESTART);
+            }
+            // We own the drive now.
+            holding_tx.send(()).unwrap();
+            release_rx.recv().unwrap();
+            Ok(())
+        })
+    });
+    holding_rx.recv_timeout(Duration::from_secs(10)).unwrap();
+
+    // A lookup that the cache can answer should finish while the drive is busy.
+    let (done_tx, done_rx) = mpsc::channel::<u8>();
+    let hit = thread::spawn(move || {
+        done_tx.send(IoGate::look(|| Ok(7)).unwrap()).unwrap();
+    });
+    assert_eq!(done_rx.recv_timeout(Duration::from_secs(10)).unwrap(), 7);
+
+    release_tx.send(()).unwrap();
+    hog.join().unwrap().unwrap();
+    hit.join().unwrap();
+}
+
+/// A panic partway through an operation should not leave this thread stuck in cache-only mode.
+#[test]
+fn flags_survive_panics() {
+    let outcome = panic::catch_unwind(|| {
+        let _cache_only = FlagGuard::raise(&CACHE_ONLY);
+        assert!(IoGate::cache_only(), "Flag should be up inside the guard.");
+        panic!("Oh no!");
+    });
+    assert!(outcome.is_err());
+    assert!(!IoGate::cache_only());
+}
@@ -1,7 +1,11 @@
 mod fuse_filesystem_methods;
 mod internal_filesystem_methods;
 pub mod filesystem_struct;
-mod file_handle;
+pub(crate) mod file_handle;
+pub mod io_gate;
+pub(crate) mod drive_scheduler;
+pub(crate) mod control_dir;
+pub(crate) mod snapshot_dir;
 mod item_flag;
 mod file_attributes;
 pub mod disk_backup;
\ No newline at end of file
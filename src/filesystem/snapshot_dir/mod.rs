@@ -0,0 +1,4 @@
+pub(crate) mod snapshot_dir_methods;
+pub(crate) mod snapshot_dir_struct;
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,181 @@
+// Flipping through the album.
+
+use std::{
+    ffi::OsStr,
+    path::Path,
+    time::SystemTime
+};
+
+use fuse_mt::{
+    DirectoryEntry,
+    FileAttr,
+    FileType
+};
+use libc::c_int;
+use log::{debug, info};
+
+use crate::{
+    error_types::filesystem::*,
+    pool::{
+        disk::standard_disk::block::directory::directory_struct::{
+            DirectoryItem,
+            DirectoryItemFlags
+        },
+        snapshot::snapshot_struct::{
+            Snapshot,
+            SnapshotGuard
+        }
+    }
+};
+
+use super::snapshot_dir_struct::{
+    SnapshotDir,
+    SnapshotView,
+    SNAPSHOT_DIR_NAME
+};
+
+impl SnapshotDir {
+    /// Is this path the snapshot directory, or something inside of it?
+    pub(crate) fn is_snapshot_path(path: &Path) -> bool {
+        path.starts_with(Path::new("/").join(SNAPSHOT_DIR_NAME))
+    }
+
+    /// Is this path the snapshot directory itself?
+    pub(crate) fn is_snapshot_dir(path: &Path) -> bool {
+        path == Path::new("/").join(SNAPSHOT_DIR_NAME)
+    }
+
+    /// Check that something new can be made at this path.
+    ///
+    /// Snapshots are read-only, and the snapshot directory can't be made on disk.
+    ///
+    /// Snapshots are taken with mkdir, which has to check for that before calling this.
+    pub(crate) fn check_creatable(path: &Path) -> Result<(), c_int> {
+        if Self::is_snapshot_dir(path) {
+            Err(ITEM_ALREADY_EXISTS)
+        } else {
+            Self::check_writable(path)
+        }
+    }
+
+    /// Check that the item at this path can be changed, which is anything not in a snapshot.
+    pub(crate) fn check_writable(path: &Path) -> Result<(), c_int> {
+        if Self::is_snapshot_path(path) {
+            debug!("`{}` is in a snapshot, which can't be changed.", path.display());
+            Err(READ_ONLY)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Attributes of the snapshot directory itself.
+    pub(crate) fn attributes() -> FileAttr {
+        let now = SystemTime::now();
+        FileAttr {
+            size: 0,
+            blocks: 0,
+            atime: now,
+            mtime: now,
+            ctime: now,
+            crtime: SystemTime::UNIX_EPOCH,
+            kind: FileType::Directory,
+            // Writable, otherwise you couldn't take snapshots.
+            perm: 0o755,
+            nlink: 2,
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+            flags: 0,
+        }
+    }
+
+    /// Every snapshot, as directories.
+    pub(crate) fn list() -> Vec<DirectoryEntry> {
+        let mut entries: Vec<DirectoryEntry> = Snapshot::list()
+            .into_iter()
+            .map(|snapshot| DirectoryEntry {
+                name: snapshot.name.into(),
+                kind: FileType::Directory,
+            })
+            .collect();
+        entries.push(DirectoryEntry {
+            name: OsStr::new(".").into(),
+            kind: FileType::Directory,
+        });
+        entries
+    }
+
+    /// Take a new snapshot.
+    ///
+    /// Returns the attributes of the snapshot's root.
+    pub(crate) fn take(name: &OsStr) -> Result<FileAttr, c_int> {
+        let name: &str = name.to_str().expect("Should be valid utf8");
+        if name.len() > u8::MAX.into() {
+            return Err(FILE_NAME_TOO_LONG);
+        }
+        if Snapshot::find(name).is_some() {
+            return Err(ITEM_ALREADY_EXISTS);
+        }
+        if !Snapshot::has_room_for(name) {
+            debug!("No room left in the snapshot table.");
+            return Err(NO_SPACE);
+        }
+        info!("Taking snapshot `{name}`...");
+        let snapshot: Snapshot = Snapshot::take(name)?;
+        let root: DirectoryItem = DirectoryItem {
+            flags: DirectoryItemFlags::IsDirectory,
+            name_length: name.len() as u8,
+            name: name.to_string(),
+            location: snapshot.root.inode,
+            placement: None,
+        };
+        Ok(root.try_into()?)
+    }
+
+    /// Delete a snapshot, along with everything in it.
+    pub(crate) fn delete(name: &OsStr) -> Result<(), c_int> {
+        let name: &str = name.to_str().expect("Should be valid utf8");
+        info!("Deleting snapshot `{name}`...");
+        if Snapshot::delete(name)? {
+            Ok(())
+        } else {
+            Err(NO_SUCH_ITEM)
+        }
+    }
+}
+
+impl SnapshotView {
+    /// Look at a path from inside of whatever snapshot it is in.
+    ///
+    /// Paths outside of the snapshot directory are left as they are.
+    ///
+    /// Returns NO_SUCH_ITEM if the snapshot does not exist. The snapshot directory itself is not in
+    /// any snapshot, so callers need to handle it first.
+    pub(crate) fn enter(path: &Path) -> Result<Self, c_int> {
+        let inner: &Path = if let Ok(inner) = path.strip_prefix(Path::new("/").join(SNAPSHOT_DIR_NAME)) {
+            inner
+        } else {
+            return Ok(SnapshotView {
+                path: path.to_path_buf(),
+                _guard: None,
+            });
+        };
+
+        let mut components = inner.components();
+        let name: &str = components
+            .next()
+            .and_then(|name| name.as_os_str().to_str())
+            .ok_or(NO_SUCH_ITEM)?;
+        let snapshot: Snapshot = Snapshot::find(name).ok_or(NO_SUCH_ITEM)?;
+
+        Ok(SnapshotView {
+            path: Path::new("/").join(components.as_path()),
+            _guard: Some(SnapshotGuard::enter(snapshot.root)),
+        })
+    }
+
+    /// The path, as seen from the root of the snapshot.
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+}
@@ -0,0 +1,33 @@
+//
+//
+// ======
+// Snapshot directory
+// ======
+//
+//
+
+// Where old pictures of the pool are kept.
+// `/.snapshots/` is made up by the FUSE layer, like the control directory. Every snapshot shows up in
+// there as a directory, which looks exactly like the root did when it was taken. Making a directory in
+// here takes a snapshot, removing one deletes it, and nothing else can be changed.
+
+use std::path::PathBuf;
+
+use crate::pool::snapshot::snapshot_struct::SnapshotGuard;
+
+/// What the snapshot directory is called. It always lives in the root.
+pub(crate) const SNAPSHOT_DIR_NAME: &str = ".snapshots";
+
+/// The snapshot directory itself.
+pub(crate) struct SnapshotDir;
+
+/// A path as seen from inside of the snapshot it is in.
+///
+/// Lookups on this thread start from the snapshot's root until this is dropped, so the path can be
+/// handed to the usual methods like any other.
+pub(crate) struct SnapshotView {
+    /// The path with the snapshot part taken off, or the original path if it wasn't in a snapshot.
+    pub(super) path: PathBuf,
+    /// Keeps the snapshot's root in place. None outside of snapshots.
+    pub(super) _guard: Option<SnapshotGuard>,
+}
@@ -0,0 +1,93 @@
+// Looking back.
+// Unwrapping is okay here, since we want unexpected outcomes to fail tests.
+#![allow(clippy::unwrap_used)]
+
+use std::{ffi::OsStr, path::Path};
+
+use fuse_mt::{FileType, FilesystemMT, RequestInfo};
+use test_log::test;
+
+use crate::{
+    error_types::filesystem::*,
+    pool::disk::standard_disk::block::io::directory::tests::get_filesystem
+};
+
+use super::snapshot_dir_struct::SnapshotDir;
+
+fn request() -> RequestInfo {
+    RequestInfo {
+        unique: 0,
+        uid: 0,
+        gid: 0,
+        pid: 0,
+    }
+}
+
+#[test]
+fn paths_resolve() {
+    assert!(SnapshotDir::is_snapshot_dir(Path::new("/.snapshots")));
+    assert!(!SnapshotDir::is_snapshot_dir(Path::new("/.snapshots/old")));
+    assert!(SnapshotDir::is_snapshot_path(Path::new("/.snapshots/old/file")));
+    assert!(!SnapshotDir::is_snapshot_path(Path::new("/.snapshotsish")));
+    assert!(!SnapshotDir::is_snapshot_path(Path::new("/stuff/.snapshots")));
+}
+
+/// mkdir takes one, reading goes through it, rmdir gets rid of it.
+#[test]
+fn take_read_delete() {
+    let fs = get_filesystem();
+    let root = Path::new("/");
+    let snapshots = Path::new("/.snapshots");
+    let _ = fs.create(request(), root, OsStr::new("hello.txt"), 0, 0).unwrap();
+    let (handle, _) = fs.open(request(), Path::new("/hello.txt"), libc::O_RDWR as u32).unwrap();
+    let _ = fs.write(request(), Path::new("/hello.txt"), handle, 0, b"old".to_vec(), 0).unwrap();
+
+    let (_, attributes) = fs.mkdir(request(), snapshots, OsStr::new("monday"), 0).unwrap();
+    assert_eq!(attributes.kind, FileType::Directory);
+    assert_eq!(fs.mkdir(request(), snapshots, OsStr::new("monday"), 0).err(), Some(ITEM_ALREADY_EXISTS));
+
+    let _ = fs.write(request(), Path::new("/hello.txt"), handle, 0, b"new".to_vec(), 0).unwrap();
+
+    // Listed, but hidden from the root.
+    let (listing, _) = fs.opendir(request(), snapshots, 0).unwrap();
+    let listed = fs.readdir(request(), snapshots, listing).unwrap();
+    assert!(listed.iter().any(|entry| entry.name == "monday"));
+    let (listing, _) = fs.opendir(request(), root, 0).unwrap();
+    let listed = fs.readdir(request(), root, listing).unwrap();
+    assert!(listed.iter().all(|entry| entry.name != ".snapshots"));
+
+    // The old contents are still in there.
+    let path = Path::new("/.snapshots/monday/hello.txt");
+    let (_, attributes) = fs.getattr(request(), path, None).unwrap();
+    assert_eq!(attributes.size, 3);
+    let (old, _) = fs.open(request(), path, libc::O_RDONLY as u32).unwrap();
+    assert_eq!(fs.read_bytes(path, old, 0, 100).unwrap(), b"old");
+    let (listing, _) = fs.opendir(request(), Path::new("/.snapshots/monday"), 0).unwrap();
+    let listed = fs.readdir(request(), Path::new("/.snapshots/monday"), listing).unwrap();
+    assert!(listed.iter().any(|entry| entry.name == "hello.txt"));
+
+    // Gone once deleted.
+    assert_eq!(fs.rmdir(request(), snapshots, OsStr::new("monday")), Ok(()));
+    assert_eq!(fs.getattr(request(), path, None).err(), Some(NO_SUCH_ITEM));
+    assert_eq!(fs.rmdir(request(), snapshots, OsStr::new("monday")), Err(NO_SUCH_ITEM));
+}
+
+/// Nothing in a snapshot can be changed.
+#[test]
+fn read_only() {
+    let fs = get_filesystem();
+    let root = Path::new("/");
+    let _ = fs.mkdir(request(), root, OsStr::new("folder"), 0).unwrap();
+    let _ = fs.create(request(), root, OsStr::new("file"), 0, 0).unwrap();
+    let _ = fs.mkdir(request(), Path::new("/.snapshots"), OsStr::new("frozen"), 0).unwrap();
+
+    let frozen = Path::new("/.snapshots/frozen");
+    assert_eq!(fs.mkdir(request(), root, OsStr::new(".snapshots"), 0).err(), Some(ITEM_ALREADY_EXISTS));
+    assert_eq!(fs.create(request(), frozen, OsStr::new("new"), 0, 0).err(), Some(READ_ONLY));
+    assert_eq!(fs.mkdir(request(), frozen, OsStr::new("new"), 0).err(), Some(READ_ONLY));
+    assert_eq!(fs.unlink(request(), frozen, OsStr::new("file")), Err(READ_ONLY));
+    assert_eq!(fs.rmdir(request(), frozen, OsStr::new("folder")), Err(READ_ONLY));
+    assert_eq!(fs.rename(request(), frozen, OsStr::new("file"), root, OsStr::new("moved")), Err(READ_ONLY));
+    assert_eq!(fs.truncate(request(), &frozen.join("file"), None, 0), Err(READ_ONLY));
+    assert_eq!(fs.open(request(), &frozen.join("file"), libc::O_WRONLY as u32).err(), Some(READ_ONLY));
+}
@@ -12,13 +12,28 @@ use std::{
     time::Duration
 };
 
-use clap::Parser;
+use clap::{
+    Args,
+    Parser,
+    Subcommand,
+    ValueEnum
+};
 use fluster_fs::{
-    filesystem::filesystem_struct::{
-        FilesystemOptions,
-        FlusterFS
+    filesystem::{
+        filesystem_struct::{
+            CachePolicyKind,
+            FilesystemOptions,
+            FlusterFS
+        },
+        io_gate::{
+            fallocate_shim::FallocateShim,
+            gated_filesystem::GatedFlusterFS
+        }
     },
-    tui::notify::TUI_MANAGER
+    tui::{
+        notify::TUI_MANAGER,
+        prompt_script::prompt_script_struct::PromptSource
+    }
 };
 
 // Logging
@@ -40,13 +55,16 @@ use ratatui::{
 use tui_logger::TuiLoggerFile;
 
 #[derive(Parser)]
+#[command(subcommand_negates_reqs = true, args_conflicts_with_subcommands = true)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
     /// Path to the floppy block device.
-    #[arg(long)]
-    block_device_path: String,
+    #[arg(long, required = true)]
+    block_device_path: Option<String>,
     /// The mount point to mount the Fluster pool.
-    #[arg(long)]
-    mount_point: String,
+    #[arg(long, required = true)]
+    mount_point: Option<String>,
     /// Run with virtual floppy disks for testing. Path to put tempfiles in.
     #[arg(long)]
     use_virtual_disks: Option<String>,
@@ -57,14 +75,161 @@ struct Cli {
     /// Disable the TUI interface.
     #[arg(long)]
     disable_tui: Option<bool>,
+    /// Share identical data blocks between files. Saves floppies when storing lots of
+    /// near-identical files, at the cost of hashing every block written.
+    /// Defaults to on if the pool already shares blocks, or was made with it.
+    #[arg(long)]
+    enable_dedup: Option<bool>,
+    /// Move deleted files and directories into a hidden trash instead of freeing them right away.
+    /// They can be listed with `/.fluster/trash` and put back by writing their number to `/.fluster/restore`.
+    /// The trash is emptied, oldest first, before asking for another disk.
+    /// Defaults to on if the pool already has a trash, or was made with it.
+    #[arg(long)]
+    enable_trash: Option<bool>,
+    /// Keep a checksum of the contents of every new file. It's checked whenever a file is read from start
+    /// to finish, and can be checked at any time by reading the `user.fluster.checksum` attribute.
+    /// Defaults to on if the pool already has files with checksums, or was made with it.
+    #[arg(long)]
+    enable_checksums: Option<bool>,
+    /// Fix blocks with one or two flipped bits when they fail their CRC check, instead of treating
+    /// the block as unreadable. The damaged block stays on the disk until a scrub repairs it.
+    #[arg(long)]
+    enable_error_correction: Option<bool>,
+    /// How many threads FUSE gets to answer calls with. Only one of them ever uses the
+    /// floppy drive at a time, the rest answer whatever they can from the cache. Defaults to 4.
+    #[arg(long)]
+    worker_threads: Option<usize>,
+    /// How many blocks the cache can hold in total. Each block takes up a bit over 512 bytes
+    /// of RAM. Defaults to 46080, which is 16 floppies worth.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    cache_blocks: Option<u64>,
+    /// How the cache is split between tiers 0, 1 and 2, as comma separated weights. Defaults to `2,1,1`.
+    /// Every tier has to end up with at least one block.
+    #[arg(long, value_delimiter = ',', num_args = 3, value_parser = clap::value_parser!(u64).range(1..))]
+    cache_tier_ratios: Option<Vec<u64>>,
+    /// How the cache picks which blocks to throw out when it's full. `tiered` (the default) or
+    /// `adaptive`, which keeps blocks that are read a lot safe from big one-off reads.
+    #[arg(long)]
+    cache_policy: Option<CachePolicyKind>,
+    /// Write down every block the cache is asked for to this file, to compare the cache policies
+    /// against later with `compare-cache`. Off by default.
+    #[arg(long)]
+    cache_trace: Option<String>,
+    /// Write every change to disk right away, instead of holding it in the cache until a flush.
+    /// Safer if the machine goes down, but expect a lot more disk swapping.
+    #[arg(long)]
+    write_through: Option<bool>,
+    /// Flush the cache once it has been holding unwritten data for this many seconds.
+    /// No limit by default.
+    #[arg(long)]
+    max_dirty_age_secs: Option<u64>,
+    /// Save the cache to this file on unmount, and load it back in when mounting. Saves a lot of
+    /// disk swapping on startup. Off by default.
+    #[arg(long)]
+    cache_file: Option<String>,
+    /// What to call the pool on its disk labels. Defaults to whatever it was named by `mkfs`, or `Fluster!`.
+    #[arg(long)]
+    pool_name: Option<String>,
+    /// Write a list of what's on every disk, and printable labels for them, into this directory on
+    /// unmount. Building it reads through every disk. Off by default.
+    #[arg(long)]
+    manifest_directory: Option<String>,
+    /// Send prompts to another process instead of the TUI. `stdio` uses stdin and stdout (needs
+    /// the TUI disabled), anything else is the path of a unix socket to connect to.
+    #[arg(long)]
+    prompt_script: Option<PromptSource>,
+    /// How many seconds the prompt script gets to answer each prompt. No limit by default.
+    #[arg(long)]
+    prompt_timeout_secs: Option<u64>,
+    /// Add a new disk when the pool runs out of room. When off, full pools fail writes with
+    /// "No space left on device" instead. On by default.
+    #[arg(long)]
+    auto_grow: Option<bool>,
+    /// The most disks the pool can grow to, not counting the pool disk. No limit by default.
+    #[arg(long)]
+    max_disks: Option<u16>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Make a new pool on a blank pool disk, instead of mounting one.
+    Mkfs(MkfsArgs),
+    /// Replay a trace recorded with `--cache-trace` through every cache policy, and print how each did.
+    CompareCache(CompareCacheArgs),
+}
+
+#[derive(Args)]
+struct MkfsArgs {
+    /// Path to the floppy block device.
+    #[arg(long)]
+    block_device_path: String,
+    /// Make the pool on virtual floppy disks. Path to put the disk images in.
+    #[arg(long)]
+    use_virtual_disks: Option<String>,
+    /// Make backups of the new disks in /var/fluster. On by default.
+    #[arg(long)]
+    enable_disk_backup: Option<bool>,
+    /// What to call the pool. Saved on every disk, so it doesn't have to be given again when mounting.
+    /// Only the first 32 bytes are kept.
+    #[arg(long)]
+    pool_name: Option<String>,
+    /// How many standard disks to set up now, not counting the pool disk. More are made as they're needed.
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    disks: u16,
+    /// Optional features to have on by default for every mount, as a comma separated list.
+    #[arg(long, value_delimiter = ',')]
+    features: Vec<MkfsFeature>,
+    /// Send prompts to another process, see the mount option of the same name.
+    #[arg(long)]
+    prompt_script: Option<PromptSource>,
+}
+
+#[derive(Args)]
+struct CompareCacheArgs {
+    /// The trace file to replay.
+    #[arg(long)]
+    trace: String,
+    /// How many blocks the cache can hold, see the mount option of the same name.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    cache_blocks: Option<u64>,
+    /// How the tiered cache is split, see the mount option of the same name.
+    #[arg(long, value_delimiter = ',', num_args = 3, value_parser = clap::value_parser!(u64).range(1..))]
+    cache_tier_ratios: Option<Vec<u64>>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum MkfsFeature {
+    Trash,
+    Dedup,
+    Checksums,
 }
 
 fn main() {    
     // Get cli arguments
     let cli = Cli::parse();
 
+    match cli.command {
+        Some(Command::Mkfs(args)) => {
+            mkfs(args);
+            return;
+        },
+        Some(Command::CompareCache(args)) => {
+            compare_cache(args);
+            return;
+        },
+        None => {},
+    }
+
+    // Catch a cache that can't start before anything else does.
+    // The adaptive policy doesn't use tiers.
+    let cache_blocks: Option<usize> = cli.cache_blocks.map(|blocks| blocks as usize);
+    let tier_ratios: Option<[usize; 3]> = cache_tier_ratios(cli.cache_tier_ratios);
+    if cli.cache_policy.unwrap_or_default() == CachePolicyKind::Tiered {
+        check_cache_tiers(cache_blocks, tier_ratios);
+    }
+
     // get the mount point
-    let mount_point = PathBuf::from(cli.mount_point);
+    let mount_point = PathBuf::from(cli.mount_point.expect("Clap makes sure this is here."));
 
     // Start the logger
     // If we are using the tui, we need to use the TUI logger instead of env.
@@ -116,7 +281,24 @@ fn main() {
     let enable_tui = !cli.disable_tui.unwrap_or(false);
 
     let options: FilesystemOptions =
-        FilesystemOptions::new(use_virtual_disks, cli.block_device_path.into(), backup, enable_tui);
+        FilesystemOptions::new(use_virtual_disks, cli.block_device_path.expect("Clap makes sure this is here.").into(), backup, enable_tui)
+        .with_dedup(cli.enable_dedup)
+        .with_trash(cli.enable_trash)
+        .with_checksums(cli.enable_checksums)
+        .with_error_correction(cli.enable_error_correction.unwrap_or(false))
+        .with_write_through(cli.write_through.unwrap_or(false))
+        .with_max_dirty_age(cli.max_dirty_age_secs.map(Duration::from_secs))
+        .with_cache_blocks(cache_blocks)
+        .with_cache_file(cli.cache_file.map(PathBuf::from))
+        .with_cache_tier_ratios(tier_ratios)
+        .with_cache_policy(cli.cache_policy)
+        .with_cache_trace(cli.cache_trace.map(PathBuf::from))
+        .with_pool_name(cli.pool_name)
+        .with_manifest(cli.manifest_directory.map(PathBuf::from))
+        .with_prompt_timeout(cli.prompt_timeout_secs.map(Duration::from_secs))
+        .with_auto_grow(cli.auto_grow.unwrap_or(true))
+        .with_max_disks(cli.max_disks)
+        .with_prompt_script(cli.prompt_script);
 
 
     // Now before starting the filesystem, we need to start the TUI if needed.
@@ -178,8 +360,10 @@ fn main() {
 
     // Internal fuse_mt startup stuff i think, no comments on the function implementation.
     // takes in the filesystem, and the number of threads the filesystem will use
-    // Fluster! Is single threaded, so we actually set it to 0 threads. Weirdly.
-    let mt_thing = fuse_mt::FuseMT::new(filesystem, 0);
+    // The gate makes sure only one of those threads is ever using the drive.
+    let mt_thing = fuse_mt::FuseMT::new(GatedFlusterFS::new(filesystem), cli.worker_threads.unwrap_or(4));
+    // And the shim gets fallocate to us, since fuse_mt won't.
+    let mt_thing = FallocateShim::new(mt_thing);
 
 
     match fuse_mt::mount(mt_thing, &mount_point, &fuse_options) {
@@ -200,3 +384,65 @@ fn main() {
     }
 
 }
+
+/// Make a new pool, then leave. There's only a few disk swaps, so there's no TUI for this.
+fn mkfs(args: MkfsArgs) {
+    env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+    log_panics::Config::default().backtrace_mode(log_panics::BacktraceMode::Resolved).install_panic_hook();
+
+    let options: FilesystemOptions =
+        FilesystemOptions::new(args.use_virtual_disks.map(PathBuf::from), args.block_device_path.into(), args.enable_disk_backup, false)
+        .with_pool_name(args.pool_name)
+        .with_trash(Some(args.features.contains(&MkfsFeature::Trash)))
+        .with_dedup(Some(args.features.contains(&MkfsFeature::Dedup)))
+        .with_checksums(Some(args.features.contains(&MkfsFeature::Checksums)))
+        .with_prompt_script(args.prompt_script);
+
+    let _ = FlusterFS::mkfs(&options, args.disks);
+    println!("Made a new Fluster! pool with {} disks. Mount it whenever.", args.disks);
+}
+
+/// Turn the ratios clap hands back into something the cache can use.
+fn cache_tier_ratios(ratios: Option<Vec<u64>>) -> Option<[usize; 3]> {
+    ratios.map(|ratios| {
+        let ratios: [u64; 3] = ratios.try_into().expect("Clap only lets three ratios through.");
+        ratios.map(|ratio| ratio as usize)
+    })
+}
+
+/// Bail out before anything starts if the tiered cache would end up with an empty tier.
+fn check_cache_tiers(cache_blocks: Option<usize>, tier_ratios: Option<[usize; 3]>) {
+    let sizes: [usize; 3] = FlusterFS::cache_tier_sizes(cache_blocks, tier_ratios);
+    if sizes.contains(&0) {
+        eprintln!(
+            "`--cache-blocks` is too small for `--cache-tier-ratios`, the tiers would get {}, {} and {} blocks. Every tier needs at least one.",
+            sizes[0], sizes[1], sizes[2]
+        );
+        std::process::exit(1);
+    }
+}
+
+fn compare_cache(args: CompareCacheArgs) {
+    let cache_blocks: Option<usize> = args.cache_blocks.map(|blocks| blocks as usize);
+    let ratios: Option<[usize; 3]> = cache_tier_ratios(args.cache_tier_ratios);
+    // Both policies get replayed, including the tiered one.
+    check_cache_tiers(cache_blocks, ratios);
+    let reports = match FlusterFS::compare_cache_policies(&PathBuf::from(&args.trace), cache_blocks, ratios) {
+        Ok(reports) => reports,
+        Err(error) => {
+            eprintln!("Couldn't replay the trace: {error}");
+            std::process::exit(1);
+        },
+    };
+    println!("{:<10} {:>10} {:>10} {:>9} {:>8}", "policy", "hits", "misses", "hit rate", "swaps");
+    for report in reports {
+        println!(
+            "{:<10} {:>10} {:>10} {:>8.2}% {:>8}",
+            report.policy.to_string(),
+            report.hits,
+            report.misses,
+            report.hit_rate * 100.0,
+            report.swaps
+        );
+    }
+}
@@ -0,0 +1,427 @@
+// Dedup index upkeep.
+
+// Imports
+
+use log::{debug, error, warn};
+
+use crate::{
+    error_types::drive::DriveError,
+    filesystem::filesystem_struct::ENABLE_DEDUP,
+    pool::{
+        dedup::dedup_struct::{
+            DedupEntry,
+            DedupIndex,
+            DEDUP_INDEX
+        },
+        disk::{
+            drive_struct::{
+                DiskType,
+                FloppyDrive
+            },
+            generic::{
+                block::{
+                    block_structs::RawBlock,
+                    crc::add_crc_to_block
+                },
+                disk_trait::GenericDiskMethods,
+                generic_structs::pointer_struct::DiskPointer
+            },
+            pool_disk::block::header::header_struct::PoolDiskHeader
+        }
+    }
+};
+
+// Consts
+
+/// Each entry is a u64 hash, a disk pointer, and a u16 reference count.
+const ENTRY_SIZE: usize = 8 + 4 + 2;
+
+/// Index blocks start with a single byte holding the number of entries in the block,
+/// then the entries, then the usual CRC.
+pub(super) const ENTRIES_PER_BLOCK: usize = (508 - 1) / ENTRY_SIZE;
+
+/// The pool disk has 2880 blocks, but block 0 is the pool header, and the final two blocks are the item counts
+/// and the snapshot table.
+pub(super) const MAX_INDEX_BLOCKS: usize = 2880 - 3;
+
+/// Hash used for blocks that are shared without anyone having looked at their contents.
+///
+/// These never go in the hash lookup, since they don't actually hash to this.
+const UNKNOWN_HASH: u64 = 0;
+
+// Grab the index.
+macro_rules! get_index {
+    () => {
+        DEDUP_INDEX.lock().expect("Other mutex holders should not panic.")
+    };
+}
+
+// Implementations
+
+impl DedupIndex {
+    /// Make a new, empty index.
+    pub(super) fn new() -> Self {
+        DedupIndex::default()
+    }
+
+    /// Is deduplication of new writes turned on?
+    ///
+    /// Even if this is off, blocks that are already shared must still be respected.
+    pub(crate) fn enabled() -> bool {
+        *ENABLE_DEDUP.get().unwrap_or(&false)
+    }
+
+    /// Hash the contents of a data block.
+    ///
+    /// Ignores the CRC, since that is derived from the contents anyways.
+    ///
+    /// This is FNV-1a, since it needs to be stable across builds, std's hasher makes no such promises.
+    pub(crate) fn hash_block(data: &[u8; 512]) -> u64 {
+        hash_block(data)
+    }
+
+    /// Find a block that (probably) has the same contents as the provided hash.
+    ///
+    /// You MUST compare the contents of the returned block before sharing it, hashes collide.
+    pub(crate) fn find_duplicate(hash: u64) -> Option<DiskPointer> {
+        get_index!().by_hash.get(&hash).copied()
+    }
+
+    /// How many references are there to this block?
+    ///
+    /// Blocks we dont know about only have one reference.
+    pub(crate) fn reference_count(block: DiskPointer) -> u16 {
+        references_of(&get_index!(), block)
+    }
+
+    /// Something else now points at this block.
+    ///
+    /// The block must already be tracked.
+    pub(crate) fn add_reference(block: DiskPointer) {
+        go_add_reference(&mut get_index!(), block)
+    }
+
+    /// Is there room in the index for this many more shared blocks?
+    ///
+    /// Shared blocks can't be dropped from the index when it fills up, so nothing new should be
+    /// shared once they won't fit.
+    pub(crate) fn room_for_shares(count: usize) -> bool {
+        go_room_for_shares(&get_index!(), count)
+    }
+
+    /// Something else now points at each of these blocks, tracked or not.
+    ///
+    /// Untracked blocks are taken to have one reference already, and are tracked without a hash
+    /// since reading every block just to hash it would take forever.
+    pub(crate) fn share(blocks: &[DiskPointer]) {
+        let index = &mut get_index!();
+        for block in blocks {
+            go_share(index, *block);
+        }
+    }
+
+    /// Start tracking a block that is referenced exactly once, or update the hash of such a block
+    /// after it was re-written in place.
+    ///
+    /// Shared blocks cannot be re-tracked, since they should never be written to in place.
+    pub(crate) fn track(block: DiskPointer, hash: u64) {
+        go_track(&mut get_index!(), block, hash)
+    }
+
+    /// Stop tracking blocks entirely, regardless of how many references they had.
+    ///
+    /// This is called when blocks are freed, so new allocations never inherit stale entries.
+    pub(crate) fn forget(blocks: &[DiskPointer]) {
+        let index = &mut get_index!();
+        for block in blocks {
+            go_forget(index, *block);
+        }
+    }
+
+    /// Drop one reference to each of the provided blocks.
+    ///
+    /// A block may show up more than once if a file pointed at it more than once.
+    ///
+    /// Returns the blocks that no longer have any references, and thus should be freed.
+    /// Blocks that were not tracked are always returned.
+    pub(crate) fn release(blocks: Vec<DiskPointer>) -> Vec<DiskPointer> {
+        go_release(&mut get_index!(), blocks)
+    }
+
+    /// Remember that these blocks on a dense disk are no longer used by anything.
+    ///
+    /// Dense disks can only be given back all at once, but snapshots can let go of one a little at a time.
+    ///
+    /// Returns how many blocks on that disk are now unused.
+    pub(crate) fn bury(blocks: &[DiskPointer]) -> usize {
+        go_bury(&mut get_index!(), blocks)
+    }
+
+    /// Read the index off of the pool disk.
+    ///
+    /// Will swap to the pool disk if there is an index to read.
+    pub(crate) fn load(index_blocks: u16) -> Result<(), DriveError> {
+        go_load_index(index_blocks)
+    }
+
+    /// Write the index to the pool disk, and update the provided header to match.
+    ///
+    /// Does not write the header itself.
+    pub(crate) fn flush(header: &mut PoolDiskHeader) -> Result<(), DriveError> {
+        go_flush_index(header)
+    }
+}
+
+// Functions
+
+pub(super) fn hash_block(data: &[u8; 512]) -> u64 {
+    // FNV-1a, 64 bit.
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in &data[..508] {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+pub(super) fn references_of(index: &DedupIndex, block: DiskPointer) -> u16 {
+    if let Some(entry) = index.by_pointer.get(&block) {
+        entry.references
+    } else {
+        // Not tracked, so only one thing points at it.
+        1
+    }
+}
+
+pub(super) fn go_add_reference(index: &mut DedupIndex, block: DiskPointer) {
+    // You can only share blocks that we know the contents of.
+    let entry = index.by_pointer.get_mut(&block).expect("Cannot add a reference to an untracked block!");
+    // If something manages to reference the same block 65 thousand times, it can have its own copy.
+    assert!(entry.references < u16::MAX, "Too many references to a single block!");
+    entry.references += 1;
+}
+
+pub(super) fn go_room_for_shares(index: &DedupIndex, count: usize) -> bool {
+    let kept: usize = index.by_pointer.values().filter(|entry| entry.references != 1).count();
+    kept + count <= MAX_INDEX_BLOCKS * ENTRIES_PER_BLOCK
+}
+
+pub(super) fn go_share(index: &mut DedupIndex, block: DiskPointer) {
+    if index.by_pointer.contains_key(&block) {
+        go_add_reference(index, block);
+        return;
+    }
+    // Whoever had it before, plus the new one.
+    let _ = index.by_pointer.insert(block, DedupEntry {
+        hash: UNKNOWN_HASH,
+        references: 2,
+    });
+}
+
+pub(super) fn go_track(index: &mut DedupIndex, block: DiskPointer, hash: u64) {
+    // Drop the old hash if there was one.
+    if let Some(old) = index.by_pointer.get(&block).copied() {
+        // Shared blocks are never updated in place, that would change the contents of other files.
+        assert!(old.references <= 1, "Tried to re-track a shared block!");
+        remove_hash_if_owner(index, old.hash, block);
+    }
+
+    let _ = index.by_pointer.insert(block, DedupEntry {
+        hash,
+        references: 1,
+    });
+
+    // If another block already has these contents, keep that one as the canonical copy.
+    let _ = index.by_hash.entry(hash).or_insert(block);
+}
+
+pub(super) fn go_forget(index: &mut DedupIndex, block: DiskPointer) {
+    if let Some(old) = index.by_pointer.remove(&block) {
+        remove_hash_if_owner(index, old.hash, block);
+    }
+}
+
+pub(super) fn go_release(index: &mut DedupIndex, blocks: Vec<DiskPointer>) -> Vec<DiskPointer> {
+    let mut to_free: Vec<DiskPointer> = Vec::with_capacity(blocks.len());
+    for block in blocks {
+        let entry = if let Some(entry) = index.by_pointer.get_mut(&block) {
+            entry
+        } else {
+            // Untracked, this was the only reference.
+            to_free.push(block);
+            continue;
+        };
+
+        entry.references = entry.references.saturating_sub(1);
+        if entry.references == 0 {
+            // Last one out turns off the lights.
+            go_forget(index, block);
+            to_free.push(block);
+        }
+    }
+    to_free
+}
+
+pub(super) fn go_bury(index: &mut DedupIndex, blocks: &[DiskPointer]) -> usize {
+    for block in blocks {
+        go_forget(index, *block);
+        // Nothing points at these, so they stay out of the hash lookup.
+        let _ = index.by_pointer.insert(*block, DedupEntry {
+            hash: UNKNOWN_HASH,
+            references: 0,
+        });
+    }
+    let disk = blocks.first().map_or(0, |block| block.disk);
+    index.by_pointer
+        .iter()
+        .filter(|(pointer, entry)| pointer.disk == disk && entry.references == 0)
+        .count()
+}
+
+/// Hashes only point at one block, so we need to make sure we only remove the hash if it
+/// was pointing at the block we are removing.
+fn remove_hash_if_owner(index: &mut DedupIndex, hash: u64, block: DiskPointer) {
+    if index.by_hash.get(&hash) == Some(&block) {
+        let _ = index.by_hash.remove(&hash);
+    }
+}
+
+/// Turn the index into blocks to write to the pool disk.
+///
+/// Shared and buried blocks are always written first, since forgetting about those would cause them to be freed
+/// while other files still use them, or never be freed at all. Blocks with a single reference are just hints,
+/// and can be dropped if we run out of room.
+///
+/// Returns the block data, without origins set, or `NoSpace` if even the shared blocks don't fit.
+pub(super) fn index_to_blocks(index: &DedupIndex) -> Result<Vec<[u8; 512]>, DriveError> {
+    let mut entries: Vec<(DiskPointer, DedupEntry)> = index.by_pointer.iter().map(|(pointer, entry)| (*pointer, *entry)).collect();
+
+    // Shared and buried first, then in disk order so the output is stable.
+    entries.sort_unstable_by_key(|(pointer, entry)| (entry.references == 1, pointer.disk, pointer.block));
+
+    let max_entries = MAX_INDEX_BLOCKS * ENTRIES_PER_BLOCK;
+    if entries.len() > max_entries {
+        // Make sure we aren't about to lose a shared block.
+        if entries[max_entries].1.references != 1 {
+            // Can't write it out without losing track of shared blocks. Leave the old index on
+            // disk alone, the one in memory is still right.
+            error!("Too many shared blocks to fit in the dedup index!");
+            return Err(DriveError::NoSpace);
+        }
+        warn!("Dedup index is full, dropping {} unshared entries.", entries.len() - max_entries);
+        entries.truncate(max_entries);
+    }
+
+    let mut blocks: Vec<[u8; 512]> = Vec::with_capacity(entries.len().div_ceil(ENTRIES_PER_BLOCK));
+    for chunk in entries.chunks(ENTRIES_PER_BLOCK) {
+        let mut buffer: [u8; 512] = [0u8; 512];
+        // Always less than 256.
+        buffer[0] = chunk.len() as u8;
+        let mut offset: usize = 1;
+        for (pointer, entry) in chunk {
+            buffer[offset..offset + 8].copy_from_slice(&entry.hash.to_le_bytes());
+            offset += 8;
+            buffer[offset..offset + 4].copy_from_slice(&pointer.to_bytes());
+            offset += 4;
+            buffer[offset..offset + 2].copy_from_slice(&entry.references.to_le_bytes());
+            offset += 2;
+        }
+        add_crc_to_block(&mut buffer);
+        blocks.push(buffer);
+    }
+    Ok(blocks)
+}
+
+/// Rebuild the index from blocks read off the pool disk.
+pub(super) fn index_from_blocks(blocks: &[[u8; 512]]) -> DedupIndex {
+    let mut index = DedupIndex::new();
+    for block in blocks {
+        let count = block[0] as usize;
+        let mut offset: usize = 1;
+        for _ in 0..count {
+            let hash = u64::from_le_bytes(block[offset..offset + 8].try_into().expect("8 = 8"));
+            offset += 8;
+            let pointer = DiskPointer::from_bytes(block[offset..offset + 4].try_into().expect("4 = 4"));
+            offset += 4;
+            let references = u16::from_le_bytes(block[offset..offset + 2].try_into().expect("2 = 2"));
+            offset += 2;
+
+            let _ = index.by_pointer.insert(pointer, DedupEntry { hash, references });
+            if hash != UNKNOWN_HASH && references != 0 {
+                let _ = index.by_hash.entry(hash).or_insert(pointer);
+            }
+        }
+    }
+    index
+}
+
+fn go_load_index(index_blocks: u16) -> Result<(), DriveError> {
+    if index_blocks == 0 {
+        // Nothing to load, this pool has never been deduplicated.
+        debug!("No dedup index on the pool disk.");
+        return Ok(());
+    }
+    debug!("Loading {index_blocks} dedup index blocks from the pool disk...");
+
+    #[allow(deprecated)] // Pool disks cannot use the cache.
+    let disk = match FloppyDrive::open(0)? {
+        DiskType::Pool(pool_disk) => pool_disk,
+        _ => unreachable!("Disk 0 should NEVER be assigned to a non-pool disk!"),
+    };
+
+    let mut blocks: Vec<[u8; 512]> = Vec::with_capacity(index_blocks.into());
+    // The index always starts right after the header.
+    for block_number in 1..=index_blocks {
+        blocks.push(disk.unchecked_read_block(block_number)?.data);
+    }
+
+    *get_index!() = index_from_blocks(&blocks);
+    debug!("Dedup index loaded.");
+    Ok(())
+}
+
+fn go_flush_index(header: &mut PoolDiskHeader) -> Result<(), DriveError> {
+    let blocks = index_to_blocks(&get_index!())?;
+
+    // If there was never an index, and there still isn't one, we dont need to touch the disk.
+    if blocks.is_empty() && header.dedup_index_blocks == 0 {
+        return Ok(());
+    }
+
+    debug!("Writing {} dedup index blocks to the pool disk...", blocks.len());
+
+    #[allow(deprecated)] // Pool disks cannot use the cache.
+    let mut disk = match FloppyDrive::open(0)? {
+        DiskType::Pool(pool_disk) => pool_disk,
+        _ => unreachable!("Disk 0 should NEVER be assigned to a non-pool disk!"),
+    };
+
+    for (number, data) in blocks.iter().enumerate() {
+        let block = RawBlock {
+            block_origin: DiskPointer {
+                disk: 0,
+                // Skip the header. Always fits, MAX_INDEX_BLOCKS is below u16::MAX.
+                block: number as u16 + 1,
+            },
+            data: *data,
+        };
+        disk.unchecked_write_block(&block)?;
+    }
+
+    // Now update the header. The index is always right after the header, so only that stretch of the
+    // allocation map changes. Everything past it belongs to someone else.
+    let new_count: u16 = blocks.len() as u16;
+    for block in 1..=MAX_INDEX_BLOCKS {
+        let bit: u8 = 0b10000000 >> (block % 8);
+        if block <= new_count as usize {
+            header.block_usage_map[block / 8] |= bit;
+        } else {
+            header.block_usage_map[block / 8] &= !bit;
+        }
+    }
+    header.block_usage_map[0] |= 0b10000000;
+    header.dedup_index_blocks = new_count;
+
+    debug!("Dedup index flushed.");
+    Ok(())
+}
@@ -0,0 +1,47 @@
+// Why store it twice when you can store it once and argue about who owns it?
+
+// Imports
+
+use std::{
+    collections::HashMap,
+    sync::Mutex
+};
+
+use lazy_static::lazy_static;
+
+use crate::pool::disk::generic::generic_structs::pointer_struct::DiskPointer;
+
+// Structs, Enums, Flags
+
+lazy_static! {
+    /// The global dedup index.
+    /// 
+    /// Loaded from the pool disk on startup, and written back when the pool is flushed.
+    pub(crate) static ref DEDUP_INDEX: Mutex<DedupIndex> = Mutex::new(DedupIndex::new());
+}
+
+/// Content addressed index of data blocks.
+/// 
+/// Blocks that are not in the index are assumed to be referenced exactly once, which
+/// is the case for every block that was written without deduplication.
+#[derive(Debug, Default)]
+pub(crate) struct DedupIndex {
+    /// Hash of a block's contents to the block that holds those contents.
+    /// 
+    /// Hashes can collide, so the contents of the block must be compared before
+    /// it is shared.
+    pub(super) by_hash: HashMap<u64, DiskPointer>,
+    /// Every block we know about.
+    pub(super) by_pointer: HashMap<DiskPointer, DedupEntry>,
+}
+
+/// Information about a single indexed block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct DedupEntry {
+    /// Hash of the contents of the block.
+    pub(crate) hash: u64,
+    /// How many places in the pool point at this block.
+    /// 
+    /// Blocks are only freed when this hits zero.
+    pub(crate) references: u16,
+}
@@ -0,0 +1,4 @@
+pub(crate) mod dedup_struct;
+pub(crate) mod dedup_methods;
+#[cfg(test)]
+mod tests;
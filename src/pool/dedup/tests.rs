@@ -0,0 +1,142 @@
+// Two of the same test would be a waste, wouldn't it?
+// Unwrapping is okay here, since we want unexpected outcomes to fail tests.
+#![allow(clippy::unwrap_used)]
+
+// Imports
+use rand::{Rng, RngCore};
+
+use crate::pool::{
+    dedup::{
+        dedup_methods::{
+            ENTRIES_PER_BLOCK,
+            MAX_INDEX_BLOCKS,
+            go_add_reference,
+            go_bury,
+            go_release,
+            go_room_for_shares,
+            go_share,
+            go_track,
+            hash_block,
+            index_from_blocks,
+            index_to_blocks,
+            references_of
+        },
+        dedup_struct::DedupIndex
+    },
+    disk::generic::{
+        block::crc::check_crc,
+        generic_structs::pointer_struct::DiskPointer
+    }
+};
+
+use test_log::test; // We want to see logs while testing.
+
+// Tests
+
+/// Blocks are only released once every reference is gone.
+#[test]
+fn release_only_at_zero() {
+    let mut index = DedupIndex::new();
+    let block = DiskPointer { disk: 3, block: 40 };
+    go_track(&mut index, block, 1234);
+    go_add_reference(&mut index, block);
+    go_add_reference(&mut index, block);
+    assert_eq!(references_of(&index, block), 3);
+
+    // Two of the references go away at once, like a file that used the block twice.
+    assert!(go_release(&mut index, vec![block, block]).is_empty());
+    assert_eq!(references_of(&index, block), 1);
+
+    // Last one.
+    assert_eq!(go_release(&mut index, vec![block]), vec![block]);
+    assert!(index.by_pointer.is_empty());
+    assert!(index.by_hash.is_empty());
+}
+
+/// Untracked blocks only have one reference, so releasing them always frees them.
+#[test]
+fn untracked_blocks_are_freed() {
+    let mut index = DedupIndex::new();
+    let block = DiskPointer::get_random();
+    assert_eq!(references_of(&index, block), 1);
+    assert_eq!(go_release(&mut index, vec![block]), vec![block]);
+}
+
+/// Sharing works on untracked blocks too, without making them findable by hash.
+#[test]
+fn sharing_untracked_blocks() {
+    let mut index = DedupIndex::new();
+    let block = DiskPointer { disk: 5, block: 12 };
+    go_share(&mut index, block);
+    assert_eq!(references_of(&index, block), 2);
+    assert!(index.by_hash.is_empty());
+    assert!(go_release(&mut index, vec![block]).is_empty());
+    assert_eq!(go_release(&mut index, vec![block]), vec![block]);
+}
+
+/// Buried blocks are counted per disk, and survive the trip to disk.
+#[test]
+fn buried_blocks_add_up() {
+    let mut index = DedupIndex::new();
+    let first: Vec<DiskPointer> = (1..=10).map(|block| DiskPointer { disk: 7, block }).collect();
+    let second: Vec<DiskPointer> = (11..=15).map(|block| DiskPointer { disk: 7, block }).collect();
+    let elsewhere: Vec<DiskPointer> = (1..=3).map(|block| DiskPointer { disk: 8, block }).collect();
+    assert_eq!(go_bury(&mut index, &first), 10);
+    assert_eq!(go_bury(&mut index, &elsewhere), 3);
+    assert_eq!(go_bury(&mut index, &second), 15);
+
+    let mut reloaded = index_from_blocks(&index_to_blocks(&index).unwrap());
+    assert_eq!(go_bury(&mut reloaded, &[DiskPointer { disk: 7, block: 16 }]), 16);
+}
+
+/// Same contents, same hash. Different contents, (almost certainly) different hash.
+#[test]
+fn hash_follows_contents() {
+    let mut random = rand::rng();
+    let mut a: [u8; 512] = [0u8; 512];
+    random.fill_bytes(&mut a);
+    let mut b: [u8; 512] = a;
+    // CRC is ignored.
+    b[510] ^= 0xFF;
+    assert_eq!(hash_block(&a), hash_block(&b));
+    b[100] ^= 0xFF;
+    assert_ne!(hash_block(&a), hash_block(&b));
+}
+
+/// The index survives a trip to the disk and back.
+#[test]
+fn index_ping_pong() {
+    let mut random = rand::rng();
+    let mut index = DedupIndex::new();
+    // Enough to need a few blocks.
+    for _ in 0..200 {
+        let block = DiskPointer::get_random();
+        go_track(&mut index, block, random.random());
+        for _ in 0..random.random_range(0..4) {
+            go_add_reference(&mut index, block);
+        }
+    }
+
+    let blocks = index_to_blocks(&index).unwrap();
+    assert!(blocks.len() > 1);
+    assert!(blocks.iter().all(|block| check_crc(*block)));
+
+    let read_back = index_from_blocks(&blocks);
+    assert_eq!(read_back.by_pointer, index.by_pointer);
+}
+
+/// Sharing stops once the index can't hold another shared block, and writing out an index that
+/// is too full anyway is an error instead of a crash.
+#[test]
+fn full_index_refuses_shares() {
+    let mut index = DedupIndex::new();
+    let fits: u32 = (MAX_INDEX_BLOCKS * ENTRIES_PER_BLOCK) as u32;
+    for next in 0..fits {
+        go_share(&mut index, DiskPointer { disk: (next / 2880) as u16 + 1, block: (next % 2880) as u16 });
+    }
+    assert!(!go_room_for_shares(&index, 1), "A full index should have no room left.");
+    assert!(index_to_blocks(&index).is_ok(), "A full index should still fit.");
+
+    go_share(&mut index, DiskPointer { disk: (fits / 2880) as u16 + 1, block: (fits % 2880) as u16 });
+    assert!(index_to_blocks(&index).is_err(), "An overfull index should not be written.");
+}
@@ -0,0 +1,186 @@
+// Dense disk
+
+// Imports
+
+use std::fs::File;
+
+use log::debug;
+
+use crate::{
+    error_types::drive::DriveError,
+    filesystem::filesystem_struct::POOL_NAME,
+    pool::disk::{
+        drive_struct::DiskBootstrap,
+        generic::{
+            block::{
+                allocate::block_allocation::BlockAllocation,
+                block_structs::RawBlock,
+                crc::add_crc_to_block
+            },
+            disk_trait::GenericDiskMethods,
+            generic_structs::pointer_struct::DiskPointer,
+            io::{
+                cache::cache_io::CachedBlockIO,
+                read::{
+                    read_block_direct,
+                    read_multiple_blocks_direct
+                },
+                write::{
+                    write_block_direct,
+                    write_large_direct
+                }
+            }
+        },
+        standard_disk::{
+            block::header::header_struct::{
+                StandardDiskHeader,
+                StandardHeaderFlags
+            },
+            standard_disk_struct::StandardDisk
+        }
+    },
+    pool::format::format_struct::FORMAT_VERSION
+};
+
+use super::dense_disk_struct::{
+    DenseDisk,
+    DENSE_DISK_BLOCKS
+};
+
+// Implementations
+
+impl DenseDisk {
+    /// Every data block on a dense disk, in order.
+    pub(crate) fn data_pointers(disk_number: u16) -> Vec<DiskPointer> {
+        (1..=DENSE_DISK_BLOCKS).map(|block| DiskPointer {
+            disk: disk_number,
+            block,
+        }).collect()
+    }
+}
+
+// The cache only knows how to talk to standard disks. Since dense disks have the same header layout
+// and every block is always allocated, the cache can treat them like a standard disk that happens to be full.
+impl From<DenseDisk> for StandardDisk {
+    fn from(value: DenseDisk) -> Self {
+        StandardDisk {
+            number: value.number,
+            header: value.header,
+            disk_file: value.disk_file,
+        }
+    }
+}
+
+impl DiskBootstrap for DenseDisk {
+    fn bootstrap(file: File, disk_number: u16) -> Result<Self, DriveError> {
+        debug!("Bootstrapping a dense disk...");
+
+        // Dense disks are never allocated against, so the whole disk is marked as used from the start.
+        let header: StandardDiskHeader = StandardDiskHeader {
+            flags: StandardHeaderFlags::Dense,
+            disk_number,
+            generation: 0,
+            format_version: FORMAT_VERSION,
+            pool_label: POOL_NAME.get().cloned().unwrap_or_default(),
+            block_usage_map: [u8::MAX; 360],
+        };
+
+        let mut disk: DenseDisk = DenseDisk {
+            number: disk_number,
+            header,
+            disk_file: file,
+        };
+
+        debug!("Writing header...");
+        disk.unchecked_write_block(&disk.header.to_block())?;
+
+        // Blocks are read before they are written if a write doesn't fill them, so they all
+        // need a valid crc. One big write is way faster than going block by block.
+        debug!("Writing empty data blocks...");
+        let mut empty_block: [u8; 512] = [0u8; 512];
+        add_crc_to_block(&mut empty_block);
+        let data: Vec<u8> = empty_block.repeat(DENSE_DISK_BLOCKS.into());
+        let start: DiskPointer = DiskPointer {
+            disk: disk_number,
+            block: 1,
+        };
+        disk.unchecked_write_large(data, start)?;
+
+        debug!("Done bootstrapping dense disk.");
+        Ok(disk)
+    }
+
+    fn from_header(block: RawBlock, file: File) -> Self {
+        let header: StandardDiskHeader = StandardDiskHeader::from_block(&block);
+        DenseDisk {
+            number: header.disk_number,
+            disk_file: file,
+            header,
+        }
+    }
+}
+
+// Dense disks are full by definition.
+impl BlockAllocation for DenseDisk {
+    fn get_allocation_table(&self) -> &[u8] {
+        &self.header.block_usage_map
+    }
+
+    fn set_allocation_table(&mut self, _new_table: &[u8]) -> Result<(), DriveError> {
+        unreachable!("Block allocation is not supported on dense disks.")
+    }
+}
+
+// Generic disk operations
+impl GenericDiskMethods for DenseDisk {
+    #[doc = " Read a block"]
+    #[doc = " Cannot bypass CRC."]
+    fn unchecked_read_block(&self, block_number: u16) -> Result<RawBlock, DriveError> {
+        // This is the first call, we have not recursed.
+        read_block_direct(&self.disk_file, self.number, block_number, false, false)
+    }
+
+    #[doc = " Write a block"]
+    fn unchecked_write_block(&mut self, block: &RawBlock) -> Result<(), DriveError> {
+        // This is the first call, we have not recursed.
+        write_block_direct(&self.disk_file, block, false)
+    }
+
+    #[doc = " Write chunked data, starting at a block."]
+    fn unchecked_write_large(&mut self, data:Vec<u8>, start_block:DiskPointer) -> Result<(), DriveError> {
+        write_large_direct(&self.disk_file, &data, start_block)
+    }
+
+    #[doc = " Get the inner file used for IO operations"]
+    fn disk_file(self) -> File {
+        self.disk_file
+    }
+
+    #[doc = " Get the number of the floppy disk."]
+    fn get_disk_number(&self) -> u16 {
+        self.number
+    }
+
+    #[doc = " Set the number of this disk."]
+    fn set_disk_number(&mut self, disk_number: u16) {
+        self.number = disk_number
+    }
+
+    #[doc = " Get the inner file used for write operations"]
+    fn disk_file_mut(&mut self) -> &mut File {
+        &mut self.disk_file
+    }
+
+    #[doc = " Sync all in-memory information to disk"]
+    #[doc = " Headers and such."]
+    fn flush(&mut self) -> Result<(), DriveError> {
+        CachedBlockIO::update_block(&self.header.to_block())
+    }
+
+    #[doc = " Read multiple blocks"]
+    #[doc = " Does not check CRC!"]
+    fn unchecked_read_multiple_blocks(&self, block_number: u16, num_block_to_read: u16) -> Result<Vec<RawBlock>, DriveError> {
+        // This is the first call, we have not recursed.
+        read_multiple_blocks_direct(&self.disk_file, self.number, block_number, num_block_to_read, false)
+    }
+}
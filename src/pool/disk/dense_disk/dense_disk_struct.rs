@@ -0,0 +1,26 @@
+// Thick as a brick.
+
+// Imports
+
+use crate::pool::disk::standard_disk::block::header::header_struct::StandardDiskHeader;
+
+// Consts
+
+/// How many data blocks a dense disk holds. Everything but the header.
+pub(crate) const DENSE_DISK_BLOCKS: u16 = 2879;
+
+// Structs, Enums, Flags
+
+/// A disk given over entirely to one big file.
+/// 
+/// The header is laid out just like a standard disk header, but with the dense flag set and
+/// every block marked as used, since nothing else is ever allocated here.
+#[derive(Debug)]
+pub struct DenseDisk {
+    /// Which disk is this?
+    pub number: u16,
+    /// The disk header
+    pub header: StandardDiskHeader,
+    /// The file that refers to this disk
+    pub(in super::super) disk_file: std::fs::File,
+}
@@ -0,0 +1,2 @@
+mod dense_disk_methods;
+pub mod dense_disk_struct;
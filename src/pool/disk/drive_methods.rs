@@ -17,6 +17,7 @@ use crate::error_types::drive::DriveIOError;
 use crate::error_types::drive::WrappedIOError;
 use crate::helpers::hex_view::hex_view;
 use crate::pool::disk::blank_disk::blank_disk_struct::BlankDisk;
+use crate::pool::disk::dense_disk::dense_disk_struct::DenseDisk;
 use crate::pool::disk::drive_struct::DiskBootstrap;
 use crate::pool::disk::generic::block::block_structs::RawBlock;
 use crate::pool::disk::generic::disk_trait::GenericDiskMethods;
@@ -138,6 +139,15 @@ fn open_and_deduce_disk(disk_number: u16, new_disk: bool) -> Result<DiskType, Dr
         )));
     }
 
+    // Dense disk.
+    if header_block.data[8] & 0b01000000 != 0 {
+        trace!("Head is for a dense disk, returning.");
+        return Ok(DiskType::Dense(DenseDisk::from_header(
+            header_block,
+            disk_file,
+        )));
+    }
+
     // it should be impossible to get here
     error!("Hexdump:\n{}", hex_view(header_block.data.to_vec()));
     error!("We cannot continue with an un-deducible disk!");
@@ -149,7 +159,7 @@ fn get_floppy_drive_file(disk_number: u16, new_disk: bool) -> Result<File, Drive
     // If we are running with virtual disks enabled, we are going to use a temp folder instead of the actual disk to speed up
     // development, waiting for disk seeks is slow and loud lol.
 
-    if let Ok(maybe_path) = USE_VIRTUAL_DISKS.try_lock() {
+    if let Ok(maybe_path) = USE_VIRTUAL_DISKS.lock() {
         if let Some(virtual_disk_path) = maybe_path.clone() {
             // Virtual disks are enabled.
             trace!("Attempting to access virtual disk {disk_number}...");
@@ -205,7 +215,7 @@ fn get_floppy_drive_file(disk_number: u16, new_disk: bool) -> Result<File, Drive
     }
 
     // Get the global path to the floppy disk drive
-    let disk_path = if let Ok(path) = FLOPPY_PATH.try_lock() {
+    let disk_path = if let Ok(path) = FLOPPY_PATH.lock() {
         path.clone()
     } else {
         // Poison? In MY drive method?
@@ -215,7 +225,7 @@ fn get_floppy_drive_file(disk_number: u16, new_disk: bool) -> Result<File, Drive
         // We need the disk path to be able to flush contents to disk upon panic, so we have to clean this up.
         error!("FLOPPY_PATH is poisoned! Clearing, but you REALLY need to shut down immediately.");
         FLOPPY_PATH.clear_poison();
-        if let Ok(round_two) = FLOPPY_PATH.try_lock() {
+        if let Ok(round_two) = FLOPPY_PATH.lock() {
             round_two.clone()
         } else {
             // Err...
@@ -376,7 +386,8 @@ fn prompt_for_disk(disk_number: u16) -> Result<DiskType, DriveError> {
         let result = TuiPrompt::prompt_wait_for_disk_swap(
             "Please swap disks.".to_string(),
             format!("Please remove disk {previous_disk}, and insert disk {disk_number}"),
-            true
+            true,
+            disk_number
         );
 
         match result {
@@ -397,7 +408,7 @@ fn prompt_for_blank_disk(disk_number: u16) -> Result<BlankDisk, DriveError> {
     let mut try_again: bool = false;
 
     // If we are on virtual disks, skip the initial prompt
-    let use_virtual: bool = if let Ok(locked) = USE_VIRTUAL_DISKS.try_lock() {
+    let use_virtual: bool = if let Ok(locked) = USE_VIRTUAL_DISKS.lock() {
         locked.is_some()
     } else {
         // Poisoned. We should not be adding new disks after being poisoned. We should be shutting down.
@@ -409,7 +420,8 @@ fn prompt_for_blank_disk(disk_number: u16) -> Result<BlankDisk, DriveError> {
         TuiPrompt::prompt_wait_for_disk_swap(
             "New disk.".to_string(),
             format!("Creating a new disk, please insert a blank disk that will become disk {disk_number}."),
-            true
+            true,
+            disk_number
         )?;
     }
 
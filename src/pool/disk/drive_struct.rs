@@ -3,7 +3,7 @@
 // Imports
 
 use crate::{error_types::drive::DriveError, pool::disk::{
-    blank_disk::blank_disk_struct::BlankDisk, unknown_disk::unknown_disk_struct::UnknownDisk,
+    blank_disk::blank_disk_struct::BlankDisk, dense_disk::dense_disk_struct::DenseDisk, unknown_disk::unknown_disk_struct::UnknownDisk,
 }};
 use std::fs::File;
 
@@ -31,6 +31,7 @@ pub struct FloppyDrive {
 pub enum DiskType {
     Pool(PoolDisk),
     Standard(StandardDisk),
+    Dense(DenseDisk),
     Unknown(UnknownDisk),
     Blank(BlankDisk),
 }
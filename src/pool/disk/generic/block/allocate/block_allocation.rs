@@ -15,6 +15,7 @@ use crate::{
 };
 use crate::pool::disk::pool_disk::pool_disk_struct::PoolDisk;
 use crate::pool::disk::standard_disk::standard_disk_struct::StandardDisk;
+use crate::pool::disk::dense_disk::dense_disk_struct::DenseDisk;
 use crate::pool::disk::unknown_disk::unknown_disk_struct::UnknownDisk;
 use crate::pool::disk::blank_disk::blank_disk_struct::BlankDisk;
 use log::{
@@ -1,5 +1,10 @@
 // CRC check
 
+use std::{
+    collections::HashMap,
+    sync::OnceLock
+};
+
 // Check whether the CRC matches the block or not
 // returns true if crc matches the block correctly.
 pub fn check_crc(block: [u8; 512]) -> bool {
@@ -21,4 +26,86 @@ pub(crate) fn add_crc_to_block(block: &mut [u8; 512]) {
 }
 
 // Correcting detected errors could in theory be done with trying to flip every bit, but realistically,
-// we're better off just re-reading it, or restoring it from backup.
\ No newline at end of file
+// we're better off just re-reading it, or restoring it from backup.
+//
+// Unless the bits are flipped on the disk itself, and re-reading just gets the same wrong bits back.
+// A CRC is a cyclic code, same family as BCH, and CRC32C over a whole block has a minimum distance of 6.
+// That's enough to fix any one or two flipped bits, while still noticing any three, without taking a single
+// byte away from the blocks. So the format stays the same, and fixing is just a smarter way of reading it.
+// It's still only a guess, not real parity. A block format with its own Reed-Solomon or BCH parity doesn't
+// exist yet, see design_choices.md.
+
+/// Every block is 4096 bits, CRC included.
+const BLOCK_BITS: usize = 512 * 8;
+
+/// What flipping each bit of a block does to the CRC check, see [`syndrome`].
+static SYNDROMES: OnceLock<Syndromes> = OnceLock::new();
+
+struct Syndromes {
+    /// Indexed by bit.
+    by_bit: Vec<u32>,
+    /// The other way around. Every bit has its own syndrome, that's the whole trick.
+    to_bit: HashMap<u32, usize>,
+}
+
+/// How far off the CRC in a block is from what the contents say it should be.
+///
+/// Zero if the block is fine. Flipping bits changes this the same way no matter what else is in the
+/// block, so a set of flipped bits can be worked out from it.
+fn syndrome(block: &[u8; 512]) -> u32 {
+    let stored: u32 = u32::from_le_bytes(block[508..512].try_into().expect("4 = 4"));
+    let computed: u32 = u32::from_le_bytes(compute_crc(&block[0..508]));
+    stored ^ computed
+}
+
+fn syndromes() -> &'static Syndromes {
+    SYNDROMES.get_or_init(|| {
+        // Start from a block that passes, and see what each flip does to it.
+        let mut block: [u8; 512] = [0; 512];
+        add_crc_to_block(&mut block);
+        let by_bit: Vec<u32> = (0..BLOCK_BITS)
+            .map(|bit| {
+                block[bit / 8] ^= 1 << (bit % 8);
+                let flipped: u32 = syndrome(&block);
+                block[bit / 8] ^= 1 << (bit % 8);
+                flipped
+            })
+            .collect();
+        let to_bit: HashMap<u32, usize> = by_bit.iter().enumerate().map(|(bit, syndrome)| (*syndrome, bit)).collect();
+        Syndromes {
+            by_bit,
+            to_bit,
+        }
+    })
+}
+
+/// Try to fix a block that failed its CRC check, by finding the one or two bits that got flipped.
+///
+/// Returns how many bits were flipped back, or None if the block is more broken than that (in which case
+/// it is left alone). Blocks that are all zeros were never written, and aren't fixed.
+///
+/// Three or more flipped bits can't be fixed, and in rare cases (about one in five hundred for a
+/// block of complete garbage) will look like two, and get "fixed" into something else that passes
+/// the check. That's why this is optional, and why a fixed block is never written back over the original.
+pub(crate) fn correct_block(block: &mut [u8; 512]) -> Option<u8> {
+    let wrong: u32 = syndrome(block);
+    if wrong == 0 || block.iter().all(|byte| *byte == 0) {
+        return None;
+    }
+    let syndromes = syndromes();
+    let flipped: Vec<usize> = if let Some(bit) = syndromes.to_bit.get(&wrong) {
+        vec![*bit]
+    } else {
+        syndromes.by_bit.iter().enumerate().find_map(|(first, first_syndrome)| {
+            syndromes
+                .to_bit
+                .get(&(wrong ^ first_syndrome))
+                .filter(|second| **second > first)
+                .map(|second| vec![first, *second])
+        })?
+    };
+    for bit in &flipped {
+        block[bit / 8] ^= 1 << (bit % 8);
+    }
+    Some(flipped.len() as u8)
+}
\ No newline at end of file
@@ -1,3 +1,5 @@
 pub mod allocate;
 pub mod block_structs;
-pub mod crc;
\ No newline at end of file
+pub mod crc;
+#[cfg(test)]
+mod tests;
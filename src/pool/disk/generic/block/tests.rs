@@ -0,0 +1,103 @@
+// Bit flips and their antidotes.
+// Unwrapping is okay here, since we want unexpected outcomes to fail tests.
+#![allow(clippy::unwrap_used)]
+use test_log::test; // We want to see logs while testing.
+
+use std::os::unix::fs::FileExt;
+
+use rand::{
+    Rng,
+    RngCore,
+    rngs::ThreadRng
+};
+
+use crate::{
+    filesystem::filesystem_struct::ENABLE_ERROR_CORRECTION,
+    pool::disk::{
+        generic::io::read::read_block_direct,
+        standard_disk::block::io::directory::tests::get_filesystem
+    }
+};
+
+use super::crc::{
+    add_crc_to_block,
+    check_crc,
+    correct_block
+};
+
+fn random_block(random: &mut ThreadRng) -> [u8; 512] {
+    let mut block: [u8; 512] = [0; 512];
+    random.fill_bytes(&mut block[..508]);
+    add_crc_to_block(&mut block);
+    block
+}
+
+/// Pick some different bits anywhere in a block, CRC included.
+fn random_bits(random: &mut ThreadRng, how_many: usize) -> Vec<usize> {
+    let mut bits: Vec<usize> = Vec::with_capacity(how_many);
+    while bits.len() < how_many {
+        let bit: usize = random.random_range(0..4096);
+        if !bits.contains(&bit) {
+            bits.push(bit);
+        }
+    }
+    bits
+}
+
+fn flip(block: &mut [u8; 512], bits: &[usize]) {
+    for bit in bits {
+        block[bit / 8] ^= 1 << (bit % 8);
+    }
+}
+
+/// One or two flipped bits always come back.
+#[test]
+fn fixes_small_flips() {
+    let mut random: ThreadRng = rand::rng();
+    for _ in 0..200 {
+        let original = random_block(&mut random);
+        let how_many: usize = random.random_range(1..=2);
+        let mut block = original;
+        flip(&mut block, &random_bits(&mut random, how_many));
+        assert!(!check_crc(block));
+        assert_eq!(correct_block(&mut block), Some(how_many as u8));
+        assert_eq!(block, original);
+    }
+}
+
+/// Three flipped bits are always noticed, and never get turned into something that passes.
+#[test]
+fn refuses_three_flips() {
+    let mut random: ThreadRng = rand::rng();
+    for _ in 0..200 {
+        let mut block = random_block(&mut random);
+        flip(&mut block, &random_bits(&mut random, 3));
+        let before = block;
+        assert_eq!(correct_block(&mut block), None);
+        assert_eq!(block, before);
+    }
+    // Never written blocks stay that way.
+    let mut blank: [u8; 512] = [0; 512];
+    assert_eq!(correct_block(&mut blank), None);
+}
+
+/// Reading a block with a flipped bit on the disk hands back the fixed block, but leaves the disk alone.
+#[test]
+fn read_fixes_without_writing() {
+    let _fs = get_filesystem();
+    ENABLE_ERROR_CORRECTION.set(true).unwrap();
+    let mut random: ThreadRng = rand::rng();
+    let original = random_block(&mut random);
+    let mut damaged = original;
+    flip(&mut damaged, &random_bits(&mut random, 1));
+
+    let disk_file = tempfile::tempfile().unwrap();
+    disk_file.set_len(2880 * 512).unwrap();
+    disk_file.write_all_at(&damaged, 7 * 512).unwrap();
+
+    let read = read_block_direct(&disk_file, 1, 7, false, false).unwrap();
+    assert_eq!(read.data, original);
+    let mut on_disk: [u8; 512] = [0; 512];
+    disk_file.read_exact_at(&mut on_disk, 7 * 512).unwrap();
+    assert_eq!(on_disk, damaged);
+}
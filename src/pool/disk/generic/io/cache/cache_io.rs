@@ -1,7 +1,18 @@
 // External interaction with the block cache
 
+use std::{path::Path, time::Duration};
+
+use log::debug;
+
 use crate::{
     error_types::drive::DriveError,
+    filesystem::{
+        filesystem_struct::{
+            CachePolicyReport,
+            WRITE_THROUGH
+        },
+        io_gate::io_gate_struct::IoGate
+    },
     pool::disk::{
         drive_struct::FloppyDrive,
         generic::{
@@ -13,10 +24,16 @@ use crate::{
             generic_structs::pointer_struct::DiskPointer,
             io::cache::{
                 cache_implementation::{
+                    tier_sizes_for,
                     BlockCache,
                     CachedBlock
                 },
-            cached_allocation::CachedAllocationDisk
+            cached_allocation::CachedAllocationDisk,
+            trace::{
+                self,
+                TraceEntry
+            },
+            warm_cache
         }
     }, standard_disk::standard_disk_struct::StandardDisk
 }, tui::notify::NotifyTui};
@@ -82,13 +99,41 @@ impl CachedBlockIO {
         None
     }
 
+    /// Check if a block is in the cache, without counting it as a use of the block.
+    pub fn is_block_cached(block_origin: DiskPointer) -> bool {
+        BlockCache::try_find_silent(block_origin).is_some()
+    }
+
+    /// How often reads have been answered by the cache, from 0 to 1.
+    pub fn hit_rate() -> f64 {
+        BlockCache::get_hit_rate()
+    }
+
+    /// How full the cache is, from 0 to 1. For the tiered policy, this is just tier 0.
+    pub fn pressure() -> f64 {
+        BlockCache::get_pressure()
+    }
+
+    /// Pull a set of blocks from one disk into the cache ahead of time, in a single read.
+    /// 
+    /// Blocks that are already cached are skipped, and if the cache cannot fit all of them, the
+    /// blocks at the start of the slice are preferred. Blocks between the requested ones are read but
+    /// discarded.
+    /// 
+    /// This will never swap disks, if the disk is not already in the drive, nothing is read.
+    /// 
+    /// Returns how many blocks were added to the cache.
+    pub fn prefetch_blocks(disk_number: u16, blocks: &[u16]) -> Result<usize, DriveError> {
+        go_prefetch_blocks(disk_number, blocks)
+    }
+
     /// Reads in a block from disk, attempts to read it from the cache first.
     /// 
     /// Block must already be allocated on origin disk.
     /// 
     /// Only works on standard disks.
     pub fn read_block(block_origin: DiskPointer) -> Result<RawBlock, DriveError> {
-        go_read_cached_block(block_origin)
+        go_read_cached_block(block_origin).inspect(|_| trace::record(TraceEntry::Read(block_origin)))
     }
 
     // /// Writes a block to disk. Adds newly written block to cache.
@@ -106,7 +151,7 @@ impl CachedBlockIO {
     /// 
     /// Only works on standard disks.
     pub fn update_block(raw_block: &RawBlock) -> Result<(), DriveError> {
-        go_update_cached_block(raw_block)
+        go_update_cached_block(raw_block).inspect(|_| trace::record(TraceEntry::Write(raw_block.block_origin)))
     }
 
     /// Sometimes you just need to remove a block from the cache, not even set it to zeros.
@@ -118,12 +163,56 @@ impl CachedBlockIO {
 
     /// Flush the entire cache to disk.
     pub fn flush() -> Result<(), DriveError> {
-        // There are currently 3 tiers of cache.
-        // ! If that changes, this must be updated !
-        // ! or there will be unflushed data still !
-        BlockCache::flush(0)?;
-        BlockCache::flush(1)?;
-        BlockCache::flush(2)
+        BlockCache::flush_all()?;
+        BlockCache::mark_clean();
+        trace::record(TraceEntry::Flush);
+        Ok(())
+    }
+
+    /// How many blocks each tier of the tiered cache would get with these settings.
+    /// 
+    /// Anything left as None gets the default.
+    pub fn tier_sizes(cache_blocks: Option<usize>, tier_ratios: Option<[usize; 3]>) -> [usize; 3] {
+        tier_sizes_for(cache_blocks, tier_ratios)
+    }
+
+    /// Replay a recorded cache trace through every cache policy, and report how each did.
+    /// 
+    /// Never touches a disk.
+    pub fn compare_policies(trace: &Path, cache_blocks: Option<usize>, tier_ratios: Option<[usize; 3]>) -> std::io::Result<Vec<CachePolicyReport>> {
+        trace::compare_policies(trace, cache_blocks, tier_ratios)
+    }
+
+    /// Write the blocks in the cache out to a file, so the next mount can start with them.
+    /// 
+    /// This flushes the cache.
+    /// 
+    /// Returns how many blocks were saved.
+    pub fn save_to_file(path: &Path) -> std::io::Result<usize> {
+        warm_cache::save(path)
+    }
+
+    /// Fill the cache with blocks saved by `save_to_file()`, then delete the file.
+    /// 
+    /// Blocks from a disk are checked against that disk when it is next opened, and dropped if it has
+    /// changed since they were saved.
+    /// 
+    /// Returns how many blocks were loaded.
+    pub fn load_from_file(path: &Path) -> std::io::Result<usize> {
+        warm_cache::load(path)
+    }
+
+    /// Flush the entire cache to disk, if it has been holding onto unwritten blocks for longer than `max_age`.
+    /// 
+    /// Returns true if a flush happened.
+    pub fn flush_if_older_than(max_age: Duration) -> Result<bool, DriveError> {
+        if BlockCache::dirty_age().is_none_or(|age| age < max_age) {
+            // Still fresh, or nothing to write.
+            return Ok(false);
+        }
+        debug!("Cache has held unwritten blocks for too long, flushing...");
+        Self::flush()?;
+        Ok(true)
     }
 }
 
@@ -165,8 +254,15 @@ fn go_read_cached_block(block_location: DiskPointer) -> Result<RawBlock, DriveEr
     }
 
     
+    // The block was not in the cache. If we aren't allowed to touch the drive right now, the
+    // caller will have to try again once it owns it.
+    if IoGate::cache_only() {
+        IoGate::missed(block_location.disk);
+        return Err(DriveError::Retry);
+    }
+
     // The block was not in the cache, we need to go get it old-school style.
-    // If we are about to swap disks, we will flush tier 0 of the disk.
+    // If we are about to swap disks, we will flush whatever we can of the disk in the drive.
     if disk_in_drive != block_location.disk {
         // About to swap, do the flush.
         // Dont care how many blocks this flushes.
@@ -181,8 +277,8 @@ fn go_read_cached_block(block_location: DiskPointer) -> Result<RawBlock, DriveEr
     // We also check to make sure we got something back, otherwise we have to fall back to the other read style.
 
     // But if we don't have room for 96 blocks, we will read as many as we can fit.
-    let tier_free_space = BlockCache::get_tier_space(0);
-    let to_read = std::cmp::min(tier_free_space, 96);
+    let free_space = BlockCache::get_room();
+    let to_read = std::cmp::min(free_space, 96);
 
     if let Ok(blocks) = &disk.unchecked_read_multiple_blocks(block_location.block, to_read as u16) && !blocks.is_empty() {
         for block in blocks {
@@ -214,6 +310,54 @@ fn go_read_cached_block(block_location: DiskPointer) -> Result<RawBlock, DriveEr
     Ok(read_block)
 }
 
+fn go_prefetch_blocks(disk_number: u16, blocks: &[u16]) -> Result<usize, DriveError> {
+    // Never touch the drive if we aren't allowed to, and never swap for a guess.
+    if IoGate::cache_only() || FloppyDrive::currently_inserted_disk_number() != disk_number {
+        return Ok(0);
+    }
+
+    // Only grab what we don't already have, and only as much as fits.
+    let room = BlockCache::get_room();
+    let mut wanted: Vec<u16> = blocks.iter()
+        .copied()
+        .filter(|block| *block != 0)
+        .filter(|block| BlockCache::try_find_silent(DiskPointer { disk: disk_number, block: *block }).is_none())
+        .take(room)
+        .collect();
+
+    if wanted.is_empty() {
+        // Nothing to do.
+        return Ok(0);
+    }
+
+    wanted.sort_unstable();
+    wanted.dedup();
+
+    // One big read that covers every block we want.
+    let first = wanted[0];
+    let last = wanted[wanted.len() - 1];
+    debug!("Prefetching {} blocks from disk {disk_number}, spanning {first}..={last}...", wanted.len());
+
+    let disk: StandardDisk = super::cache_implementation::disk_load_header_invalidation(disk_number)?;
+    let read = disk.unchecked_read_multiple_blocks(first, last - first + 1)?;
+
+    let mut added: usize = 0;
+    for block in read {
+        if wanted.binary_search(&block.block_origin.block).is_err() {
+            // Just passing through.
+            continue;
+        }
+        // Something may have cached it in the mean time, which may be newer than what is on disk.
+        if BlockCache::try_find_silent(block.block_origin).is_some() {
+            continue;
+        }
+        BlockCache::add_or_update_item(CachedBlock::from_raw(&block, false))?;
+        added += 1;
+    }
+
+    Ok(added)
+}
+
 // fn go_write_cached_block(raw_block: &RawBlock) -> Result<(), DriveError> {
 //     // Write a block to the disk, also updating the cache with the block (or adding it if it does not yet exist.)
 // 
@@ -246,6 +390,24 @@ fn go_update_cached_block(raw_block: &RawBlock) -> Result<(), DriveError> {
         assert!(is_allocated, "Tried to use the cache to update a block that was not allocated!");
     }
 
+    // Blocks from the cache file have to be checked before we build anything on top of them.
+    if warm_cache::is_unchecked(raw_block.block_origin.disk) {
+        let _ = super::cache_implementation::disk_load_header_invalidation(raw_block.block_origin.disk)?;
+    }
+
+    if WRITE_THROUGH.get() == Some(&true) {
+        // Straight to the disk, no waiting around in the cache.
+        let mut disk: StandardDisk = super::cache_implementation::disk_load_header_invalidation(raw_block.block_origin.disk)?;
+        disk.unchecked_write_block(raw_block)?;
+        super::cache_implementation::bump_generation(&mut disk)?;
+        // The disk now matches this block, so the cached copy doesn't need flushing. Updates must
+        // be dirty, so get rid of the old copy first.
+        BlockCache::remove_item(&raw_block.block_origin);
+        BlockCache::add_or_update_item(CachedBlock::from_raw(raw_block, false))?;
+        NotifyTui::write_cached();
+        return Ok(());
+    }
+
     // Update the cache with the updated block.
     // This is an update, so it must be flushed, since the block has changed.
     BlockCache::add_or_update_item(CachedBlock::from_raw(raw_block, true))?;
@@ -0,0 +1,505 @@
+// Deciding who gets kicked out of the cache. Bouncers, basically.
+
+// The cache only holds the blocks, a policy decides which of them leave first. The policy
+//  only ever sees pointers, the cache tells it whenever a block comes in, gets used, or leaves.
+
+// Both policies run out of room the same way, see `plan_eviction()`. The only thing that
+//  differs is which blocks they offer up when that happens.
+
+use std::collections::{
+    HashMap,
+    VecDeque
+};
+
+use crate::{
+    filesystem::filesystem_struct::CachePolicyKind,
+    pool::disk::generic::generic_structs::pointer_struct::DiskPointer
+};
+
+//
+// =========
+// The trait
+// =========
+//
+
+/// How the cache decides which blocks to keep.
+///
+/// The cache holds the blocks themselves, policies only keep track of pointers.
+pub(super) trait CachePolicy: Send {
+    /// Which policy this is.
+    fn kind(&self) -> CachePolicyKind;
+    /// A block already in the cache was read.
+    fn hit(&mut self, pointer: DiskPointer);
+    /// A block already in the cache was changed.
+    ///
+    /// Freshens the block up, but isn't a reason to think better of it. Otherwise every
+    /// read-then-write would look like a popular block.
+    fn touch(&mut self, pointer: DiskPointer);
+    /// A block that isn't in the cache is being added. There must be room for it.
+    fn admit(&mut self, pointer: DiskPointer);
+    /// A block left the cache to make room. The policy may still remember that it was here.
+    fn evicted(&mut self, pointer: DiskPointer);
+    /// A block was taken out of the cache for good, forget everything about it.
+    fn forget(&mut self, pointer: DiskPointer);
+    /// How many more blocks can be added before something has to leave.
+    fn room(&self) -> usize;
+    /// How full the part of the cache that gets emptied first is, from 0 to 1.
+    fn pressure(&self) -> f64;
+    /// The blocks that should leave first when the cache is out of room, worst first.
+    fn expendable(&self) -> Vec<DiskPointer>;
+    /// Every block in the cache, best first.
+    fn ranked(&self) -> Vec<DiskPointer>;
+}
+
+/// Make a new, empty policy that holds `capacity` blocks.
+///
+/// Tier ratios are only used by the tiered policy.
+pub(super) fn new_policy(kind: CachePolicyKind, capacity: usize, tier_ratios: [usize; 3]) -> Box<dyn CachePolicy> {
+    match kind {
+        CachePolicyKind::Tiered => Box::new(TieredPolicy::new(capacity, tier_ratios)),
+        CachePolicyKind::Adaptive => Box::new(AdaptivePolicy::new(capacity)),
+    }
+}
+
+//
+// =========
+// Making room
+// =========
+//
+
+/// What to do with the expendable blocks when the cache is full.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum Eviction {
+    /// These blocks were never changed, just throw them away.
+    Drop(Vec<DiskPointer>),
+    /// Write these blocks out, then throw them away.
+    Flush(Vec<DiskPointer>),
+}
+
+/// Work out how to make room in a full cache, given the expendable blocks and if they need flushing.
+///
+/// Clean blocks are dropped first, since that's free. Otherwise we flush whatever is on the disk in
+/// the drive. If that frees less than a quarter of the expendable blocks, we would just end up back
+/// here right away, so the disk with the most blocks gets flushed too, or everything if even that
+/// isn't enough.
+pub(super) fn plan_eviction(candidates: &[(DiskPointer, bool)], current_disk: u16) -> Eviction {
+    let clean: Vec<DiskPointer> = candidates.iter()
+        .filter(|(_, dirty)| !dirty)
+        .map(|(pointer, _)| *pointer)
+        .collect();
+    if !clean.is_empty() {
+        return Eviction::Drop(clean);
+    }
+
+    // Everything needs writing. Always free at least one block, or we'd never make room.
+    let required: usize = (candidates.len() / 4).max(1);
+    let mut flushing: Vec<DiskPointer> = candidates.iter()
+        .map(|(pointer, _)| *pointer)
+        .filter(|pointer| pointer.disk == current_disk)
+        .collect();
+    if flushing.len() >= required {
+        return Eviction::Flush(flushing);
+    }
+
+    // Tally up the rest by disk. Ties go to the lower disk, so this is the same every time.
+    let mut disks: HashMap<u16, usize> = HashMap::new();
+    for (pointer, _) in candidates.iter().filter(|(pointer, _)| pointer.disk != current_disk) {
+        *disks.entry(pointer.disk).or_insert(0) += 1;
+    }
+    if let Some((common_disk, count)) = disks.into_iter().max_by_key(|(disk, count)| (*count, std::cmp::Reverse(*disk)))
+        && flushing.len() + count >= required {
+        flushing.extend(candidates.iter().map(|(pointer, _)| *pointer).filter(|pointer| pointer.disk == common_disk));
+        return Eviction::Flush(flushing);
+    }
+
+    // Still not enough, write out all of it.
+    Eviction::Flush(candidates.iter().map(|(pointer, _)| *pointer).collect())
+}
+
+/// Sort key for writing blocks out.
+///
+/// The disk already in the drive goes first since it's free, then the rest of the disks in
+/// order, and blocks in order within each disk so the head doesn't jump around.
+pub(super) fn flush_order(pointer: &DiskPointer, current_disk: u16) -> (bool, u16, u16) {
+    (pointer.disk != current_disk, pointer.disk, pointer.block)
+}
+
+//
+// =========
+// Tiered
+// =========
+//
+
+// Some details about the tiers:
+// The lowest tier, 0, is completely emptied when it's full. Since we
+//  assume that the data within there is of very low quality. If it was
+//  worth keeping around, it would have been promoted already
+// Tier 1 pushes it's best cached item to tier 2 when it's full.
+// Tier 2 moves its least valuable cache item back down to tier 0 when it's full.
+// Within tiers, items are promoted to a higher position whenever a read
+//  successfully hits them. The only exception to this is tier 0, where
+//  successful reads promote an item up to tier 1.
+
+// When a new item is added to a tier, it starts in the highest position, as it
+//  is the most fresh. It is expected that if this item is weaker than pre-existing
+//  items, that the newly added top item will quickly slide down in rank.
+
+// The lower cache tiers are inherently more volatile, so they need to be
+//  larger to support more opportunities for items to promote before being
+//  thrashed out of the cache. Thus we will split the cache into:
+// 0: 1/2   of total allowed cache size
+// 1: 1/4th of total allowed cache size
+// 2: 1/4th of total allowed cache size
+// (The total size and the split can be changed with FilesystemOptions, the above is the default.)
+// It may seem weird to make the highest tier the same size as the one below it,
+//  but items that reach this tier are now such a high quality that they would be
+//  very quickly replaced if they became stale, since the constant read hits that are
+//  expected of these items would move stale items to the lowest positions very quickly.
+
+// Promotion within tiers always moves the item from whatever index it's currently at, to
+//  the very top of the tier. This should ensure that the hottest items stay close to the
+//  top, previously I used bubble sort, which could lead to slightly less used items to
+//  not promote away from the bottom of the queue fast enough.
+
+/// The original three tier cache.
+pub(super) struct TieredPolicy {
+    /// Highest quality, items in this level came from the highest spot from the tier below when
+    /// it was completely full. IE filled with the best of level_1.
+    tier_2: Tier,
+    /// Might be useful, promoted from level 0 after being read at least once.
+    tier_1: Tier,
+    /// Unproven items, might as well be garbage.
+    tier_0: Tier,
+}
+
+/// One tier of the tiered policy.
+struct Tier {
+    /// How many blocks fit in this tier.
+    size: usize,
+    /// The blocks in this tier, best at the front.
+    order: VecDeque<DiskPointer>,
+}
+
+impl TieredPolicy {
+    /// Split the cache up between the tiers.
+    pub(super) fn new(capacity: usize, tier_ratios: [usize; 3]) -> Self {
+        let [size_0, size_1, size_2] = tier_sizes(capacity, tier_ratios);
+        assert!(size_0 > 0 && size_1 > 0 && size_2 > 0, "Cache is too small to give every tier some room!");
+        Self {
+            tier_2: Tier::new(size_2),
+            tier_1: Tier::new(size_1),
+            tier_0: Tier::new(size_0),
+        }
+    }
+}
+
+/// Split the total cache size between the tiers by weight.
+///
+/// Division rounds down, so this is fine.
+pub(super) fn tier_sizes(total: usize, ratios: [usize; 3]) -> [usize; 3] {
+    let total_weight: usize = ratios.iter().sum();
+    ratios.map(|ratio| total * ratio / total_weight)
+}
+
+impl Tier {
+    fn new(size: usize) -> Self {
+        Self {
+            size,
+            order: VecDeque::with_capacity(size),
+        }
+    }
+    /// Pull a block out of the tier, if it's in here.
+    fn take(&mut self, pointer: &DiskPointer) -> bool {
+        match self.order.iter().position(|item| item == pointer) {
+            Some(index) => self.order.remove(index).is_some(),
+            None => false,
+        }
+    }
+    /// Put a block on top of the tier.
+    fn add(&mut self, pointer: DiskPointer) {
+        assert!(!self.is_full(), "Tried to add an item to a tier that is already full!");
+        self.order.push_front(pointer);
+    }
+    fn is_full(&self) -> bool {
+        self.order.len() == self.size
+    }
+}
+
+impl CachePolicy for TieredPolicy {
+    fn kind(&self) -> CachePolicyKind {
+        CachePolicyKind::Tiered
+    }
+
+    fn hit(&mut self, pointer: DiskPointer) {
+        // Higher tiers just move the block to the top.
+        for tier in [&mut self.tier_2, &mut self.tier_1] {
+            if tier.take(&pointer) {
+                tier.add(pointer);
+                return;
+            }
+        }
+        if !self.tier_0.take(&pointer) {
+            // Not ours.
+            return;
+        }
+
+        // This is where the magic happens.
+        // Since tiers only change size or have new items added to them when tier 0 has a good read,
+        // we only have to implement a cache-wide promotion scheme for tier 0.
+        if self.tier_1.is_full() {
+            // No room in tier 1, we need to move its best upwards.
+            let t1_best: DiskPointer = self.tier_1.order.pop_front().expect("How are we empty and full?");
+            if self.tier_2.is_full() {
+                // We will have to move the worst tier 2 item to tier 0. If we discarded it
+                // outright, the block it contains would never get flushed to disk.
+                // We just took an item out of tier 0, so there's room.
+                let worst_of_2: DiskPointer = self.tier_2.order.pop_back().expect("How are we empty and full?");
+                self.tier_0.add(worst_of_2);
+            }
+            self.tier_2.add(t1_best);
+        }
+        self.tier_1.add(pointer);
+    }
+
+    fn touch(&mut self, pointer: DiskPointer) {
+        // Updating is an access after all, but it stays in its tier.
+        for tier in [&mut self.tier_2, &mut self.tier_1, &mut self.tier_0] {
+            if tier.take(&pointer) {
+                tier.add(pointer);
+                return;
+            }
+        }
+    }
+
+    fn admit(&mut self, pointer: DiskPointer) {
+        // New blocks always start out as garbage.
+        self.tier_0.add(pointer);
+    }
+
+    fn evicted(&mut self, pointer: DiskPointer) {
+        // Tiers have no memory.
+        self.forget(pointer);
+    }
+
+    fn forget(&mut self, pointer: DiskPointer) {
+        for tier in [&mut self.tier_2, &mut self.tier_1, &mut self.tier_0] {
+            if tier.take(&pointer) {
+                return;
+            }
+        }
+    }
+
+    fn room(&self) -> usize {
+        // Only tier 0 takes new blocks.
+        self.tier_0.size - self.tier_0.order.len()
+    }
+
+    fn pressure(&self) -> f64 {
+        self.tier_0.order.len() as f64 / self.tier_0.size as f64
+    }
+
+    fn expendable(&self) -> Vec<DiskPointer> {
+        // All of tier 0 goes when it's full.
+        self.tier_0.order.iter().rev().copied().collect()
+    }
+
+    fn ranked(&self) -> Vec<DiskPointer> {
+        [&self.tier_2, &self.tier_1, &self.tier_0].iter()
+            .flat_map(|tier| tier.order.iter().copied())
+            .collect()
+    }
+}
+
+//
+// =========
+// Adaptive
+// =========
+//
+
+// This is ARC, the adaptive replacement cache.
+// Blocks that have only been used once live in the recent list, anything that is used again
+//  moves to the frequent list. Together they hold as many blocks as the cache can.
+// When a block leaves, we keep its pointer around in a ghost list for the list it left from.
+//  If that block comes back while it's still a ghost, we evicted the wrong kind of block:
+// - A recent ghost coming back means we should have kept more recent blocks, so the recent list
+//    gets a bigger share of the cache.
+// - A frequent ghost coming back means the opposite.
+// Ghosts always come back into the frequent list, since they've now been used twice.
+// Reading through a big file only ever goes through the recent list, so it can't push out
+//  the blocks we keep coming back to, like directories and allocation tables.
+
+// Flushing a single block at a time would mean seeking all over the place, so when we run out
+//  of room we offer up a quarter of the cache's worth of blocks from the end of whichever list
+//  is over its share.
+
+/// Which list an adaptive policy is keeping a pointer in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum AdaptiveList {
+    /// In the cache, only used once.
+    Recent,
+    /// In the cache, used more than once.
+    Frequent,
+    /// Recently left the recent list.
+    RecentGhost,
+    /// Recently left the frequent list.
+    FrequentGhost,
+}
+
+/// Adaptive replacement, balances blocks used once against blocks used a lot.
+pub(super) struct AdaptivePolicy {
+    /// How many blocks can be in the cache.
+    capacity: usize,
+    /// How many of those blocks the recent list should get. Moved around by ghost hits.
+    target: usize,
+    /// Blocks used once, newest at the front.
+    recent: VecDeque<DiskPointer>,
+    /// Blocks used more than once, newest at the front.
+    frequent: VecDeque<DiskPointer>,
+    /// Blocks that left the recent list, newest at the front.
+    recent_ghosts: VecDeque<DiskPointer>,
+    /// Blocks that left the frequent list, newest at the front.
+    frequent_ghosts: VecDeque<DiskPointer>,
+    /// Which list every pointer is in, so we only have to search that one.
+    lists: HashMap<DiskPointer, AdaptiveList>,
+}
+
+impl AdaptivePolicy {
+    /// A new policy with no history, which starts off splitting the cache evenly.
+    pub(super) fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "Cache is too small to hold anything!");
+        Self {
+            capacity,
+            target: capacity / 2,
+            recent: VecDeque::new(),
+            frequent: VecDeque::new(),
+            recent_ghosts: VecDeque::new(),
+            frequent_ghosts: VecDeque::new(),
+            lists: HashMap::new(),
+        }
+    }
+
+    /// How many blocks the recent list should get.
+    #[cfg(test)]
+    pub(super) fn target(&self) -> usize {
+        self.target
+    }
+
+    /// Which list a pointer is in, if any.
+    #[cfg(test)]
+    pub(super) fn list_of(&self, pointer: &DiskPointer) -> Option<AdaptiveList> {
+        self.lists.get(pointer).copied()
+    }
+
+    fn list_mut(&mut self, list: AdaptiveList) -> &mut VecDeque<DiskPointer> {
+        match list {
+            AdaptiveList::Recent => &mut self.recent,
+            AdaptiveList::Frequent => &mut self.frequent,
+            AdaptiveList::RecentGhost => &mut self.recent_ghosts,
+            AdaptiveList::FrequentGhost => &mut self.frequent_ghosts,
+        }
+    }
+
+    /// Pull a pointer out of whatever list it's in, and say where it was.
+    fn take(&mut self, pointer: &DiskPointer) -> Option<AdaptiveList> {
+        let list: AdaptiveList = self.lists.remove(pointer)?;
+        let queue = self.list_mut(list);
+        let index: usize = queue.iter().position(|item| item == pointer).expect("Pointer should be in the list we said it was in.");
+        let _ = queue.remove(index);
+        Some(list)
+    }
+
+    /// Put a pointer at the front of a list.
+    fn put(&mut self, pointer: DiskPointer, list: AdaptiveList) {
+        self.list_mut(list).push_front(pointer);
+        let _ = self.lists.insert(pointer, list);
+    }
+
+    /// Forget the oldest ghosts once we're remembering more than we need to.
+    ///
+    /// The recent side never remembers more than the cache holds, and all four lists never add
+    /// up to more than twice that.
+    fn trim_ghosts(&mut self) {
+        while self.recent.len() + self.recent_ghosts.len() > self.capacity {
+            let Some(oldest) = self.recent_ghosts.pop_back() else { break };
+            let _ = self.lists.remove(&oldest);
+        }
+        while self.lists.len() > self.capacity * 2 {
+            let Some(oldest) = self.frequent_ghosts.pop_back().or_else(|| self.recent_ghosts.pop_back()) else { break };
+            let _ = self.lists.remove(&oldest);
+        }
+    }
+}
+
+impl CachePolicy for AdaptivePolicy {
+    fn kind(&self) -> CachePolicyKind {
+        CachePolicyKind::Adaptive
+    }
+
+    fn hit(&mut self, pointer: DiskPointer) {
+        // Used again, so it's frequent now, whichever list it was in.
+        if self.take(&pointer).is_some() {
+            self.put(pointer, AdaptiveList::Frequent);
+        }
+    }
+
+    fn touch(&mut self, pointer: DiskPointer) {
+        if let Some(list) = self.take(&pointer) {
+            self.put(pointer, list);
+        }
+    }
+
+    fn admit(&mut self, pointer: DiskPointer) {
+        // Grab the sizes before the ghost leaves its list.
+        let recent_ghosts: usize = self.recent_ghosts.len().max(1);
+        let frequent_ghosts: usize = self.frequent_ghosts.len().max(1);
+        match self.take(&pointer) {
+            None => self.put(pointer, AdaptiveList::Recent),
+            Some(AdaptiveList::RecentGhost) => {
+                // Should have kept more recent blocks around. The fewer recent ghosts there are, the
+                // more this one says.
+                self.target = (self.target + (frequent_ghosts / recent_ghosts).max(1)).min(self.capacity);
+                self.put(pointer, AdaptiveList::Frequent);
+            },
+            Some(AdaptiveList::FrequentGhost) => {
+                // Should have kept more frequent blocks around.
+                self.target = self.target.saturating_sub((recent_ghosts / frequent_ghosts).max(1));
+                self.put(pointer, AdaptiveList::Frequent);
+            },
+            Some(AdaptiveList::Recent | AdaptiveList::Frequent) => panic!("Tried to add a block to the cache that was already there!"),
+        }
+        self.trim_ghosts();
+    }
+
+    fn evicted(&mut self, pointer: DiskPointer) {
+        match self.take(&pointer) {
+            Some(AdaptiveList::Recent) => self.put(pointer, AdaptiveList::RecentGhost),
+            Some(AdaptiveList::Frequent) => self.put(pointer, AdaptiveList::FrequentGhost),
+            // Wasn't in the cache.
+            _ => return,
+        }
+        self.trim_ghosts();
+    }
+
+    fn forget(&mut self, pointer: DiskPointer) {
+        let _ = self.take(&pointer);
+    }
+
+    fn room(&self) -> usize {
+        self.capacity.saturating_sub(self.recent.len() + self.frequent.len())
+    }
+
+    fn pressure(&self) -> f64 {
+        (self.recent.len() + self.frequent.len()) as f64 / self.capacity as f64
+    }
+
+    fn expendable(&self) -> Vec<DiskPointer> {
+        // Take from the recent list when it's over its share, or when there is nothing else.
+        let from_recent: bool = !self.recent.is_empty() && (self.recent.len() > self.target || self.frequent.is_empty());
+        let list: &VecDeque<DiskPointer> = if from_recent { &self.recent } else { &self.frequent };
+        list.iter().rev().take((self.capacity / 4).max(1)).copied().collect()
+    }
+
+    fn ranked(&self) -> Vec<DiskPointer> {
+        self.frequent.iter().chain(self.recent.iter()).copied().collect()
+    }
+}
@@ -1,4 +1,8 @@
 mod cache_implementation;
+mod cache_policy;
 pub(crate) mod cache_io;
 mod statistics;
-pub(crate) mod cached_allocation;
\ No newline at end of file
+mod trace;
+mod warm_cache;
+pub(crate) mod cached_allocation;#[cfg(test)]
+mod tests;
@@ -42,7 +42,7 @@ impl BlockCacheStatistics {
     }
     pub(super) fn get_hit_rate() -> f64 {
         // Get ourselves
-        let stats = CACHE_STATISTICS.lock().expect("Single threaded");
+        let stats = CACHE_STATISTICS.lock().expect("Other mutex holders should not panic.");
         if stats.hits_and_misses.is_empty() {
             return 0.0
         }
@@ -55,7 +55,7 @@ impl BlockCacheStatistics {
     /// Two functions to avoid confusion.
     pub(super) fn record_hit() {
         // Get ourselves
-        let stats = &mut CACHE_STATISTICS.lock().expect("Single threaded");
+        let stats = &mut CACHE_STATISTICS.lock().expect("Other mutex holders should not panic.");
 
         // Need to pop the oldest hit if we're out of room.
         if stats.hits_and_misses.len() >= HIT_MEMORY {
@@ -69,7 +69,7 @@ impl BlockCacheStatistics {
     /// Two functions to avoid confusion.
     pub(super) fn record_miss() {
         // Get ourselves
-        let stats = &mut CACHE_STATISTICS.lock().expect("Single threaded");
+        let stats = &mut CACHE_STATISTICS.lock().expect("Other mutex holders should not panic.");
 
         // Need to pop the oldest hit if we're out of room.
         if stats.hits_and_misses.len() >= HIT_MEMORY {
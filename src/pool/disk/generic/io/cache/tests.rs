@@ -0,0 +1,305 @@
+// Cache me if you can.
+// Unwrapping is okay here, since we want unexpected outcomes to fail tests.
+#![allow(clippy::unwrap_used)]
+
+use std::time::Duration;
+
+use test_log::test;
+
+use crate::{
+    filesystem::filesystem_struct::{
+        CachePolicyKind,
+        CACHE_POLICY,
+        CACHE_TRACE,
+        WRITE_THROUGH
+    },
+    pool::{
+        disk::{
+            generic::generic_structs::pointer_struct::DiskPointer,
+            standard_disk::block::io::directory::tests::{
+                get_filesystem,
+                get_new_temp_dir
+            }
+        },
+        pool_actions::pool_struct::Pool
+    }
+};
+
+use super::{
+    cache_implementation::BlockCache,
+    cache_io::CachedBlockIO,
+    cache_policy::{
+        plan_eviction,
+        tier_sizes,
+        AdaptiveList,
+        AdaptivePolicy,
+        CachePolicy,
+        Eviction,
+        TieredPolicy
+    },
+    trace::{
+        self,
+        TraceEntry
+    }
+};
+
+fn pointer(disk: u16, block: u16) -> DiskPointer {
+    DiskPointer { disk, block }
+}
+
+/// Tiers are split by weight, and rounding never hands out more than we have.
+#[test]
+fn tier_split() {
+    assert_eq!(tier_sizes(2880 * 16, [2, 1, 1]), [23040, 11520, 11520]);
+    assert_eq!(tier_sizes(100, [1, 1, 1]), [33, 33, 33]);
+    assert_eq!(tier_sizes(10, [8, 1, 1]), [8, 1, 1]);
+    // Too small to split is reported, instead of blowing up later.
+    assert_eq!(CachedBlockIO::tier_sizes(Some(2), None), [1, 0, 0]);
+    assert_eq!(CachedBlockIO::tier_sizes(None, Some([1, 1, 1])), [1920, 1920, 1920]);
+}
+
+/// Unwritten data should only be flushed once it's old enough.
+#[test]
+fn flush_old_dirty_blocks() {
+    let _fs = get_filesystem();
+    CachedBlockIO::flush().unwrap();
+    assert!(BlockCache::dirty_age().is_none());
+
+    let mut root_block = Pool::get_root_directory().unwrap();
+    let _ = root_block.new_file("dirty.txt".to_string()).unwrap();
+    assert!(BlockCache::dirty_age().is_some());
+
+    // Not old enough yet.
+    assert!(!CachedBlockIO::flush_if_older_than(Duration::from_secs(3600)).unwrap());
+    assert!(BlockCache::dirty_age().is_some());
+
+    // Now it is.
+    assert!(CachedBlockIO::flush_if_older_than(Duration::ZERO).unwrap());
+    assert!(BlockCache::dirty_age().is_none());
+    assert!(!CachedBlockIO::flush_if_older_than(Duration::ZERO).unwrap());
+}
+
+/// With write-through on, nothing should ever be waiting in the cache to be written.
+#[test]
+fn write_through_leaves_nothing_dirty() {
+    let _fs = get_filesystem();
+    CachedBlockIO::flush().unwrap();
+    let _ = WRITE_THROUGH.set(true);
+
+    let mut root_block = Pool::get_root_directory().unwrap();
+    let file = root_block.new_file("safe.txt".to_string()).unwrap();
+    let _ = file.write_file(&[4; 2000], 0).unwrap();
+
+    // Nothing dirty ever made it into the cache.
+    assert!(BlockCache::dirty_age().is_none());
+    assert_eq!(file.read_file(0, 2000).unwrap(), vec![4; 2000]);
+}
+
+/// Blocks saved to the cache file should come back on the next load, and the file should be used up.
+#[test]
+fn cache_file_round_trip() {
+    let _fs = get_filesystem();
+    let mut root_block = Pool::get_root_directory().unwrap();
+    let file = root_block.new_file("warm.txt".to_string()).unwrap();
+    let _ = file.write_file(&[7; 3000], 0).unwrap();
+
+    let dir = get_new_temp_dir();
+    let path = dir.path().join("cache.bin");
+    let saved = CachedBlockIO::save_to_file(&path).unwrap();
+    assert!(saved > 0);
+
+    // Saving flushed, so the cache is empty, like we just mounted.
+    assert!(BlockCache::all_blocks().iter().all(|block| block.clone().into_raw().block_origin.disk != 1));
+
+    // Anything still in the cache after the flush is kept over the saved copy.
+    let loaded = CachedBlockIO::load_from_file(&path).unwrap();
+    assert!(loaded > 0 && loaded <= saved);
+    assert!(!path.exists());
+    assert_eq!(file.read_file(0, 3000).unwrap(), vec![7; 3000]);
+}
+
+/// If a disk was written to after the cache file was saved, its saved blocks can't be trusted.
+#[test]
+fn cache_file_drops_stale_disks() {
+    let _fs = get_filesystem();
+    let mut root_block = Pool::get_root_directory().unwrap();
+    let file = root_block.new_file("stale.txt".to_string()).unwrap();
+    let _ = file.write_file(&[1; 3000], 0).unwrap();
+
+    let dir = get_new_temp_dir();
+    let path = dir.path().join("cache.bin");
+    let _ = CachedBlockIO::save_to_file(&path).unwrap();
+
+    // Change the disk after saving.
+    let _ = file.write_file(&[2; 3000], 0).unwrap();
+    CachedBlockIO::flush().unwrap();
+    let _ = BlockCache::drop_clean_blocks_of_disk(1);
+
+    // The disk has already been seen this mount, so the blocks are checked right away.
+    let _ = CachedBlockIO::load_from_file(&path).unwrap();
+    assert!(BlockCache::all_blocks().iter().all(|block| block.clone().into_raw().block_origin.disk != 1));
+    assert_eq!(file.read_file(0, 3000).unwrap(), vec![2; 3000]);
+}
+
+/// Blocks coming back soon after being thrown out should move the adaptive policy's balance around.
+#[test]
+fn adaptive_ghosts_move_the_target() {
+    let mut policy = AdaptivePolicy::new(8);
+    for block in 1..=8 {
+        policy.admit(pointer(1, block));
+    }
+    assert_eq!(policy.room(), 0);
+    assert_eq!(policy.target(), 4);
+
+    // Nothing has been used twice, so the oldest of the recent blocks go first.
+    let worst: DiskPointer = policy.expendable()[0];
+    assert_eq!(worst, pointer(1, 1));
+    policy.evicted(worst);
+    assert_eq!(policy.list_of(&worst), Some(AdaptiveList::RecentGhost));
+
+    // It came back, so recent blocks should get more room, and it's now frequent.
+    policy.admit(worst);
+    assert_eq!(policy.target(), 5);
+    assert_eq!(policy.list_of(&worst), Some(AdaptiveList::Frequent));
+
+    // A frequent block coming back pushes the other way.
+    policy.evicted(worst);
+    assert_eq!(policy.list_of(&worst), Some(AdaptiveList::FrequentGhost));
+    policy.admit(worst);
+    assert_eq!(policy.target(), 4);
+
+    // Forgotten blocks aren't remembered at all.
+    policy.forget(worst);
+    assert_eq!(policy.list_of(&worst), None);
+}
+
+/// Reading through lots of blocks once shouldn't push out the ones being used over and over.
+#[test]
+fn adaptive_survives_scans() {
+    let mut policy = AdaptivePolicy::new(16);
+    let hot: Vec<DiskPointer> = (1..=4).map(|block| pointer(1, block)).collect();
+    for block in &hot {
+        policy.admit(*block);
+        policy.hit(*block);
+    }
+    for block in 100..200 {
+        while policy.room() == 0 {
+            for leaving in policy.expendable() {
+                policy.evicted(leaving);
+            }
+        }
+        policy.admit(pointer(2, block));
+    }
+    for block in &hot {
+        assert_eq!(policy.list_of(block), Some(AdaptiveList::Frequent));
+    }
+    // Ghosts don't pile up forever either.
+    assert!((100..200).filter(|block| policy.list_of(&pointer(2, *block)).is_some()).count() <= 16);
+}
+
+/// Tiered policy should still promote on reads, and only give up tier 0.
+#[test]
+fn tiered_promotes_on_hit() {
+    let mut policy = TieredPolicy::new(8, [2, 1, 1]);
+    for block in 1..=4 {
+        policy.admit(pointer(1, block));
+    }
+    assert_eq!(policy.room(), 0);
+    policy.hit(pointer(1, 2));
+    assert_eq!(policy.room(), 1);
+    assert!(!policy.expendable().contains(&pointer(1, 2)));
+    assert_eq!(policy.ranked()[0], pointer(1, 2));
+    // Worst first.
+    assert_eq!(policy.expendable(), vec![pointer(1, 1), pointer(1, 3), pointer(1, 4)]);
+}
+
+/// Making room drops clean blocks, then flushes the disk in the drive, then the busiest other disk.
+#[test]
+fn eviction_plans() {
+    // Clean blocks are free to drop.
+    let mixed = [(pointer(1, 1), true), (pointer(2, 1), false)];
+    assert_eq!(plan_eviction(&mixed, 1), Eviction::Drop(vec![pointer(2, 1)]));
+
+    // The disk in the drive is enough on its own.
+    let current = [(pointer(1, 1), true), (pointer(1, 2), true), (pointer(2, 1), true), (pointer(3, 1), true)];
+    assert_eq!(plan_eviction(&current, 1), Eviction::Flush(vec![pointer(1, 1), pointer(1, 2)]));
+
+    // Nothing on the disk in the drive, so the busiest other disk goes.
+    let busy: Vec<(DiskPointer, bool)> = (1..=6).map(|block| (pointer(3, block), true))
+        .chain([(pointer(2, 1), true), (pointer(4, 1), true)])
+        .collect();
+    assert_eq!(plan_eviction(&busy, 1), Eviction::Flush((1..=6).map(|block| pointer(3, block)).collect()));
+
+    // Spread too thin, everything goes.
+    let thin: Vec<(DiskPointer, bool)> = (2..=9).map(|disk| (pointer(disk, 1), true)).collect();
+    assert_eq!(plan_eviction(&thin, 1), Eviction::Flush(thin.iter().map(|(pointer, _)| *pointer).collect()));
+}
+
+/// Replaying a trace should count hits, misses and swaps. When the blocks being read over and over
+/// don't fit in the upper tiers, the adaptive policy should keep them around where the tiered one can't.
+#[test]
+fn replay_compares_policies() {
+    let mut entries: Vec<TraceEntry> = Vec::new();
+    for round in 0..20u16 {
+        // The hot blocks, read twice each round.
+        for _ in 0..2 {
+            entries.extend((1..=40).map(|block| TraceEntry::Read(pointer(1, block))));
+        }
+        // Then a scan that never comes back.
+        entries.extend((0..40).map(|block| TraceEntry::Read(pointer(2, round * 40 + block))));
+        entries.push(TraceEntry::Write(pointer(1, 1)));
+    }
+
+    let tiered = trace::replay(&entries, Box::new(TieredPolicy::new(64, [2, 1, 1])));
+    let adaptive = trace::replay(&entries, Box::new(AdaptivePolicy::new(64)));
+    for report in [&tiered, &adaptive] {
+        assert_eq!(report.hits + report.misses, 20 * (80 + 40));
+        // Has to get to the scan at least once.
+        assert!(report.swaps > 0);
+    }
+    assert_eq!(tiered.policy, CachePolicyKind::Tiered);
+    assert_eq!(adaptive.policy, CachePolicyKind::Adaptive);
+    assert!(adaptive.hit_rate > tiered.hit_rate, "{adaptive:?} vs {tiered:?}");
+    assert!(adaptive.swaps < tiered.swaps, "{adaptive:?} vs {tiered:?}");
+}
+
+/// A mounted filesystem should record its trace, which can then be replayed.
+#[test]
+fn recorded_trace_replays() {
+    let dir = get_new_temp_dir();
+    let path = dir.path().join("trace.txt");
+    CACHE_TRACE.set(path.clone()).unwrap();
+    let _fs = get_filesystem();
+
+    let mut root_block = Pool::get_root_directory().unwrap();
+    let file = root_block.new_file("traced.txt".to_string()).unwrap();
+    let _ = file.write_file(&[3; 2000], 0).unwrap();
+    assert_eq!(file.read_file(0, 2000).unwrap(), vec![3; 2000]);
+    CachedBlockIO::flush().unwrap();
+
+    let entries: Vec<TraceEntry> = trace::load(&path).unwrap();
+    assert!(entries.iter().any(|entry| matches!(entry, TraceEntry::Write(_))));
+    assert_eq!(entries.last(), Some(&TraceEntry::Flush));
+
+    let reads: u64 = entries.iter().filter(|entry| matches!(entry, TraceEntry::Read(_))).count() as u64;
+    let reports = CachedBlockIO::compare_policies(&path, None, None).unwrap();
+    assert_eq!(reports.len(), 2);
+    assert!(reports.iter().all(|report| report.hits + report.misses == reads));
+}
+
+/// The filesystem should work just the same on the adaptive policy.
+#[test]
+fn adaptive_cache_round_trip() {
+    CACHE_POLICY.set(CachePolicyKind::Adaptive).unwrap();
+    let _fs = get_filesystem();
+    assert_eq!(BlockCache::policy_kind(), CachePolicyKind::Adaptive);
+
+    let mut root_block = Pool::get_root_directory().unwrap();
+    let file = root_block.new_file("adaptive.txt".to_string()).unwrap();
+    // Bigger than the test cache, so it has to make room along the way.
+    let data: Vec<u8> = (0..4_000_000u32).map(|byte| (byte % 251) as u8).collect();
+    let _ = file.write_file(&data, 0).unwrap();
+    assert_eq!(file.read_file(0, data.len() as u32).unwrap(), data);
+    CachedBlockIO::flush().unwrap();
+    assert_eq!(file.read_file(0, data.len() as u32).unwrap(), data);
+}
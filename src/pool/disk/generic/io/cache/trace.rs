@@ -0,0 +1,287 @@
+// Keeping a diary of everything the cache was asked for, then reading it back to the other policies.
+
+// Traces are plain text, one access per line:
+// `r <disk> <block>` for a read, `w <disk> <block>` for a write, and `f` for a full flush.
+
+// Replaying a trace runs it through a cache that only keeps pointers, so it never touches a disk.
+//  It makes room the same way the real cache does, and counts a swap every time the block it
+//  needs (or has to write out) is on a different disk than the one in the drive.
+// Readahead isn't replayed, so real hit rates will be a bit better than this says. That's the
+//  same for every policy though, so they can still be compared.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{
+        BufWriter,
+        Write
+    },
+    path::Path,
+    sync::Mutex
+};
+
+use lazy_static::lazy_static;
+use log::warn;
+
+use crate::{
+    filesystem::filesystem_struct::{
+        CachePolicyKind,
+        CachePolicyReport,
+        CACHE_TRACE
+    },
+    pool::disk::generic::{
+        generic_structs::pointer_struct::DiskPointer,
+        io::cache::{
+            cache_implementation::build_policy,
+            cache_policy::{
+                flush_order,
+                plan_eviction,
+                CachePolicy,
+                Eviction
+            }
+        }
+    }
+};
+
+lazy_static! {
+    /// Where the trace is being written. Opened on the first access, None if there's no trace.
+    static ref TRACE_FILE: Mutex<Option<BufWriter<File>>> = Mutex::new(open_trace());
+}
+
+//
+// =========
+// Structs
+// =========
+//
+
+/// One thing the cache was asked to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum TraceEntry {
+    /// A block was read.
+    Read(DiskPointer),
+    /// A block was written.
+    Write(DiskPointer),
+    /// The whole cache was flushed.
+    Flush,
+}
+
+/// A cache with no data in it, for replaying traces.
+struct SimulatedCache {
+    /// Every cached block, and if it needs flushing.
+    items: HashMap<DiskPointer, bool>,
+    /// The policy being tested.
+    policy: Box<dyn CachePolicy>,
+    /// The disk in the drive.
+    in_drive: u16,
+    hits: u64,
+    misses: u64,
+    swaps: u64,
+}
+
+//
+// =========
+// Recording
+// =========
+//
+
+impl TraceEntry {
+    fn to_line(self) -> String {
+        match self {
+            TraceEntry::Read(pointer) => format!("r {} {}", pointer.disk, pointer.block),
+            TraceEntry::Write(pointer) => format!("w {} {}", pointer.disk, pointer.block),
+            TraceEntry::Flush => "f".to_string(),
+        }
+    }
+
+    fn from_line(line: &str) -> Option<Self> {
+        let mut parts = line.split_whitespace();
+        let kind: &str = parts.next()?;
+        if kind == "f" {
+            return parts.next().is_none().then_some(TraceEntry::Flush);
+        }
+        let pointer: DiskPointer = DiskPointer {
+            disk: parts.next()?.parse().ok()?,
+            block: parts.next()?.parse().ok()?,
+        };
+        if parts.next().is_some() {
+            return None;
+        }
+        match kind {
+            "r" => Some(TraceEntry::Read(pointer)),
+            "w" => Some(TraceEntry::Write(pointer)),
+            _ => None,
+        }
+    }
+}
+
+fn open_trace() -> Option<BufWriter<File>> {
+    let path = CACHE_TRACE.get()?;
+    match File::create(path) {
+        Ok(file) => Some(BufWriter::new(file)),
+        Err(error) => {
+            warn!("Couldn't make the cache trace file, not recording one. Error: {error}");
+            None
+        },
+    }
+}
+
+/// Write down an access, if we are keeping a trace.
+///
+/// Flushes get the file written out too, so the trace is never far behind.
+pub(super) fn record(entry: TraceEntry) {
+    if CACHE_TRACE.get().is_none() {
+        // Don't even bother with the lock.
+        return;
+    }
+    let mut trace = TRACE_FILE.lock().expect("Other mutex holders should not panic.");
+    let Some(file) = trace.as_mut() else { return };
+    let mut written = writeln!(file, "{}", entry.to_line());
+    if entry == TraceEntry::Flush {
+        written = written.and_then(|_| file.flush());
+    }
+    if let Err(error) = written {
+        // Not worth failing the filesystem over.
+        warn!("Couldn't write to the cache trace, giving up on it. Error: {error}");
+        *trace = None;
+    }
+}
+
+/// Read a trace back in.
+pub(super) fn load(path: &Path) -> std::io::Result<Vec<TraceEntry>> {
+    std::fs::read_to_string(path)?
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(number, line)| {
+            TraceEntry::from_line(line).ok_or_else(|| std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Line {} of the cache trace doesn't make sense: `{line}`", number + 1)
+            ))
+        })
+        .collect()
+}
+
+//
+// =========
+// Replaying
+// =========
+//
+
+/// Replay a trace file through every policy.
+pub(super) fn compare_policies(path: &Path, cache_blocks: Option<usize>, tier_ratios: Option<[usize; 3]>) -> std::io::Result<Vec<CachePolicyReport>> {
+    let entries: Vec<TraceEntry> = load(path)?;
+    Ok([CachePolicyKind::Tiered, CachePolicyKind::Adaptive]
+        .into_iter()
+        .map(|kind| replay(&entries, build_policy(kind, cache_blocks, tier_ratios)))
+        .collect())
+}
+
+/// Run a trace through a policy, and see how it did.
+pub(super) fn replay(entries: &[TraceEntry], policy: Box<dyn CachePolicy>) -> CachePolicyReport {
+    let mut cache: SimulatedCache = SimulatedCache {
+        items: HashMap::new(),
+        policy,
+        // Mounting starts on the pool disk.
+        in_drive: 0,
+        hits: 0,
+        misses: 0,
+        swaps: 0,
+    };
+    for entry in entries {
+        match entry {
+            TraceEntry::Read(pointer) => cache.read(*pointer),
+            TraceEntry::Write(pointer) => cache.write(*pointer),
+            TraceEntry::Flush => cache.flush_all(),
+        }
+    }
+    // Whatever is left still has to get to disk eventually.
+    cache.flush_all();
+
+    let reads: u64 = cache.hits + cache.misses;
+    CachePolicyReport {
+        policy: cache.policy.kind(),
+        hits: cache.hits,
+        misses: cache.misses,
+        hit_rate: if reads == 0 { 0.0 } else { cache.hits as f64 / reads as f64 },
+        swaps: cache.swaps,
+    }
+}
+
+impl SimulatedCache {
+    fn read(&mut self, pointer: DiskPointer) {
+        if self.items.contains_key(&pointer) {
+            self.hits += 1;
+            self.policy.hit(pointer);
+            return;
+        }
+        self.misses += 1;
+        if pointer.disk != self.in_drive {
+            // The real cache writes out what it can before swapping.
+            let leaving: Vec<DiskPointer> = self.policy.expendable().into_iter()
+                .filter(|expendable| expendable.disk == self.in_drive && self.items[expendable])
+                .collect();
+            self.write_out(&leaving);
+            self.go_to(pointer.disk);
+        }
+        self.add(pointer, false);
+    }
+
+    fn write(&mut self, pointer: DiskPointer) {
+        if let Some(dirty) = self.items.get_mut(&pointer) {
+            *dirty = true;
+            self.policy.touch(pointer);
+            return;
+        }
+        self.add(pointer, true);
+    }
+
+    fn add(&mut self, pointer: DiskPointer, dirty: bool) {
+        while self.policy.room() == 0 {
+            let candidates: Vec<(DiskPointer, bool)> = self.policy.expendable().into_iter()
+                .map(|pointer| (pointer, self.items[&pointer]))
+                .collect();
+            match plan_eviction(&candidates, self.in_drive) {
+                Eviction::Drop(pointers) => {
+                    for pointer in pointers {
+                        let _ = self.items.remove(&pointer);
+                        self.policy.evicted(pointer);
+                    }
+                },
+                Eviction::Flush(pointers) => self.write_out(&pointers),
+            }
+        }
+        self.policy.admit(pointer);
+        let _ = self.items.insert(pointer, dirty);
+    }
+
+    fn flush_all(&mut self) {
+        let mut pointers: Vec<DiskPointer> = self.policy.ranked();
+        pointers.reverse();
+        self.write_out(&pointers);
+    }
+
+    /// Write out the dirty blocks in the same order as the real cache, then remove all of them.
+    fn write_out(&mut self, pointers: &[DiskPointer]) {
+        let mut dirty: Vec<DiskPointer> = pointers.iter()
+            .copied()
+            .filter(|pointer| self.items.get(pointer).copied().unwrap_or(false))
+            .collect();
+        let current_disk: u16 = self.in_drive;
+        dirty.sort_unstable_by_key(|pointer| flush_order(pointer, current_disk));
+        for pointer in dirty {
+            self.go_to(pointer.disk);
+        }
+        for pointer in pointers {
+            if self.items.remove(pointer).is_some() {
+                self.policy.evicted(*pointer);
+            }
+        }
+    }
+
+    fn go_to(&mut self, disk: u16) {
+        if disk != self.in_drive {
+            self.swaps += 1;
+            self.in_drive = disk;
+        }
+    }
+}
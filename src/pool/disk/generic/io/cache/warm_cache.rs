@@ -0,0 +1,194 @@
+// Leftovers from last time.
+// Mounting with an empty cache means swapping through every disk just to look at the directory tree again.
+// So on unmount, we can write the clean part of the cache out to a file, and pick it back up on the next mount.
+
+// The file is taken apart on load, so if we crash before the next clean unmount, we start cold instead of
+// trusting blocks that may have changed since.
+
+// Cache file format, all little endian:
+// 8 bytes: Magic, `FlusterC`
+// 2 bytes: Number of disks
+// Then for every disk:
+//   2 bytes: Disk number
+//   8 bytes: Generation of that disk when the file was written
+// 4 bytes: Number of blocks
+// Then for every block:
+//   4 bytes: DiskPointer
+//   512 bytes: The block, which carries its own CRC.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{
+        BufReader,
+        BufWriter,
+        Read,
+        Write
+    },
+    path::Path,
+    sync::Mutex
+};
+
+use lazy_static::lazy_static;
+use log::{debug, warn};
+
+use crate::pool::disk::{
+    generic::{
+        block::{
+            block_structs::RawBlock,
+            crc::check_crc
+        },
+        generic_structs::pointer_struct::DiskPointer
+    },
+    standard_disk::block::header::header_struct::StandardDiskHeader
+};
+
+use super::{
+    cache_implementation::{
+        BlockCache,
+        CachedBlock
+    },
+    cache_io::CachedBlockIO
+};
+
+const MAGIC: &[u8; 8] = b"FlusterC";
+
+lazy_static! {
+    /// The latest generation we know of for every disk we've seen this mount.
+    static ref GENERATIONS: Mutex<HashMap<u16, u64>> = Mutex::new(HashMap::new());
+    /// Disks that have blocks from the cache file that we haven't checked yet, and the generation they were saved at.
+    static ref UNCHECKED: Mutex<HashMap<u16, u64>> = Mutex::new(HashMap::new());
+}
+
+/// We've just read the header of a disk, so we know its current generation.
+/// 
+/// If this disk still had unchecked blocks from the cache file, and the disk has changed since, they are dropped.
+pub(super) fn saw_generation(disk_number: u16, generation: u64) {
+    let _ = GENERATIONS.lock().expect("Other mutex holders should not panic.").insert(disk_number, generation);
+
+    let saved = UNCHECKED.lock().expect("Other mutex holders should not panic.").remove(&disk_number);
+    if let Some(saved) = saved && saved != generation {
+        let dropped = BlockCache::drop_clean_blocks_of_disk(disk_number);
+        warn!("Disk {disk_number} changed since the cache file was written, dropped {dropped} stale blocks.");
+    }
+}
+
+/// Are there blocks from this disk in the cache that came from the cache file, and haven't been checked yet?
+pub(super) fn is_unchecked(disk_number: u16) -> bool {
+    UNCHECKED.lock().expect("Other mutex holders should not panic.").contains_key(&disk_number)
+}
+
+/// Flush the cache, then write everything that was in it out to a file.
+/// 
+/// Blocks from disks we don't know the generation of are skipped.
+/// 
+/// Returns how many blocks were saved.
+pub(super) fn save(path: &Path) -> std::io::Result<usize> {
+    // Flushing empties the cache, so grab everything first. Once the flush is done, all of it matches the disks.
+    let snapshot: Vec<CachedBlock> = BlockCache::all_blocks();
+    CachedBlockIO::flush().map_err(|error| std::io::Error::other(format!("{error:?}")))?;
+
+    // Anything we haven't checked yet is still as good as when it was loaded.
+    let mut generations: HashMap<u16, u64> = UNCHECKED.lock().expect("Other mutex holders should not panic.").clone();
+    generations.extend(GENERATIONS.lock().expect("Other mutex holders should not panic.").iter());
+
+    let blocks: Vec<RawBlock> = snapshot
+        .into_iter()
+        .map(CachedBlock::into_raw)
+        .filter_map(|block| {
+            let generation = generations.get(&block.block_origin.disk)?;
+            if block.block_origin.block != 0 {
+                return Some(block);
+            }
+            // The flush moved the generation along after we took the copy of the header.
+            let mut header: StandardDiskHeader = StandardDiskHeader::from_block(&block);
+            header.generation = *generation;
+            Some(header.to_block())
+        })
+        .collect();
+
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(MAGIC)?;
+    file.write_all(&(generations.len() as u16).to_le_bytes())?;
+    for (disk, generation) in &generations {
+        file.write_all(&disk.to_le_bytes())?;
+        file.write_all(&generation.to_le_bytes())?;
+    }
+    file.write_all(&(blocks.len() as u32).to_le_bytes())?;
+    for block in &blocks {
+        file.write_all(&block.block_origin.to_bytes())?;
+        file.write_all(&block.data)?;
+    }
+    file.flush()?;
+
+    debug!("Saved {} blocks to the cache file.", blocks.len());
+    Ok(blocks.len())
+}
+
+/// Load blocks from a cache file into the cache, then delete the file.
+/// 
+/// Blocks are only loaded while the cache has room for them without throwing anything out, and blocks with a bad CRC are skipped.
+/// Every loaded block is checked against the generation of its disk the next time that disk is opened.
+/// 
+/// Returns how many blocks were loaded.
+pub(super) fn load(path: &Path) -> std::io::Result<usize> {
+    let mut file = BufReader::new(File::open(path)?);
+    // Never use the same file twice, see the top of the file.
+    std::fs::remove_file(path)?;
+
+    let mut magic: [u8; 8] = [0; 8];
+    file.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Not a Fluster! cache file."));
+    }
+
+    let disk_count = u16::from_le_bytes(read_array(&mut file)?);
+    let mut generations: HashMap<u16, u64> = HashMap::with_capacity(disk_count.into());
+    for _ in 0..disk_count {
+        let disk = u16::from_le_bytes(read_array(&mut file)?);
+        let generation = u64::from_le_bytes(read_array(&mut file)?);
+        let _ = generations.insert(disk, generation);
+    }
+
+    let block_count = u32::from_le_bytes(read_array(&mut file)?);
+    let mut room = BlockCache::get_room();
+    let mut loaded: usize = 0;
+    for _ in 0..block_count {
+        let block_origin = DiskPointer::from_bytes(read_array(&mut file)?);
+        let data: [u8; 512] = read_array(&mut file)?;
+        if room == 0 {
+            // Full, the rest are worse than what we already have anyways.
+            break;
+        }
+        if !generations.contains_key(&block_origin.disk) || !check_crc(data) {
+            warn!("Skipping bad block from the cache file.");
+            continue;
+        }
+        // Whatever is already in the cache is newer.
+        if BlockCache::try_find_silent(block_origin).is_some() {
+            continue;
+        }
+        let block = RawBlock { block_origin, data };
+        BlockCache::add_or_update_item(CachedBlock::from_raw(&block, false)).map_err(|error| {
+            std::io::Error::other(format!("{error:?}"))
+        })?;
+        room -= 1;
+        loaded += 1;
+    }
+
+    // Disks we have already opened this mount are checked right now.
+    let known: HashMap<u16, u64> = GENERATIONS.lock().expect("Other mutex holders should not panic.").clone();
+    UNCHECKED.lock().expect("Other mutex holders should not panic.").extend(generations);
+    for (disk, generation) in known {
+        saw_generation(disk, generation);
+    }
+
+    debug!("Loaded {loaded} blocks from the cache file.");
+    Ok(loaded)
+}
+
+fn read_array<const N: usize>(file: &mut impl Read) -> std::io::Result<[u8; N]> {
+    let mut buffer: [u8; N] = [0; N];
+    file.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
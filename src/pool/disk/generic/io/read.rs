@@ -23,6 +23,7 @@ use crate::{
             WrappedIOError
         }
     },
+    filesystem::filesystem_struct::ENABLE_ERROR_CORRECTION,
     pool::disk::{drive_struct::FloppyDrive, generic::generic_structs::pointer_struct::DiskPointer},
     tui::{
         notify::NotifyTui,
@@ -31,7 +32,10 @@ use crate::{
 };
 
 use super::super::block::block_structs::RawBlock;
-use super::super::block::crc::check_crc;
+use super::super::block::crc::{
+    check_crc,
+    correct_block
+};
 use std::{
     fs::File,
     os::unix::fs::FileExt
@@ -72,6 +76,9 @@ pub(crate) fn read_block_direct(
     // Calculate the offset into the disk
     let read_offset: u64 = block_index as u64 * 512;
 
+    // The last read that came back, but failed the CRC check.
+    let mut damaged: Option<[u8; 512]> = None;
+
     // Enter a loop to retry reading the block 10 times at most.
     // If we try 3 times without success, we are cooked.
 
@@ -112,6 +119,7 @@ pub(crate) fn read_block_direct(
         if !ignore_crc && !check_crc(read_buffer) {
             // CRC check failed, we have to try again.
             warn!("CRC check failed, retrying...");
+            damaged = Some(read_buffer);
             continue;
         }
 
@@ -133,6 +141,14 @@ pub(crate) fn read_block_direct(
         });
     }
 
+    // Bits that got flipped on the disk itself come back wrong every time, but might be fixable.
+    if let Some(fixed) = damaged.and_then(|data| try_correcting(pointer, data)) {
+        NotifyTui::complete_task_step(&handle);
+        NotifyTui::finish_task(handle);
+        NotifyTui::block_read(1);
+        return Ok(fixed);
+    }
+
     // If we've recursed, critical cleanup has failed.
     if has_recursed {
         return Err(DriveError::Retry)
@@ -158,6 +174,9 @@ pub(crate) fn read_block_direct(
         crate::pool::disk::drive_struct::DiskType::Standard(standard_disk) => {
             standard_disk.disk_file
         },
+        crate::pool::disk::drive_struct::DiskType::Dense(dense_disk) => {
+            dense_disk.disk_file
+        },
         crate::pool::disk::drive_struct::DiskType::Unknown(unknown_disk) => {
             unknown_disk.disk_file
         },
@@ -169,6 +188,24 @@ pub(crate) fn read_block_direct(
     read_block_direct(&new_file, originating_disk, block_index, ignore_crc, true)
 }
 
+/// Fix a block that keeps failing its CRC check, if error correction is on and the block isn't too far gone.
+///
+/// The fix is only a good guess, so it's never written back. The disk keeps the damaged block until a
+/// scrub puts the copy from the backup over it.
+fn try_correcting(pointer: DiskPointer, mut data: [u8; 512]) -> Option<RawBlock> {
+    if !*ENABLE_ERROR_CORRECTION.get().unwrap_or(&false) {
+        return None;
+    }
+    let bits: u8 = correct_block(&mut data)?;
+    warn!(
+        "Block {} of disk {} only passes its CRC check with {bits} bit(s) flipped back. Using that for now, but the disk still has the damaged block, scrub the pool to repair it.",
+        pointer.block, pointer.disk
+    );
+    Some(RawBlock {
+        block_origin: pointer,
+        data,
+    })
+}
 
 /// Automatically truncate reads if it would go off of the end of the disk.
 /// 
@@ -296,6 +333,9 @@ pub(crate) fn read_multiple_blocks_direct(
         crate::pool::disk::drive_struct::DiskType::Standard(standard_disk) => {
             standard_disk.disk_file
         },
+        crate::pool::disk::drive_struct::DiskType::Dense(dense_disk) => {
+            dense_disk.disk_file
+        },
         crate::pool::disk::drive_struct::DiskType::Unknown(unknown_disk) => {
             unknown_disk.disk_file
         },
@@ -125,6 +125,9 @@ pub(crate) fn write_block_direct(disk_file: &File, block: &RawBlock, has_recurse
         crate::pool::disk::drive_struct::DiskType::Standard(standard_disk) => {
             standard_disk.disk_file
         },
+        crate::pool::disk::drive_struct::DiskType::Dense(dense_disk) => {
+            dense_disk.disk_file
+        },
         crate::pool::disk::drive_struct::DiskType::Unknown(unknown_disk) => {
             unknown_disk.disk_file
         },
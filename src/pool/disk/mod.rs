@@ -1,4 +1,5 @@
 pub mod blank_disk;
+pub mod dense_disk;
 mod drive_methods;
 pub mod drive_struct;
 pub mod generic;
@@ -5,11 +5,13 @@
 use std::process::exit;
 
 use log::debug;
+use log::error;
 use log::warn;
 
 use crate::error_types::drive::DriveError;
 use crate::error_types::header::HeaderError;
 use crate::filesystem::disk_backup::restore::restore_disk;
+use crate::filesystem::filesystem_struct::FORMAT_POOL;
 use crate::filesystem::filesystem_struct::USE_VIRTUAL_DISKS;
 use crate::pool::disk::blank_disk::blank_disk_struct::BlankDisk;
 use crate::pool::disk::drive_methods::check_for_magic;
@@ -18,10 +20,18 @@ use crate::pool::disk::drive_struct::DiskType;
 use crate::pool::disk::drive_struct::FloppyDrive;
 use crate::pool::disk::generic::block::block_structs::RawBlock;
 use crate::pool::disk::generic::block::crc::add_crc_to_block;
+use crate::pool::disk::generic::block::crc::check_crc;
 use crate::pool::disk::generic::disk_trait::GenericDiskMethods;
 use crate::pool::disk::generic::generic_structs::pointer_struct::DiskPointer;
 use crate::pool::disk::generic::io::wipe::destroy_disk;
+use crate::pool::disk::pool_disk::block::header::header_struct::CompatFeatures;
+use crate::pool::disk::pool_disk::block::header::header_struct::IncompatFeatures;
+use crate::pool::disk::pool_disk::block::header::header_struct::ItemCounts;
 use crate::pool::disk::pool_disk::block::header::header_struct::PoolHeaderFlags;
+use crate::pool::disk::pool_disk::block::header::header_struct::RoCompatFeatures;
+use crate::pool::disk::pool_disk::block::header::header_struct::COUNTS_BLOCK;
+use crate::pool::disk::pool_disk::block::header::header_struct::MAX_DENSE_DISKS;
+use crate::pool::format::format_struct::FORMAT_VERSION;
 use crate::pool::disk::pool_disk::pool_disk_struct::PoolDisk;
 use crate::tui::prompts::TuiPrompt;
 use super::header_struct::PoolDiskHeader;
@@ -45,8 +55,36 @@ impl PoolDiskHeader {
     pub fn from_block(block: &RawBlock) -> Result<PoolDiskHeader, HeaderError> {
         pool_header_from_raw_block(block)
     }
+    /// How many blocks the inodes and directories in the pool take up, at least.
+    ///
+    /// Directories can grow past a single block, and inode blocks can have gaps in them, so it may be more.
+    pub fn metadata_blocks(&self) -> u64 {
+        let inode_bytes: u64 = self.items.files * FILE_INODE_BYTES + self.items.directories * DIRECTORY_INODE_BYTES;
+        inode_bytes.div_ceil(INODE_BLOCK_BYTES) + self.items.directories
+    }
+}
+
+impl ItemCounts {
+    /// Convert the counts into the block they live in on the pool disk.
+    pub fn to_block(self) -> RawBlock {
+        counts_to_raw_block(self)
+    }
+    /// Read the counts back out of their block. Returns None if the block is damaged.
+    pub fn from_block(block: &RawBlock) -> Option<ItemCounts> {
+        counts_from_raw_block(block)
+    }
 }
 
+// Flags, the size and extent pointer, and two timestamps. Checksums add another 8 bytes on top.
+const FILE_INODE_BYTES: u64 = 1 + 12 + 24;
+// Flags, the directory block pointer, and two timestamps.
+const DIRECTORY_INODE_BYTES: u64 = 1 + 4 + 24;
+// Room for inodes in each inode block.
+const INODE_BLOCK_BYTES: u64 = 501;
+
+// Counts only live on pools from this version onwards. Older pools get them when they're upgraded.
+const COUNTS_SINCE_VERSION: u16 = 2;
+
 /// This function bypasses the usual disk types.
 /// I dont want to rewrite this right now. It'll do.
 fn read_pool_header_from_disk() -> Result<PoolDiskHeader, DriveError> {
@@ -62,11 +100,12 @@ fn read_pool_header_from_disk() -> Result<PoolDiskHeader, DriveError> {
     // This is messy. Sorry.
 
     loop {
-        // if we are running with virtual disks, we skip the prompt.
+        // if we are running with virtual disks, or making a pool on the disk we were given, we skip the prompt.
         if !USE_VIRTUAL_DISKS
-            .try_lock()
-            .expect("Fluster is single threaded.")
+            .lock()
+            .expect("Other mutex holders should not panic.")
             .is_some()
+            && !*FORMAT_POOL.get().unwrap_or(&false)
         {
             // Not using virtual disks, prompt the user...
             let result = TuiPrompt::prompt_input(
@@ -98,7 +137,15 @@ fn read_pool_header_from_disk() -> Result<PoolDiskHeader, DriveError> {
         match some_disk {
             crate::pool::disk::drive_struct::DiskType::Pool(pool_disk) => {
                 // This is what we want!
-                return Ok(pool_disk.header);
+                let mut header: PoolDiskHeader = pool_disk.header;
+                if header.format_version >= COUNTS_SINCE_VERSION {
+                    // The counts aren't in the header block, so they need to be picked up too.
+                    match ItemCounts::from_block(&pool_disk.unchecked_read_block(COUNTS_BLOCK)?) {
+                        Some(items) => header.items = items,
+                        None => warn!("The file and directory counts on the pool disk are damaged, starting them over from zero."),
+                    }
+                }
+                return Ok(header);
             }
             crate::pool::disk::drive_struct::DiskType::Standard(standard_disk) => {
                 // For any disk type other than Blank, we will ask if user wants to wipe it.
@@ -106,52 +153,35 @@ fn read_pool_header_from_disk() -> Result<PoolDiskHeader, DriveError> {
                 // Start the loop over, if they wiped the disk, the outcome will change.
                 continue;
             }
+            crate::pool::disk::drive_struct::DiskType::Dense(dense_disk) => {
+                display_info_and_ask_wipe(&mut DiskType::Dense(dense_disk))?;
+                continue;
+            }
             crate::pool::disk::drive_struct::DiskType::Unknown(file) => {
                 display_info_and_ask_wipe(&mut DiskType::Unknown(file))?;
                 continue;
             }
             crate::pool::disk::drive_struct::DiskType::Blank(disk) => {
-                // The disk is blank, we will ask if the user wants to create a new pool.
-                prompt_for_new_pool(disk)?;
-                // The user either created a new pool, or didnt, so we just continue and run through this again.
+                // The disk is blank, which is only okay if we're here to make a new pool.
+                new_pool_or_bail(disk)?;
+                // The pool disk exists now, so we just continue and run through this again.
                 continue;
             }
         }
     }
 }
 
-/// Ask the user if they want to create a new pool with the currently inserted disk.
-/// If so, we blank out the disk
-fn prompt_for_new_pool(disk: BlankDisk) -> Result<(), DriveError> {
-    // if we are running with virtual disks, we skip the prompt.
-    debug!("Locking USE_VIRTUAL_DISKS...");
-    if USE_VIRTUAL_DISKS
-        .try_lock()
-        .expect("Fluster is single threaded.")
-        .is_some()
-    {
-        debug!("We are running with virtual disks, skipping the new pool prompt.");
-        // Using virtual disks, we are going to create the pool immediately.
-        return create_new_pool_disk(disk);
-    } else {
-        // If we are running a test, we should never be asking for user input, thus we should always
-        // be using virtual disks.
-        assert!(!cfg!(test), "Asked for user input during a test!");
+/// Turn the blank disk into a new pool, if we're running `mkfs`.
+/// Mounting a blank disk is a mistake, so that's where things end.
+fn new_pool_or_bail(disk: BlankDisk) -> Result<(), DriveError> {
+    if !*FORMAT_POOL.get().unwrap_or(&false) {
+        error!("The pool disk is blank, there's no pool on it to mount.");
+        error!("Make a new pool with `fluster_fs mkfs` first.");
+        error!("Fluster will now exit.");
+        panic!("Tried to mount a blank disk!");
     }
 
-    // Ask the user if they want to create a new pool starting on this disk (hereafer disk 0 / root disk)
-    let reply = TuiPrompt::prompt_input(
-        "Create new pool?".to_string(),
-        "This disk is blank.\nDo you wish to create a new pool?\n\"yes\"/\"no\"".to_string(),
-        false
-    );
-
-    if !reply.to_lowercase().contains("yes") {
-        // They dont wanna make a new one.
-        return Ok(());
-    }
-
-    // User said yes. Make the disk.
+    // mkfs was already told which disk to use, no need to ask again.
     create_new_pool_disk(disk)
 }
 
@@ -209,6 +239,40 @@ fn pool_header_from_raw_block(block: &RawBlock) -> Result<PoolDiskHeader, Header
     let pool_standard_blocks_free: u32 =
         u32::from_le_bytes(block.data[offset..offset + 4].try_into().expect("2 bytes = 2 bytes"));
 
+    offset += 4;
+
+    // Dedup index size
+    let dedup_index_blocks: u16 =
+        u16::from_le_bytes(block.data[offset..offset + 2].try_into().expect("2 bytes = 2 bytes"));
+
+    offset += 2;
+
+    // Dense disks
+    let mut dense_disks: [u16; MAX_DENSE_DISKS] = [0u16; MAX_DENSE_DISKS];
+    for disk in dense_disks.iter_mut() {
+        *disk = u16::from_le_bytes(block.data[offset..offset + 2].try_into().expect("2 bytes = 2 bytes"));
+        offset += 2;
+    }
+
+    // Format version
+    let format_version: u16 =
+        u16::from_le_bytes(block.data[offset..offset + 2].try_into().expect("2 bytes = 2 bytes"));
+    offset += 2;
+
+    // Features. Unknown ones are kept, so they can be complained about.
+    let compat: CompatFeatures =
+        CompatFeatures::from_bits_retain(u16::from_le_bytes(block.data[offset..offset + 2].try_into().expect("2 bytes = 2 bytes")));
+    offset += 2;
+    let ro_compat: RoCompatFeatures =
+        RoCompatFeatures::from_bits_retain(u16::from_le_bytes(block.data[offset..offset + 2].try_into().expect("2 bytes = 2 bytes")));
+    offset += 2;
+    let incompat: IncompatFeatures =
+        IncompatFeatures::from_bits_retain(u16::from_le_bytes(block.data[offset..offset + 2].try_into().expect("2 bytes = 2 bytes")));
+    offset += 2;
+
+    // Snapshot count
+    let snapshots: u8 = block.data[offset];
+
     // Block allocation map
     // Stop using the offset since this is always at the end.
     let block_usage_map: [u8; 360] = block.data[148..148 + 360].try_into().expect("2 bytes = 2 bytes");
@@ -216,13 +280,24 @@ fn pool_header_from_raw_block(block: &RawBlock) -> Result<PoolDiskHeader, Header
     // The latest inode write is not persisted between launches, so we point at the root inode.
     let latest_inode_write: DiskPointer = DiskPointer { disk: 1, block: 1 };
 
+    // Counts are in their own block, whoever reads the header has to go get them.
+    let items: ItemCounts = ItemCounts::default();
+
     Ok(PoolDiskHeader {
         flags,
         highest_known_disk,
         disk_with_next_free_block,
         pool_standard_blocks_free,
+        dedup_index_blocks,
         latest_inode_write, // This is not persisted between launches.
+        dense_disks,
+        format_version,
+        compat,
+        ro_compat,
+        incompat,
+        snapshots,
         block_usage_map,
+        items,
     })
 }
 
@@ -234,8 +309,16 @@ fn pool_header_to_raw_block(header: PoolDiskHeader) -> RawBlock {
         highest_known_disk,
         disk_with_next_free_block,
         pool_standard_blocks_free,
+        dedup_index_blocks,
         latest_inode_write,
+        dense_disks,
+        format_version,
+        compat,
+        ro_compat,
+        incompat,
+        snapshots,
         block_usage_map,
+        items,
     } = header;
 
     // Create buffer for the header
@@ -261,10 +344,39 @@ fn pool_header_to_raw_block(header: PoolDiskHeader) -> RawBlock {
 
     // Free blocks
     buffer[offset..offset + 4].copy_from_slice(&pool_standard_blocks_free.to_le_bytes());
+    offset += 4;
+
+    // Dedup index size
+    buffer[offset..offset + 2].copy_from_slice(&dedup_index_blocks.to_le_bytes());
+    offset += 2;
+
+    // Dense disks
+    for disk in dense_disks {
+        buffer[offset..offset + 2].copy_from_slice(&disk.to_le_bytes());
+        offset += 2;
+    }
+
+    // Format version
+    buffer[offset..offset + 2].copy_from_slice(&format_version.to_le_bytes());
+    offset += 2;
+
+    // Features
+    buffer[offset..offset + 2].copy_from_slice(&compat.bits().to_le_bytes());
+    offset += 2;
+    buffer[offset..offset + 2].copy_from_slice(&ro_compat.bits().to_le_bytes());
+    offset += 2;
+    buffer[offset..offset + 2].copy_from_slice(&incompat.bits().to_le_bytes());
+    offset += 2;
+
+    // Snapshot count
+    buffer[offset] = snapshots;
 
     // We do not save the inode write disk information.
     let _ = latest_inode_write;
 
+    // Or the counts, they have their own block.
+    let _ = items;
+
     // Block usage map
     // Doesn't use offset, static location.
     buffer[148..148 + 360].copy_from_slice(&block_usage_map);
@@ -300,6 +412,7 @@ fn create_new_pool_disk(mut disk: BlankDisk) -> Result<(), DriveError> {
     // We dont use cached IO here, since pool disks cannot have any caching on them.
     
     disk.unchecked_write_block(&writeable_block)?;
+    disk.unchecked_write_block(&new_header.items.to_block())?;
 
     // Done!
     Ok(())
@@ -324,9 +437,28 @@ fn new_pool_header() -> PoolDiskHeader {
     // How many pool blocks are free? None! We only have the root disk!
     let pool_standard_blocks_free: u32 = 0;
 
-    // What blocks are free on the pool disk? Not the first one!
+    // Nothing has been deduplicated yet.
+    let dedup_index_blocks: u16 = 0;
+
+    // No dense disks yet.
+    let dense_disks: [u16; MAX_DENSE_DISKS] = [0u16; MAX_DENSE_DISKS];
+
+    // Or snapshots.
+    let snapshots: u8 = 0;
+
+    // New pools are always the newest format, and haven't used any features yet.
+    let format_version: u16 = FORMAT_VERSION;
+    let compat: CompatFeatures = CompatFeatures::empty();
+    let ro_compat: RoCompatFeatures = RoCompatFeatures::empty();
+    let incompat: IncompatFeatures = IncompatFeatures::empty();
+
+    // What blocks are free on the pool disk? Not the first one, or where the counts go!
     let mut block_usage_map: [u8; 360] = [0u8; 360];
     block_usage_map[0] = 0b10000000;
+    block_usage_map[COUNTS_BLOCK as usize / 8] |= 0b10000000 >> (COUNTS_BLOCK % 8);
+
+    // Nothing exists yet, not even the root directory. That gets counted when disk 1 is made.
+    let items: ItemCounts = ItemCounts::default();
 
     // Everything is empty, so the latest write is just gonna be the root inode.
     let latest_inode_write: DiskPointer = DiskPointer { disk: 1, block: 1 };
@@ -336,8 +468,16 @@ fn new_pool_header() -> PoolDiskHeader {
         highest_known_disk,
         disk_with_next_free_block,
         pool_standard_blocks_free,
+        dedup_index_blocks,
         latest_inode_write, // This is not persisted on disk.
+        dense_disks,
+        format_version,
+        compat,
+        ro_compat,
+        incompat,
+        snapshots,
         block_usage_map,
+        items,
     }
 }
 
@@ -359,11 +499,38 @@ fn write_pool_header_to_disk(header: &PoolDiskHeader) -> Result<(), DriveError>
     // Write it.
     // We cant use the usual disk.flush() since that usually uses cache methods. Pool disk blocks are not cached.
     disk.unchecked_write_block(&header_block)?;
+    if header.format_version >= COUNTS_SINCE_VERSION {
+        disk.unchecked_write_block(&header.items.to_block())?;
+    }
 
     // All done.
     Ok(())
 }
 
+fn counts_to_raw_block(items: ItemCounts) -> RawBlock {
+    let mut buffer: [u8; 512] = [0u8; 512];
+    buffer[0..8].copy_from_slice(&items.files.to_le_bytes());
+    buffer[8..16].copy_from_slice(&items.directories.to_le_bytes());
+    add_crc_to_block(&mut buffer);
+    RawBlock {
+        block_origin: DiskPointer {
+            disk: 0,
+            block: COUNTS_BLOCK,
+        },
+        data: buffer,
+    }
+}
+
+fn counts_from_raw_block(block: &RawBlock) -> Option<ItemCounts> {
+    if !check_crc(block.data) {
+        return None;
+    }
+    Some(ItemCounts {
+        files: u64::from_le_bytes(block.data[0..8].try_into().expect("8 bytes = 8 bytes")),
+        directories: u64::from_le_bytes(block.data[8..16].try_into().expect("8 bytes = 8 bytes")),
+    })
+}
+
 /// Disk wiper mode
 fn disk_wiper_mode() -> ! {
     // Time to wipe some disks!
@@ -411,6 +578,7 @@ fn disk_wiper_mode() -> ! {
                 match err {
                     DriveError::DriveEmpty => {},
                     DriveError::Retry => {},
+                    DriveError::NoSpace => {},
                     DriveError::TakingTooLong => {
                         println!("That disk is responding VERY slowly to writes, its probably bad.")
                     },
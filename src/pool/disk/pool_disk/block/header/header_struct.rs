@@ -5,6 +5,15 @@ use bitflags::bitflags;
 
 use crate::pool::disk::generic::generic_structs::pointer_struct::DiskPointer;
 
+// Consts
+
+/// How many dense disks the pool header has room to keep track of.
+pub(crate) const MAX_DENSE_DISKS: usize = 60;
+
+/// The header block is full, so the file and directory counts get a block of their own on the pool disk,
+/// right before the snapshot table.
+pub(crate) const COUNTS_BLOCK: u16 = 2880 - 2;
+
 // Structs, Enums, Flags
 
 /// The header of the pool disk
@@ -18,11 +27,38 @@ pub struct PoolDiskHeader {
     pub disk_with_next_free_block: u16,
     /// The number of free standard blocks across all disks
     pub pool_standard_blocks_free: u32,
+    /// How many blocks on the pool disk the dedup index takes up.
+    /// The index starts at block 1.
+    pub dedup_index_blocks: u16,
     /// The disk with the most recent inode write.
     /// Used for speeding up inode additions.
     pub latest_inode_write: DiskPointer,
+    /// Which disks are dense disks. Unused slots are 0, since the pool disk can never be dense.
+    pub dense_disks: [u16; MAX_DENSE_DISKS],
+    /// What version of the on-disk format the pool is in. Pools from before there was a version are 0.
+    pub format_version: u16,
+    /// Features in use that older versions can safely ignore.
+    pub compat: CompatFeatures,
+    /// Features in use that older versions can read, but would break by writing.
+    pub ro_compat: RoCompatFeatures,
+    /// Features in use that older versions would misread.
+    pub incompat: IncompatFeatures,
+    /// How many snapshots there are.
+    /// The snapshot table lives in the final block of the pool disk.
+    pub snapshots: u8,
     /// Map of used blocks on this disk
     pub block_usage_map: [u8; 360],
+    /// How many files and directories are in the pool.
+    /// Not in the header block, these live at `COUNTS_BLOCK`.
+    pub items: ItemCounts,
+}
+
+/// How many files and directories there are, counting everything in snapshots and the trash too.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct ItemCounts {
+    pub files: u64,
+    /// Includes the root directory.
+    pub directories: u64,
 }
 
 bitflags! {
@@ -31,4 +67,41 @@ bitflags! {
         // All Pool headers MUST have this bit set.
         const RequiredHeaderBit = 0b10000000;
     }
-}
\ No newline at end of file
+}
+// Features work like ext4's. Once a feature is used it stays set, since there's no cheap way of
+// knowing when the last thing using it is gone.
+
+bitflags! {
+    /// Features an older fluster can ignore, and still read and write the pool just fine.
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    pub struct CompatFeatures: u16 {
+        // There's a trash directory in the root. It's just a directory to anyone else.
+        const Trash = 0b00000001;
+    }
+}
+
+bitflags! {
+    /// Features an older fluster can read around, but would break by writing to the pool.
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    pub struct RoCompatFeatures: u16 {
+        // Blocks can be shared between files, and freeing one would pull it out from under the others.
+        const Dedup = 0b00000001;
+        // The snapshot table is in the final block of the pool disk.
+        const Snapshots = 0b00000010;
+    }
+}
+
+bitflags! {
+    /// Features an older fluster would misread.
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    pub struct IncompatFeatures: u16 {
+        // Some disks are dense disks, and some extents point at a whole one.
+        const DenseDisks = 0b00000001;
+        // Some inodes have a checksum tacked on the end.
+        const Checksums = 0b00000010;
+        // Some extents are holes.
+        const Holes = 0b00000100;
+        // Some directory items have a placement tacked on the end.
+        const Placement = 0b00001000;
+    }
+}
@@ -3,12 +3,28 @@
 #![allow(clippy::unwrap_used)]
 
 // Imports
+use std::ffi::OsStr;
+use std::path::Path;
+
+use fuse_mt::FilesystemMT;
+use fuse_mt::RequestInfo;
 use rand::Rng;
 use rand::rngs::ThreadRng;
 
 use crate::pool::disk::generic::block::block_structs::RawBlock;
+use crate::pool::disk::generic::generic_structs::pointer_struct::DiskPointer;
+use crate::pool::disk::generic::io::cache::cache_io::CachedBlockIO;
+use crate::pool::disk::pool_disk::block::header::header_struct::CompatFeatures;
+use crate::pool::disk::pool_disk::block::header::header_struct::IncompatFeatures;
+use crate::pool::disk::pool_disk::block::header::header_struct::ItemCounts;
 use crate::pool::disk::pool_disk::block::header::header_struct::PoolDiskHeader;
 use crate::pool::disk::pool_disk::block::header::header_struct::PoolHeaderFlags;
+use crate::pool::disk::pool_disk::block::header::header_struct::RoCompatFeatures;
+use crate::pool::disk::standard_disk::block::header::header_struct::StandardDiskHeader;
+use crate::pool::disk::standard_disk::block::io::directory::tests::get_filesystem;
+use crate::pool::manifest::manifest_methods::count_used;
+use crate::pool::pool_actions::pool_struct::Pool;
+use crate::pool::pool_actions::pool_struct::GLOBAL_POOL;
 
 use test_log::test; // We want to see logs while testing.
 
@@ -28,11 +44,70 @@ fn block_ping_pong() {
     }
 }
 
+#[test]
+fn counts_ping_pong() {
+    let mut random: ThreadRng = rand::rng();
+    for _ in 0..1000 {
+        let counts = ItemCounts {
+            files: random.random(),
+            directories: random.random(),
+        };
+        assert_eq!(ItemCounts::from_block(&counts.to_block()), Some(counts));
+    }
+    let mut damaged = ItemCounts::default().to_block();
+    damaged.data[3] ^= 0b1000;
+    assert_eq!(ItemCounts::from_block(&damaged), None);
+}
+
+// Files and directories get counted as they come and go, and statfs shows them.
+#[test]
+fn counts_follow_the_tree() {
+    let fs = get_filesystem();
+    let request = RequestInfo {
+        unique: 0,
+        uid: 0,
+        gid: 0,
+        pid: 0,
+    };
+    let root = Path::new("/");
+    let items = || GLOBAL_POOL.get().unwrap().lock().unwrap().header.items;
+    // Just the root.
+    assert_eq!(items(), ItemCounts { files: 0, directories: 1 });
+
+    for name in ["one.txt", "two.txt"] {
+        let created = fs.create(request, root, OsStr::new(name), 0o644, 0).unwrap();
+        fs.release(request, &root.join(name), created.fh, 0, 0, false).unwrap();
+    }
+    let _ = fs.mkdir(request, root, OsStr::new("folder"), 0o755).unwrap();
+    let _ = fs.mkdir(request, Path::new("/folder"), OsStr::new("inner"), 0o755).unwrap();
+    assert_eq!(items(), ItemCounts { files: 2, directories: 3 });
+
+    fs.unlink(request, root, OsStr::new("two.txt")).unwrap();
+    fs.rmdir(request, Path::new("/folder"), OsStr::new("inner")).unwrap();
+    assert_eq!(items(), ItemCounts { files: 1, directories: 2 });
+    // Replacing a directory gets rid of the old one.
+    let _ = fs.mkdir(request, root, OsStr::new("spare"), 0o755).unwrap();
+    let _ = fs.mkdir(request, Path::new("/spare"), OsStr::new("empty"), 0o755).unwrap();
+    fs.rename(request, Path::new("/spare"), OsStr::new("empty"), root, OsStr::new("folder")).unwrap();
+    assert_eq!(items(), ItemCounts { files: 1, directories: 3 });
+
+    let stats = fs.statfs(request, root).unwrap();
+    assert_eq!(stats.files, 4);
+    assert_eq!(stats.ffree, stats.bfree);
+    // Disk 1 has room for everything but its header, minus one inode block and a block for each directory.
+    assert_eq!(stats.blocks, 2879 - 1 - 3);
+    // Every block that's free on disk, and nothing more.
+    let disk_one = StandardDiskHeader::from_block(&CachedBlockIO::read_block(DiskPointer { disk: 1, block: 0 }).unwrap());
+    assert_eq!(stats.bfree, u64::from(2880 - count_used(&disk_one.block_usage_map)));
+
+    // And they survive a trip to the pool disk.
+    Pool::flush().unwrap();
+    assert_eq!(PoolDiskHeader::read().unwrap().items, items());
+}
+
 #[cfg(test)]
 impl PoolDiskHeader {
     fn random() -> Self {
-        use crate::pool::disk::generic::generic_structs::pointer_struct::DiskPointer;
-
         let mut random: ThreadRng = rand::rng();
         // latest inode write isnt persisted to the pool on deserialization so we dont care here either
         let latest_inode_write: DiskPointer = DiskPointer { disk: 1, block: 1 };
@@ -42,8 +117,16 @@ impl PoolDiskHeader {
             highest_known_disk: random.random(),
             disk_with_next_free_block: random.random(),
             pool_standard_blocks_free: random.random(),
+            dedup_index_blocks: random.random(),
+            dense_disks: std::array::from_fn(|_| random.random()),
+            format_version: random.random(),
+            compat: CompatFeatures::from_bits_retain(random.random()),
+            ro_compat: RoCompatFeatures::from_bits_retain(random.random()),
+            incompat: IncompatFeatures::from_bits_retain(random.random()),
+            snapshots: random.random(),
             block_usage_map: random_allocations(),
             latest_inode_write, // This does not get saved to disk.
+            items: ItemCounts::default(), // Neither do these, they have their own block.
         }
     }
 }
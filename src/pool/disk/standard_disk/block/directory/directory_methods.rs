@@ -6,7 +6,7 @@
 
 use log::debug;
 
-use crate::{error_types::{block::BlockManipulationError, drive::DriveError}, pool::disk::{
+use crate::{error_types::{block::BlockManipulationError, drive::DriveError}, pool::{disk::{
     generic::{
         block::{
             block_structs::RawBlock,
@@ -26,7 +26,7 @@ use crate::{error_types::{block::BlockManipulationError, drive::DriveError}, poo
             InodeTimestamp
         },
     }
-}, tui::{notify::NotifyTui, tasks::TaskType}};
+}, placement::placement_struct::Placement}, tui::{notify::NotifyTui, tasks::TaskType}};
 
 // We can convert from a raw block to a directory bock, but not the other way around.
 impl From<RawBlock> for DirectoryBlock {
@@ -278,7 +278,7 @@ impl DirectoryBlock {
             let (item_size, item) = DirectoryItem::from_bytes(&bytes[index..], origin_disk);
 
             // increment index
-            index += item_size as usize;
+            index += item_size;
 
             // Done with this one
             items.push(item)
@@ -293,9 +293,11 @@ impl DirectoryBlock {
 impl DirectoryItem {
     /// Turn an item into bytes. Requires the destination disk.
     pub(super) fn to_bytes(&self, destination_disk: u16) -> Vec<u8> {
-        let mut vec: Vec<u8> = Vec::with_capacity(262); // Theoretical limit
+        let mut vec: Vec<u8> = Vec::with_capacity(267); // Theoretical limit
         // Flags
-        vec.push(self.flags.bits());
+        let mut flags: DirectoryItemFlags = self.flags;
+        flags.set(DirectoryItemFlags::HasPlacement, self.placement.is_some());
+        vec.push(flags.bits());
 
         // Item name length
         vec.push(self.name_length);
@@ -306,15 +308,21 @@ impl DirectoryItem {
         // location of the inode
         vec.extend(self.location.as_bytes(destination_disk));
 
+        // Placement, if there is one
+        if let Some(placement) = self.placement {
+            vec.extend(placement.to_bytes());
+        }
+
         // All done
         vec
     }
 
     // Returns self, and how many bytes it took to construct this.
-    pub(super) fn from_bytes(bytes: &[u8], origin_disk: u16) -> (u8, Self) {
+    // Long names with a placement can take more than 255 bytes, so this is not a u8.
+    pub(super) fn from_bytes(bytes: &[u8], origin_disk: u16) -> (usize, Self) {
         let mut index: usize = 0;
         // Flags
-        let flags: DirectoryItemFlags =
+        let mut flags: DirectoryItemFlags =
             DirectoryItemFlags::from_bits(bytes[index]).expect("Flags should only have used bits set.");
         index += 1;
 
@@ -334,14 +342,26 @@ impl DirectoryItem {
         let (location_size, location) = InodeLocation::from_bytes(&bytes[index..], origin_disk);
         index += location_size as usize;
 
+        // Placement
+        let placement: Option<Placement> = if flags.contains(DirectoryItemFlags::HasPlacement) {
+            let placement = Placement::from_bytes(&bytes[index..]);
+            index += Placement::SIZE;
+            Some(placement)
+        } else {
+            None
+        };
+        // That flag only matters on disk.
+        flags.remove(DirectoryItemFlags::HasPlacement);
+
         let done = Self {
             flags,
             name_length,
             name,
             location,
+            placement,
         };
 
-        (index as u8, done)
+        (index, done)
     }
 
     /// Get the size of the item. Regardless of type.
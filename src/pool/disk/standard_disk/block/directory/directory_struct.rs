@@ -4,9 +4,12 @@
 
 use bitflags::bitflags;
 
-use crate::pool::disk::{
-    generic::generic_structs::pointer_struct::DiskPointer,
-    standard_disk::block::inode::inode_struct::InodeLocation,
+use crate::pool::{
+    disk::{
+        generic::generic_structs::pointer_struct::DiskPointer,
+        standard_disk::block::inode::inode_struct::InodeLocation,
+    },
+    placement::placement_struct::Placement,
 };
 
 // Structs / Enums / Flags
@@ -17,6 +20,8 @@ pub struct DirectoryItem {
     pub name_length: u8,
     pub name: String,
     pub location: InodeLocation,
+    // Only directories can have a placement.
+    pub placement: Option<Placement>,
 }
 
 // This type is not clone, since you could end up with a block that is out of sync due to
@@ -39,6 +44,7 @@ bitflags! {
     #[derive(Debug, PartialEq, Eq, Clone, Copy)]
     pub struct DirectoryItemFlags: u8 {
         const IsDirectory = 0b00000010; // Set if directory
+        const HasPlacement = 0b00000100; // Set if a placement follows the inode location. Only used on disk.
         const MarkerBit = 0b10000000;
     }
 }
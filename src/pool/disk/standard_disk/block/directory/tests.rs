@@ -11,6 +11,7 @@ use crate::pool::disk::standard_disk::block::directory::directory_struct::Direct
 use crate::pool::disk::standard_disk::block::directory::directory_struct::DirectoryItemFlags;
 use crate::pool::disk::standard_disk::block::directory::directory_struct::DirectoryItem;
 use crate::pool::disk::standard_disk::block::inode::inode_struct::InodeLocation;
+use crate::pool::placement::placement_struct::Placement;
 
 use test_log::test; // We want to see logs while testing.
 
@@ -137,11 +138,21 @@ impl DirectoryItem {
         assert_eq!(name_length as usize, name.len());
         let location = InodeLocation::get_random();
         let flags = DirectoryItemFlags::new();
+        let mut random = rand::rng();
+        let placement = match random.random_range(0..3) {
+            0 => None,
+            1 => Some(Placement::Together),
+            _ => {
+                let first: u16 = random.random_range(1..u16::MAX);
+                Some(Placement::Disks { first, last: random.random_range(first..=u16::MAX) })
+            },
+        };
         DirectoryItem {
             flags,
             name_length,
             name,
             location,
+            placement,
         }
     }
 }
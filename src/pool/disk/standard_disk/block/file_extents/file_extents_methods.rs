@@ -8,6 +8,7 @@ pub(crate) const DATA_BLOCK_OVERHEAD: u64 = 5; // 1 flag, 4 checksum.
 
 
 use crate::{error_types::block::BlockManipulationError, pool::disk::{
+    dense_disk::dense_disk_struct::DenseDisk,
     generic::{
         block::{
             block_structs::RawBlock,
@@ -142,7 +143,7 @@ impl FileExtentBlock {
         // Now add the new extents, fixing the disk numbers as needed.
         for new in &mut to_add {
             // if the disk is the same as the block origin, we will set the local flag and such.
-            if new.start_block.disk == our_disk {
+            if new.start_block.disk == our_disk && !new.is_hole() && !new.is_dense() {
                 // Disk matched, Give the extent the local flag.
                 // We don't need to update the disk number, since that'll toss itself on write.
                 new.flags.insert(ExtentFlags::LocalExtent);
@@ -324,6 +325,23 @@ impl FileExtent {
     pub(super) fn to_bytes(mut self, destination_disk_number: u16) -> Vec<u8> {
         let mut vec: Vec<u8> = Vec::with_capacity(6); // At most 6 bytes.
 
+        // Holes dont point anywhere, so they are just the flags and the length.
+        if self.flags.contains(ExtentFlags::HoleExtent) {
+            self.flags.remove(ExtentFlags::LocalExtent);
+            vec.push(self.flags.bits());
+            vec.push(self.length);
+            return vec;
+        }
+
+        // Dense extents are just the disk. You cannot have a local dense disk, since extent
+        // blocks never live on one.
+        if self.flags.contains(ExtentFlags::DenseExtent) {
+            self.flags.remove(ExtentFlags::LocalExtent);
+            vec.push(self.flags.bits());
+            vec.extend_from_slice(&self.start_block.disk.to_le_bytes());
+            return vec;
+        }
+
         // If the disk number is the same, we set the local flag.
         if self.start_block.disk == destination_disk_number {
             self.flags.insert(ExtentFlags::LocalExtent);
@@ -370,6 +388,22 @@ impl FileExtent {
         
         offset += 1;
 
+        // Holes only have a length.
+        if flags.contains(ExtentFlags::HoleExtent) {
+            let hole = FileExtent {
+                flags,
+                start_block: DiskPointer::new_final_pointer(),
+                length: bytes[offset],
+            };
+            return (2, hole);
+        }
+
+        // Dense extents only have a disk.
+        if flags.contains(ExtentFlags::DenseExtent) {
+            let disk: u16 = u16::from_le_bytes(bytes[offset..offset + 2].try_into().expect("2 = 2 "));
+            return (3, FileExtent::new_dense(disk));
+        }
+
         let disk_number: u16;
 
         // Disk number
@@ -412,7 +446,15 @@ impl FileExtent {
     /// Only gets info about this specific extent, does no traversal.
     /// 
     /// Needs to know what disk this FileExtent came from.
+    /// 
+    /// Holes return a pointer with no destination for each block they cover.
     pub(crate) fn get_pointers(&self) -> Vec<DiskPointer> {
+        if self.is_hole() {
+            return vec![DiskPointer::new_final_pointer(); self.length.into()];
+        }
+        if self.is_dense() {
+            return DenseDisk::data_pointers(self.start_block.disk);
+        }
         // Each block that the extent references
         let mut pointers: Vec<DiskPointer> = Vec::with_capacity(self.length.into());
         for n in 0..self.length {
@@ -433,6 +475,39 @@ impl FileExtent {
             length,
         }
     }
+
+    /// Make a new hole, which covers `length` blocks without storing any of them.
+    pub(crate) fn new_hole(length: u8) -> Self {
+        Self {
+            flags: ExtentFlags::MarkerBit | ExtentFlags::HoleExtent,
+            start_block: DiskPointer::new_final_pointer(),
+            length,
+        }
+    }
+
+    /// Make a new dense extent, which covers every data block on a dense disk.
+    /// 
+    /// The length is meaningless for these, the whole disk is always used.
+    pub(crate) fn new_dense(disk: u16) -> Self {
+        Self {
+            flags: ExtentFlags::MarkerBit | ExtentFlags::DenseExtent,
+            start_block: DiskPointer {
+                disk,
+                block: 1,
+            },
+            length: 0,
+        }
+    }
+
+    /// Is this extent a hole?
+    pub(crate) fn is_hole(&self) -> bool {
+        self.flags.contains(ExtentFlags::HoleExtent)
+    }
+
+    /// Does this extent cover a whole dense disk?
+    pub(crate) fn is_dense(&self) -> bool {
+        self.flags.contains(ExtentFlags::DenseExtent)
+    }
 }
 
 // Default bitflags
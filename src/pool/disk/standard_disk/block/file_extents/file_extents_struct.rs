@@ -28,6 +28,11 @@ bitflags! {
         // we save bytes by tossing the disk bytes if the extent is local. The disk number is
         // then reconstructed on read.
         const LocalExtent = 0b00000001;
+        // Dense extents cover every data block on a dense disk, so they only need the disk number.
+        const DenseExtent = 0b00000010;
+        // Holes in sparse files have no blocks behind them, only a length. Reading
+        // a hole returns zeros.
+        const HoleExtent = 0b00000100;
         const MarkerBit = 0b10000000;
     }
 }
@@ -118,6 +118,14 @@ impl FileExtent {
         // Flags do not matter, they are auto deduced.
         let flags = ExtentFlags::new();
         let length: u8 = random.random();
+        // Sometimes make a hole
+        if random.random_bool(0.1) {
+            return FileExtent::new_hole(length);
+        }
+        // Or a whole dense disk
+        if random.random_bool(0.05) {
+            return FileExtent::new_dense(random.random());
+        }
         let start_block: DiskPointer = DiskPointer::get_random();
 
         // All done.
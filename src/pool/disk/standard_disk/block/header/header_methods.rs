@@ -2,7 +2,7 @@
 
 use crate::pool::disk::{
     generic::{block::{block_structs::RawBlock, crc::add_crc_to_block}, generic_structs::pointer_struct::DiskPointer},
-    standard_disk::block::header::header_struct::{StandardDiskHeader, StandardHeaderFlags},
+    standard_disk::block::header::header_struct::{StandardDiskHeader, StandardHeaderFlags, MAX_POOL_LABEL_LENGTH},
 };
 
 // Implementations
@@ -36,6 +36,18 @@ fn extract_header(raw_block: &RawBlock) -> StandardDiskHeader {
     let disk_number: u16 =
         u16::from_le_bytes(raw_block.data[9..9 + 2].try_into().expect("Impossible"));
 
+    // The generation
+    let generation: u64 =
+        u64::from_le_bytes(raw_block.data[11..11 + 8].try_into().expect("Impossible"));
+
+    // The format version
+    let format_version: u16 =
+        u16::from_le_bytes(raw_block.data[19..19 + 2].try_into().expect("Impossible"));
+
+    // The pool label, length first
+    let label_length: usize = usize::from(raw_block.data[21]).min(MAX_POOL_LABEL_LENGTH);
+    let pool_label: String = String::from_utf8_lossy(&raw_block.data[22..22 + label_length]).to_string();
+
     // block usage bitplane
     let block_usage_map: [u8; 360] = raw_block.data[148..148 + 360]
         .try_into()
@@ -44,6 +56,9 @@ fn extract_header(raw_block: &RawBlock) -> StandardDiskHeader {
     StandardDiskHeader {
         flags,
         disk_number,
+        generation,
+        format_version,
+        pool_label,
         block_usage_map,
     }
 }
@@ -57,6 +72,9 @@ fn to_disk_block(header: &StandardDiskHeader) -> RawBlock {
     let StandardDiskHeader {
         flags,
         disk_number,
+        generation,
+        format_version,
+        pool_label,
         block_usage_map,
     } = header;
 
@@ -72,6 +90,20 @@ fn to_disk_block(header: &StandardDiskHeader) -> RawBlock {
     // The disk number
     buffer[9..9 + 2].copy_from_slice(&disk_number.to_le_bytes());
 
+    // The generation
+    buffer[11..11 + 8].copy_from_slice(&generation.to_le_bytes());
+
+    // The format version
+    buffer[19..19 + 2].copy_from_slice(&format_version.to_le_bytes());
+
+    // The pool label, cut short without splitting a character
+    let mut label_length: usize = pool_label.len().min(MAX_POOL_LABEL_LENGTH);
+    while !pool_label.is_char_boundary(label_length) {
+        label_length -= 1;
+    }
+    buffer[21] = label_length as u8;
+    buffer[22..22 + label_length].copy_from_slice(&pool_label.as_bytes()[..label_length]);
+
     // The block map
     buffer[148..148 + 360].copy_from_slice(block_usage_map);
 
@@ -1,6 +1,11 @@
 // Imports
 use bitflags::bitflags;
 
+// Consts
+
+/// The longest pool name a disk header can hold, in bytes.
+pub const MAX_POOL_LABEL_LENGTH: usize = 32;
+
 // Structs, Enums, Flags
 
 /// The header of a disk
@@ -8,6 +13,14 @@ use bitflags::bitflags;
 pub struct StandardDiskHeader {
     pub flags: StandardHeaderFlags,
     pub disk_number: u16,
+    /// Goes up every time the cache flushes to this disk, so copies of its blocks held
+    /// elsewhere can tell if they are out of date.
+    pub generation: u64,
+    /// What version of the on-disk format this disk is in. Disks from before there was a version are 0.
+    pub format_version: u16,
+    /// The name of the pool this disk belongs to, so it's known without being told every mount.
+    /// Empty if the pool was never named. Cut short to [MAX_POOL_LABEL_LENGTH] bytes when written.
+    pub pool_label: String,
     pub block_usage_map: [u8; 360], // not to be indexed directly, use a method to check.
 }
 
@@ -15,7 +28,7 @@ bitflags! {
     #[derive(Debug, PartialEq, Eq, Clone, Copy)]
     pub struct StandardHeaderFlags: u8 {
         const Marker = 0b00100000; // Must be set.
-        // 0b01000000; // Reserved for dense disk
+        const Dense = 0b01000000; // Set instead of the marker on dense disks.
         // 0b10000000; // Reserved for pool disk
     }
 }
@@ -1 +1,38 @@
 // You need to test head? You can try on me, I guess...
+// Unwrapping is okay here, since we want unexpected outcomes to fail tests.
+#![allow(clippy::unwrap_used)]
+
+use test_log::test;
+
+use super::header_struct::{
+    StandardDiskHeader,
+    StandardHeaderFlags,
+    MAX_POOL_LABEL_LENGTH
+};
+
+fn header_with_label(pool_label: &str) -> StandardDiskHeader {
+    StandardDiskHeader {
+        flags: StandardHeaderFlags::Marker,
+        disk_number: 7,
+        generation: 3,
+        format_version: 1,
+        pool_label: pool_label.to_string(),
+        block_usage_map: [0b10100000; 360],
+    }
+}
+
+/// Labels survive the trip, and long ones get cut without splitting a character.
+#[test]
+fn labels_fit() {
+    let header = header_with_label("Floppy farm");
+    assert_eq!(StandardDiskHeader::from_block(&header.to_block()), header);
+
+    let unnamed = header_with_label("");
+    assert_eq!(StandardDiskHeader::from_block(&unnamed.to_block()), unnamed);
+
+    // 31 bytes, then a 2 byte character that doesn't fit.
+    let long = format!("{}é and then some", "a".repeat(MAX_POOL_LABEL_LENGTH - 1));
+    let read = StandardDiskHeader::from_block(&header_with_label(&long).to_block());
+    assert_eq!(read.pool_label, "a".repeat(MAX_POOL_LABEL_LENGTH - 1));
+    assert_eq!(read.block_usage_map, [0b10100000; 360]);
+}
@@ -334,10 +334,13 @@ fn to_raw_bytes(block: &InodeBlock) -> RawBlock {
 
 impl Inode {
     pub(super) fn as_bytes(&self) -> Vec<u8> {
-        let mut vec: Vec<u8> = Vec::with_capacity(37); // max size of an inode
+        let mut vec: Vec<u8> = Vec::with_capacity(45); // max size of an inode
 
         // flags
-        vec.push(self.flags.bits());
+        // The checksum flag always matches the file, so the inode can be read back.
+        let mut flags: InodeFlags = self.flags;
+        flags.set(InodeFlags::HasChecksum, self.file.is_some_and(|file| file.checksum.is_some()));
+        vec.push(flags.bits());
 
         // Inode data
         // There should never be both a file and a directory in an inode.
@@ -347,6 +350,9 @@ impl Inode {
 
         if let Some(file) = self.file {
             vec.extend(file.as_bytes());
+            if let Some(checksum) = file.checksum {
+                vec.extend(checksum.to_le_bytes());
+            }
         }
 
         // Timestamps
@@ -378,9 +384,14 @@ impl Inode {
         // File or directory
         let file: Option<InodeFile> = if flags.contains(InodeFlags::FileType) {
             timestamp_offset += 12;
-            Some(InodeFile::from_bytes(
+            let mut file = InodeFile::from_bytes(
                 bytes[1..1 + 12].try_into().expect("12 = 12"),
-            ))
+            );
+            if flags.contains(InodeFlags::HasChecksum) {
+                file.checksum = Some(u64::from_le_bytes(bytes[13..13 + 8].try_into().expect("8 = 8")));
+                timestamp_offset += 8;
+            }
+            Some(file)
         } else {
             None
         };
@@ -435,6 +446,7 @@ impl InodeFile {
         Self {
             size: u64::from_le_bytes(bytes[..8].try_into().expect("8 = 8")),
             pointer: DiskPointer::from_bytes(bytes[8..].try_into().expect("4 = 4")),
+            checksum: None,
         }
     }
 }
@@ -611,8 +623,21 @@ impl InodeFile {
         Self {
             size: 0, // New files are empty
             pointer: disk_pointer,
+            checksum: None,
         }
     }
+    /// Get the checksum of the file's contents, if it keeps one.
+    pub fn get_checksum(&self) -> Option<u64> {
+        self.checksum
+    }
+    /// Replace the file's checksum.
+    /// 
+    /// Adding or removing a checksum changes the size of the inode, so that can only be done
+    /// before the inode is stored anywhere.
+    /// Does not flush change to disk.
+    pub fn set_checksum(&mut self, checksum: Option<u64>) {
+        self.checksum = checksum;
+    }
     /// Get size of a file
     pub fn get_size(&self) -> u64 {
         self.size
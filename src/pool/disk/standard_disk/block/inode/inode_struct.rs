@@ -23,6 +23,9 @@ pub struct InodeFile {
     pub(super) size: u64,
     /// Points to the first extent block in the chain for this file.
     pub(crate) pointer: DiskPointer,
+    /// Hash of the whole file's contents, if it has one.
+    /// Files either always have one, or never do, since inodes can't change size.
+    pub(super) checksum: Option<u64>,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -69,6 +72,7 @@ bitflags! {
     #[derive(Debug, PartialEq, Eq, Clone, Copy)]
     pub struct InodeFlags: u8 {
         const FileType = 0b00000001; // Set if this is a file
+        const HasChecksum = 0b00000010; // Set if a file checksum follows the file data
         const MarkerBit = 0b10000000; // Always set
     }
 }
@@ -118,9 +118,10 @@ fn inode_location_consistency() {
 fn inode_correct_sizes() {
     for _ in 0..1000 {
         let test_inode: Inode = Inode::get_random();
-        if test_inode.file.is_some() {
-            // A file inode should be 37 bytes long
-            assert_eq!(test_inode.as_bytes().len(), 37)
+        if let Some(file) = test_inode.file {
+            // A file inode should be 37 bytes long, or 45 with a checksum
+            let expected: usize = if file.checksum.is_some() { 45 } else { 37 };
+            assert_eq!(test_inode.as_bytes().len(), expected)
         } else {
             // A directory inode should be 29 bytes long
             assert_eq!(test_inode.as_bytes().len(), 29)
@@ -179,10 +180,12 @@ impl Inode {
             // A file
             let mut flags = InodeFlags::MarkerBit;
             flags.insert(InodeFlags::FileType);
+            let file = InodeFile::get_random();
+            flags.set(InodeFlags::HasChecksum, file.checksum.is_some());
 
             Inode {
                 flags,
-                file: Some(InodeFile::get_random()),
+                file: Some(file),
                 directory: None,
                 created: InodeTimestamp::get_random(),
                 modified: InodeTimestamp::get_random(),
@@ -207,6 +210,7 @@ impl InodeFile {
         InodeFile {
             size: random.random(),
             pointer: DiskPointer::get_random(),
+            checksum: if random.random_bool(0.5) { Some(random.random()) } else { None },
         }
     }
 }
@@ -16,7 +16,8 @@ use crate::{error_types::drive::DriveError, pool::{
             io::directory::types::NamedItem,
         }
     },
-    pool_actions::pool_struct::Pool
+    pool_actions::pool_struct::Pool,
+    snapshot::snapshot_struct::SnapshotRoot
 }, tui::{notify::NotifyTui, tasks::TaskType}};
 
 impl DirectoryBlock {
@@ -112,6 +113,8 @@ impl DirectoryBlock {
     /// Check if this DirectoryBlock is the head of the root directory.
     /// 
     /// This will return false on any other block than the head block.
+    ///
+    /// If this thread is looking at a snapshot, this checks for the snapshot's root instead.
     fn is_root(&self) -> bool {
         // Lives in a static place.
         static ROOT_BLOCK_LOCATION: DiskPointer = DiskPointer {
@@ -119,7 +122,10 @@ impl DirectoryBlock {
             block: 2,
         };
 
-        self.block_origin == ROOT_BLOCK_LOCATION
+        match SnapshotRoot::current() {
+            Some(snapshot) => self.block_origin == snapshot.directory,
+            None => self.block_origin == ROOT_BLOCK_LOCATION,
+        }
     }
 
     /// Extracts an item from a directory block, blanking out the space it used to occupy.
@@ -188,6 +194,8 @@ impl DirectoryBlock {
             Some(ok) => ok,
             None => {
                 // No cleanup required!
+                // The head might have been where the item was, so it needs to be updated.
+                *self = blocks.swap_remove(0);
                 return Ok(Some(found.item));
             },
         };
@@ -199,6 +207,7 @@ impl DirectoryBlock {
         // switching the pointers around would be needlessly complicated.
         if found.origin_index == 0 {
             // Cool!
+            *self = blocks.swap_remove(0);
             return Ok(Some(found.item));
         }
 
@@ -238,7 +238,7 @@ pub fn get_filesystem() -> FlusterFS {
     let temp_dir = get_new_temp_dir();
     let floppy_drive: PathBuf = PathBuf::new(); // This is never read since we are using temporary disks.
     let fs_options = FilesystemOptions::new(Some(temp_dir.path().to_path_buf()), floppy_drive, Some(false), false);
-    FlusterFS::start(&fs_options)
+    FlusterFS::mkfs(&fs_options, 1)
     // We don't actually have to mount it for non-integration testing.
 }
 
@@ -90,6 +90,9 @@ impl DirectoryBlock {
         // inode block.
         let read: RawBlock = CachedBlockIO::read_block(self_item.location.pointer)?;
         let mut inode_block: InodeBlock = InodeBlock::from_block(&read);
+        let inode: Inode = inode_block
+            .try_read_inode(self_item.location.offset)
+            .expect("Directory items should point at real inodes.");
 
         if let Err(error) = inode_block.try_remove_inode(self_item.location.offset) {
             // Not good. Something was wrong with the inode pointer.
@@ -101,6 +104,7 @@ impl DirectoryBlock {
 
         // Write back the updated inode block
         CachedBlockIO::update_block(&inode_block.to_block())?;
+        Pool::item_removed(&inode);
 
         // Now we can free the block that the directory occupied.
         let _ = Pool::free_pool_block_from_disk(&[self.block_origin])?;
@@ -110,6 +114,31 @@ impl DirectoryBlock {
         drop(self_item); // Space Cowboy
         Ok(())
     }
+
+    /// Delete everything inside of this directory, leaving the directory itself.
+    ///
+    /// Goes all the way down, so be sure.
+    ///
+    /// May swap disks.
+    pub(crate) fn delete_contents(&mut self) -> Result<(), DriveError> {
+        go_delete_contents(self)
+    }
+}
+
+fn go_delete_contents(directory: &mut DirectoryBlock) -> Result<(), DriveError> {
+    for item in directory.list()? {
+        if !item.flags.contains(DirectoryItemFlags::IsDirectory) {
+            let _ = directory.delete_file(NamedItem::File(item.name))?;
+            continue;
+        }
+        let extracted: DirectoryItem = directory
+            .find_and_extract_item(&NamedItem::Directory(item.name))?
+            .expect("We just listed it.");
+        let mut inner: DirectoryBlock = extracted.get_directory_block()?;
+        inner.delete_contents()?;
+        inner.delete_self(extracted)?;
+    }
+    Ok(())
 }
 
 fn go_make_directory(
@@ -168,6 +197,7 @@ fn go_make_directory(
         name_length: name.len() as u8,
         name,
         location: inode_result,
+        placement: None,
     };
 
     // Put it into the caller directory!
@@ -184,6 +214,8 @@ fn go_make_directory(
 ///
 /// Returns where the new block is.
 ///
+/// Follows the current placement, if there is one.
+///
 /// May swap disks, does not return to original disk.
 fn go_make_new_directory_block() -> Result<DiskPointer, DriveError> {
     // Ask the pool for a new block
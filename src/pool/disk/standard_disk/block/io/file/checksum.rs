@@ -0,0 +1,215 @@
+// Trust, but verify.
+// Whole-file checksums, kept up to date a few blocks at a time.
+
+// The checksum of a file is the sum of a hash of every block in it, where the hash of a block
+// full of zeros is zero. That way a change only needs to look at the blocks it touches, and
+// holes, preallocated blocks, and growing the file with truncate never change the checksum.
+
+use std::ops::Range;
+
+use crate::{
+    error_types::drive::DriveError,
+    filesystem::filesystem_struct::ENABLE_CHECKSUMS,
+    pool::disk::standard_disk::block::{
+        directory::directory_struct::DirectoryItem,
+        file_extents::file_extents_methods::DATA_BLOCK_OVERHEAD,
+        inode::inode_struct::InodeFile
+    }
+};
+
+// How much data a block can hold
+const BLOCK_CAPACITY: u64 = 512 - DATA_BLOCK_OVERHEAD;
+
+/// How many blocks to look at in one go, so huge ranges don't need huge buffers.
+const BLOCKS_PER_CHUNK: u64 = 128;
+
+/// Extended attribute that holds the checksum of a file.
+///
+/// Reading it checks the whole file against the checksum first.
+pub(crate) const CHECKSUM_XATTR: &str = "user.fluster.checksum";
+
+/// A checksum being worked out a piece at a time, as a file is read from front to back.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RunningChecksum {
+    /// Where the next read has to start to keep going.
+    next_offset: u64,
+    /// Every block we've finished so far, added up.
+    sum: u64,
+    /// The start of the block we're in the middle of.
+    partial: Vec<u8>,
+}
+
+impl RunningChecksum {
+    /// Add the next bytes of the file.
+    ///
+    /// Returns false and ignores the bytes if they don't start where the last ones left off.
+    pub(crate) fn feed(&mut self, offset: u64, bytes: &[u8]) -> bool {
+        if offset != self.next_offset {
+            return false;
+        }
+        for byte in bytes {
+            self.partial.push(*byte);
+            if self.partial.len() == BLOCK_CAPACITY as usize {
+                let index: u64 = self.next_offset / BLOCK_CAPACITY;
+                self.sum = self.sum.wrapping_add(block_term(index, &self.partial));
+                self.partial.clear();
+            }
+            self.next_offset += 1;
+        }
+        true
+    }
+
+    /// How much of the file has been fed in.
+    pub(crate) fn next_offset(&self) -> u64 {
+        self.next_offset
+    }
+
+    /// The checksum of everything fed in, as if the file ended here.
+    pub(crate) fn finish(&self) -> u64 {
+        let index: u64 = self.next_offset / BLOCK_CAPACITY;
+        self.sum.wrapping_add(block_term(index, &self.partial))
+    }
+}
+
+impl DirectoryItem {
+    /// The checksum stored for this file, if it keeps one.
+    ///
+    /// Panics if fed a directory.
+    pub fn get_checksum(&self) -> Result<Option<u64>, DriveError> {
+        let file: InodeFile = self.get_inode()?.extract_file().expect("Can only checksum files.");
+        Ok(file.get_checksum())
+    }
+
+    /// Read the whole file, and check it against the stored checksum.
+    ///
+    /// Returns None if the file does not keep a checksum.
+    ///
+    /// Panics if fed a directory.
+    pub fn verify_checksum(&self) -> Result<Option<bool>, DriveError> {
+        let file: InodeFile = self.get_inode()?.extract_file().expect("Can only checksum files.");
+        let Some(expected) = file.get_checksum() else {
+            return Ok(None);
+        };
+        let blocks: u64 = file.get_size().div_ceil(BLOCK_CAPACITY);
+        let actual: u64 = sum_blocks(&file, 0..blocks, file.get_size(), |_, _| {})?.1;
+        Ok(Some(actual == expected))
+    }
+}
+
+/// Should new files keep a checksum?
+pub(crate) fn checksums_enabled() -> bool {
+    *ENABLE_CHECKSUMS.get().unwrap_or(&false)
+}
+
+/// Work out what a file's checksum will be after changing the bytes in `start..end`, before
+/// the change actually happens.
+///
+/// `change` is handed each chunk of the touched blocks (along with the offset it starts at),
+/// holding what is there right now, and must turn it into what will be there afterwards.
+/// Anything past `new_size` is treated as zeros no matter what.
+///
+/// Returns None if the file does not keep a checksum.
+pub(super) fn checksum_after(
+    file: &InodeFile,
+    start: u64,
+    end: u64,
+    new_size: u64,
+    change: impl Fn(u64, &mut [u8]),
+) -> Result<Option<u64>, DriveError> {
+    let Some(checksum) = file.get_checksum() else {
+        return Ok(None);
+    };
+    if new_size == 0 {
+        // Nothing left, nothing to add up.
+        return Ok(Some(0));
+    }
+    if start >= end {
+        return Ok(Some(checksum));
+    }
+    let blocks: Range<u64> = start / BLOCK_CAPACITY..end.div_ceil(BLOCK_CAPACITY);
+    let (before, after) = sum_blocks(file, blocks, new_size, change)?;
+    Ok(Some(checksum.wrapping_sub(before).wrapping_add(after)))
+}
+
+/// Add up a range of blocks of a file, both as they are and after being changed.
+fn sum_blocks(
+    file: &InodeFile,
+    blocks: Range<u64>,
+    new_size: u64,
+    change: impl Fn(u64, &mut [u8]),
+) -> Result<(u64, u64), DriveError> {
+    let size: u64 = file.get_size();
+    let mut before: u64 = 0;
+    let mut after: u64 = 0;
+    let mut index: u64 = blocks.start;
+    while index < blocks.end {
+        let chunk_end: u64 = (index + BLOCKS_PER_CHUNK).min(blocks.end);
+        let chunk_start: u64 = index * BLOCK_CAPACITY;
+        let mut contents: Vec<u8> = vec![0; ((chunk_end - index) * BLOCK_CAPACITY) as usize];
+
+        // Past the end of the file is already zeros.
+        let read_end: u64 = (chunk_end * BLOCK_CAPACITY).min(size);
+        if chunk_start < read_end {
+            let read: Vec<u8> = file.read(chunk_start, (read_end - chunk_start) as u32)?;
+            contents[..read.len()].copy_from_slice(&read);
+        }
+        before = before.wrapping_add(sum_terms(index, &contents));
+
+        change(chunk_start, &mut contents);
+        if new_size < chunk_start + contents.len() as u64 {
+            let cut: usize = new_size.saturating_sub(chunk_start) as usize;
+            contents[cut..].fill(0);
+        }
+        after = after.wrapping_add(sum_terms(index, &contents));
+
+        index = chunk_end;
+    }
+    Ok((before, after))
+}
+
+/// Add up the terms of a run of whole blocks, starting at block `first`.
+fn sum_terms(first: u64, contents: &[u8]) -> u64 {
+    contents
+        .chunks(BLOCK_CAPACITY as usize)
+        .zip(first..)
+        .fold(0, |sum, (block, index)| sum.wrapping_add(block_term(index, block)))
+}
+
+/// What one block adds to the checksum.
+///
+/// Blocks shorter than a full block are padded with zeros.
+fn block_term(index: u64, contents: &[u8]) -> u64 {
+    if contents.iter().all(|byte| *byte == 0) {
+        return 0;
+    }
+    // FNV-1a, 64 bit, over the block number then the block.
+    // The block number means the same data in a different place adds something different.
+    let padding = std::iter::repeat_n(&0u8, BLOCK_CAPACITY as usize - contents.len());
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in index.to_le_bytes().iter().chain(contents).chain(padding) {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Copy the part of a write that lands in a chunk into it.
+pub(super) fn overlay(bytes: &[u8], seek_point: u64, chunk_start: u64, chunk: &mut [u8]) {
+    let start: u64 = seek_point.max(chunk_start);
+    let end: u64 = (seek_point + bytes.len() as u64).min(chunk_start + chunk.len() as u64);
+    if start >= end {
+        return;
+    }
+    chunk[(start - chunk_start) as usize..(end - chunk_start) as usize]
+        .copy_from_slice(&bytes[(start - seek_point) as usize..(end - seek_point) as usize]);
+}
+
+/// Zero the part of `start..end` that lands in a chunk.
+pub(super) fn blank(start: u64, end: u64, chunk_start: u64, chunk: &mut [u8]) {
+    let start: u64 = start.max(chunk_start);
+    let end: u64 = end.min(chunk_start + chunk.len() as u64);
+    if start >= end {
+        return;
+    }
+    chunk[(start - chunk_start) as usize..(end - chunk_start) as usize].fill(0);
+}
@@ -1,5 +1,9 @@
 pub mod write;
 pub mod read;
 pub mod movement;
+pub mod sparse;
+pub mod reflink;
+pub mod readahead;
+pub mod checksum;
 #[cfg(test)]
 mod tests;
\ No newline at end of file
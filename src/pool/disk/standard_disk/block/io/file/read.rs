@@ -34,7 +34,7 @@ use crate::{error_types::drive::DriveError, pool::disk::{
 impl InodeFile {
     // Local functions
     /// Extract all of the extents and spit out a list of all of the blocks.
-    pub(super) fn as_pointers(&self) -> Result<Vec<DiskPointer>, DriveError> {
+    pub(crate) fn as_pointers(&self) -> Result<Vec<DiskPointer>, DriveError> {
         go_to_pointers(self)
     }
     /// Extract all of the extents.
@@ -47,7 +47,7 @@ impl InodeFile {
         go_get_root_block(self)
     }
     /// Read a file
-    fn read(&self, seek_point: u64, size: u32) -> Result<Vec<u8>, DriveError> {
+    pub(super) fn read(&self, seek_point: u64, size: u32) -> Result<Vec<u8>, DriveError> {
         go_read_file(self, seek_point, size)
     }
 }
@@ -107,13 +107,9 @@ fn go_to_pointers(location: &InodeFile) -> Result<Vec<DiskPointer>, DriveError>
 
     // For each extent
     for e in extents {
-        // each block that the extent references
-        for n in 0..e.length {
-            blocks.push(DiskPointer {
-                disk: e.start_block.disk,
-                block: e.start_block.block + n as u16
-            });
-        }
+        // each block that the extent references.
+        // Holes show up as pointers with no destination.
+        blocks.extend(e.get_pointers());
     }
 
     Ok(blocks)
@@ -237,6 +233,8 @@ fn go_read_file(file: &InodeFile, seek_point: u64, size: u32) -> Result<Vec<u8>,
 /// 
 /// Places read bytes into the provided buffer.
 /// 
+/// Holes are not read, the buffer must already be zeroed.
+/// 
 /// Returns number of bytes read.
 fn read_bytes_from_block(buffer: &mut [u8], buffer_offset: usize, block: DiskPointer, internal_block_offset: u16, bytes_to_read: u32) -> Result<u16, DriveError> {
 
@@ -257,6 +255,11 @@ fn read_bytes_from_block(buffer: &mut [u8], buffer_offset: usize, block: DiskPoi
     // Why panic? It won't if you fix the caller! :D
     assert_ne!(bytes_to_read, 0, "Tried to read 0 bytes from a block!");
 
+    // Holes are all zeros, and the buffer already is, so there's nothing to load.
+    if block.no_destination() {
+        return Ok(bytes_to_read as u16);
+    }
+
 
     // load the block
     let block_copy: RawBlock = CachedBlockIO::read_block(block)?;
@@ -0,0 +1,72 @@
+// Guessing what you'll read next, and reading it before you ask.
+// Floppies are slow to swap and slow to seek, so one big read beats a pile of small ones.
+
+use log::debug;
+
+use crate::{error_types::drive::DriveError, pool::disk::{
+    drive_struct::FloppyDrive,
+    generic::{
+        generic_structs::pointer_struct::DiskPointer,
+        io::cache::cache_io::CachedBlockIO
+    },
+    standard_disk::block::{
+        directory::directory_struct::{
+            DirectoryItem,
+            DirectoryItemFlags
+        },
+        file_extents::file_extents_methods::DATA_BLOCK_OVERHEAD,
+        inode::inode_struct::InodeFile
+    }
+}};
+
+// How much data a block can hold
+const BLOCK_CAPACITY: u64 = 512 - DATA_BLOCK_OVERHEAD;
+
+impl DirectoryItem {
+    /// Pull the rest of this file into the cache, starting at `seek_point`.
+    ///
+    /// Only blocks on the disk that is currently in the drive are read, so this never swaps disks.
+    /// Blocks are preferred in file order, so the rest of the current extent goes in first.
+    ///
+    /// Returns the byte offset up to which the file can now be read without going to the disk.
+    ///
+    /// Panics if fed a directory.
+    pub fn prefetch(&self, seek_point: u64) -> Result<u64, DriveError> {
+        go_prefetch(self, seek_point)
+    }
+}
+
+fn go_prefetch(item: &DirectoryItem, seek_point: u64) -> Result<u64, DriveError> {
+    // We only handle files here
+    if item.flags.contains(DirectoryItemFlags::IsDirectory) {
+        panic!("Cannot prefetch a directory!");
+    }
+
+    let file: InodeFile = item.get_inode()?.extract_file().expect("File flag means a file inode should exist.");
+    let blocks: Vec<DiskPointer> = file.as_pointers()?;
+    let (start, _) = InodeFile::byte_finder(seek_point);
+
+    let Some(rest) = blocks.get(start..) else {
+        // Past the end, nothing to grab.
+        return Ok(seek_point);
+    };
+
+    // Everything we could get without a swap.
+    let disk: u16 = FloppyDrive::currently_inserted_disk_number();
+    let wanted: Vec<u16> = rest.iter()
+        .filter(|pointer| !pointer.no_destination() && pointer.disk == disk)
+        .map(|pointer| pointer.block)
+        .collect();
+
+    if !wanted.is_empty() {
+        let added = CachedBlockIO::prefetch_blocks(disk, &wanted)?;
+        debug!("Readahead cached {added} blocks.");
+    }
+
+    // How far can we go now before we miss?
+    let ready = rest.iter()
+        .take_while(|pointer| pointer.no_destination() || CachedBlockIO::is_block_cached(**pointer))
+        .count();
+
+    Ok((start + ready) as u64 * BLOCK_CAPACITY)
+}
@@ -0,0 +1,126 @@
+// Two names, one set of blocks.
+// Backs snapshots, which need a copy of every file without actually copying anything.
+
+use log::debug;
+
+use crate::{error_types::drive::DriveError, pool::{
+    dedup::dedup_struct::DedupIndex,
+    disk::{
+        generic::{
+            block::block_structs::RawBlock,
+            generic_structs::pointer_struct::DiskPointer,
+            io::cache::cache_io::CachedBlockIO
+        },
+        standard_disk::block::{
+            directory::directory_struct::{
+                DirectoryBlock,
+                DirectoryItem,
+                DirectoryItemFlags
+            },
+            file_extents::file_extents_struct::FileExtentBlock,
+            inode::inode_struct::{
+                Inode,
+                InodeFile
+            }
+        }
+    },
+    pool_actions::pool_struct::Pool
+}};
+
+use super::write::{
+    release_blocks,
+    rewrite_file_extents
+};
+
+impl DirectoryBlock {
+    /// Add a copy of a file to this directory, that shares every data block with the original.
+    ///
+    /// The copy gets its own inode and extent blocks, so the two can grow and shrink separately.
+    /// Writing to a shared block copies it first, so neither file can see changes to the other.
+    ///
+    /// Keeps the name and timestamps of the original.
+    ///
+    /// If this fails, everything the copy took is given back, and the original is left alone.
+    ///
+    /// Panics if fed a directory.
+    pub(crate) fn reflink_file(&mut self, source: &DirectoryItem) -> Result<DirectoryItem, DriveError> {
+        go_reflink_file(self, source)
+    }
+}
+
+fn go_reflink_file(directory: &mut DirectoryBlock, source: &DirectoryItem) -> Result<DirectoryItem, DriveError> {
+    if source.flags.contains(DirectoryItemFlags::IsDirectory) {
+        panic!("Cannot reflink a directory!");
+    }
+    debug!("Reflinking `{}`...", source.name);
+
+    let source_inode: Inode = source.get_inode()?;
+    let source_file: InodeFile = source_inode.extract_file().expect("File is a file, but not a file. Nice.");
+    let pointers: Vec<DiskPointer> = source_file.as_pointers()?;
+
+    // Everything the original points at now has one more user.
+    let shared: Vec<DiskPointer> = pointers.iter().copied().filter(|block| !block.no_destination()).collect();
+    // Unless the dedup index can't keep track of that many more shared blocks.
+    if !DedupIndex::room_for_shares(shared.len()) {
+        return Err(DriveError::NoSpace);
+    }
+
+    // Fresh extent block for the copy to fill up.
+    // No crc needed, we're writing over it right away.
+    let extent_pointer: DiskPointer = Pool::find_and_allocate_pool_blocks(1, false)?[0];
+    let raw: RawBlock = FileExtentBlock::new(extent_pointer).to_block();
+    if let Err(error) = CachedBlockIO::update_block(&raw) {
+        let _ = release_blocks(vec![extent_pointer])?;
+        return Err(error);
+    }
+    DedupIndex::share(&shared);
+
+    let mut file: InodeFile = InodeFile::new(extent_pointer);
+    if let Err(error) = rewrite_file_extents(file, &pointers) {
+        discard_extents(extent_pointer, shared)?;
+        return Err(error);
+    }
+    file.set_size(source_file.get_size());
+    // Same blocks, same checksum.
+    file.set_checksum(source_file.get_checksum());
+
+    let inode: Inode = Inode {
+        file: Some(file),
+        ..source_inode
+    };
+    let location = match Pool::fast_add_inode(inode) {
+        Ok(ok) => ok,
+        Err(error) => {
+            discard_extents(extent_pointer, shared)?;
+            return Err(error);
+        },
+    };
+
+    let copy: DirectoryItem = DirectoryItem {
+        location,
+        ..source.clone()
+    };
+    if let Err(error) = directory.add_item(&copy) {
+        // The copy is all there, it just has nowhere to live.
+        copy.delete_unlinked()?;
+        return Err(error);
+    }
+    debug!("Done, {} blocks shared.", shared.len());
+    Ok(copy)
+}
+
+/// Give back the extent blocks of a copy that never got an inode, along with its references to the
+/// blocks it was sharing.
+fn discard_extents(extent_pointer: DiskPointer, shared: Vec<DiskPointer>) -> Result<(), DriveError> {
+    let mut chain: Vec<DiskPointer> = Vec::new();
+    let mut next: DiskPointer = extent_pointer;
+    while !next.no_destination() {
+        chain.push(next);
+        let read: RawBlock = CachedBlockIO::read_block(next)?;
+        next = FileExtentBlock::from_block(&read).next_block;
+    }
+    // Cant hit zero, the original still has them.
+    let _ = release_blocks(shared)?;
+    let _ = release_blocks(chain)?;
+    Ok(())
+}
@@ -0,0 +1,249 @@
+// Holes, and the things that fill them.
+// Backs fallocate, so files can be preallocated or have chunks punched out of them.
+
+use log::debug;
+
+use crate::{error_types::drive::DriveError, pool::{
+    dedup::dedup_struct::DedupIndex,
+    disk::{
+        generic::{
+            block::{
+                block_structs::RawBlock,
+                crc::add_crc_to_block
+            },
+            generic_structs::pointer_struct::DiskPointer,
+            io::cache::cache_io::CachedBlockIO
+        },
+        standard_disk::block::{
+            directory::directory_struct::{
+                DirectoryItem,
+                DirectoryItemFlags
+            },
+            file_extents::file_extents_methods::DATA_BLOCK_OVERHEAD,
+            inode::inode_struct::{
+                Inode,
+                InodeBlock,
+                InodeFile,
+                InodeTimestamp
+            }
+        }
+    },
+    pool_actions::pool_struct::Pool
+}};
+
+use super::checksum::{
+    blank,
+    checksum_after
+};
+use super::write::{
+    release_blocks,
+    rewrite_file_extents
+};
+
+// How much data a block can hold
+const BLOCK_CAPACITY: u64 = 512 - DATA_BLOCK_OVERHEAD;
+
+impl DirectoryItem {
+    /// Make sure every block in a byte range of this file has a real block behind it.
+    ///
+    /// Holes in the range are filled with zeroed blocks, and the file is extended with zeroed blocks
+    /// if the range goes past the end of it.
+    ///
+    /// If `keep_size` is set, the size of the file will not change, even if blocks were added
+    /// past the end of the file.
+    ///
+    /// Panics if fed a directory.
+    pub fn preallocate(&self, offset: u64, length: u64, keep_size: bool) -> Result<(), DriveError> {
+        go_preallocate(self, offset, length, keep_size)
+    }
+
+    /// Turn a byte range of this file into a hole, freeing every block that was entirely inside of it.
+    ///
+    /// Blocks that are only partially covered have that part zeroed instead.
+    ///
+    /// Never changes the size of the file.
+    ///
+    /// Panics if fed a directory.
+    pub fn punch_hole(&self, offset: u64, length: u64) -> Result<(), DriveError> {
+        go_punch_hole(self, offset, length)
+    }
+}
+
+fn go_preallocate(item: &DirectoryItem, offset: u64, length: u64, keep_size: bool) -> Result<(), DriveError> {
+    let (mut inode_block, mut inode, mut file) = load_file(item)?;
+    let mut blocks: Vec<DiskPointer> = file.as_pointers()?;
+
+    // Blocks that the range touches.
+    let end = offset.saturating_add(length);
+    let first_block = (offset / BLOCK_CAPACITY) as usize;
+    let end_block = end.div_ceil(BLOCK_CAPACITY) as usize;
+
+    // Anything past the end starts out as a hole, then gets filled like every other hole.
+    if end_block > blocks.len() {
+        blocks.resize(end_block, DiskPointer::new_final_pointer());
+    }
+
+    let holes: Vec<usize> = (first_block..end_block)
+        .filter(|index| blocks[*index].no_destination())
+        .collect();
+
+    if !holes.is_empty() {
+        debug!("Preallocating {} blocks...", holes.len());
+        let mut fresh: Vec<DiskPointer> = Vec::with_capacity(holes.len());
+        // Allocation is capped to u16 blocks at a time.
+        for chunk in holes.chunks(u16::MAX.into()) {
+            // Need the crc, these will be read before they are written.
+            let allocated = match Pool::find_and_allocate_pool_blocks(chunk.len() as u16, true) {
+                Ok(ok) => ok,
+                Err(error) => {
+                    // Nothing points at the earlier chunks yet.
+                    let _ = release_blocks(fresh)?;
+                    return Err(error);
+                },
+            };
+            for (index, pointer) in chunk.iter().zip(allocated) {
+                blocks[*index] = pointer;
+                fresh.push(pointer);
+            }
+        }
+        if let Err(error) = rewrite_file_extents(file, &blocks) {
+            if error == DriveError::NoSpace {
+                // The extents weren't touched, so nothing points at these yet.
+                let _ = release_blocks(fresh)?;
+            }
+            return Err(error);
+        }
+    }
+
+    // Grow the file if we need to.
+    if keep_size || end <= file.get_size() {
+        // Size stays the same.
+        return Ok(());
+    }
+
+    file.set_size(end);
+    inode.file = Some(file);
+    inode.modified = InodeTimestamp::now();
+    inode_block.update_inode(item.location.offset, inode)?;
+    Ok(())
+}
+
+fn go_punch_hole(item: &DirectoryItem, offset: u64, length: u64) -> Result<(), DriveError> {
+    let (mut inode_block, mut inode, mut file) = load_file(item)?;
+    let size = file.get_size();
+    let end = offset.saturating_add(length);
+
+    // Only the part of the range inside the file has data we need to zero out.
+    let data_end = end.min(size);
+
+    // Blocks entirely inside of the range.
+    let first_full = offset.div_ceil(BLOCK_CAPACITY) as usize;
+    let mut end_full = (end / BLOCK_CAPACITY) as usize;
+    if end >= size {
+        // Everything after the end of the file is zeros anyways, so the final block
+        // (and anything preallocated after it) can go as well.
+        end_full = usize::MAX;
+    }
+
+    // Everything in the range reads as zeros afterwards, however it gets there.
+    let new_checksum = checksum_after(&file, offset, data_end, size, |start, chunk| {
+        blank(offset, data_end, start, chunk);
+    })?;
+
+    // Zero out the partially covered blocks on either end.
+    let head_end = (first_full as u64 * BLOCK_CAPACITY).min(data_end);
+    if offset < head_end {
+        zero_range(&mut file, offset, head_end)?;
+    }
+    if end < size {
+        let tail_start = (end_full as u64 * BLOCK_CAPACITY).max(head_end);
+        if tail_start < end {
+            zero_range(&mut file, tail_start, end)?;
+        }
+    }
+
+    // Zeroing may have moved blocks around, so get the pointers after that.
+    let mut blocks: Vec<DiskPointer> = file.as_pointers()?;
+    let end_full = end_full.min(blocks.len());
+
+    let mut punched: Vec<DiskPointer> = Vec::new();
+    // Dense disks are owned as a whole, so their blocks get zeroed instead of freed.
+    // Shared ones can't be zeroed in place though, so those get punched like normal.
+    let mut zeroed: Vec<DiskPointer> = Vec::new();
+    if first_full < end_full {
+        for block in &mut blocks[first_full..end_full] {
+            if !block.no_destination() && Pool::is_dense_disk(block.disk) && DedupIndex::reference_count(*block) <= 1 {
+                zeroed.push(*block);
+            } else if !block.no_destination() {
+                punched.push(*block);
+                *block = DiskPointer::new_final_pointer();
+            }
+        }
+    }
+
+    if !punched.is_empty() {
+        debug!("Punching {} blocks out of a file...", punched.len());
+        // Stop pointing at the blocks before we free them.
+        rewrite_file_extents(file, &blocks)?;
+        let freed = release_blocks(punched)?;
+        debug!("Freed {freed} blocks.");
+    }
+
+    if !zeroed.is_empty() {
+        debug!("Zeroing {} blocks on dense disks...", zeroed.len());
+        let mut empty: [u8; 512] = [0u8; 512];
+        add_crc_to_block(&mut empty);
+        for block in zeroed {
+            CachedBlockIO::update_block(&RawBlock {
+                block_origin: block,
+                data: empty,
+            })?;
+        }
+    }
+
+    if new_checksum.is_some() {
+        file.set_checksum(new_checksum);
+    }
+    inode.file = Some(file);
+    inode.modified = InodeTimestamp::now();
+    inode_block.update_inode(item.location.offset, inode)?;
+    Ok(())
+}
+
+/// Writes zeros from `start` up to `end`, skipping over holes since they are already zero.
+fn zero_range(file: &mut InodeFile, start: u64, end: u64) -> Result<(), DriveError> {
+    let blocks: Vec<DiskPointer> = file.as_pointers()?;
+    let (index, _) = InodeFile::byte_finder(start);
+    if blocks.get(index).is_none_or(|block| block.no_destination()) {
+        // Nothing to zero.
+        return Ok(());
+    }
+    let zeros: Vec<u8> = vec![0; (end - start) as usize];
+    let _ = file.write(&zeros, start)?;
+    Ok(())
+}
+
+/// Pull the inode and file out of a directory item, along with the block the inode lives in.
+fn load_file(item: &DirectoryItem) -> Result<(InodeBlock, Inode, InodeFile), DriveError> {
+    // We only handle files here
+    if item.flags.contains(DirectoryItemFlags::IsDirectory) {
+        panic!("Cannot allocate space in a directory!");
+    }
+
+    let read: RawBlock = CachedBlockIO::read_block(item.location.pointer)?;
+    let inode_block: InodeBlock = InodeBlock::from_block(&read);
+
+    let inode: Inode = if let Ok(inode) = inode_block.try_read_inode(item.location.offset) {
+        inode
+    } else {
+        panic!("No inode exists for this DirectoryItem's file.");
+    };
+
+    let file: InodeFile = if let Some(the_file) = inode.extract_file() {
+        the_file
+    } else {
+        panic!("File is a file, but not a file. Nice.");
+    };
+
+    Ok((inode_block, inode, file))
+}
@@ -1,10 +1,19 @@
 // Files, direct to thee.
 // Unwrapping is okay here, since we want unexpected outcomes to fail tests.
 #![allow(clippy::unwrap_used)]
+use std::{ffi::OsStr, path::Path};
+
+use fuse_mt::{FilesystemMT, RequestInfo, Xattr};
 use rand::{rngs::ThreadRng, Rng, RngCore};
 use test_log::test;
 
-use crate::pool::{disk::{standard_disk::block::{directory::directory_struct::DirectoryItem, io::directory::{tests::get_filesystem, types::NamedItem}}}, pool_actions::pool_struct::Pool}; // We want to see logs while testing.
+use crate::error_types::{drive::DriveError, filesystem::{GENERIC_FAILURE, NO_SPACE, NO_SUCH_ATTRIBUTE}};
+
+use crate::filesystem::filesystem_struct::{FlusterFS, AUTO_GROW, ENABLE_CHECKSUMS, ENABLE_DEDUP, MAX_DISKS};
+use crate::pool::disk::generic::block::{block_structs::RawBlock, crc::add_crc_to_block};
+use crate::pool::disk::dense_disk::dense_disk_struct::DENSE_DISK_BLOCKS;
+use crate::pool::disk::generic::io::cache::cache_io::CachedBlockIO;
+use crate::pool::{disk::{standard_disk::block::{directory::directory_struct::DirectoryItem, io::{directory::{tests::get_filesystem, types::NamedItem}, file::checksum::RunningChecksum}}}, pool_actions::pool_struct::Pool}; // We want to see logs while testing.
 
 /// Can we make a new file?
 #[test]
@@ -255,4 +264,393 @@ fn check_byte_vec_equality(a: &[u8], b: &[u8]) {
         assert_eq!(byte_a, byte_b, "Byte mismatch at index `{index}`! a: `{byte_a}`, b: `{byte_b}`");
         // We cant use enumerate here (i think?) so manual index tracking.
     }
-}
\ No newline at end of file
+}
+/// Identical files should share their data blocks, and modifying or deleting one
+/// should not affect the other.
+#[test]
+fn dedup_identical_files() {
+    let fs = get_filesystem();
+    let _ = ENABLE_DEDUP.set(true);
+    let mut root_block = Pool::get_root_directory().unwrap();
+    let first = root_block.new_file("first.bin".to_string()).unwrap();
+    let second = root_block.new_file("second.bin".to_string()).unwrap();
+
+    // A few full blocks worth of data.
+    let mut random: ThreadRng = rand::rng();
+    let mut bytes: Vec<u8> = vec![0; 507 * 8];
+    random.fill_bytes(&mut bytes);
+
+    let _ = first.write_file(&bytes, 0).unwrap();
+    let _ = second.write_file(&bytes, 0).unwrap();
+
+    // Both files should point at the same blocks.
+    let first_blocks = first.get_inode().unwrap().extract_file().unwrap().as_pointers().unwrap();
+    let second_blocks = second.get_inode().unwrap().extract_file().unwrap().as_pointers().unwrap();
+    assert_eq!(first_blocks, second_blocks);
+
+    // Change the second file, the first one must not see it.
+    let _ = second.write_file(&[1, 2, 3, 4], 10).unwrap();
+    check_byte_vec_equality(&first.read_file(0, bytes.len() as u32).unwrap(), &bytes);
+    let second_blocks = second.get_inode().unwrap().extract_file().unwrap().as_pointers().unwrap();
+    assert_ne!(first_blocks[0], second_blocks[0]);
+    assert_eq!(first_blocks[1..], second_blocks[1..]);
+
+    // Deleting the first file cannot free the blocks the second one still uses.
+    let mut root_block = Pool::get_root_directory().unwrap();
+    root_block.delete_file(NamedItem::File("first.bin".to_string())).unwrap().unwrap();
+    let mut expected = bytes.clone();
+    expected[10..14].copy_from_slice(&[1, 2, 3, 4]);
+    check_byte_vec_equality(&second.read_file(0, bytes.len() as u32).unwrap(), &expected);
+
+    // The index should make it onto the pool disk.
+    Pool::flush().unwrap();
+    assert!(fs.pool.lock().expect("testing").header.dedup_index_blocks > 0);
+}
+
+/// Writing past the end, and growing with truncate, should leave holes instead of zero blocks.
+#[test]
+fn sparse_write_and_grow() {
+    let _fs = get_filesystem();
+    let mut root_block = Pool::get_root_directory().unwrap();
+    let sparse = root_block.new_file("sparse.bin".to_string()).unwrap();
+
+    // Way past the end of the file.
+    let seek_point: u64 = 1024 * 1024;
+    let _ = sparse.write_file(&[1, 2, 3, 4], seek_point).unwrap();
+    assert_eq!(sparse.get_size().unwrap(), seek_point + 4);
+
+    // Only the block we wrote to should be real.
+    let blocks = sparse.get_inode().unwrap().extract_file().unwrap().as_pointers().unwrap();
+    assert_eq!(blocks.iter().filter(|block| !block.no_destination()).count(), 1);
+
+    // Holes read as zeros.
+    let read = sparse.read_file(seek_point - 1000, 1004).unwrap();
+    assert!(read[..1000].iter().all(|byte| *byte == 0));
+    assert_eq!(read[1000..], [1, 2, 3, 4]);
+
+    // Growing should not allocate anything either.
+    sparse.truncate(seek_point * 2).unwrap();
+    assert_eq!(sparse.get_size().unwrap(), seek_point * 2);
+    let blocks = sparse.get_inode().unwrap().extract_file().unwrap().as_pointers().unwrap();
+    assert_eq!(blocks.iter().filter(|block| !block.no_destination()).count(), 1);
+    assert!(sparse.read_file(seek_point * 2 - 600, 600).unwrap().iter().all(|byte| *byte == 0));
+
+    // Shrinking into a hole works too.
+    sparse.truncate(seek_point / 2).unwrap();
+    assert_eq!(sparse.get_size().unwrap(), seek_point / 2);
+}
+
+/// Punch some holes, then fill them back in.
+#[test]
+fn punch_and_preallocate() {
+    let _fs = get_filesystem();
+    let mut root_block = Pool::get_root_directory().unwrap();
+    let file = root_block.new_file("holey.bin".to_string()).unwrap();
+
+    let mut random: ThreadRng = rand::rng();
+    let mut bytes: Vec<u8> = vec![0; 507 * 20];
+    random.fill_bytes(&mut bytes);
+    let _ = file.write_file(&bytes, 0).unwrap();
+
+    // Not aligned to blocks on either end.
+    let (start, length) = (1000_u64, 507 * 8);
+    file.punch_hole(start, length).unwrap();
+    let mut expected = bytes.clone();
+    expected[start as usize..(start + length) as usize].fill(0);
+
+    assert_eq!(file.get_size().unwrap(), bytes.len() as u64);
+    check_byte_vec_equality(&file.read_file(0, bytes.len() as u32).unwrap(), &expected);
+
+    // Blocks entirely inside of the range are gone.
+    let blocks = file.get_inode().unwrap().extract_file().unwrap().as_pointers().unwrap();
+    assert_eq!(blocks.len(), 20);
+    assert!(blocks[2..9].iter().all(|block| block.no_destination()));
+    assert!(!blocks[1].no_destination() && !blocks[9].no_destination());
+
+    // Fill it back in, past the end while we're at it.
+    file.preallocate(0, 507 * 25, true).unwrap();
+    let blocks = file.get_inode().unwrap().extract_file().unwrap().as_pointers().unwrap();
+    assert_eq!(blocks.len(), 25);
+    assert!(blocks.iter().all(|block| !block.no_destination()));
+    assert_eq!(file.get_size().unwrap(), bytes.len() as u64);
+    check_byte_vec_equality(&file.read_file(0, bytes.len() as u32).unwrap(), &expected);
+
+    // Without keeping the size, the file grows.
+    file.preallocate(507 * 25, 10, false).unwrap();
+    assert_eq!(file.get_size().unwrap(), 507 * 25 + 10);
+}
+
+/// Preallocating in a pool that runs out of room partway leaves the file, and the pool, alone.
+#[test]
+fn full_pools_refuse_preallocation() {
+    let fs = get_filesystem();
+    let _ = AUTO_GROW.set(false);
+    let free = || fs.pool.lock().expect("testing").header.pool_standard_blocks_free;
+    let mut root_block = Pool::get_root_directory().unwrap();
+
+    // Every other block is a hole, so filling them in makes the extents bigger.
+    let file = root_block.new_file("checkered.bin".to_string()).unwrap();
+    let _ = file.write_file(&vec![3; 507 * 300], 0).unwrap();
+    for block in (0..300_u64).step_by(2) {
+        file.punch_hole(block * 507, 507).unwrap();
+    }
+
+    // Leave exactly enough room for the data blocks, but not for the extent block they need.
+    let spare = root_block.new_file("spare.bin".to_string()).unwrap();
+    let _ = spare.write_file(&vec![2; 507 * 300], 0).unwrap();
+    let filler = root_block.new_file("filler.bin".to_string()).unwrap();
+    let mut filled: u64 = 0;
+    for chunk in [vec![1; 507 * 200], vec![1; 507]] {
+        while filler.write_file(&chunk, filled).is_ok() {
+            filled += chunk.len() as u64;
+        }
+    }
+    root_block.delete_file(NamedItem::File("spare.bin".to_string())).unwrap().unwrap();
+    while free() > 150 {
+        let _ = filler.write_file(&[1; 507], filled).unwrap();
+        filled += 507;
+    }
+    assert_eq!(free(), 150);
+
+    assert_eq!(file.preallocate(0, 507 * 300, true).err(), Some(DriveError::NoSpace));
+    assert_eq!(free(), 150);
+    let blocks = file.get_inode().unwrap().extract_file().unwrap().as_pointers().unwrap();
+    assert_eq!(blocks.iter().filter(|block| block.no_destination()).count(), 150);
+}
+
+/// Readahead should pull the rest of a file into the cache in one go.
+#[test]
+fn prefetch_rest_of_file() {
+    let _fs = get_filesystem();
+    let mut root_block = Pool::get_root_directory().unwrap();
+    let file = root_block.new_file("stream.bin".to_string()).unwrap();
+
+    let mut random: ThreadRng = rand::rng();
+    let mut bytes: Vec<u8> = vec![0; 507 * 40];
+    random.fill_bytes(&mut bytes);
+    let _ = file.write_file(&bytes, 0).unwrap();
+
+    // Get the data out of the cache, so the next read would have to go to disk.
+    CachedBlockIO::flush().unwrap();
+    let blocks = file.get_inode().unwrap().extract_file().unwrap().as_pointers().unwrap();
+    for block in &blocks {
+        CachedBlockIO::remove_block(block);
+    }
+    assert!(blocks.iter().all(|block| !CachedBlockIO::is_block_cached(*block)));
+
+    // Start a bit in, the start of the file shouldn't come along.
+    let until = file.prefetch(507 * 10).unwrap();
+    assert_eq!(until, 507 * 40);
+    assert!(blocks[..10].iter().all(|block| !CachedBlockIO::is_block_cached(*block)));
+    assert!(blocks[10..].iter().all(|block| CachedBlockIO::is_block_cached(*block)));
+
+    // And the data should still be right.
+    check_byte_vec_equality(&file.read_file(0, bytes.len() as u32).unwrap(), &bytes);
+}
+
+/// Files bigger than a floppy should get whole dense disks, and give them back when they shrink.
+#[test]
+fn big_files_use_dense_disks() {
+    let fs = get_filesystem();
+    let dense_count = || fs.pool.lock().expect("testing").header.dense_disks.iter().filter(|disk| **disk != 0).count();
+    let mut root_block = Pool::get_root_directory().unwrap();
+    let new_file = root_block.new_file("big.bin".to_string()).unwrap();
+
+    // Two floppies worth, and a bit.
+    let mut random: ThreadRng = rand::rng();
+    let mut bytes: Vec<u8> = vec![0; DENSE_DISK_BLOCKS as usize * 507 * 2 + 1000];
+    random.fill_bytes(&mut bytes);
+    let _ = new_file.write_file(&bytes, 0).unwrap();
+    assert_eq!(dense_count(), 2);
+    check_byte_vec_equality(&new_file.read_file(0, bytes.len() as u32).unwrap(), &bytes);
+
+    // Shrinking past the dense disks hands them back as standard disks.
+    new_file.truncate(1000).unwrap();
+    assert_eq!(dense_count(), 0);
+    check_byte_vec_equality(&new_file.read_file(0, 1000).unwrap(), &bytes[..1000]);
+
+    // And those disks can be used for normal blocks again.
+    let _ = new_file.write_file(&bytes[..507 * 4000], 0).unwrap();
+    assert_eq!(dense_count(), 1);
+    let mut root_block = Pool::get_root_directory().unwrap();
+    root_block.delete_file(NamedItem::File("big.bin".to_string())).unwrap().unwrap();
+    assert_eq!(dense_count(), 0);
+}
+
+/// Checksums should follow every kind of change, and notice when a block goes bad.
+#[test]
+fn checksums_follow_changes() {
+    let _fs = get_filesystem();
+    let _ = ENABLE_CHECKSUMS.set(true);
+    let mut root_block = Pool::get_root_directory().unwrap();
+    let file = root_block.new_file("summed.bin".to_string()).unwrap();
+    assert_eq!(file.get_checksum().unwrap(), Some(0));
+
+    // What the checksum of some bytes should be, worked out from scratch.
+    let from_scratch = |bytes: &[u8]| {
+        let mut running = RunningChecksum::default();
+        assert!(running.feed(0, bytes));
+        running.finish()
+    };
+    let check = |expected: &[u8]| {
+        assert_eq!(file.get_checksum().unwrap(), Some(from_scratch(expected)));
+        assert_eq!(file.verify_checksum().unwrap(), Some(true));
+    };
+
+    let mut random: ThreadRng = rand::rng();
+    let mut bytes: Vec<u8> = vec![0; 507 * 12 + 100];
+    random.fill_bytes(&mut bytes);
+    let _ = file.write_file(&bytes, 0).unwrap();
+    check(&bytes);
+
+    // Overwrite some of the middle.
+    let mut patch: Vec<u8> = vec![0; 1300];
+    random.fill_bytes(&mut patch);
+    let _ = file.write_file(&patch, 800).unwrap();
+    bytes[800..2100].copy_from_slice(&patch);
+    check(&bytes);
+
+    // Write way past the end, leaving a hole.
+    let _ = file.write_file(&patch, 507 * 30).unwrap();
+    bytes.resize(507 * 30, 0);
+    bytes.extend_from_slice(&patch);
+    check(&bytes);
+
+    // Punch out the middle.
+    file.punch_hole(1000, 507 * 5).unwrap();
+    bytes[1000..1000 + 507 * 5].fill(0);
+    check(&bytes);
+
+    // Growing adds nothing but zeros.
+    file.truncate(507 * 40).unwrap();
+    bytes.resize(507 * 40, 0);
+    check(&bytes);
+
+    // Shrink into the middle of a block.
+    file.truncate(3000).unwrap();
+    bytes.truncate(3000);
+    check(&bytes);
+
+    // Change a byte behind the checksum's back.
+    let block = file.get_inode().unwrap().extract_file().unwrap().as_pointers().unwrap()[0];
+    let mut raw: RawBlock = CachedBlockIO::read_block(block).unwrap();
+    raw.data[100] ^= 0xFF;
+    add_crc_to_block(&mut raw.data);
+    CachedBlockIO::update_block(&raw).unwrap();
+    assert_eq!(file.verify_checksum().unwrap(), Some(false));
+
+    // Emptying the file starts over.
+    file.truncate(0).unwrap();
+    assert_eq!(file.get_checksum().unwrap(), Some(0));
+    assert_eq!(file.verify_checksum().unwrap(), Some(true));
+}
+
+/// Reading a whole file, or its checksum attribute, should catch corruption.
+#[test]
+fn checksums_checked_on_read() {
+    let fs = get_filesystem();
+    let _ = ENABLE_CHECKSUMS.set(true);
+    let request = RequestInfo { unique: 0, uid: 0, gid: 0, pid: 0 };
+    let path = Path::new("/checked.bin");
+    let _ = fs.create(request, Path::new("/"), OsStr::new("checked.bin"), 0, 0).unwrap();
+    let (handle, _) = fs.open(request, path, libc::O_RDWR as u32).unwrap();
+
+    let mut random: ThreadRng = rand::rng();
+    let mut bytes: Vec<u8> = vec![0; 507 * 6];
+    random.fill_bytes(&mut bytes);
+    let _ = fs.write(request, path, handle, 0, bytes.clone(), 0).unwrap();
+
+    // Fine while nothing is wrong, in two pieces.
+    assert_eq!(fs.read_bytes(path, handle, 0, 1000).unwrap(), bytes[..1000]);
+    assert_eq!(fs.read_bytes(path, handle, 1000, 5000).unwrap(), bytes[1000..]);
+    let Xattr::Data(hex) = fs.getxattr(request, path, OsStr::new("user.fluster.checksum"), 100).unwrap() else {
+        panic!("Asked for data.");
+    };
+    assert_eq!(hex.len(), 16);
+    assert_eq!(fs.getxattr(request, Path::new("/"), OsStr::new("user.fluster.checksum"), 100).err(), Some(NO_SUCH_ATTRIBUTE));
+
+    // Scribble on the last block.
+    let item = Pool::get_root_directory().unwrap().find_item(&NamedItem::File("checked.bin".to_string())).unwrap().unwrap();
+    let block = *item.get_inode().unwrap().extract_file().unwrap().as_pointers().unwrap().last().unwrap();
+    let mut raw: RawBlock = CachedBlockIO::read_block(block).unwrap();
+    raw.data[50] ^= 0xFF;
+    add_crc_to_block(&mut raw.data);
+    CachedBlockIO::update_block(&raw).unwrap();
+
+    // Jumping around can't tell.
+    assert!(fs.read_bytes(path, handle, 2000, 5000).is_ok());
+    // Going from start to end can.
+    assert!(fs.read_bytes(path, handle, 0, 1000).is_ok());
+    assert_eq!(fs.read_bytes(path, handle, 1000, 5000).err(), Some(GENERIC_FAILURE));
+    assert_eq!(fs.getxattr(request, path, OsStr::new("user.fluster.checksum"), 100).err(), Some(GENERIC_FAILURE));
+
+    // Even when the last piece had to be looked up twice.
+    assert!(fs.read_bytes(path, handle, 0, 1000).is_ok());
+    let _ = fs.fetch_bytes(path, handle, 1000, 5000).unwrap();
+    let mut read = fs.fetch_bytes(path, handle, 1000, 5000).unwrap();
+    FlusterFS::note_finished_read(handle, 1000, &mut read);
+    assert_eq!(fs.follow_up_read(path, handle, &read).err(), Some(GENERIC_FAILURE));
+}
+
+/// A pool that can't grow should say it's full, and leave the file alone.
+#[test]
+fn full_pools_refuse_writes() {
+    let fs = get_filesystem();
+    let _ = AUTO_GROW.set(false);
+    let request = RequestInfo { unique: 0, uid: 0, gid: 0, pid: 0 };
+    let path = Path::new("/filler.bin");
+    let handle = fs.create(request, Path::new("/"), OsStr::new("filler.bin"), 0, 0).unwrap().fh;
+
+    // Keep writing until there's no room left.
+    let mut random: ThreadRng = rand::rng();
+    let mut chunk: Vec<u8> = vec![0; 507 * 200];
+    let mut written: Vec<u8> = Vec::new();
+    let error = loop {
+        random.fill_bytes(&mut chunk);
+        match fs.write(request, path, handle, written.len() as u64, chunk.clone(), 0) {
+            Ok(_) => written.extend_from_slice(&chunk),
+            Err(error) => break error,
+        }
+    };
+    assert_eq!(error, NO_SPACE);
+    assert_eq!(fs.pool.lock().expect("testing").header.highest_known_disk, 1);
+
+    // Whatever made it in is still there, and nothing leaked.
+    let free = fs.pool.lock().expect("testing").header.pool_standard_blocks_free;
+    let (_, attributes) = fs.getattr(request, path, Some(handle)).unwrap();
+    assert_eq!(attributes.size, written.len() as u64);
+    check_byte_vec_equality(&fs.read_bytes(path, handle, 0, written.len() as u32).unwrap(), &written);
+    assert_eq!(fs.write(request, path, handle, written.len() as u64, chunk.clone(), 0).err(), Some(NO_SPACE));
+    assert_eq!(fs.pool.lock().expect("testing").header.pool_standard_blocks_free, free);
+
+    // Deleting things makes room again.
+    fs.release(request, path, handle, 0, 0, false).unwrap();
+    fs.unlink(request, Path::new("/"), OsStr::new("filler.bin")).unwrap();
+    let mut root_block = Pool::get_root_directory().unwrap();
+    let again = root_block.new_file("again.bin".to_string()).unwrap();
+    let _ = again.write_file(&chunk, 0).unwrap();
+}
+
+/// Capping the pool should stop it from claiming dense disks past the cap too.
+#[test]
+fn capped_pools_give_back_dense_disks() {
+    let fs = get_filesystem();
+    let _ = MAX_DISKS.set(2);
+    let dense_count = || fs.pool.lock().expect("testing").header.dense_disks.iter().filter(|disk| **disk != 0).count();
+    let mut root_block = Pool::get_root_directory().unwrap();
+    let new_file = root_block.new_file("big.bin".to_string()).unwrap();
+    let _ = new_file.write_file(b"small", 0).unwrap();
+
+    // Only room for one more disk, and this needs way more than that.
+    let bytes: Vec<u8> = vec![1; DENSE_DISK_BLOCKS as usize * 507 * 2 + 1000];
+    assert_eq!(new_file.write_file(&bytes, 0), Err(DriveError::NoSpace));
+    assert_eq!(fs.pool.lock().expect("testing").header.highest_known_disk, 2);
+    assert_eq!(dense_count(), 0);
+    assert_eq!(new_file.get_size().unwrap(), 5);
+    assert_eq!(new_file.read_file(0, 5).unwrap(), b"small");
+
+    // The disk it tried to claim is a standard disk now, so something smaller fits.
+    let _ = new_file.write_file(&bytes[..507 * 4000], 0).unwrap();
+    assert_eq!(new_file.get_size().unwrap(), 507 * 4000);
+    assert_eq!(fs.pool.lock().expect("testing").header.highest_known_disk, 2);
+}
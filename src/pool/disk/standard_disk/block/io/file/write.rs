@@ -3,19 +3,18 @@
 // We will take in InodeFile(s) instead of Extent related types, since we need info about how big files are so they are easier to extend.
 // Creating files is handles on the directory side, since new files just have a name and location.
 
-use std::{
-    cmp::max,
-    ops::{
-        Div,
-        Rem
-    }
-};
+use std::cmp::max;
 
 use log::{debug, warn};
 use log::error;
 
-use crate::{error_types::drive::DriveError, pool::{
+use crate::{error_types::drive::DriveError, filesystem::file_handle::file_handle_struct::FileHandle, pool::{
+    dedup::dedup_struct::DedupIndex,
     disk::{
+        dense_disk::dense_disk_struct::{
+            DenseDisk,
+            DENSE_DISK_BLOCKS
+        },
         generic::{
             block::{
                 block_structs::RawBlock,
@@ -45,9 +44,16 @@ use crate::{error_types::drive::DriveError, pool::{
                 io::directory::types::NamedItem
             }
     },
+    format::format_struct::Feature,
     pool_actions::pool_struct::Pool
 }, tui::{notify::NotifyTui, tasks::TaskType}};
 
+use super::checksum::{
+    checksum_after,
+    checksums_enabled,
+    overlay
+};
+
 impl InodeFile {
     /// Update the contents of a file starting at the provided seek point.
     /// Will automatically grow file if needed.
@@ -58,7 +64,7 @@ impl InodeFile {
     /// Optionally returns to a provided disk when done.
     /// 
     /// Returns number of bytes written, but also updates the incoming file's size automatically
-    fn write(&mut self, bytes: &[u8], seek_point: u64) -> Result<u32, DriveError> {
+    pub(super) fn write(&mut self, bytes: &[u8], seek_point: u64) -> Result<u32, DriveError> {
        go_write(self, bytes, seek_point)
     }
 }
@@ -82,6 +88,8 @@ impl DirectoryBlock {
     /// 
     /// If you are looking to truncate a file, you need to call truncate() on the actual directory item.
     /// 
+    /// If the file is still open, it's only taken out of the directory, and freed once it's closed.
+    /// 
     /// Returns `None` if the file did not exist.
     ///
     /// Panics if fed a directory. Use remove_directory() !
@@ -102,8 +110,10 @@ impl DirectoryBlock {
             return Ok(None)
         };
 
-        // Delete it.
-        truncate_or_delete_file(&extracted_item, true, None)?;
+        // Delete it, unless someone still has it open.
+        if let Some(unused) = FileHandle::defer_delete(extracted_item) {
+            truncate_or_delete_file(&unused, true, None)?;
+        }
 
         // Since the extraction function already handles pulling out the item from the directory blocks, we are done.
         Ok(Some(()))
@@ -166,9 +176,18 @@ impl DirectoryItem {
             panic!("File is a file, but not a file. Nice.");
         };
 
+        // Work out the new checksum while the old contents are still around to compare against.
+        let write_end: u64 = seek_point + bytes.len() as u64;
+        let new_checksum = checksum_after(&file, seek_point, write_end, max(file.get_size(), write_end), |start, chunk| {
+            overlay(bytes, seek_point, start, chunk);
+        })?;
+
         // Write to the file
         // This automatically updates the underlying file with the new size.
         let num_bytes_written = file.write(bytes, seek_point)?;
+        if new_checksum.is_some() {
+            file.set_checksum(new_checksum);
+        }
 
         // Now that the bytes are written, the size of the file may have changed, so we need to flush this new information to disk.
 
@@ -196,6 +215,15 @@ impl DirectoryItem {
     pub fn truncate(&self, new_size: u64) -> Result<(), DriveError> {
         truncate_or_delete_file(self, false, Some(new_size))
     }
+
+    /// Frees every block a file takes up, including its inode.
+    /// 
+    /// Only for files that have already been taken out of their directory.
+    /// 
+    /// Panics if fed a directory.
+    pub(crate) fn delete_unlinked(&self) -> Result<(), DriveError> {
+        truncate_or_delete_file(self, true, None)
+    }
 }
 
 fn go_write(inode_file: &mut InodeFile, bytes: &[u8], seek_point: u64) -> Result<u32, DriveError> {
@@ -210,10 +238,12 @@ fn go_write(inode_file: &mut InodeFile, bytes: &[u8], seek_point: u64) -> Result
     // The byte_finder already skips the flag, so it ends up adding one, we need to subtract that.
     byte_index -= 1;
 
-    // Make sure we actually have a block at that offset. We cannot start writing from unallocated space.
+    // Writing past the end of the file leaves a hole between the old end and the start of the write.
     if block_index > blocks.len() {
-        // Being told to write to a point we do not have.
-        panic!("Attempted to write to unallocated space!");
+        let gap = block_index - blocks.len();
+        debug!("Write starts {gap} blocks past the end of the file, adding a hole...");
+        append_holes(*inode_file, gap)?;
+        blocks.resize(block_index, DiskPointer::new_final_pointer());
     }
     
     // Now we can calculate where the final byte of this write will end up.
@@ -246,28 +276,58 @@ fn go_write(inode_file: &mut InodeFile, bytes: &[u8], seek_point: u64) -> Result
 
         // Add that many more blocks to this file.
         // Since we know its already less than u16::MAX this cast is fine.
-        let new_pointers = expand_file(*inode_file, needed_blocks as u16)?;
+        let new_pointers = expand_file(*inode_file, blocks.len(), needed_blocks as u16)?;
 
         // The new pointers are already in order for us, and we will add them onto the end of the
         // pointers we grabbed earlier from the file.
         blocks.extend(new_pointers.iter());
     }
 
+    // Any holes we are about to write into need real blocks behind them first.
+    let holes: Vec<usize> = (block_index..final_block_index.min(blocks.len()))
+        .filter(|index| blocks[*index].no_destination())
+        .collect();
+    if !holes.is_empty() {
+        debug!("Filling {} blocks of holes before writing...", holes.len());
+        // Already capped to u16 blocks above.
+        let fresh = Pool::find_and_allocate_pool_blocks(holes.len() as u16, true)?;
+        for (index, pointer) in holes.iter().zip(fresh.iter()) {
+            blocks[*index] = *pointer;
+        }
+        // Point at the new blocks before writing to them, so a failed write doesn't leak them.
+        if let Err(error) = rewrite_file_extents(*inode_file, &blocks) {
+            if error == DriveError::NoSpace {
+                // The extents weren't touched, so nothing points at these yet.
+                let _ = release_blocks(fresh)?;
+            }
+            return Err(error);
+        }
+    }
+
     // Now we know we have enough space for this write, let's get started.
 
     let mut bytes_written: usize = 0;
     // Since the write can start un-aligned, we need to use an offset until its aligned again.
     let mut byte_write_index: u16 = byte_index;
 
+    // Blocks that ended up somewhere else due to dedup. We need to fix up the extents after the
+    // writes, and only then can we let go of the old blocks.
+    let mut moved_blocks: Vec<DiskPointer> = Vec::new();
+
     // Now we will loop through the blocks starting at the current index
-    for block in &blocks[block_index..] {
+    for block in blocks.iter_mut().skip(block_index) {
         // are we out of bytes to write?
         if bytes_written == bytes.len() {
             // All done!
             break
         }
         // Update the block
-        let written = update_block(*block, &bytes[bytes_written..], byte_write_index)?;
+        let (written, landed) = update_block(*block, &bytes[bytes_written..], byte_write_index)?;
+        if landed != *block {
+            // Block moved.
+            moved_blocks.push(*block);
+            *block = landed;
+        }
         // After the first write, the offset should be fixed now, since we've either written all of our bytes, in
         // which case we would be done, or we ran out of room in the block, thus the next block's offset would be 0.
         byte_write_index = 0;
@@ -278,6 +338,14 @@ fn go_write(inode_file: &mut InodeFile, bytes: &[u8], seek_point: u64) -> Result
         continue;
     }
 
+    // Did anything move?
+    if !moved_blocks.is_empty() {
+        debug!("{} blocks moved due to dedup, rewriting extents...", moved_blocks.len());
+        rewrite_file_extents(*inode_file, &blocks)?;
+        // Now nothing points at the old blocks from this file anymore.
+        let _ = release_blocks(moved_blocks)?;
+    }
+
     // Done writing bytes!
     // Update the file size, only if we wrote past the end.
     let write_end = seek_point + bytes_written as u64;
@@ -297,8 +365,11 @@ fn go_write(inode_file: &mut InodeFile, bytes: &[u8], seek_point: u64) -> Result
 /// 
 /// Offset is the first data byte, not the first byte of the block!
 /// 
-/// Returns number of bytes written.
-fn update_block(block: DiskPointer, bytes: &[u8], offset: u16) -> Result<usize, DriveError> {
+/// Shared blocks are never updated in place, the new contents will be written somewhere else.
+/// If dedup is enabled, the block may also end up pointing at an identical existing block instead.
+/// 
+/// Returns number of bytes written, and where the block ended up.
+fn update_block(block: DiskPointer, bytes: &[u8], offset: u16) -> Result<(usize, DiskPointer), DriveError> {
 
     // How much data a block can hold
     let data_capacity = 512 - DATA_BLOCK_OVERHEAD as usize;
@@ -348,45 +419,284 @@ fn update_block(block: DiskPointer, bytes: &[u8], offset: u16) -> Result<usize,
     add_crc_to_block(&mut block_copy.data);
 
     // Write that sucker
-    CachedBlockIO::update_block(&block_copy)?;
+    let landed = write_data_block(block_copy)?;
 
     // Return the number of bytes we wrote.
-    Ok(bytes_to_write)
+    Ok((bytes_to_write, landed))
+}
+
+/// Writes a finished data block, taking dedup into account.
+/// 
+/// The block is written to where it came from, unless:
+/// - Dedup is on, and an identical block already exists. That block gets another reference instead.
+/// - The block is shared with something else, in which case it is copied to a new block.
+/// 
+/// Does not release the old block if the block moved, the caller must do that after
+/// the file no longer points at it.
+/// 
+/// Returns where the data ended up.
+fn write_data_block(block: RawBlock) -> Result<DiskPointer, DriveError> {
+    let origin = block.block_origin;
+
+    // Blocks on dense disks stay put, otherwise the file would stop owning the whole disk.
+    // Unless a snapshot has them too, then they get copied like anything else.
+    if Pool::is_dense_disk(origin.disk) && DedupIndex::reference_count(origin) <= 1 {
+        CachedBlockIO::update_block(&block)?;
+        return Ok(origin);
+    }
+
+    let hash = DedupIndex::hash_block(&block.data);
+
+    // Is there already a block with these contents?
+    // Not if the index is out of room for another shared block, then it just gets its own.
+    if DedupIndex::enabled() && DedupIndex::room_for_shares(1) && let Some(existing) = DedupIndex::find_duplicate(hash) && existing != origin {
+        // Hashes collide, make sure its actually the same.
+        let candidate: RawBlock = CachedBlockIO::read_block(existing)?;
+        if candidate.data == block.data {
+            // Free real estate.
+            debug!("Reusing identical block (disk {} block {}).", existing.disk, existing.block);
+            DedupIndex::add_reference(existing);
+            return Ok(existing);
+        }
+    }
+
+    // Is someone else using this block?
+    if DedupIndex::reference_count(origin) > 1 {
+        // Copy on write.
+        debug!("Block is shared, copying before writing...");
+        // No crc needed, we're writing over it right away.
+        let new_home: DiskPointer = Pool::find_and_allocate_pool_blocks(1, false)?[0];
+        let moved: RawBlock = RawBlock {
+            block_origin: new_home,
+            data: block.data,
+        };
+        CachedBlockIO::update_block(&moved)?;
+        if DedupIndex::enabled() {
+            DedupIndex::track(new_home, hash);
+        }
+        return Ok(new_home);
+    }
+
+    // Just us, write in place.
+    CachedBlockIO::update_block(&block)?;
+    if DedupIndex::enabled() {
+        // Contents changed, so does the hash.
+        DedupIndex::track(origin, hash);
+    } else {
+        // The old hash (if any) is stale now.
+        DedupIndex::forget(&[origin]);
+    }
+    Ok(origin)
+}
+
+/// Drops a reference to each of these blocks, freeing the ones that are no longer used by anything.
+/// 
+/// Holes are skipped, since there is nothing behind them.
+/// 
+/// Returns how many blocks were actually freed.
+pub(super) fn release_blocks(mut blocks: Vec<DiskPointer>) -> Result<usize, DriveError> {
+    blocks.retain(|block| !block.no_destination());
+    let mut to_free: Vec<DiskPointer> = DedupIndex::release(blocks);
+    to_free.sort_unstable_by_key(|block| (block.disk, block.block));
+    for chunk in to_free.chunk_by(|a, b| a.disk == b.disk) {
+        let _ = Pool::free_pool_block_from_disk(chunk)?;
+    }
+    Ok(to_free.len())
+}
+
+/// Makes sure nothing else is using the block at this index in the file, so it can be modified in place.
+/// 
+/// Copies the block and rewrites the extents if needed.
+fn unshare_block(file: InodeFile, index: usize) -> Result<(), DriveError> {
+    let mut pointers = file.as_pointers()?;
+    let shared = if let Some(block) = pointers.get(index) {
+        *block
+    } else {
+        // Not a block we have, nothing to do.
+        return Ok(());
+    };
+
+    if DedupIndex::reference_count(shared) <= 1 {
+        // Already ours.
+        return Ok(());
+    }
+
+    debug!("Unsharing block (disk {} block {})...", shared.disk, shared.block);
+    let contents: RawBlock = CachedBlockIO::read_block(shared)?;
+    let new_home: DiskPointer = Pool::find_and_allocate_pool_blocks(1, false)?[0];
+    CachedBlockIO::update_block(&RawBlock {
+        block_origin: new_home,
+        data: contents.data,
+    })?;
+
+    pointers[index] = new_home;
+    rewrite_file_extents(file, &pointers)?;
+
+    // Cant hit zero, it was shared.
+    let _ = release_blocks(vec![shared])?;
+    Ok(())
+}
+
+/// Replace every extent in a file with new ones made from the provided pointers.
+/// 
+/// Re-uses the FileExtentBlocks the file already has, adding or freeing them as needed.
+/// 
+/// Pointers with no destination become holes.
+/// 
+/// Any new FileExtentBlocks are reserved before anything is rewritten, so running out of room
+/// leaves the file as it was.
+pub(super) fn rewrite_file_extents(file: InodeFile, pointers: &[DiskPointer]) -> Result<(), DriveError> {
+    // Reversed so we can pop them off the back.
+    let mut remaining: Vec<FileExtent> = pointers_into_extents(pointers);
+    remaining.reverse();
+
+    let raw_read: RawBlock = CachedBlockIO::read_block(file.pointer)?;
+    let mut current: FileExtentBlock = FileExtentBlock::from_block(&raw_read);
+
+    // Work out how many blocks the new extents take up. Most rewrites fit in the first
+    // block, which always exists, so the chain only gets walked when it might run short.
+    let mut empty: FileExtentBlock = current.clone();
+    empty.force_replace_all_extents(Vec::new());
+    let needed: usize = 1 + extra_extent_blocks(&empty, &remaining);
+
+    // See if the chain already goes that far, no need to look any further than that.
+    let mut chain_length: usize = 1;
+    let mut next: DiskPointer = current.next_block;
+    while chain_length < needed && !next.no_destination() {
+        chain_length += 1;
+        let read: RawBlock = CachedBlockIO::read_block(next)?;
+        next = FileExtentBlock::from_block(&read).next_block;
+    }
+    let mut spares: Vec<DiskPointer> = reserve_extent_blocks(needed.saturating_sub(chain_length))?;
+
+    loop {
+        // Hold onto where the chain used to go.
+        let old_next: DiskPointer = current.next_block;
+
+        // Empty the block out. New extents can only go into the final block in a chain, so
+        // we pretend this is the end for now.
+        current.next_block = DiskPointer::new_final_pointer();
+        current.force_replace_all_extents(Vec::new());
+
+        // Fill it back up.
+        while let Some(last) = remaining.last() {
+            if current.add_extent(*last).is_err() {
+                // Full.
+                break
+            }
+            let _ = remaining.pop();
+        }
+
+        if remaining.is_empty() {
+            // This is the new end of the chain.
+            flush_to_disk(&current)?;
+            // Anything past here isn't needed anymore.
+            let mut leftover: Vec<DiskPointer> = Vec::new();
+            let mut next = old_next;
+            while !next.no_destination() {
+                leftover.push(next);
+                let read: RawBlock = CachedBlockIO::read_block(next)?;
+                next = FileExtentBlock::from_block(&read).next_block;
+            }
+            let _ = release_blocks(leftover)?;
+            break
+        }
+
+        // Still more to go, move to the next block, making one if needed.
+        if old_next.no_destination() {
+            expand_extent_block(&mut current, spares.pop().expect("Reserved enough blocks up front."))?;
+        } else {
+            current.next_block = old_next;
+        }
+        flush_to_disk(&current)?;
+        let read: RawBlock = CachedBlockIO::read_block(current.next_block)?;
+        current = FileExtentBlock::from_block(&read);
+    }
+
+    Ok(())
 }
 
 
 /// Expands a file by adding `x` new blocks to the extents. Returns disk pointers for the new extents.
 /// Updates underlying ExtentBlock(s) for this file.
 /// 
+/// Files bigger than a floppy get whole dense disks where possible, so this may return more
+/// blocks than asked for. The extras sit past the end of the file until later writes fill them.
+/// 
 /// May swap disks, does not return to any start disk.
-fn expand_file(inode_file: InodeFile, blocks: u16) -> Result<Vec<DiskPointer>, DriveError> {
+fn expand_file(inode_file: InodeFile, current_blocks: usize, blocks: u16) -> Result<Vec<DiskPointer>, DriveError> {
     debug!("Expanding a file by {blocks} blocks...");
+    let dense_disks = Pool::claim_dense_disks(dense_disks_wanted(current_blocks, blocks.into()))?;
+    let standard_blocks = (blocks as usize).saturating_sub(dense_disks.len() * DENSE_DISK_BLOCKS as usize);
+
+    let mut reserved_blocks: Vec<DiskPointer> = Vec::new();
+    for disk in dense_disks {
+        reserved_blocks.extend(DenseDisk::data_pointers(disk));
+    }
+
     // Go grabby some new blocks.
     // These will be already reserved for us.
     // We also need to write the CRC for later.
-    let reserved_blocks = Pool::find_and_allocate_pool_blocks(blocks, true)?;
+    if standard_blocks > 0 {
+        match Pool::find_and_allocate_pool_blocks(standard_blocks as u16, true) {
+            Ok(found) => reserved_blocks.extend(found),
+            Err(error) => {
+                // Don't hang onto dense disks for a write that isn't happening.
+                let _ = release_blocks(reserved_blocks)?;
+                return Err(error);
+            },
+        }
+    }
+    reserved_blocks.sort_unstable_by_key(|pointer| (pointer.disk, pointer.block));
 
     // Make some extents from that
     let new_extents = pointers_into_extents(&reserved_blocks);
 
     // Add the extents.
-    expanding_add_extents(inode_file, &new_extents)?;
+    if let Err(error) = expanding_add_extents(inode_file, &new_extents) {
+        if error == DriveError::NoSpace {
+            // The extents weren't touched, so the file never saw these.
+            let _ = release_blocks(reserved_blocks)?;
+        }
+        return Err(error);
+    }
 
     // Return the pointers to those new extents.
     Ok(reserved_blocks)
 }
 
-/// Expands an ExtentBlockBlock.
+/// How many dense disks a file should claim when growing by `growth` blocks.
+/// 
+/// Files that stay within a floppy never get one. Files that grow past a floppy get one
+/// for every full floppy they are growing by, and files that are already that big do all of
+/// their growing on dense disks, since they are probably going to keep growing.
+fn dense_disks_wanted(current_blocks: usize, growth: usize) -> usize {
+    let per_disk = DENSE_DISK_BLOCKS as usize;
+    if current_blocks + growth <= per_disk {
+        0
+    } else if current_blocks >= per_disk {
+        growth.div_ceil(per_disk)
+    } else {
+        growth / per_disk
+    }
+}
+
+/// Expands a file by adding a hole of `blocks` blocks to the end of the extents.
+/// 
+/// Nothing is allocated for the hole itself, but the extent chain may grow.
+fn append_holes(inode_file: InodeFile, blocks: usize) -> Result<(), DriveError> {
+    debug!("Adding a hole of {blocks} blocks to a file...");
+    let holes = pointers_into_extents(&vec![DiskPointer::new_final_pointer(); blocks]);
+    expanding_add_extents(inode_file, &holes)
+}
+
+/// Expands an ExtentBlockBlock into a block that was already reserved for it.
 /// Always extends by one block.
 /// 
 /// Will swap disks to the location of the new block. Will not return to the disk the caller started on.
 /// 
 /// Sets the new destination in incoming block.
-fn expand_extent_block(block: &mut FileExtentBlock) -> Result<(), DriveError> {
-    // Get a new block from the pool.
-    // No need for crc, we will immediately write over it.
-    let new_block_location = Pool::find_and_allocate_pool_blocks(1, false)?[0];
-
+fn expand_extent_block(block: &mut FileExtentBlock, new_block_location: DiskPointer) -> Result<(), DriveError> {
     // Put the a block there
     let new_block: RawBlock = FileExtentBlock::new(new_block_location).to_block();
 
@@ -400,12 +710,44 @@ fn expand_extent_block(block: &mut FileExtentBlock) -> Result<(), DriveError> {
     Ok(())
 }
 
+/// How many more FileExtentBlocks are needed to fit these extents after the ones already in `block`.
+fn extra_extent_blocks(block: &FileExtentBlock, extents: &[FileExtent]) -> usize {
+    let mut scratch: FileExtentBlock = block.clone();
+    let mut extra: usize = 0;
+    for extent in extents.iter().rev() {
+        if scratch.add_extent(*extent).is_ok() {
+            continue;
+        }
+        // Full, start on a fresh one. The location doesn't matter, it's never written.
+        extra += 1;
+        scratch = FileExtentBlock::new(scratch.block_origin);
+        let _ = scratch.add_extent(*extent);
+    }
+    extra
+}
+
+/// Reserve blocks for new FileExtentBlocks, returned in the order they should be used in.
+fn reserve_extent_blocks(count: usize) -> Result<Vec<DiskPointer>, DriveError> {
+    if count == 0 {
+        return Ok(Vec::new());
+    }
+    // No need for crc, we will immediately write over them.
+    // Extent chains are way shorter than u16::MAX blocks.
+    let mut reserved = Pool::find_and_allocate_pool_blocks(count as u16, false)?;
+    // Popped off the back.
+    reserved.reverse();
+    Ok(reserved)
+}
+
 /// Will automatically deduce runs of blocks from a slice of disk pointers, then add those extents to the block,
 /// expanding the block if needed.
 /// 
 /// We assume the incoming pointers are already sorted by disk, block. (1, 1), (1, 2), (2, 1) etc.
 /// 
 /// Does not check if blocks are already allocated, caller _MUST_ provide marked blocks.
+/// 
+/// Any new FileExtentBlocks are reserved before anything is added, so running out of room
+/// leaves the file as it was.
 fn expanding_add_extents(file: InodeFile, extents: &[FileExtent]) -> Result<(), DriveError> {
     // We will reverse the extents vec so we can pop them off the back
     // for easier adding, avoiding an index.
@@ -420,6 +762,13 @@ fn expanding_add_extents(file: InodeFile, extents: &[FileExtent]) -> Result<(),
     let raw_read: RawBlock = CachedBlockIO::read_block(file.pointer)?;
     current_extent_block = FileExtentBlock::from_block(&raw_read);
 
+    // Find the end of the chain, and reserve room past it up front.
+    while !current_extent_block.next_block.no_destination() {
+        let reader_mc_deeder: RawBlock = CachedBlockIO::read_block(current_extent_block.next_block)?;
+        current_extent_block = FileExtentBlock::from_block(&reader_mc_deeder);
+    }
+    let mut spares: Vec<DiskPointer> = reserve_extent_blocks(extra_extent_blocks(&current_extent_block, extents))?;
+
     loop {
         // Is this the final block?
         if !current_extent_block.next_block.no_destination() {
@@ -460,7 +809,7 @@ fn expanding_add_extents(file: InodeFile, extents: &[FileExtent]) -> Result<(),
 
         // We must've ran out of room.
         // Expand the block please.
-        expand_extent_block(&mut current_extent_block)?;
+        expand_extent_block(&mut current_extent_block, spares.pop().expect("Reserved enough blocks up front."))?;
         
         // Now we must write that extended block to disk.
         flush_to_disk(&current_extent_block)?;
@@ -478,6 +827,9 @@ fn expanding_add_extents(file: InodeFile, extents: &[FileExtent]) -> Result<(),
 
 /// Automatically groups incoming pointers into a new vec of file extents.
 /// assumes all of the incoming pointers are already sorted.
+/// 
+/// Runs of pointers with no destination are turned into holes, and runs that cover a whole
+/// dense disk become a single dense extent.
 fn pointers_into_extents(pointers: &[DiskPointer]) -> Vec<FileExtent> {
     // I feel like there is 100% a better way to do this, but i dont know it. so too bad!
 
@@ -486,21 +838,42 @@ fn pointers_into_extents(pointers: &[DiskPointer]) -> Vec<FileExtent> {
     let mut new_extents: Vec<FileExtent> = Vec::new();
     
     // Loop over the pointers and create extents.
-    for pointer in pointers {
+    let mut index: usize = 0;
+    while let Some(pointer) = pointers.get(index) {
+        index += 1;
+        // Whole dense disks only take one extent.
+        if pointer.block == 1 && !pointer.no_destination() && Pool::is_dense_disk(pointer.disk) {
+            let whole_disk = DenseDisk::data_pointers(pointer.disk);
+            if pointers[index - 1..].starts_with(&whole_disk) {
+                new_extents.push(FileExtent::new_dense(pointer.disk));
+                index += whole_disk.len() - 1;
+                continue;
+            }
+        }
         // Check if we need to make a new extent.
         // We start at 0 since we increment at the end of the loop.
-        let new: FileExtent = FileExtent::new(*pointer, 0);
+        let hole: bool = pointer.no_destination();
+        let new: FileExtent = if hole {
+            FileExtent::new_hole(0)
+        } else {
+            FileExtent::new(*pointer, 0)
+        };
         // We need a new one if:
         // - There are no extents
-        // - The disk number is different
+        // - We are switching between a hole and real blocks
         // - The length is maxed out
+        // - The disk number is different
         // - The next block is not contiguous. (ie last block was 1, new block != 2)
 
         // yes this is ugly, at least it doesnt have to check for local disks anymore
         if let Some(extent) = new_extents.last() {
-            if extent.start_block.disk != pointer.disk || // Is the disk number different?
+            if extent.is_hole() != hole || // Hole-ness changed?
+            extent.is_dense() || // Nothing can be added onto a dense disk.
             extent.length == u8::MAX || // Is this extent out of room?
-            extent.start_block.block + extent.length as u16 != pointer.block // Non contiguous?
+            (!hole && (
+                extent.start_block.disk != pointer.disk || // Is the disk number different?
+                extent.start_block.block + extent.length as u16 != pointer.block // Non contiguous?
+            ))
             {
                 // Need a new one.
                 new_extents.push(new);
@@ -523,6 +896,11 @@ fn pointers_into_extents(pointers: &[DiskPointer]) -> Vec<FileExtent> {
         }
     }
 
+    // Older versions don't know what a hole is.
+    if new_extents.iter().any(FileExtent::is_hole) {
+        Pool::use_feature(Feature::Holes);
+    }
+
     // All done!
     new_extents
 }
@@ -556,7 +934,12 @@ fn go_make_new_file(directory_block: &mut DirectoryBlock, name: String) -> Resul
     CachedBlockIO::update_block(&raw)?;
 
     // Construct the file that we'll be returning.
-    let finished_new_file: InodeFile = InodeFile::new(reserved_block);
+    let mut finished_new_file: InodeFile = InodeFile::new(reserved_block);
+    if checksums_enabled() {
+        // Empty files add up to nothing.
+        finished_new_file.set_checksum(Some(0));
+        Pool::use_feature(Feature::Checksums);
+    }
 
     // Now that the block has been written, put that sucker into the directory
     
@@ -589,6 +972,7 @@ fn go_make_new_file(directory_block: &mut DirectoryBlock, name: String) -> Resul
         name_length: name.len() as u8,
         name,
         location: new_inode_location,
+        placement: None,
     };
 
     directory_block.add_item(&new_file)?;
@@ -645,44 +1029,24 @@ fn truncate_or_delete_file(item: &DirectoryItem, delete: bool, new_size: Option<
         panic!("Flag for file set, but no file.");
     };
 
-    // If the truncation is just growing the file, we can do this directly on the file itself without messing with the extents.
-
     // Truncation can also grow files, check if the truncation is larger than the current size.
     // Growing cannot happen at the same time as deletion.
     if let Some(extracted_new_size) = new_size && file_size < extracted_new_size && !delete {
         // We are just growing.
-        // Growing is easy, we just write zeros to make it the new size.
-
-        // The difference
-        let grow_size: usize = (extracted_new_size - file_size) as usize;
-        
-        // We need to do this in a loop, since would be consuming as much ram as the write is big, which isn't great.
-        // So we will do it in 1MB chunks, but this may change in the future if its too slow.
-        const CHUNK_SIZE: usize = 1024*1024;
-        let zero_chunk: Vec<u8> = vec![0; CHUNK_SIZE];
-
-        // Write to the end of the file with the zeros.
-        let mut seek_point = file_size;
-        for _ in 0..grow_size.div(CHUNK_SIZE) {
-            // Yeah... Keep eating...
-            let _ = item.write_file(&zero_chunk, seek_point)?;
-            seek_point += CHUNK_SIZE as u64;
+        // Growing is easy, the new space is all zeros, so we just tack a hole onto the end.
+        // The tail of the current final block is already zeroed, so it doesn't need touching.
+        let have_blocks: usize = file.as_pointers()?.len();
+        let need_blocks: usize = extracted_new_size.div_ceil(512 - DATA_BLOCK_OVERHEAD) as usize;
+        if need_blocks > have_blocks {
+            append_holes(file, need_blocks - have_blocks)?;
         }
 
-        // Final write if there are any remaining bytes.
-        let remainder = grow_size.rem(CHUNK_SIZE);
-        if remainder != 0 {
-            let final_zeros: Vec<u8> = vec![0; remainder];
-            let _ = item.write_file(&final_zeros, seek_point)?;
-        }
-        
-        // Make sure the new size is correct
-        let gotten_size = item.get_size()?;
-        if extracted_new_size != gotten_size {
-            // Truncation did not work properly.
-            error!("Truncated to the wrong size! Expected {extracted_new_size} got {gotten_size} !")
-        };
-        
+        // Now just update the size.
+        file.set_size(extracted_new_size);
+        inode_with_file.file = Some(file);
+        inode_with_file.modified = InodeTimestamp::now();
+        inode_block.update_inode(file_inode_location.offset, inode_with_file)?;
+
         // All done.
         return Ok(());
     }
@@ -710,6 +1074,12 @@ fn truncate_or_delete_file(item: &DirectoryItem, delete: bool, new_size: Option<
         // We dont have to worry about updating the underlying block, since the deletion call
         // will discard the item automagically.
 
+        // Holes don't have anything to free.
+        used_blocks.retain(|block| !block.no_destination());
+
+        // Only blocks nothing else is using can be freed.
+        let mut used_blocks = DedupIndex::release(used_blocks);
+
         // Sort the blocks to reduce swap
         used_blocks.sort_unstable_by_key(|block| (block.disk, block.block));
 
@@ -721,6 +1091,15 @@ fn truncate_or_delete_file(item: &DirectoryItem, delete: bool, new_size: Option<
             let _ = Pool::free_pool_block_from_disk(chunk)?;
         }
 
+        // Then the inode. Freeing could've touched the inode block, so get a fresh copy.
+        let mut inode_block: InodeBlock = InodeBlock::from_block(&CachedBlockIO::read_block(the_pointer_in_question)?);
+        if let Err(error) = inode_block.try_remove_inode(file_inode_location.offset) {
+            // Same as directories, nothing can be trusted if the inode isn't where it should be.
+            panic!("Tried to remove an invalid inode. Unrecoverable. {error:#?}")
+        }
+        CachedBlockIO::update_block(&inode_block.to_block())?;
+        Pool::item_removed(&inode_with_file);
+
         // All done!
         return Ok(());
     }
@@ -729,6 +1108,13 @@ fn truncate_or_delete_file(item: &DirectoryItem, delete: bool, new_size: Option<
     // This should be guarded.
     let new_size = new_size.expect("Cannot truncate a file without a size to truncate to.");
 
+    // Everything past the new end is going away, so work out what the checksum will be without it.
+    let new_checksum = checksum_after(&file, new_size, file_size, new_size, |_, _| {})?;
+
+    // The new final block gets its tail zeroed in place, which would clobber any other files sharing it.
+    let (final_index, _) = InodeFile::byte_finder(new_size);
+    unshare_block(file, final_index)?;
+
 
     // To truncate, several things need to happen:
     // - We need to update the data that is contained within the final data block to write in zeros
@@ -865,16 +1251,22 @@ fn truncate_or_delete_file(item: &DirectoryItem, delete: bool, new_size: Option<
     // Find the index into the block where everything past it will be blanked out...
     // jk, we already know that hehe, its in new_final_block_byte_index
 
-    // Now load in the old block so we can update it
-    let mut updated_final_data_block: RawBlock = CachedBlockIO::read_block(pointer_to_new_final_data_block)?;
+    // If the new final block is a hole, its already all zeros, nothing to update.
+    let updated_final_data_block: Option<RawBlock> = if pointer_to_new_final_data_block.no_destination() {
+        None
+    } else {
+        // Now load in the old block so we can update it
+        let mut block: RawBlock = CachedBlockIO::read_block(pointer_to_new_final_data_block)?;
 
-    // Now blank it out.
-    // Currently, the last 4 bytes of the block are the checksum. but since we're going to be updating the block anyways, we can write
-    // over it, since we'll have to re-checksum it anyways.
-    updated_final_data_block.data[new_final_block_byte_index as usize..].fill(0_u8);
+        // Now blank it out.
+        // Currently, the last 4 bytes of the block are the checksum. but since we're going to be updating the block anyways, we can write
+        // over it, since we'll have to re-checksum it anyways.
+        block.data[new_final_block_byte_index as usize..].fill(0_u8);
 
-    // Put the checksum back on
-    add_crc_to_block(&mut updated_final_data_block.data);
+        // Put the checksum back on
+        add_crc_to_block(&mut block.data);
+        Some(block)
+    };
 
     // Dont write the block yet, we'll hold onto it until _after_ we do the FileExtentBlock update.
 
@@ -912,7 +1304,8 @@ fn truncate_or_delete_file(item: &DirectoryItem, delete: bool, new_size: Option<
 
             // Splitting will panic if this is past the end of the array. Which would be the case
             // if we found exactly as many blocks as we needed in the final extent.
-            if split_point > pointers.len() {
+            // Dense disks can't be split up, the blocks past the end just stay with the file.
+            if split_point > pointers.len() || extent.is_dense() {
                 // We dont need to update this extent.
                 continue;
             }
@@ -981,10 +1374,16 @@ fn truncate_or_delete_file(item: &DirectoryItem, delete: bool, new_size: Option<
     // resulting in the next read containing old data from pre-truncation.
 
     let extent_block_result = CachedBlockIO::update_block(&finished_extent_block);
-    let data_block_result = CachedBlockIO::update_block(&updated_final_data_block);
+    let data_block_result = match &updated_final_data_block {
+        Some(block) => CachedBlockIO::update_block(block),
+        None => Ok(()),
+    };
     
     // Update the file
     file.set_size(new_size);
+    if new_checksum.is_some() {
+        file.set_checksum(new_checksum);
+    }
     inode_with_file.file = Some(file);
     // might as well set the time here too
     inode_with_file.modified = InodeTimestamp::now();
@@ -1146,6 +1545,12 @@ fn truncate_cleanup(pre_collected: Vec<DiskPointer>, next_extent_block: DiskPoin
     // == Free all of the blocks we've collected ==
 
 
+    // Holes don't have anything to free.
+    to_free.retain(|block| !block.no_destination());
+
+    // Shared blocks only lose a reference, they stick around until nothing uses them.
+    let mut to_free = DedupIndex::release(to_free);
+
     // Sort the blocks to reduce the amount of head seeking. This also groups together the disks.
     to_free.sort_unstable_by_key(|block| (block.disk, block.block));
 
@@ -37,7 +37,7 @@ use crate::{
 // The pool MUST exist for inodes to be created.
 macro_rules! get_pool {
     () => {
-        if let Ok(innards) = GLOBAL_POOL.get().expect("There has to be a global pool at this point.").try_lock() {
+        if let Ok(innards) = GLOBAL_POOL.get().expect("There has to be a global pool at this point.").lock() {
             innards
         } else {
             // Cannot do inode stuff with dying pool, dying pools need to just shut down immediately.
@@ -173,6 +173,9 @@ fn go_add_inode(inode: Inode, start_block: InodeBlock) -> Result<InodeLocation,
     // We are updating, because how would we be writing back to a block that was not allocated when we read it?
     CachedBlockIO::update_block(&block_to_write)?;
 
+    // Every inode is a file or directory, so there's one more of those.
+    Pool::item_added(&inode);
+
     // All done! Now we can return where that inode eventually ended up
     let pointer: DiskPointer = DiskPointer {
         disk: current_disk,
@@ -197,7 +200,8 @@ fn get_next_block(current_block: InodeBlock) -> Result<DiskPointer, DriveError>
     let new_block_location: DiskPointer = make_new_inode_block()?;
 
     // Now we just need to update the block we were called on.
-    let mut the_cooler_inode = current_block;
+    // Allocating can purge the trash, which removes inodes, so get a fresh copy first.
+    let mut the_cooler_inode = InodeBlock::from_block(&CachedBlockIO::read_block(current_block.block_origin)?);
     the_cooler_inode.new_destination(new_block_location);
     let please_let_me_hit = the_cooler_inode.to_block();
 
@@ -209,6 +213,8 @@ fn get_next_block(current_block: InodeBlock) -> Result<DiskPointer, DriveError>
 }
 
 /// We need a new inode block, we will reach upwards and get a new block made for us.
+///
+/// Inode blocks are shared by the whole pool, but new ones still land wherever the current placement asks for.
 fn make_new_inode_block() -> Result<DiskPointer, DriveError> {
     // Ask the pool for a new block pwease
     // No need for crc since we'll be overwriting it immediately.
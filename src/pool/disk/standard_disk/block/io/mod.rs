@@ -1,3 +1,3 @@
 pub(crate) mod directory;
-mod file;
+pub(crate) mod file;
 mod inode;
@@ -5,6 +5,7 @@ use log::debug;
 
 use crate::{
     error_types::drive::DriveError,
+    filesystem::filesystem_struct::POOL_NAME,
     pool::{
         disk::{
             drive_struct::{
@@ -49,6 +50,7 @@ use crate::{
                 standard_disk_struct::StandardDisk,
             },
         },
+        format::format_struct::FORMAT_VERSION,
         pool_actions::pool_struct::{
             Pool,
             GLOBAL_POOL
@@ -67,17 +69,16 @@ impl DiskBootstrap for StandardDisk {
         // Update how many blocks are free in the pool
         // New standard disks have only the header allocated.
         // 2880 - 1 = 2879
-        // But, the disk setup process will automatically decrement this count for us.
         {
             let arc = GLOBAL_POOL.get().expect("Shouldn't be allocating disks without a pool");
-            let mut pool = if let Ok(innards) = arc.try_lock() {
+            let mut pool = if let Ok(innards) = arc.lock() {
                 innards
             } else {
                 // Poisoned! We can't make new disks while poisoned.
                 panic!("Attempted to bootstrap a standard disk while pool is poisoned!");
             };
 
-            pool.header.pool_standard_blocks_free += 2880;
+            pool.header.pool_standard_blocks_free += 2879;
         }
 
         // Make the disk
@@ -100,6 +101,11 @@ impl DiskBootstrap for StandardDisk {
         let _ = disk.allocate_blocks(&vec![1,2])?;
         // Ignoring resulting value, since it will always be 2.
         // Which means we also need to update the pool block count again.
+        {
+            let arc = GLOBAL_POOL.get().expect("Shouldn't be allocating disks without a pool");
+            let mut pool = arc.lock().expect("Other mutex holders should not panic.");
+            pool.header.pool_standard_blocks_free -= 2;
+        }
 
 
         // Write the inode block
@@ -210,6 +216,9 @@ impl StandardDiskHeader {
         Self {
             flags: StandardHeaderFlags::from_bits_retain(0b00100000), // Gotta set that marker bit.
             disk_number: u16::MAX,
+            generation: 0,
+            format_version: FORMAT_VERSION,
+            pool_label: String::new(),
             block_usage_map: [1u8; 360],
         }
     }
@@ -275,6 +284,9 @@ fn initialize_numbered(disk: &mut StandardDisk, disk_number: u16) -> Result<(),
     let header = StandardDiskHeader {
         flags,
         disk_number,
+        generation: 0,
+        format_version: FORMAT_VERSION,
+        pool_label: POOL_NAME.get().cloned().unwrap_or_default(),
         block_usage_map,
     };
 
@@ -0,0 +1,352 @@
+// Reading the old ways.
+
+// Imports
+
+use crate::pool::disk::standard_disk::block::inode::inode_struct::InodeTimestamp;
+
+use super::fat12_struct::{
+    Fat12Entry,
+    Fat12Layout,
+    Fat12Volume
+};
+
+// Consts
+
+/// Anything with this many clusters or more is FAT16, not FAT12.
+const MAX_FAT12_CLUSTERS: usize = 4085;
+
+/// Every directory entry, short or long, is this big.
+const ENTRY_SIZE: usize = 32;
+
+/// Directory entry attributes.
+const ATTRIBUTE_VOLUME_LABEL: u8 = 0x08;
+const ATTRIBUTE_DIRECTORY: u8 = 0x10;
+/// Read only, hidden, system and volume label all at once means a piece of a long name.
+const ATTRIBUTE_LONG_NAME: u8 = 0x0F;
+
+/// Windows NT keeps the case of all lowercase 8.3 names in these bits, instead of making a long name.
+const LOWERCASE_BASE: u8 = 0x08;
+const LOWERCASE_EXTENSION: u8 = 0x10;
+
+/// Midnight on the 1st of January 1980, which is as far back as DOS dates go.
+const DOS_EPOCH_SECONDS: u64 = 315_532_800;
+
+// Implementations
+
+impl Fat12Layout {
+    /// Work out where everything is from the boot sector.
+    ///
+    /// Returns None if this doesn't look like a FAT12 boot sector. There's no magic number to check, so this
+    /// leans on every field being sensible, and the cluster count being small enough for FAT12.
+    pub(crate) fn from_boot_sector(sector: &[u8]) -> Option<Fat12Layout> {
+        go_read_boot_sector(sector)
+    }
+}
+
+impl Fat12Volume {
+    /// Make sense of a whole disk. None if it isn't FAT12, or there's less of it than the boot sector says.
+    pub(crate) fn from_image(image: Vec<u8>) -> Option<Fat12Volume> {
+        let layout: Fat12Layout = Fat12Layout::from_boot_sector(image.get(..512)?)?;
+        if image.len() < layout.volume_bytes {
+            return None;
+        }
+        Some(Fat12Volume {
+            image,
+            layout,
+        })
+    }
+
+    /// The first `length` bytes of the volume, which is where the boot sector is.
+    pub(crate) fn start(&self, length: usize) -> &[u8] {
+        &self.image[..length.min(self.image.len())]
+    }
+
+    /// Everything in the root directory.
+    pub(crate) fn root(&self) -> Vec<Fat12Entry> {
+        let start: usize = self.layout.root_start;
+        parse_entries(&self.image[start..start + self.layout.root_entries * ENTRY_SIZE])
+    }
+
+    /// Everything in a subdirectory.
+    pub(crate) fn list(&self, directory: &Fat12Entry) -> Vec<Fat12Entry> {
+        parse_entries(&self.cluster_data(directory.first_cluster))
+    }
+
+    /// The contents of a file.
+    ///
+    /// Comes back short if the allocation table ends the file early.
+    pub(crate) fn read(&self, file: &Fat12Entry) -> Vec<u8> {
+        let mut data: Vec<u8> = self.cluster_data(file.first_cluster);
+        data.truncate(file.size as usize);
+        data
+    }
+
+    /// Where this cluster goes next, straight from the allocation table.
+    fn next_cluster(&self, cluster: u16) -> u16 {
+        // Entries are 12 bits, so every two of them share three bytes.
+        let offset: usize = self.layout.fat_start + cluster as usize * 3 / 2;
+        let pair: u16 = u16::from_le_bytes([self.image[offset], self.image[offset + 1]]);
+        if cluster.is_multiple_of(2) {
+            pair & 0x0FFF
+        } else {
+            pair >> 4
+        }
+    }
+
+    /// Every cluster a file or directory is made of, in order.
+    fn chain(&self, first: u16) -> Vec<u16> {
+        let mut chain: Vec<u16> = Vec::new();
+        let mut cluster: u16 = first;
+        // Anything outside of the data area ends the chain, which covers the end marker, bad clusters
+        // and free ones. Capping the length stops chains that loop back on themselves.
+        while (2..self.layout.clusters + 2).contains(&cluster) && chain.len() < self.layout.clusters as usize {
+            chain.push(cluster);
+            cluster = self.next_cluster(cluster);
+        }
+        chain
+    }
+
+    /// Every byte of every cluster in a chain.
+    fn cluster_data(&self, first: u16) -> Vec<u8> {
+        let size: usize = self.layout.cluster_bytes;
+        self.chain(first)
+            .into_iter()
+            .flat_map(|cluster| {
+                let start: usize = self.layout.data_start + (cluster as usize - 2) * size;
+                self.image[start..start + size].iter().copied()
+            })
+            .collect()
+    }
+}
+
+// Functions
+
+fn go_read_boot_sector(sector: &[u8]) -> Option<Fat12Layout> {
+    let read_u16 = |offset: usize| -> usize { u16::from_le_bytes([sector[offset], sector[offset + 1]]).into() };
+    if sector.len() < 512 {
+        return None;
+    }
+
+    let bytes_per_sector: usize = read_u16(11);
+    if ![512, 1024, 2048, 4096].contains(&bytes_per_sector) {
+        return None;
+    }
+    let sectors_per_cluster: usize = sector[13].into();
+    if !sectors_per_cluster.is_power_of_two() {
+        return None;
+    }
+    let reserved_sectors: usize = read_u16(14);
+    let fats: usize = sector[16].into();
+    // FAT32 has no fixed root directory, so this being zero rules it out too.
+    let root_entries: usize = read_u16(17);
+    let sectors_per_fat: usize = read_u16(22);
+    if reserved_sectors == 0 || fats == 0 || root_entries == 0 || sectors_per_fat == 0 {
+        return None;
+    }
+    // Media descriptors are either 0xF0 or 0xF8 and up.
+    if sector[21] < 0xF0 {
+        return None;
+    }
+    let total_sectors: usize = match read_u16(19) {
+        0 => u32::from_le_bytes(sector[32..36].try_into().expect("4 = 4")) as usize,
+        small => small,
+    };
+
+    let fat_start: usize = reserved_sectors * bytes_per_sector;
+    let root_start: usize = fat_start + fats * sectors_per_fat * bytes_per_sector;
+    let data_start: usize = root_start + (root_entries * ENTRY_SIZE).div_ceil(bytes_per_sector) * bytes_per_sector;
+    let volume_bytes: usize = total_sectors * bytes_per_sector;
+    let cluster_bytes: usize = sectors_per_cluster * bytes_per_sector;
+    if data_start >= volume_bytes {
+        return None;
+    }
+    let clusters: usize = (volume_bytes - data_start) / cluster_bytes;
+    if clusters == 0 || clusters >= MAX_FAT12_CLUSTERS {
+        return None;
+    }
+    // The allocation table has to actually have room for every cluster.
+    if (clusters + 2) * 3 / 2 + 1 > sectors_per_fat * bytes_per_sector {
+        return None;
+    }
+
+    Some(Fat12Layout {
+        volume_bytes,
+        cluster_bytes,
+        fat_start,
+        root_start,
+        root_entries,
+        data_start,
+        clusters: clusters as u16,
+    })
+}
+
+/// Pull every file and directory out of a run of directory entries.
+///
+/// Deleted entries, volume labels, and the `.` and `..` entries are left out.
+fn parse_entries(bytes: &[u8]) -> Vec<Fat12Entry> {
+    let mut entries: Vec<Fat12Entry> = Vec::new();
+    // Pieces of a long name, which come before the short entry they belong to, last piece first.
+    let mut long_name: Vec<Option<[u16; 13]>> = Vec::new();
+    let mut long_checksum: u8 = 0;
+
+    for raw in bytes.chunks_exact(ENTRY_SIZE) {
+        match raw[0] {
+            // Nothing after this point.
+            0x00 => break,
+            // Deleted.
+            0xE5 => {
+                long_name.clear();
+                continue;
+            },
+            _ => {},
+        }
+
+        let attributes: u8 = raw[11];
+        if attributes == ATTRIBUTE_LONG_NAME {
+            let sequence: usize = (raw[0] & 0x1F).into();
+            if raw[0] & 0x40 != 0 {
+                // The last piece, which is the first one we see.
+                long_name = vec![None; sequence];
+                long_checksum = raw[13];
+            }
+            if sequence == 0 || sequence > long_name.len() || raw[13] != long_checksum {
+                long_name.clear();
+                continue;
+            }
+            let mut characters: [u16; 13] = [0; 13];
+            let spots = (1..11).step_by(2).chain((14..26).step_by(2)).chain((28..32).step_by(2));
+            for (character, spot) in characters.iter_mut().zip(spots) {
+                *character = u16::from_le_bytes([raw[spot], raw[spot + 1]]);
+            }
+            long_name[sequence - 1] = Some(characters);
+            continue;
+        }
+
+        let pieces: Vec<Option<[u16; 13]>> = std::mem::take(&mut long_name);
+        if attributes & ATTRIBUTE_VOLUME_LABEL != 0 {
+            continue;
+        }
+        let short_name: String = short_name(raw);
+        if short_name == "." || short_name == ".." {
+            continue;
+        }
+
+        // Long names only count if every piece showed up, and they were made for this short name.
+        let name: String = if !pieces.is_empty()
+            && pieces.iter().all(Option::is_some)
+            && long_checksum == short_name_checksum(&raw[..11])
+        {
+            let characters: Vec<u16> = pieces
+                .into_iter()
+                .flatten()
+                .flatten()
+                .take_while(|character| *character != 0x0000)
+                .collect();
+            String::from_utf16_lossy(&characters)
+        } else {
+            short_name
+        };
+
+        let modified: InodeTimestamp = dos_timestamp(
+            u16::from_le_bytes([raw[24], raw[25]]),
+            u16::from_le_bytes([raw[22], raw[23]]),
+            0,
+        ).unwrap_or(InodeTimestamp {
+            seconds: DOS_EPOCH_SECONDS,
+            nanos: 0,
+        });
+        let created: InodeTimestamp = dos_timestamp(
+            u16::from_le_bytes([raw[16], raw[17]]),
+            u16::from_le_bytes([raw[14], raw[15]]),
+            raw[13],
+        ).unwrap_or(modified);
+
+        let directory: bool = attributes & ATTRIBUTE_DIRECTORY != 0;
+        entries.push(Fat12Entry {
+            name,
+            directory,
+            first_cluster: u16::from_le_bytes([raw[26], raw[27]]),
+            size: if directory {
+                0
+            } else {
+                u32::from_le_bytes(raw[28..32].try_into().expect("4 = 4"))
+            },
+            created,
+            modified,
+        });
+    }
+    entries
+}
+
+/// The 8.3 name of an entry, with the padding taken off.
+fn short_name(raw: &[u8]) -> String {
+    let mut base: Vec<u8> = raw[..8].to_vec();
+    // 0xE5 marks deleted entries, so names that really start with it are stored as 0x05.
+    if base[0] == 0x05 {
+        base[0] = 0xE5;
+    }
+    let trim = |bytes: &[u8], lowercase: bool| -> String {
+        let text: String = bytes
+            .iter()
+            .rev()
+            .skip_while(|byte| **byte == b' ')
+            .collect::<Vec<&u8>>()
+            .into_iter()
+            .rev()
+            // Code page 437 past ASCII is anyone's guess, so those bytes are read as Latin-1.
+            .map(|byte| char::from(*byte))
+            .collect();
+        if lowercase {
+            text.to_lowercase()
+        } else {
+            text
+        }
+    };
+    let base: String = trim(&base, raw[12] & LOWERCASE_BASE != 0);
+    let extension: String = trim(&raw[8..11], raw[12] & LOWERCASE_EXTENSION != 0);
+    if extension.is_empty() {
+        base
+    } else {
+        format!("{base}.{extension}")
+    }
+}
+
+/// Long name pieces carry this, so ones left over from a renamed short name can be spotted.
+fn short_name_checksum(name: &[u8]) -> u8 {
+    name.iter().fold(0u8, |sum, byte| sum.rotate_right(1).wrapping_add(*byte))
+}
+
+/// DOS timestamps are local time, but there's no way to know what zone the disk was written in,
+/// so they're taken as UTC. Returns None for dates that aren't set.
+fn dos_timestamp(date: u16, time: u16, hundredths: u8) -> Option<InodeTimestamp> {
+    let year: u64 = 1980 + u64::from(date >> 9);
+    let month: u64 = u64::from((date >> 5) & 0x0F);
+    let day: u64 = u64::from(date & 0x1F);
+    if !(1..=12).contains(&month) || day == 0 {
+        return None;
+    }
+    let hours: u64 = u64::from(time >> 11);
+    let minutes: u64 = u64::from((time >> 5) & 0x3F);
+    let seconds: u64 = u64::from(time & 0x1F) * 2;
+
+    Some(InodeTimestamp {
+        seconds: days_since_epoch(year, month, day) * 86_400
+            + hours * 3600
+            + minutes * 60
+            + seconds
+            + u64::from(hundredths / 100),
+        nanos: u32::from(hundredths % 100) * 10_000_000,
+    })
+}
+
+/// Days from the unix epoch to a date, which is never before it here.
+///
+/// Counts from March, so leap days land at the end of the year.
+fn days_since_epoch(year: u64, month: u64, day: u64) -> u64 {
+    let year: u64 = if month <= 2 { year - 1 } else { year };
+    let era: u64 = year / 400;
+    let year_of_era: u64 = year - era * 400;
+    let day_of_year: u64 = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era: u64 = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
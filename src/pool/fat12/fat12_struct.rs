@@ -0,0 +1,88 @@
+// Blowing the dust off.
+
+// Imports
+
+use std::{
+    path::PathBuf,
+    sync::Mutex
+};
+
+use crate::pool::disk::standard_disk::block::inode::inode_struct::InodeTimestamp;
+
+// Structs, Enums, Flags
+
+/// A DOS formatted floppy, read into memory all at once.
+///
+/// Nothing is ever written back to it. Fluster only ever reads these so their files can be copied
+/// into the pool, and maybe wipes them afterwards.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Fat12Volume {
+    /// Every byte of the volume.
+    pub(super) image: Vec<u8>,
+    /// How the volume is laid out, from the boot sector.
+    pub(super) layout: Fat12Layout,
+}
+
+/// Where everything lives on a FAT12 volume, in bytes from the start of the disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Fat12Layout {
+    /// How big the whole volume is.
+    pub(crate) volume_bytes: usize,
+    /// How big a cluster is.
+    pub(super) cluster_bytes: usize,
+    /// The first copy of the allocation table. The others are ignored.
+    pub(super) fat_start: usize,
+    /// The root directory, which is a fixed size and isn't in a cluster.
+    pub(super) root_start: usize,
+    /// How many entries fit in the root directory.
+    pub(super) root_entries: usize,
+    /// Cluster 2, the first one that holds data.
+    pub(super) data_start: usize,
+    /// How many data clusters there are.
+    pub(super) clusters: u16,
+}
+
+/// A file or directory on a FAT12 volume.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Fat12Entry {
+    /// The long name if there is one, otherwise the 8.3 name.
+    pub(crate) name: String,
+    pub(crate) directory: bool,
+    /// Where the contents start. 0 for empty files.
+    pub(crate) first_cluster: u16,
+    /// Always 0 for directories.
+    pub(crate) size: u32,
+    /// DOS didn't keep these, so it's the same as `modified` for disks from before Windows 95.
+    pub(crate) created: InodeTimestamp,
+    pub(crate) modified: InodeTimestamp,
+}
+
+/// How copying a FAT12 disk into the pool went.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub(crate) struct ImportReport {
+    /// The directory in the pool everything was copied into.
+    pub(crate) destination: PathBuf,
+    pub(crate) files: u64,
+    pub(crate) directories: u64,
+    /// How much file data was copied.
+    pub(crate) bytes: u64,
+    /// Everything that wasn't copied, and why.
+    pub(crate) skipped: Vec<String>,
+    /// The disk number the floppy was given, if it was wiped and added to the pool afterwards.
+    pub(crate) joined_as: Option<u16>,
+}
+
+/// What happened when trying to import a disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Imported {
+    /// Everything that could be copied was.
+    Done(ImportReport),
+    /// The destination isn't a directory in the pool.
+    NoDestination,
+    /// The disk in the drive isn't FAT12, so nothing was copied.
+    NotFat12,
+}
+
+// Importing a whole disk takes a while, so the result sticks around for whoever wants to look at it later.
+/// The report from the last import since mounting, if there was one.
+pub(super) static LAST_IMPORT: Mutex<Option<ImportReport>> = Mutex::new(None);
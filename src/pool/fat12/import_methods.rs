@@ -0,0 +1,325 @@
+// Moving in.
+
+// Imports
+
+use std::{
+    fmt::Write,
+    fs::File,
+    path::Path
+};
+
+use log::{debug, info, warn};
+
+use crate::{
+    error_types::drive::DriveError,
+    filesystem::filesystem_struct::USE_VIRTUAL_DISKS,
+    pool::{
+        disk::{
+            drive_struct::{
+                DiskType,
+                FloppyDrive
+            },
+            generic::{
+                block::block_structs::RawBlock,
+                disk_trait::GenericDiskMethods,
+                io::{
+                    cache::cache_io::CachedBlockIO,
+                    read::{
+                        read_block_direct,
+                        read_multiple_blocks_direct
+                    },
+                    wipe::destroy_disk
+                }
+            },
+            standard_disk::{
+                block::{
+                    directory::directory_struct::{
+                        DirectoryBlock,
+                        DirectoryItem
+                    },
+                    inode::inode_struct::{
+                        Inode,
+                        InodeBlock
+                    },
+                    io::directory::types::NamedItem
+                },
+                standard_disk_struct::StandardDisk
+            }
+        },
+        placement::placement_struct::PlacementGuard,
+        pool_actions::pool_struct::{
+            Pool,
+            GLOBAL_POOL
+        }
+    },
+    tui::{
+        notify::NotifyTui,
+        prompts::TuiPrompt,
+        tasks::{
+            TaskHandle,
+            TaskType
+        }
+    }
+};
+
+use super::fat12_struct::{
+    Fat12Entry,
+    Fat12Layout,
+    Fat12Volume,
+    ImportReport,
+    Imported,
+    LAST_IMPORT
+};
+
+// Consts
+
+/// Only floppies the same size as a fluster disk can join the pool.
+const FULL_FLOPPY_BYTES: usize = 2880 * 512;
+
+/// FAT12 directories can point back up at themselves if they're damaged, so don't go deeper than this.
+const MAX_DEPTH: usize = 32;
+
+// Implementations
+
+impl ImportReport {
+    /// Copy everything off of a FAT12 floppy into a directory in the pool, keeping names and timestamps.
+    ///
+    /// The floppy is asked for as if it were the next disk in the pool. If `join` is set, once everything has
+    /// been copied and flushed, the floppy is wiped and becomes that next disk.
+    ///
+    /// Anything with the same name as something already in the destination is skipped, except for directories,
+    /// which are merged. May swap disks.
+    pub(crate) fn run(destination: &Path, join: bool) -> Result<Imported, DriveError> {
+        go_import(destination, join)
+    }
+
+    /// The report from the last import, if there has been one since mounting.
+    pub(crate) fn last() -> Option<ImportReport> {
+        LAST_IMPORT.lock().expect("Other mutex holders should not panic.").clone()
+    }
+
+    /// Totals, then one line per skipped item.
+    pub(crate) fn report(&self) -> String {
+        let mut report: String = String::new();
+        let _ = writeln!(report, "destination: {}", self.destination.display());
+        let _ = writeln!(report, "files: {}", self.files);
+        let _ = writeln!(report, "directories: {}", self.directories);
+        let _ = writeln!(report, "bytes: {}", self.bytes);
+        match self.joined_as {
+            Some(disk) => {
+                let _ = writeln!(report, "joined_as_disk: {disk}");
+            },
+            None => {
+                let _ = writeln!(report, "joined_as_disk: none");
+            },
+        }
+        let _ = writeln!(report, "skipped: {}", self.skipped.len());
+        for reason in &self.skipped {
+            let _ = writeln!(report, "{reason}");
+        }
+        report
+    }
+}
+
+// Functions
+
+fn go_import(destination: &Path, join: bool) -> Result<Imported, DriveError> {
+    let Some(mut into) = DirectoryBlock::try_find_directory(Some(destination))? else {
+        return Ok(Imported::NoDestination);
+    };
+
+    // The floppy goes where the next disk would, so it's already in the right spot if it joins the pool.
+    let disk_number: u16 = GLOBAL_POOL
+        .get()
+        .expect("Pool must exist to import into it.")
+        .lock()
+        .expect("Other mutex holders should not panic.")
+        .header
+        .highest_known_disk + 1;
+
+    info!("Importing a FAT12 disk into `{}`...", destination.display());
+    let Some(volume) = read_volume(disk_number, "Insert the FAT12 disk to copy files from.")? else {
+        info!("That isn't a FAT12 disk.");
+        return Ok(Imported::NotFat12);
+    };
+
+    let mut report: ImportReport = ImportReport {
+        destination: destination.to_path_buf(),
+        ..Default::default()
+    };
+    {
+        let _placement = PlacementGuard::enter(destination)?;
+        let root: Vec<Fat12Entry> = volume.root();
+        let handle = NotifyTui::start_task(TaskType::ImportFiles, count_files(&volume, &root, 0));
+        copy_directory(&volume, &root, &mut into, destination, 0, &mut report, &handle)?;
+        NotifyTui::finish_task(handle);
+    }
+    info!(
+        "Copied {} files and {} directories, {} bytes in all.",
+        report.files, report.directories, report.bytes
+    );
+
+    // Everything has to be safely on the pool before the only other copy gets wiped.
+    CachedBlockIO::flush()?;
+    Pool::flush()?;
+    if join {
+        report.joined_as = join_pool(disk_number, &volume)?;
+    }
+
+    *LAST_IMPORT.lock().expect("Other mutex holders should not panic.") = Some(report.clone());
+    Ok(Imported::Done(report))
+}
+
+/// Ask for a disk, and read in the whole thing if it's FAT12.
+fn read_volume(disk_number: u16, why: &str) -> Result<Option<Fat12Volume>, DriveError> {
+    let Some(file) = open_unknown_disk(disk_number, why)? else {
+        return Ok(None);
+    };
+    // The boot sector knows how big the disk is, and it may not be a full 1.44MB.
+    let boot_sector: RawBlock = read_block_direct(&file, disk_number, 0, true, false)?;
+    let Some(layout) = Fat12Layout::from_boot_sector(&boot_sector.data) else {
+        return Ok(None);
+    };
+    let blocks: u16 = layout.volume_bytes.div_ceil(512).min(2880) as u16;
+    let image: Vec<u8> = read_multiple_blocks_direct(&file, disk_number, 0, blocks, false)?
+        .into_iter()
+        .flat_map(|block| block.data)
+        .collect();
+    Ok(Fat12Volume::from_image(image))
+}
+
+/// Get the disk in the drive, as long as it isn't a fluster disk or blank.
+fn open_unknown_disk(disk_number: u16, why: &str) -> Result<Option<File>, DriveError> {
+    let virtual_disks: bool = USE_VIRTUAL_DISKS
+        .lock()
+        .expect("Other mutex holders should not panic.")
+        .is_some();
+    if !virtual_disks {
+        TuiPrompt::prompt_wait_for_disk_swap("Import.".to_string(), why.to_string(), true, disk_number)?;
+    }
+    match FloppyDrive::open_direct(disk_number)? {
+        DiskType::Unknown(disk) => Ok(Some(disk.disk_file())),
+        other => {
+            debug!("Disk to import isn't unknown, it's `{other:?}`.");
+            Ok(None)
+        },
+    }
+}
+
+/// How many things are going to be copied, for the TUI.
+fn count_files(volume: &Fat12Volume, entries: &[Fat12Entry], depth: usize) -> u64 {
+    if depth > MAX_DEPTH {
+        return 0;
+    }
+    entries
+        .iter()
+        .map(|entry| match entry.directory {
+            true => 1 + count_files(volume, &volume.list(entry), depth + 1),
+            false => 1,
+        })
+        .sum()
+}
+
+/// Copy a FAT12 directory's contents into a pool directory, and everything under it.
+fn copy_directory(
+    volume: &Fat12Volume,
+    entries: &[Fat12Entry],
+    into: &mut DirectoryBlock,
+    path: &Path,
+    depth: usize,
+    report: &mut ImportReport,
+    handle: &TaskHandle,
+) -> Result<(), DriveError> {
+    for entry in entries {
+        NotifyTui::complete_task_step(handle);
+        let item_path = path.join(&entry.name);
+        if entry.name.len() > 255 || entry.name.contains('/') {
+            report.skipped.push(format!("{}: name doesn't fit", item_path.display()));
+            continue;
+        }
+
+        if entry.directory {
+            if depth >= MAX_DEPTH {
+                report.skipped.push(format!("{}: nested too deep", item_path.display()));
+                continue;
+            }
+            if into.find_item(&NamedItem::File(entry.name.clone()))?.is_some() {
+                report.skipped.push(format!("{}: a file is in the way", item_path.display()));
+                continue;
+            }
+            let item: DirectoryItem = match into.find_item(&NamedItem::Directory(entry.name.clone()))? {
+                Some(existing) => existing,
+                None => {
+                    report.directories += 1;
+                    into.make_directory(entry.name.clone())?
+                },
+            };
+            let mut inner: DirectoryBlock = item.get_directory_block()?;
+            copy_directory(volume, &volume.list(entry), &mut inner, &item_path, depth + 1, report, handle)?;
+            set_timestamps(&item, entry)?;
+            continue;
+        }
+
+        if into.find_item(&NamedItem::File(entry.name.clone()))?.is_some()
+            || into.find_item(&NamedItem::Directory(entry.name.clone()))?.is_some() {
+            report.skipped.push(format!("{}: already exists", item_path.display()));
+            continue;
+        }
+        let data: Vec<u8> = volume.read(entry);
+        if data.len() < entry.size as usize {
+            // Keep what there is, it's better than nothing.
+            warn!("`{}` ends early on the FAT12 disk.", item_path.display());
+            report.skipped.push(format!(
+                "{}: only {} of {} bytes could be found",
+                item_path.display(), data.len(), entry.size
+            ));
+        }
+        let item: DirectoryItem = into.new_file(entry.name.clone())?;
+        if !data.is_empty() {
+            let _ = item.write_file(&data, 0)?;
+        }
+        set_timestamps(&item, entry)?;
+        report.files += 1;
+        report.bytes += data.len() as u64;
+    }
+    Ok(())
+}
+
+/// Put the FAT12 disk's timestamps on the copy.
+fn set_timestamps(item: &DirectoryItem, entry: &Fat12Entry) -> Result<(), DriveError> {
+    let mut inode_block: InodeBlock = InodeBlock::from_block(&CachedBlockIO::read_block(item.location.pointer)?);
+    let mut inode: Inode = inode_block
+        .try_read_inode(item.location.offset)
+        .expect("Directory items should point at real inodes.");
+    inode.created = entry.created;
+    inode.modified = entry.modified;
+    inode_block.update_inode(item.location.offset, inode)
+}
+
+/// Wipe the FAT12 disk, and make it the next disk in the pool.
+///
+/// Returns the new disk's number, or None if it couldn't join.
+fn join_pool(disk_number: u16, volume: &Fat12Volume) -> Result<Option<u16>, DriveError> {
+    if volume.layout.volume_bytes != FULL_FLOPPY_BYTES {
+        info!("The FAT12 disk isn't the same size as a fluster disk, so it can't join the pool.");
+        return Ok(None);
+    }
+
+    // Flushing might have swapped it out, and it had better be the same disk.
+    let Some(mut file) = open_unknown_disk(disk_number, "Put the FAT12 disk back in, to add it to the pool.")? else {
+        warn!("The FAT12 disk wasn't put back, so it wasn't added to the pool.");
+        return Ok(None);
+    };
+    let boot_sector: RawBlock = read_block_direct(&file, disk_number, 0, true, false)?;
+    if boot_sector.data[..] != *volume.start(512) {
+        warn!("A different disk was put back, so it wasn't added to the pool.");
+        return Ok(None);
+    }
+
+    info!("Wiping the FAT12 disk, so it can become disk {disk_number}...");
+    destroy_disk(&mut file)?;
+    drop(file);
+    let new_disk: StandardDisk = Pool::new_disk::<StandardDisk>()?;
+    info!("The FAT12 disk is now disk {}.", new_disk.number);
+    Ok(Some(new_disk.number))
+}
@@ -0,0 +1,5 @@
+pub(crate) mod fat12_struct;
+pub(crate) mod fat12_methods;
+pub(crate) mod import_methods;
+#[cfg(test)]
+mod tests;
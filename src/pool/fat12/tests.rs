@@ -0,0 +1,234 @@
+// Floppies from the bottom of a drawer.
+// Unwrapping is okay here, since we want unexpected outcomes to fail tests.
+#![allow(clippy::unwrap_used)]
+
+use std::path::Path;
+
+use test_log::test;
+
+use crate::{
+    filesystem::filesystem_struct::USE_VIRTUAL_DISKS,
+    pool::{
+        disk::standard_disk::block::{
+            inode::inode_struct::InodeTimestamp,
+            io::directory::{
+                tests::get_filesystem,
+                types::NamedItem
+            }
+        },
+        pool_actions::pool_struct::{
+            Pool,
+            GLOBAL_POOL
+        }
+    }
+};
+
+use super::fat12_struct::{
+    Fat12Volume,
+    ImportReport,
+    Imported
+};
+
+// 2001-09-09 01:46:40 UTC, a billion seconds in.
+const DOS_DATE: u16 = (21 << 9) | (9 << 5) | 9;
+const DOS_TIME: u16 = (1 << 11) | (46 << 5) | 20;
+const BILLION: InodeTimestamp = InodeTimestamp {
+    seconds: 1_000_000_000,
+    nanos: 0,
+};
+
+/// A blank 1.44MB FAT12 floppy, the way DOS would format it.
+fn blank_floppy() -> Vec<u8> {
+    let mut image = vec![0u8; 2880 * 512];
+    image[11..13].copy_from_slice(&512u16.to_le_bytes());
+    image[13] = 1; // Sectors per cluster
+    image[14..16].copy_from_slice(&1u16.to_le_bytes()); // Reserved sectors
+    image[16] = 2; // FATs
+    image[17..19].copy_from_slice(&224u16.to_le_bytes()); // Root entries
+    image[19..21].copy_from_slice(&2880u16.to_le_bytes()); // Sectors
+    image[21] = 0xF0;
+    image[22..24].copy_from_slice(&9u16.to_le_bytes()); // Sectors per FAT
+    image[510] = 0x55;
+    image[511] = 0xAA;
+    set_fat(&mut image, 0, 0xFF0);
+    set_fat(&mut image, 1, 0xFFF);
+    image
+}
+
+fn set_fat(image: &mut [u8], cluster: u16, next: u16) {
+    let offset = 512 + cluster as usize * 3 / 2;
+    let mut pair = u16::from_le_bytes([image[offset], image[offset + 1]]);
+    if cluster.is_multiple_of(2) {
+        pair = (pair & 0xF000) | next;
+    } else {
+        pair = (pair & 0x000F) | (next << 4);
+    }
+    image[offset..offset + 2].copy_from_slice(&pair.to_le_bytes());
+}
+
+fn cluster_offset(cluster: u16) -> usize {
+    (33 + cluster as usize - 2) * 512
+}
+
+fn short_entry(name: &[u8; 11], attributes: u8, case: u8, cluster: u16, size: u32) -> [u8; 32] {
+    let mut entry = [0u8; 32];
+    entry[..11].copy_from_slice(name);
+    entry[11] = attributes;
+    entry[12] = case;
+    entry[22..24].copy_from_slice(&DOS_TIME.to_le_bytes());
+    entry[24..26].copy_from_slice(&DOS_DATE.to_le_bytes());
+    entry[26..28].copy_from_slice(&cluster.to_le_bytes());
+    entry[28..32].copy_from_slice(&size.to_le_bytes());
+    entry
+}
+
+/// The long name entries that go in front of a short one, in the order they're stored.
+fn long_entries(name: &str, short: &[u8; 11]) -> Vec<[u8; 32]> {
+    let checksum = short.iter().fold(0u8, |sum, byte| sum.rotate_right(1).wrapping_add(*byte));
+    let mut characters: Vec<u16> = name.encode_utf16().collect();
+    if !characters.len().is_multiple_of(13) {
+        characters.push(0x0000);
+    }
+    while !characters.len().is_multiple_of(13) {
+        characters.push(0xFFFF);
+    }
+    let pieces = characters.len() / 13;
+    (0..pieces)
+        .rev()
+        .map(|piece| {
+            let mut entry = [0u8; 32];
+            entry[0] = (piece as u8 + 1) | if piece + 1 == pieces { 0x40 } else { 0 };
+            entry[11] = 0x0F;
+            entry[13] = checksum;
+            let spots = (1..11).step_by(2).chain((14..26).step_by(2)).chain((28..32).step_by(2));
+            for (character, spot) in characters[piece * 13..piece * 13 + 13].iter().zip(spots) {
+                entry[spot..spot + 2].copy_from_slice(&character.to_le_bytes());
+            }
+            entry
+        })
+        .collect()
+}
+
+/// A floppy with a volume label, a deleted file, a lowercase 8.3 name, a long name spread over two
+/// clusters, and a directory with a file in it.
+fn filled_floppy() -> Vec<u8> {
+    let mut image = blank_floppy();
+    let mut root: Vec<[u8; 32]> = vec![
+        short_entry(b"OLD DISK   ", 0x08, 0, 0, 0),
+        short_entry(b"\xE5ONE    TXT", 0x20, 0, 7, 5),
+        short_entry(b"README  TXT", 0x20, 0x18, 2, 11),
+    ];
+    root.extend(long_entries("A rather long name.txt", b"ARATHE~1TXT"));
+    root.push(short_entry(b"ARATHE~1TXT", 0x20, 0, 5, 700));
+    root.push(short_entry(b"DOCS       ", 0x10, 0, 3, 0));
+    for (index, entry) in root.iter().enumerate() {
+        let offset = 19 * 512 + index * 32;
+        image[offset..offset + 32].copy_from_slice(entry);
+    }
+
+    let docs = [
+        short_entry(b".          ", 0x10, 0, 3, 0),
+        short_entry(b"..         ", 0x10, 0, 0, 0),
+        short_entry(b"NOTES   TXT", 0x20, 0, 4, 6),
+    ];
+    for (index, entry) in docs.iter().enumerate() {
+        let offset = cluster_offset(3) + index * 32;
+        image[offset..offset + 32].copy_from_slice(entry);
+    }
+
+    image[cluster_offset(2)..cluster_offset(2) + 11].copy_from_slice(b"hello there");
+    image[cluster_offset(4)..cluster_offset(4) + 6].copy_from_slice(b"noted.");
+    for byte in image[cluster_offset(5)..cluster_offset(5) + 700].iter_mut() {
+        *byte = b'x';
+    }
+
+    for cluster in [2, 3, 4, 6] {
+        set_fat(&mut image, cluster, 0xFFF);
+    }
+    set_fat(&mut image, 5, 6);
+    image
+}
+
+#[test]
+fn reads_names_and_contents() {
+    let volume = Fat12Volume::from_image(filled_floppy()).unwrap();
+    let root = volume.root();
+    let names: Vec<&str> = root.iter().map(|entry| entry.name.as_str()).collect();
+    assert_eq!(names, ["readme.txt", "A rather long name.txt", "DOCS"]);
+
+    assert_eq!(volume.read(&root[0]), b"hello there");
+    assert_eq!(volume.read(&root[1]), vec![b'x'; 700]);
+    assert!(root[2].directory);
+
+    let docs = volume.list(&root[2]);
+    assert_eq!(docs.len(), 1);
+    assert_eq!(docs[0].name, "NOTES.TXT");
+    assert_eq!(volume.read(&docs[0]), b"noted.");
+
+    // No creation time was set, so it falls back to the modified time.
+    assert_eq!(root[0].modified, BILLION);
+    assert_eq!(root[0].created, BILLION);
+}
+
+#[test]
+fn rejects_other_disks() {
+    assert!(Fat12Volume::from_image(vec![0u8; 2880 * 512]).is_none());
+    // Too short for what the boot sector says.
+    let mut image = blank_floppy();
+    image.truncate(1000 * 512);
+    assert!(Fat12Volume::from_image(image).is_none());
+    // Too many clusters to be FAT12.
+    let mut image = blank_floppy();
+    image[19..21].copy_from_slice(&0u16.to_le_bytes());
+    image[32..36].copy_from_slice(&100_000u32.to_le_bytes());
+    assert!(Fat12Volume::from_image(image).is_none());
+}
+
+#[test]
+fn imports_and_joins() {
+    let _fs = get_filesystem();
+    let mut root = Pool::get_root_directory().unwrap();
+    // Already there, so it gets merged into.
+    let _ = root.make_directory("DOCS".to_string()).unwrap();
+    let _ = root.new_file("readme.txt".to_string()).unwrap();
+
+    // Virtual disks get put in the drive by putting them where the next disk would go.
+    let virtual_dir = USE_VIRTUAL_DISKS.lock().unwrap().clone().unwrap();
+    std::fs::write(virtual_dir.join("disk2.fsr"), filled_floppy()).unwrap();
+
+    let Imported::Done(report) = ImportReport::run(Path::new("/"), true).unwrap() else {
+        panic!("Import should have worked.");
+    };
+    assert_eq!(report.files, 2);
+    assert_eq!(report.directories, 0);
+    assert_eq!(report.bytes, 706);
+    assert_eq!(report.skipped, ["/readme.txt: already exists"]);
+    assert_eq!(report.joined_as, Some(2));
+    assert_eq!(ImportReport::last(), Some(report));
+
+    let root = Pool::get_root_directory().unwrap();
+    let long = root.find_item(&NamedItem::File("A rather long name.txt".to_string())).unwrap().unwrap();
+    assert_eq!(long.read_file(0, 700).unwrap(), vec![b'x'; 700]);
+    assert_eq!(long.get_modified_time().unwrap(), BILLION);
+
+    let docs = root.find_item(&NamedItem::Directory("DOCS".to_string())).unwrap().unwrap();
+    let notes = docs
+        .get_directory_block()
+        .unwrap()
+        .find_item(&NamedItem::File("NOTES.TXT".to_string()))
+        .unwrap()
+        .unwrap();
+    assert_eq!(notes.read_file(0, 6).unwrap(), b"noted.");
+
+    let highest = GLOBAL_POOL.get().unwrap().lock().unwrap().header.highest_known_disk;
+    assert_eq!(highest, 2);
+}
+
+#[test]
+fn nothing_to_import() {
+    let _fs = get_filesystem();
+    let virtual_dir = USE_VIRTUAL_DISKS.lock().unwrap().clone().unwrap();
+    std::fs::write(virtual_dir.join("disk2.fsr"), vec![0u8; 2880 * 512]).unwrap();
+    assert_eq!(ImportReport::run(Path::new("/"), false).unwrap(), Imported::NotFat12);
+    assert_eq!(ImportReport::run(Path::new("/nowhere"), false).unwrap(), Imported::NoDestination);
+}
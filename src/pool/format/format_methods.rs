@@ -0,0 +1,311 @@
+// Out with the old.
+
+// Imports
+
+use std::fmt::Display;
+
+use log::{debug, info, warn};
+
+use crate::{
+    error_types::drive::DriveError,
+    filesystem::filesystem_struct::{
+        ENABLE_CHECKSUMS,
+        ENABLE_DEDUP,
+        ENABLE_TRASH
+    },
+    pool::{
+        disk::{
+            generic::{
+                generic_structs::pointer_struct::DiskPointer,
+                io::cache::cache_io::CachedBlockIO
+            },
+            pool_disk::block::header::header_struct::{
+                CompatFeatures,
+                IncompatFeatures,
+                ItemCounts,
+                PoolDiskHeader,
+                RoCompatFeatures,
+                COUNTS_BLOCK
+            },
+            standard_disk::block::{
+                directory::directory_struct::{
+                    DirectoryBlock,
+                    DirectoryItemFlags
+                },
+                header::header_struct::StandardDiskHeader
+            }
+        },
+        pool_actions::pool_struct::{
+            Pool,
+            GLOBAL_POOL
+        },
+        manifest::manifest_methods::count_used,
+        snapshot::snapshot_struct::Snapshot
+    },
+    tui::{
+        notify::NotifyTui,
+        tasks::TaskType
+    }
+};
+
+use super::format_struct::{
+    Feature,
+    FormatProblem,
+    Migration,
+    FORMAT_VERSION
+};
+
+/// Every way forward, oldest first.
+const MIGRATIONS: [Migration; 2] = [
+    Migration {
+        from: 0,
+        what: "Stamping a format version on every disk header.",
+        disk: Some(stamp_version),
+        pool: None,
+    },
+    Migration {
+        from: 1,
+        what: "Counting every file, directory and free block in the pool.",
+        disk: None,
+        pool: Some(count_items),
+    },
+];
+
+// Implementations
+
+impl PoolDiskHeader {
+    /// Can this fluster mount a pool with this header?
+    ///
+    /// Unknown compatible features are fine, and only get a warning.
+    pub(crate) fn check_format(&self) -> Result<(), FormatProblem> {
+        go_check_format(self)
+    }
+
+    /// Mark a feature as used.
+    pub(crate) fn add_feature(&mut self, feature: Feature) {
+        match feature {
+            Feature::Trash => self.compat.insert(CompatFeatures::Trash),
+            Feature::Dedup => self.ro_compat.insert(RoCompatFeatures::Dedup),
+            Feature::Snapshots => self.ro_compat.insert(RoCompatFeatures::Snapshots),
+            Feature::DenseDisks => self.incompat.insert(IncompatFeatures::DenseDisks),
+            Feature::Checksums => self.incompat.insert(IncompatFeatures::Checksums),
+            Feature::Holes => self.incompat.insert(IncompatFeatures::Holes),
+            Feature::Placement => self.incompat.insert(IncompatFeatures::Placement),
+        }
+    }
+
+    /// Mark the features that can be seen from the header alone.
+    pub(crate) fn add_features_in_use(&mut self) {
+        if self.dedup_index_blocks != 0 {
+            self.add_feature(Feature::Dedup);
+        }
+        if self.snapshots != 0 {
+            self.add_feature(Feature::Snapshots);
+        }
+        if self.dense_disks.iter().any(|disk| *disk != 0) {
+            self.add_feature(Feature::DenseDisks);
+        }
+    }
+
+    /// Turn on the optional features this pool already uses, unless they were set when mounting.
+    pub(crate) fn turn_on_features_in_use(&self) {
+        // Setting fails if the option was already picked, which is the point.
+        if self.compat.contains(CompatFeatures::Trash) {
+            let _ = ENABLE_TRASH.set(true);
+        }
+        if self.ro_compat.contains(RoCompatFeatures::Dedup) {
+            let _ = ENABLE_DEDUP.set(true);
+        }
+        if self.incompat.contains(IncompatFeatures::Checksums) {
+            let _ = ENABLE_CHECKSUMS.set(true);
+        }
+    }
+}
+
+impl Pool {
+    /// Note that the pool now uses a feature, so older versions of fluster know to keep their hands off.
+    ///
+    /// Saved with the rest of the pool header on the next flush. Does nothing if there is no pool yet.
+    pub(crate) fn use_feature(feature: Feature) {
+        if let Some(pool) = GLOBAL_POOL.get() {
+            pool.lock().expect("Other mutex holders should not panic.").header.add_feature(feature);
+        }
+    }
+
+    /// Bring a pool made by an older fluster up to the current format, one disk at a time.
+    ///
+    /// May swap through every disk in the pool. Returns whether anything needed upgrading.
+    pub(crate) fn migrate() -> Result<bool, DriveError> {
+        go_migrate()
+    }
+}
+
+impl Display for FormatProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FormatProblem::TooNew(version) => write!(
+                f,
+                "The pool is format version {version}, but this fluster only knows up to version {FORMAT_VERSION}."
+            ),
+            FormatProblem::UnknownIncompat(features) => write!(
+                f,
+                "The pool uses features this fluster would misread. (incompat bits {:#06x})",
+                features.bits()
+            ),
+            FormatProblem::UnknownRoCompat(features) => write!(
+                f,
+                "The pool uses features this fluster would break by writing, and fluster can't mount read-only. (ro_compat bits {:#06x})",
+                features.bits()
+            ),
+        }
+    }
+}
+
+// Functions
+
+fn go_check_format(header: &PoolDiskHeader) -> Result<(), FormatProblem> {
+    if header.format_version > FORMAT_VERSION {
+        return Err(FormatProblem::TooNew(header.format_version));
+    }
+    let unknown_incompat = header.incompat.difference(IncompatFeatures::all());
+    if !unknown_incompat.is_empty() {
+        return Err(FormatProblem::UnknownIncompat(unknown_incompat));
+    }
+    let unknown_ro_compat = header.ro_compat.difference(RoCompatFeatures::all());
+    if !unknown_ro_compat.is_empty() {
+        return Err(FormatProblem::UnknownRoCompat(unknown_ro_compat));
+    }
+    let unknown_compat = header.compat.difference(CompatFeatures::all());
+    if !unknown_compat.is_empty() {
+        warn!("The pool uses some features this fluster doesn't know about, but they're safe to ignore. (compat bits {:#06x})", unknown_compat.bits());
+    }
+    Ok(())
+}
+
+fn go_migrate() -> Result<bool, DriveError> {
+    let (mut version, highest_disk) = {
+        let pool = GLOBAL_POOL
+            .get()
+            .expect("Pool must exist to upgrade it.")
+            .lock()
+            .expect("Other mutex holders should not panic.");
+        (pool.header.format_version, pool.header.highest_known_disk)
+    };
+    if version >= FORMAT_VERSION {
+        return Ok(false);
+    }
+
+    info!("The pool is format version {version}, upgrading it to version {FORMAT_VERSION}...");
+    while version < FORMAT_VERSION {
+        let migration: &Migration = MIGRATIONS
+            .iter()
+            .find(|migration| migration.from == version)
+            .expect("Every old version should have a migration.");
+        info!("Version {version} to {}: {}", version + 1, migration.what);
+
+        if let Some(upgrade_disk) = migration.disk {
+            let handle = NotifyTui::start_task(TaskType::MigrateDisks(version + 1), highest_disk.into());
+            for disk in 1..=highest_disk {
+                debug!("Upgrading disk {disk}...");
+                upgrade_disk(disk)?;
+                // Finish each disk before starting on the next, so they can be swapped out.
+                CachedBlockIO::flush()?;
+                NotifyTui::complete_task_step(&handle);
+            }
+            NotifyTui::finish_task(handle);
+        }
+        if let Some(upgrade_pool) = migration.pool {
+            debug!("Upgrading the pool...");
+            upgrade_pool()?;
+            CachedBlockIO::flush()?;
+        }
+
+        // Only once every disk is done, otherwise an interrupted upgrade would be skipped next time.
+        version += 1;
+        GLOBAL_POOL
+            .get()
+            .expect("Pool must exist to upgrade it.")
+            .lock()
+            .expect("Other mutex holders should not panic.")
+            .header
+            .format_version = version;
+        Pool::flush()?;
+    }
+    info!("Upgrade finished.");
+    Ok(true)
+}
+
+fn disk_header(disk: u16) -> Result<StandardDiskHeader, DriveError> {
+    let pointer: DiskPointer = DiskPointer {
+        disk,
+        block: 0,
+    };
+    Ok(StandardDiskHeader::from_block(&CachedBlockIO::read_block(pointer)?))
+}
+
+/// Version 0 to 1. Disk headers didn't have a format version before.
+fn stamp_version(disk: u16) -> Result<(), DriveError> {
+    let mut header: StandardDiskHeader = disk_header(disk)?;
+    if header.format_version >= 1 {
+        // Already done.
+        return Ok(());
+    }
+    header.format_version = 1;
+    CachedBlockIO::update_block(&header.to_block())
+}
+
+/// Version 1 to 2. The pool header didn't keep track of how many files and directories there are, so
+/// they all have to be found. Snapshots are separate copies, so they get counted too.
+fn count_items() -> Result<(), DriveError> {
+    // The root directory doesn't show up in any listing.
+    let mut items: ItemCounts = ItemCounts {
+        files: 0,
+        directories: 1,
+    };
+    count_directory(&Pool::get_root_directory()?, &mut items)?;
+    for snapshot in Snapshot::list() {
+        items.directories += 1;
+        let root: DirectoryBlock = DirectoryBlock::from_block(&CachedBlockIO::read_block(snapshot.root.directory)?);
+        count_directory(&root, &mut items)?;
+    }
+    info!("Found {} files and {} directories.", items.files, items.directories);
+
+    // The free block count used to drift, so it gets a fresh start too.
+    let highest_disk: u16 = GLOBAL_POOL
+        .get()
+        .expect("Pool must exist to upgrade it.")
+        .lock()
+        .expect("Other mutex holders should not panic.")
+        .header
+        .highest_known_disk;
+    let mut free: u32 = 0;
+    for disk in 1..=highest_disk {
+        if Pool::is_dense_disk(disk) {
+            continue;
+        }
+        free += 2880 - count_used(&disk_header(disk)?.block_usage_map);
+    }
+
+    let mut pool = GLOBAL_POOL
+        .get()
+        .expect("Pool must exist to upgrade it.")
+        .lock()
+        .expect("Other mutex holders should not panic.");
+    pool.header.items = items;
+    pool.header.pool_standard_blocks_free = free;
+    // The counts get a block of their own, which might've never been marked as used.
+    pool.header.block_usage_map[COUNTS_BLOCK as usize / 8] |= 0b10000000 >> (COUNTS_BLOCK % 8);
+    Ok(())
+}
+
+fn count_directory(directory: &DirectoryBlock, items: &mut ItemCounts) -> Result<(), DriveError> {
+    for item in directory.list()? {
+        if !item.flags.contains(DirectoryItemFlags::IsDirectory) {
+            items.files += 1;
+            continue;
+        }
+        items.directories += 1;
+        count_directory(&item.get_directory_block()?, items)?;
+    }
+    Ok(())
+}
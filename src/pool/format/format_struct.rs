@@ -0,0 +1,69 @@
+// Fluster, as it was in the before times.
+
+// Imports
+
+use crate::{
+    error_types::drive::DriveError,
+    pool::disk::pool_disk::block::header::header_struct::{
+        IncompatFeatures,
+        RoCompatFeatures
+    }
+};
+
+// Consts
+
+/// The newest on-disk format this fluster knows how to read and write.
+///
+/// Anything that changes how a block is laid out on disk needs to bump this, and add a migration
+/// so older pools can be brought up to date.
+pub(crate) const FORMAT_VERSION: u16 = 2;
+
+// Structs, Enums, Flags
+
+/// Something a pool can use that older versions of fluster might not understand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Feature {
+    /// A trash directory.
+    Trash,
+    /// Blocks shared through the dedup index.
+    Dedup,
+    /// The snapshot table.
+    Snapshots,
+    /// Disks given entirely to one file.
+    DenseDisks,
+    /// Whole-file checksums on inodes.
+    Checksums,
+    /// Hole extents in sparse files.
+    Holes,
+    /// Placements on directories.
+    Placement,
+}
+
+/// Why a pool can't be mounted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FormatProblem {
+    /// The pool was made or upgraded by a newer fluster.
+    TooNew(u16),
+    /// The pool uses features this fluster doesn't know about, and would misread.
+    UnknownIncompat(IncompatFeatures),
+    /// The pool uses features this fluster doesn't know about, and would break by writing.
+    /// Fluster can't be mounted read-only, so these are a no too.
+    UnknownRoCompat(RoCompatFeatures),
+}
+
+/// A step from one format version to the next.
+pub(super) struct Migration {
+    /// The version this upgrades from. It always goes to the next one.
+    pub(super) from: u16,
+    /// What changes, for the logs.
+    pub(super) what: &'static str,
+    /// Upgrade a single disk, by number. Never called on the pool disk.
+    ///
+    /// Has to be fine to run on a disk that's already upgraded, since an upgrade can be interrupted
+    /// partway through the pool, and will start over from the first disk next time.
+    pub(super) disk: Option<fn(u16) -> Result<(), DriveError>>,
+    /// Upgrade anything that belongs to the whole pool, once every disk is done.
+    ///
+    /// Same deal, this can run more than once.
+    pub(super) pool: Option<fn() -> Result<(), DriveError>>,
+}
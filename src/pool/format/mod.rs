@@ -0,0 +1,4 @@
+pub(crate) mod format_struct;
+pub(crate) mod format_methods;
+#[cfg(test)]
+mod tests;
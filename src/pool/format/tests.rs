@@ -0,0 +1,171 @@
+// What year is it?
+// Unwrapping is okay here, since we want unexpected outcomes to fail tests.
+#![allow(clippy::unwrap_used)]
+
+use std::path::{Path, PathBuf};
+
+use test_log::test;
+
+use crate::{
+    filesystem::filesystem_struct::{
+        FilesystemOptions,
+        FlusterFS
+    },
+    pool::{
+        disk::{
+            generic::{
+                generic_structs::pointer_struct::DiskPointer,
+                io::cache::cache_io::CachedBlockIO
+            },
+            pool_disk::block::header::header_struct::{
+                CompatFeatures,
+                IncompatFeatures,
+                ItemCounts,
+                PoolDiskHeader,
+                RoCompatFeatures
+            },
+            standard_disk::block::{
+                header::header_struct::StandardDiskHeader,
+                io::directory::tests::{
+                    get_filesystem,
+                    get_new_temp_dir
+                }
+            }
+        },
+        manifest::manifest_methods::count_used,
+        placement::placement_struct::Placement,
+        pool_actions::pool_struct::{
+            Pool,
+            GLOBAL_POOL
+        }
+    }
+};
+
+use super::format_struct::{
+    FormatProblem,
+    FORMAT_VERSION
+};
+
+fn pool_header() -> PoolDiskHeader {
+    GLOBAL_POOL.get().unwrap().lock().unwrap().header
+}
+
+fn disk_header(disk: u16) -> StandardDiskHeader {
+    StandardDiskHeader::from_block(&CachedBlockIO::read_block(DiskPointer { disk, block: 0 }).unwrap())
+}
+
+/// New pools are the newest version, and a fresh header from disk says so too.
+#[test]
+fn new_pools_are_current() {
+    let _fs = get_filesystem();
+    assert_eq!(pool_header().format_version, FORMAT_VERSION);
+    assert_eq!(disk_header(1).format_version, FORMAT_VERSION);
+    assert_eq!(PoolDiskHeader::read().unwrap().format_version, FORMAT_VERSION);
+    assert!(!Pool::migrate().unwrap());
+}
+
+/// Anything from the future is turned away, unless it's harmless.
+#[test]
+fn refuses_the_unknown() {
+    let _fs = get_filesystem();
+    let header = pool_header();
+    assert_eq!(header.check_format(), Ok(()));
+
+    let mut newer = header;
+    newer.format_version = FORMAT_VERSION + 1;
+    assert_eq!(newer.check_format(), Err(FormatProblem::TooNew(FORMAT_VERSION + 1)));
+
+    let mut incompat = header;
+    incompat.incompat = IncompatFeatures::Holes | IncompatFeatures::from_bits_retain(0x8000);
+    assert_eq!(incompat.check_format(), Err(FormatProblem::UnknownIncompat(IncompatFeatures::from_bits_retain(0x8000))));
+
+    let mut ro_compat = header;
+    ro_compat.ro_compat = RoCompatFeatures::from_bits_retain(0x4000);
+    assert_eq!(ro_compat.check_format(), Err(FormatProblem::UnknownRoCompat(RoCompatFeatures::from_bits_retain(0x4000))));
+
+    let mut compat = header;
+    compat.compat = CompatFeatures::from_bits_retain(0x2000);
+    assert_eq!(compat.check_format(), Ok(()));
+}
+
+/// Using a feature marks it in the header, and it sticks around.
+#[test]
+fn features_get_noted() {
+    let _fs = get_filesystem();
+    assert!(pool_header().incompat.is_empty());
+    let mut root = Pool::get_root_directory().unwrap();
+    let file = root.new_file("holey.bin".to_string()).unwrap();
+    let _ = file.write_file(&[1u8; 5000], 0).unwrap();
+    file.punch_hole(0, 2000).unwrap();
+    assert!(pool_header().incompat.contains(IncompatFeatures::Holes));
+
+    // Taking a placement away doesn't need anything new.
+    let _ = root.make_directory("pinned".to_string()).unwrap();
+    assert!(Placement::set_on_directory(Path::new("/pinned"), None).unwrap());
+    assert!(!pool_header().incompat.contains(IncompatFeatures::Placement));
+    assert!(Placement::set_on_directory(Path::new("/pinned"), Some(Placement::Together)).unwrap());
+    assert!(pool_header().incompat.contains(IncompatFeatures::Placement));
+
+    Pool::flush().unwrap();
+    assert!(PoolDiskHeader::read().unwrap().incompat.contains(IncompatFeatures::Holes));
+    assert!(PoolDiskHeader::read().unwrap().incompat.contains(IncompatFeatures::Placement));
+}
+
+/// Pools from before versions get every disk stamped and everything counted, and are only marked done at the end.
+#[test]
+fn upgrades_old_pools() {
+    let _fs = get_filesystem();
+    let mut root = Pool::get_root_directory().unwrap();
+    let _ = root.new_file("old.txt".to_string()).unwrap();
+
+    // Wind the clock back.
+    let mut old_disk = disk_header(1);
+    old_disk.format_version = 0;
+    CachedBlockIO::update_block(&old_disk.to_block()).unwrap();
+    {
+        let header = &mut GLOBAL_POOL.get().unwrap().lock().unwrap().header;
+        header.format_version = 0;
+        header.items = ItemCounts::default();
+        header.pool_standard_blocks_free = 0;
+    }
+    CachedBlockIO::flush().unwrap();
+    Pool::flush().unwrap();
+    assert_eq!(PoolDiskHeader::read().unwrap().format_version, 0);
+
+    assert!(Pool::migrate().unwrap());
+    assert_eq!(disk_header(1).format_version, 1);
+    assert_eq!(PoolDiskHeader::read().unwrap().format_version, FORMAT_VERSION);
+    assert!(!Pool::migrate().unwrap());
+
+    // The counts were worked out from scratch.
+    let header = PoolDiskHeader::read().unwrap();
+    assert_eq!(header.items, ItemCounts { files: 1, directories: 1 });
+    assert_eq!(header.pool_standard_blocks_free, 2880 - count_used(&disk_header(1).block_usage_map));
+}
+
+/// mkfs sets up every disk it's asked for, names them, and marks the features it was given.
+#[test]
+fn mkfs_makes_every_disk() {
+    let temp_dir = get_new_temp_dir();
+    let options = FilesystemOptions::new(Some(temp_dir.path().to_path_buf()), PathBuf::new(), Some(false), false)
+        .with_pool_name(Some("Floppy farm".to_string()))
+        .with_trash(Some(true));
+    let _fs = FlusterFS::mkfs(&options, 3);
+
+    let header = PoolDiskHeader::read().unwrap();
+    assert_eq!(header.highest_known_disk, 3);
+    assert!(header.compat.contains(CompatFeatures::Trash));
+    assert!(header.incompat.is_empty());
+    for disk in 1..=3 {
+        assert_eq!(disk_header(disk).pool_label, "Floppy farm");
+    }
+}
+
+/// Blank disks don't get formatted just by mounting them.
+#[test]
+#[should_panic(expected = "Tried to mount a blank disk!")]
+fn mounting_blank_fails() {
+    let temp_dir = get_new_temp_dir();
+    let options = FilesystemOptions::new(Some(temp_dir.path().to_path_buf()), PathBuf::new(), Some(false), false);
+    let _fs = FlusterFS::start(&options);
+}
@@ -1,10 +1,18 @@
 // Pool level block allocations
 
 
-use log::{debug, error};
+use log::{debug, error, warn};
+
+use super::dense::free_dense_blocks;
 
 use crate::{
-    error_types::drive::DriveError, pool::{
+    error_types::drive::DriveError,
+    filesystem::filesystem_struct::{
+        AUTO_GROW,
+        MAX_DISKS
+    },
+    pool::{
+        dedup::dedup_struct::DedupIndex,
         disk::{
             generic::{
                 block::{
@@ -20,10 +28,15 @@ use crate::{
             },
             standard_disk::standard_disk_struct::StandardDisk,
         },
+        placement::placement_struct::{
+            ActivePlacement,
+            Placement
+        },
         pool_actions::pool_struct::{
             Pool,
             GLOBAL_POOL
         },
+        trash::trash_struct::Trash,
     },
     tui::{
         notify::NotifyTui,
@@ -36,7 +49,7 @@ use crate::{
 // If they dont exist at this stage, we're cooked regardless and must exit.
 macro_rules! get_pool {
     () => {
-        if let Ok(innards) = GLOBAL_POOL.get().expect("Global pool should be created at this stage!").try_lock() {
+        if let Ok(innards) = GLOBAL_POOL.get().expect("Global pool should be created at this stage!").lock() {
             innards
         } else {
             // Somebody peed in the pool.
@@ -67,7 +80,15 @@ impl Pool {
     /// You can optionally also set the CRC on the new empty blocks, which is useful for
     /// new file blocks.
     /// 
-    /// Will add new disks if needed.
+    /// Will add new disks if needed, after purging the trash.
+    /// Since purging deletes things, callers should not hold onto copies of blocks that anything
+    /// in the trash could be using while allocating.
+    /// 
+    /// If the pool isn't allowed to grow any more, every block this call already took is given back, and
+    /// this returns `DriveError::NoSpace`.
+    /// 
+    /// Follows the placement of the current thread, if one was set with a `PlacementGuard`.
+    /// If the pinned disks are full, the rest of the blocks come from wherever they usually would.
     /// 
     /// May swap disks, will not return to where it started.
     /// 
@@ -91,6 +112,27 @@ impl Pool {
     pub fn free_pool_block_from_disk(blocks: &[DiskPointer]) -> Result<u16, DriveError> {
         go_deallocate_pool_block(blocks)
     }
+
+    /// Are we allowed to add another disk to the pool, given the highest disk it has right now?
+    /// 
+    /// Growing can be turned off entirely, or capped at a number of disks (not counting the pool disk).
+    pub(crate) fn can_grow(highest_disk: u16) -> bool {
+        if !*AUTO_GROW.get().unwrap_or(&true) {
+            return false;
+        }
+        MAX_DISKS.get().is_none_or(|max| highest_disk < *max)
+    }
+
+    /// Could the pool fit this many more blocks, either in what's already free, or by growing?
+    /// 
+    /// Doesn't count anything that purging the trash would free up.
+    pub(crate) fn has_room_for(blocks: u32) -> bool {
+        let (free, highest_disk) = {
+            let header = get_pool!().header;
+            (header.pool_standard_blocks_free, header.highest_known_disk)
+        };
+        free >= blocks || Pool::can_grow(highest_disk)
+    }
 }
 
 fn go_find_free_pool_blocks(blocks: u16, add_crc: bool) -> Result<Vec<DiskPointer>, DriveError> {
@@ -105,14 +147,54 @@ fn go_find_free_pool_blocks(blocks: u16, add_crc: bool) -> Result<Vec<DiskPointe
         (header.disk_with_next_free_block, header.highest_known_disk)
     };
 
-    // First we open up the disk with the most recent successful pool allocation
-    let mut disk_to_check = probable_disk;
+    let placement: Option<ActivePlacement> = ActivePlacement::current();
+
+    // First we open up the disk with the most recent successful pool allocation, unless
+    // we're keeping things together, in which case we start where the directory lives.
+    let mut disk_to_check = match placement {
+        Some(ActivePlacement { placement: Placement::Together, home_disk }) => home_disk,
+        _ => probable_disk,
+    };
     let mut new_highest_disk = highest_disk; // We may create new disks during the process.
 
     // We will assume that we will be getting as many blocks as we need, so we can pre-allocate the
     // vec.
     let mut free_blocks: Vec<DiskPointer> = Vec::with_capacity(blocks.into());
 
+    // Pinned disks get first dibs.
+    if let Some(ActivePlacement { placement: Placement::Disks { first, last }, .. }) = placement {
+        debug!("Allocating on pinned disks {first} through {last} first...");
+        for pinned in first..=last {
+            if free_blocks.len() == blocks as usize {
+                break;
+            }
+            if pinned <= new_highest_disk && Pool::is_dense_disk(pinned) {
+                continue;
+            }
+            let disk: Box<dyn BlockAllocation> = match open_for_allocation(pinned, &mut new_highest_disk) {
+                Ok(ok) => ok,
+                Err(DriveError::NoSpace) => {
+                    // Can't reach the rest of the pinned disks, so whatever is left goes elsewhere.
+                    warn!("Pinned disk {pinned} doesn't exist, and the pool can't grow to reach it.");
+                    break;
+                },
+                Err(error) => return Err(error),
+            };
+            let mut found = take_blocks(disk, pinned, blocks - free_blocks.len() as u16, add_crc)?;
+            NotifyTui::complete_multiple_task_steps(&handle, found.len() as u64);
+            free_blocks.append(&mut found);
+        }
+
+        if free_blocks.len() == blocks as usize {
+            // Everything fit, and we didn't touch the usual spot.
+            free_blocks.sort_unstable_by_key(|pointer| (pointer.disk, pointer.block));
+            debug!("Allocation complete.");
+            NotifyTui::finish_task(handle);
+            return Ok(free_blocks);
+        }
+        warn!("Pinned disks {first} through {last} are full, the other {} blocks will go elsewhere.", blocks as usize - free_blocks.len());
+    }
+
     // Make sure the highest disks and disk to check are valid.
     // Getting here is a bad sign, allowing this continue is a worse one.
     // We will just give up if such an event transpires.
@@ -129,8 +211,25 @@ fn go_find_free_pool_blocks(blocks: u16, add_crc: bool) -> Result<Vec<DiskPointe
         // Since we use a mix of real and fake disks in here, we need to have a type that we can use for our allocation
         // methods. So we will box it up. Yes this is kinda evil.
         let mut disk: Box<dyn BlockAllocation>;
+        // Dense disks belong to a single file, nothing else can go there.
+        if disk_to_check <= new_highest_disk && Pool::is_dense_disk(disk_to_check) {
+            disk_to_check += 1;
+            continue;
+        }
         // Check if the disk we are about to load is out of range
         if disk_to_check > new_highest_disk {
+            // Anything in the trash has to go before we ask for another disk.
+            if Trash::purge_oldest()? {
+                debug!("Ran out of room, purged something from the trash. Looking again...");
+                disk_to_check = 1;
+                continue;
+            }
+            if !Pool::can_grow(new_highest_disk) {
+                warn!("Ran out of room, and the pool isn't allowed to grow. Giving back {} blocks.", free_blocks.len());
+                give_back(free_blocks)?;
+                NotifyTui::finish_task(handle);
+                return Err(DriveError::NoSpace);
+            }
             debug!("Ran out of room, creating new disk...");
             // We need to make this disk before trying to allocate blocks on it.
             let new_disk: StandardDisk = Pool::new_disk::<StandardDisk>()?;
@@ -162,8 +261,8 @@ fn go_find_free_pool_blocks(blocks: u16, add_crc: bool) -> Result<Vec<DiskPointe
                 // Trust me I found out the hard way.
                 debug!("Updating the pool's free block count...");
                 {
-                    let mut header = get_pool!().header;
-                    header.pool_standard_blocks_free -= ok.len() as u32;
+                    let header = &mut get_pool!().header;
+                    header.pool_standard_blocks_free = header.pool_standard_blocks_free.saturating_sub(ok.len() as u32);
                 }
 
                 // Add crc to blocks if requested.
@@ -200,8 +299,8 @@ fn go_find_free_pool_blocks(blocks: u16, add_crc: bool) -> Result<Vec<DiskPointe
                 // Trust me I found out the hard way.
                 debug!("Updating the pool's free block count...");
                 {
-                    let mut header = get_pool!().header;
-                    header.pool_standard_blocks_free -= blockie_doos.len() as u32;
+                    let header = &mut get_pool!().header;
+                    header.pool_standard_blocks_free = header.pool_standard_blocks_free.saturating_sub(blockie_doos.len() as u32);
                 }
                 
                 // Add crc to blocks if requested
@@ -220,7 +319,8 @@ fn go_find_free_pool_blocks(blocks: u16, add_crc: bool) -> Result<Vec<DiskPointe
     }
 
     // Now that we have allocated, the most probable disk is the last disk we got blocks from.
-    {
+    // Unless we started somewhere else to keep things together, then we could have skipped right over free blocks.
+    if !matches!(placement, Some(ActivePlacement { placement: Placement::Together, .. })) {
         let header = &mut get_pool!().header;
         header.disk_with_next_free_block = disk_to_check;
     }
@@ -235,6 +335,61 @@ fn go_find_free_pool_blocks(blocks: u16, add_crc: bool) -> Result<Vec<DiskPointe
     Ok(free_blocks)
 }
 
+/// Open a disk to allocate on, creating it (and any disks before it) if the pool doesn't reach that far yet.
+fn open_for_allocation(disk_number: u16, highest_disk: &mut u16) -> Result<Box<dyn BlockAllocation>, DriveError> {
+    if disk_number <= *highest_disk {
+        return Ok(Box::new(CachedAllocationDisk::open(disk_number)?));
+    }
+    loop {
+        if !Pool::can_grow(*highest_disk) {
+            return Err(DriveError::NoSpace);
+        }
+        debug!("Pinned disk {disk_number} doesn't exist yet, creating new disk...");
+        let new_disk: StandardDisk = Pool::new_disk::<StandardDisk>()?;
+        *highest_disk += 1;
+        if *highest_disk == disk_number {
+            return Ok(Box::new(new_disk));
+        }
+    }
+}
+
+/// Free blocks from a failed allocation, since nothing is using them yet.
+fn give_back(mut blocks: Vec<DiskPointer>) -> Result<(), DriveError> {
+    blocks.sort_unstable_by_key(|pointer| (pointer.disk, pointer.block));
+    for chunk in blocks.chunk_by(|a, b| a.disk == b.disk) {
+        let _ = go_deallocate_pool_block(chunk)?;
+    }
+    Ok(())
+}
+
+/// Grab as many of the blocks we want as a disk has free, and mark them as used.
+///
+/// Returns the blocks we got, which may be none of them.
+fn take_blocks(mut disk: Box<dyn BlockAllocation>, disk_number: u16, wanted: u16, add_crc: bool) -> Result<Vec<DiskPointer>, DriveError> {
+    let found: Vec<u16> = match disk.find_free_blocks(wanted) {
+        Ok(ok) => ok,
+        Err(0) => return Ok(Vec::new()),
+        Err(amount) => disk.find_free_blocks(amount).expect("Disk should not change its mind about how many blocks it has free"),
+    };
+    debug!("Got {} blocks from disk {disk_number}.", found.len());
+
+    let _ = disk.allocate_blocks(&found)?;
+    // Flush the new allocation table.
+    drop(disk);
+
+    {
+        let header = &mut get_pool!().header;
+        header.pool_standard_blocks_free = header.pool_standard_blocks_free.saturating_sub(found.len() as u32);
+    }
+
+    if add_crc {
+        debug!("CRC requested, adding...");
+        write_empty_crc(&found, disk_number)?;
+    }
+
+    Ok(block_indexes_to_pointers(&found, disk_number))
+}
+
 // helper
 fn block_indexes_to_pointers(blocks: &Vec<u16>, disk: u16) -> Vec<DiskPointer> {
     // We will have as many pointers as we got blocks in.
@@ -297,6 +452,16 @@ fn go_deallocate_pool_block(blocks: &[DiskPointer]) -> Result<u16, DriveError> {
         panic!("Pool deallocation attempted to free blocks from multiple different disks at once! Not allowed!");
     }
 
+    // Freed blocks can't be shared anymore, otherwise the next allocation could inherit someone else's references.
+    DedupIndex::forget(blocks);
+
+    // Dense disks go back all at once.
+    if Pool::is_dense_disk(starter.disk) {
+        let freed = free_dense_blocks(blocks)?;
+        NotifyTui::finish_task(handle);
+        return Ok(freed);
+    }
+
     let mut extracted_blocks: Vec<u16> = Vec::with_capacity(blocks.len());
     for block in blocks {
         // Hold onto the block number, need it for disk call.
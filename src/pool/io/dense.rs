@@ -0,0 +1,169 @@
+// Whole disks for whole files. Well, most of a file.
+
+use log::debug;
+
+use crate::{
+    error_types::drive::DriveError,
+    pool::{
+        dedup::dedup_struct::DedupIndex,
+        disk::{
+            dense_disk::dense_disk_struct::{
+                DenseDisk,
+                DENSE_DISK_BLOCKS
+            },
+            drive_struct::{
+                DiskBootstrap,
+                DiskType,
+                FloppyDrive
+            },
+            generic::{
+                disk_trait::GenericDiskMethods,
+                generic_structs::pointer_struct::DiskPointer,
+                io::{
+                    cache::cache_io::CachedBlockIO,
+                    wipe::destroy_disk
+                }
+            },
+            standard_disk::standard_disk_struct::StandardDisk
+        },
+        pool_actions::pool_struct::{
+            Pool,
+            GLOBAL_POOL
+        }
+    }
+};
+
+impl Pool {
+    /// Is this disk a dense disk?
+    pub fn is_dense_disk(disk: u16) -> bool {
+        // Slot 0 means empty, and disk 0 is the pool disk, so that can never match by accident.
+        disk != 0 && get_dense_disks().contains(&disk)
+    }
+
+    /// Create up to `count` new dense disks for a file to use.
+    ///
+    /// Stops early if the pool header has no more room to track dense disks, or the pool isn't
+    /// allowed to grow, so you may get fewer disks than you asked for.
+    ///
+    /// Returns the numbers of the new disks.
+    pub fn claim_dense_disks(count: usize) -> Result<Vec<u16>, DriveError> {
+        go_claim_dense_disks(count)
+    }
+}
+
+/// Free blocks that live on a dense disk.
+///
+/// Dense disks are only ever given back as a whole, which turns them back into a standard disk.
+/// Anything less than the whole disk is remembered in the dedup index, and the disk goes back once
+/// the rest of it is freed too. This happens when a snapshot and a file each hold onto part of a disk.
+///
+/// Returns how many blocks were freed.
+pub(super) fn free_dense_blocks(blocks: &[DiskPointer]) -> Result<u16, DriveError> {
+    let disk = blocks[0].disk;
+    let unused = DedupIndex::bury(blocks);
+    if unused != DENSE_DISK_BLOCKS as usize {
+        debug!("Dense disk {disk} has {unused} unused blocks, holding onto it until the rest are freed.");
+        return Ok(0);
+    }
+    DedupIndex::forget(&DenseDisk::data_pointers(disk));
+    release_dense_disk(disk)?;
+    Ok(DENSE_DISK_BLOCKS)
+}
+
+fn get_dense_disks() -> Vec<u16> {
+    GLOBAL_POOL
+        .get()
+        .expect("Pool must exist to look at its disks.")
+        .lock()
+        .expect("Other mutex holders should not panic.")
+        .header
+        .dense_disks
+        .iter()
+        .copied()
+        .filter(|disk| *disk != 0)
+        .collect()
+}
+
+fn go_claim_dense_disks(count: usize) -> Result<Vec<u16>, DriveError> {
+    let mut claimed: Vec<u16> = Vec::with_capacity(count);
+    for _ in 0..count {
+        // Make sure we can remember it before making it.
+        let (has_room, highest_disk) = {
+            let header = &GLOBAL_POOL
+                .get()
+                .expect("Pool must exist to add disks to it.")
+                .lock()
+                .expect("Other mutex holders should not panic.")
+                .header;
+            (header.dense_disks.contains(&0), header.highest_known_disk)
+        };
+        if !has_room {
+            debug!("No room left to track dense disks, falling back to standard blocks.");
+            break;
+        }
+        if !Pool::can_grow(highest_disk) {
+            debug!("The pool can't grow, falling back to standard blocks.");
+            break;
+        }
+
+        let disk: DenseDisk = Pool::new_disk::<DenseDisk>()?;
+        debug!("Claimed dense disk {}.", disk.number);
+
+        let header = &mut GLOBAL_POOL
+            .get()
+            .expect("Pool must exist to add disks to it.")
+            .lock()
+            .expect("Other mutex holders should not panic.")
+            .header;
+        let slot = header.dense_disks
+            .iter_mut()
+            .find(|slot| **slot == 0)
+            .expect("Already checked for room.");
+        *slot = disk.number;
+        claimed.push(disk.number);
+    }
+    Ok(claimed)
+}
+
+/// Turn a dense disk back into an empty standard disk.
+fn release_dense_disk(disk_number: u16) -> Result<(), DriveError> {
+    debug!("Releasing dense disk {disk_number}...");
+
+    // Nothing on this disk is worth keeping, including anything that hasn't been written yet.
+    for block in 0..2880 {
+        CachedBlockIO::remove_block(&DiskPointer {
+            disk: disk_number,
+            block,
+        });
+    }
+
+    #[allow(deprecated)] // The cache has nothing for this disk anymore.
+    let mut disk: DenseDisk = match FloppyDrive::open(disk_number)? {
+        DiskType::Dense(dense_disk) => dense_disk,
+        _ => unreachable!("Pool thinks disk {disk_number} is dense, but it isn't!"),
+    };
+
+    // Start over from a blank disk.
+    destroy_disk(disk.disk_file_mut())?;
+    // This also adds the new free blocks to the pool.
+    let _ = StandardDisk::bootstrap(disk.disk_file(), disk_number)?;
+
+    let header = &mut GLOBAL_POOL
+        .get()
+        .expect("Pool must exist to release disks.")
+        .lock()
+        .expect("Other mutex holders should not panic.")
+        .header;
+    for slot in header.dense_disks.iter_mut() {
+        if *slot == disk_number {
+            *slot = 0;
+        }
+    }
+    // There's a bunch of free space here now.
+    if header.disk_with_next_free_block > disk_number {
+        header.disk_with_next_free_block = disk_number;
+    }
+
+    debug!("Dense disk {disk_number} is now a standard disk.");
+    Ok(())
+}
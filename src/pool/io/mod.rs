@@ -1 +1,2 @@
 pub mod allocate;
+pub mod dense;
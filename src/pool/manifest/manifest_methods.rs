@@ -0,0 +1,291 @@
+// Walk the whole pool, write down where everything is.
+
+// Imports
+
+use std::{
+    collections::{
+        BTreeMap,
+        BTreeSet
+    },
+    fmt::Write,
+    path::Path
+};
+
+use log::{debug, warn};
+
+use crate::{
+    error_types::drive::DriveError,
+    filesystem::filesystem_struct::POOL_NAME,
+    pool::{
+        disk::{
+            generic::{
+                generic_structs::pointer_struct::DiskPointer,
+                io::cache::cache_io::CachedBlockIO
+            },
+            standard_disk::block::{
+                directory::directory_struct::{
+                    DirectoryBlock,
+                    DirectoryItem,
+                    DirectoryItemFlags
+                },
+                header::header_struct::{
+                    StandardDiskHeader,
+                    StandardHeaderFlags
+                }
+            }
+        },
+        pool_actions::pool_struct::{
+            Pool,
+            GLOBAL_POOL
+        }
+    }
+};
+
+use super::manifest_struct::{
+    DiskManifest,
+    PoolManifest,
+    DEFAULT_POOL_NAME
+};
+
+/// How many items a label lists before giving up and saying how many are left.
+const LABEL_ITEMS: usize = 4;
+/// How many characters fit on a line of a text label, between the borders.
+const LABEL_WIDTH: usize = 32;
+
+// Implementations
+
+impl PoolManifest {
+    /// Walk every file and directory in the pool, and work out which disks they live on.
+    ///
+    /// Reads every directory, inode and extent in the pool, and every disk header, so expect swaps.
+    pub(crate) fn build() -> Result<PoolManifest, DriveError> {
+        go_build_manifest()
+    }
+
+    /// The full manifest, every disk followed by everything on it.
+    pub(crate) fn report(&self) -> String {
+        let mut report = format!("Pool: {}\n", self.pool_name);
+        for disk in &self.disks {
+            let _ = writeln!(report, "\nDisk {} ({}) - {} used, {} free", disk.number, disk.kind, disk.used, disk.free);
+            for item in &disk.items {
+                let _ = writeln!(report, "  {item}");
+            }
+        }
+        report
+    }
+
+    /// Plain text labels for every disk, one after another.
+    pub(crate) fn label_sheet_text(&self) -> String {
+        let border = format!("+{}+\n", "-".repeat(LABEL_WIDTH + 2));
+        let mut sheet = String::new();
+        for disk in &self.disks {
+            sheet.push_str(&border);
+            for line in self.label_lines(disk) {
+                let _ = writeln!(sheet, "| {line:<LABEL_WIDTH$} |");
+            }
+            sheet.push_str(&border);
+            sheet.push('\n');
+        }
+        sheet
+    }
+
+    /// Labels for every disk as an SVG, two to a row, sized for 3.5" floppy labels.
+    pub(crate) fn label_sheet_svg(&self) -> String {
+        // Everything is in millimeters.
+        const LABEL_WIDE: usize = 70;
+        const LABEL_TALL: usize = 54;
+        const MARGIN: usize = 10;
+        const LINE_HEIGHT: usize = 6;
+
+        let rows = self.disks.len().div_ceil(2);
+        let width = MARGIN * 3 + LABEL_WIDE * 2;
+        let height = MARGIN + rows * (LABEL_TALL + MARGIN);
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}mm\" height=\"{height}mm\" viewBox=\"0 0 {width} {height}\">\n"
+        );
+        for (index, disk) in self.disks.iter().enumerate() {
+            let x = MARGIN + (index % 2) * (LABEL_WIDE + MARGIN);
+            let y = MARGIN + (index / 2) * (LABEL_TALL + MARGIN);
+            let _ = writeln!(
+                svg,
+                "  <rect x=\"{x}\" y=\"{y}\" width=\"{LABEL_WIDE}\" height=\"{LABEL_TALL}\" rx=\"3\" fill=\"none\" stroke=\"black\" stroke-width=\"0.4\"/>"
+            );
+            for (line_number, line) in self.label_lines(disk).iter().enumerate() {
+                // The pool name and disk number are the important bits.
+                let (size, weight) = if line_number < 2 { (5, "bold") } else { (3, "normal") };
+                let _ = writeln!(
+                    svg,
+                    "  <text x=\"{}\" y=\"{}\" font-family=\"monospace\" font-size=\"{size}\" font-weight=\"{weight}\">{}</text>",
+                    x + 4,
+                    y + 8 + line_number * LINE_HEIGHT,
+                    escape_xml(line)
+                );
+            }
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// Write the manifest and both label sheets into a directory.
+    pub(crate) fn write_files(&self, directory: &Path) -> std::io::Result<()> {
+        std::fs::create_dir_all(directory)?;
+        std::fs::write(directory.join("manifest.txt"), self.report())?;
+        std::fs::write(directory.join("labels.txt"), self.label_sheet_text())?;
+        std::fs::write(directory.join("labels.svg"), self.label_sheet_svg())?;
+        Ok(())
+    }
+
+    /// The lines that go on a disk's label, each one short enough to fit.
+    fn label_lines(&self, disk: &DiskManifest) -> Vec<String> {
+        let files = disk.items.iter().filter(|item| !item.ends_with('/')).count();
+        let directories = disk.items.len() - files;
+        let mut lines: Vec<String> = vec![
+            self.pool_name.clone(),
+            format!("Disk {} of {}", disk.number, self.disks.len() - 1),
+            format!("{} - {} used, {} free", disk.kind, disk.used, disk.free),
+            format!("{files} files, {directories} directories"),
+        ];
+        lines.extend(disk.items.iter().take(LABEL_ITEMS).cloned());
+        if disk.items.len() > LABEL_ITEMS {
+            lines.push(format!("...and {} more", disk.items.len() - LABEL_ITEMS));
+        }
+        lines.into_iter().map(|line| fit_to_label(&line)).collect()
+    }
+}
+
+/// Write the manifest and labels into a directory.
+///
+/// This is best effort, failing to write it never stops an unmount.
+pub(crate) fn update_manifest(directory: &Path) {
+    debug!("Updating the disk manifest...");
+    let manifest = match PoolManifest::build() {
+        Ok(ok) => ok,
+        Err(error) => {
+            warn!("Couldn't build the disk manifest, skipping it. Error: {error}");
+            return;
+        },
+    };
+    if let Err(error) = manifest.write_files(directory) {
+        warn!("Couldn't write the disk manifest to {}. Error: {error}", directory.display());
+        return;
+    }
+    debug!("Done.");
+}
+
+// Functions
+
+fn go_build_manifest() -> Result<PoolManifest, DriveError> {
+    let (highest_disk, pool_used) = {
+        let pool = GLOBAL_POOL
+            .get()
+            .expect("Pool must exist to list what's in it.")
+            .lock()
+            .expect("Other mutex holders should not panic.");
+        (pool.header.highest_known_disk, count_used(&pool.header.block_usage_map))
+    };
+
+    // Sets keep everything sorted, and stop items from being listed twice on a disk.
+    let mut items_by_disk: BTreeMap<u16, BTreeSet<String>> = BTreeMap::new();
+
+    let mut to_visit: Vec<(String, DirectoryItem)> = vec![("/".to_string(), Pool::get_root_directory_item())];
+    while let Some((path, item)) = to_visit.pop() {
+        let mut disks: BTreeSet<u16> = BTreeSet::new();
+        // The inode counts as a block of the item.
+        let _ = disks.insert(item.location.pointer.disk);
+
+        if item.flags.contains(DirectoryItemFlags::IsDirectory) {
+            let head: DirectoryBlock = item.get_directory_block()?;
+            disks.extend(directory_chain_disks(head.block_origin)?);
+            for child in head.list()? {
+                let child_path = if child.flags.contains(DirectoryItemFlags::IsDirectory) {
+                    format!("{path}{}/", child.name)
+                } else {
+                    format!("{path}{}", child.name)
+                };
+                to_visit.push((child_path, child));
+            }
+        } else {
+            let file = item.get_inode()?.extract_file().expect("Files should have a file in their inode.");
+            disks.extend(
+                file.as_pointers()?
+                    .into_iter()
+                    .filter(|pointer| !pointer.no_destination())
+                    .map(|pointer| pointer.disk)
+            );
+        }
+
+        for disk in disks {
+            let _ = items_by_disk.entry(disk).or_default().insert(path.clone());
+        }
+    }
+
+    let mut disks: Vec<DiskManifest> = Vec::with_capacity(highest_disk as usize + 1);
+    disks.push(DiskManifest {
+        number: 0,
+        kind: "pool",
+        used: pool_used,
+        free: 2880 - pool_used,
+        items: items_by_disk.remove(&0).unwrap_or_default().into_iter().collect(),
+    });
+    for number in 1..=highest_disk {
+        let header: StandardDiskHeader = StandardDiskHeader::from_block(&CachedBlockIO::read_block(DiskPointer {
+            disk: number,
+            block: 0,
+        })?);
+        let kind: &'static str = if header.flags.contains(StandardHeaderFlags::Dense) {
+            "dense"
+        } else if header.flags.contains(StandardHeaderFlags::Marker) {
+            "standard"
+        } else {
+            "unknown"
+        };
+        let used = count_used(&header.block_usage_map);
+        disks.push(DiskManifest {
+            number,
+            kind,
+            used,
+            free: 2880 - used,
+            items: items_by_disk.remove(&number).unwrap_or_default().into_iter().collect(),
+        });
+    }
+
+    Ok(PoolManifest {
+        pool_name: POOL_NAME.get().map_or(DEFAULT_POOL_NAME, |name| name.as_str()).to_string(),
+        disks,
+    })
+}
+
+/// Every disk a chain of directory blocks touches.
+fn directory_chain_disks(head: DiskPointer) -> Result<BTreeSet<u16>, DriveError> {
+    let mut disks: BTreeSet<u16> = BTreeSet::new();
+    let mut current: DiskPointer = head;
+    while !current.no_destination() {
+        let _ = disks.insert(current.disk);
+        current = DirectoryBlock::from_block(&CachedBlockIO::read_block(current)?).next_block;
+    }
+    Ok(disks)
+}
+
+/// How many blocks a block usage map has marked as used.
+pub(crate) fn count_used(usage_map: &[u8]) -> u32 {
+    usage_map.iter().map(|byte| byte.count_ones()).sum()
+}
+
+/// Cut a line down to the width of a label, keeping the end of it, since that's the interesting
+/// part of a path.
+fn fit_to_label(line: &str) -> String {
+    let length = line.chars().count();
+    if length <= LABEL_WIDTH {
+        return line.to_string();
+    }
+    let tail: String = line.chars().skip(length - (LABEL_WIDTH - 3)).collect();
+    format!("...{tail}")
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,33 @@
+// What's on the disk? Don't ask me, read the label.
+
+// Imports
+
+// Structs, Enums, Flags
+
+/// What pools are called on their labels if nobody named them.
+pub(crate) const DEFAULT_POOL_NAME: &str = "Fluster!";
+
+/// Everything in the pool, sorted by the disk it lives on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PoolManifest {
+    /// The name printed on the labels.
+    pub(crate) pool_name: String,
+    /// Every disk in the pool, in order, starting with the pool disk.
+    pub(crate) disks: Vec<DiskManifest>,
+}
+
+/// Everything on a single disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DiskManifest {
+    /// Which disk this is.
+    pub(crate) number: u16,
+    /// What sort of disk this is, `pool`, `standard`, `dense` or `unknown`.
+    pub(crate) kind: &'static str,
+    /// Blocks in use, according to the disk's block usage map.
+    pub(crate) used: u32,
+    /// Blocks free, according to the disk's block usage map.
+    pub(crate) free: u32,
+    /// Full path of every file and directory with at least one block on this disk, sorted.
+    /// Directories end with a `/`.
+    pub(crate) items: Vec<String>,
+}
@@ -0,0 +1,4 @@
+pub(crate) mod manifest_struct;
+pub(crate) mod manifest_methods;
+#[cfg(test)]
+mod tests;
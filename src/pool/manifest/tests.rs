@@ -0,0 +1,82 @@
+// Checking the labels before they go on the disks.
+// Unwrapping is okay here, since we want unexpected outcomes to fail tests.
+#![allow(clippy::unwrap_used)]
+
+use tempfile::tempdir;
+use test_log::test;
+
+use crate::pool::{
+    disk::standard_disk::block::io::directory::tests::get_filesystem,
+    pool_actions::pool_struct::Pool
+};
+
+use super::manifest_struct::{
+    DiskManifest,
+    PoolManifest
+};
+
+/// Everything should show up on the disk it lives on.
+#[test]
+fn items_are_found() {
+    let _fs = get_filesystem();
+    let mut root = Pool::get_root_directory().unwrap();
+    let _ = root.make_directory("stuff".to_string()).unwrap();
+    let file = root.new_file("notes.txt".to_string()).unwrap();
+    let _ = file.write_file(&[7u8; 4000], 0).unwrap();
+
+    let manifest = PoolManifest::build().unwrap();
+    assert_eq!(manifest.disks[0].number, 0);
+    assert_eq!(manifest.disks[0].kind, "pool");
+
+    let everything: Vec<&String> = manifest.disks.iter().flat_map(|disk| disk.items.iter()).collect();
+    assert!(everything.contains(&&"/".to_string()));
+    assert!(everything.contains(&&"/stuff/".to_string()));
+    assert!(everything.contains(&&"/notes.txt".to_string()));
+
+    // Usage should match up with the disk.
+    for disk in &manifest.disks {
+        assert_eq!(disk.used + disk.free, 2880);
+    }
+    assert!(manifest.report().contains("  /notes.txt\n"));
+}
+
+/// Labels list a few items, then give up.
+#[test]
+fn labels_fit() {
+    let manifest = PoolManifest {
+        pool_name: "Family photos <1998>".to_string(),
+        disks: vec![
+            DiskManifest {
+                number: 0,
+                kind: "pool",
+                used: 10,
+                free: 2870,
+                items: Vec::new(),
+            },
+            DiskManifest {
+                number: 1,
+                kind: "standard",
+                used: 100,
+                free: 2780,
+                items: (0..10).map(|number| format!("/a/very/long/path/that/wont/fit/on/a/label/{number}.jpg")).collect(),
+            },
+        ],
+    };
+
+    let text = manifest.label_sheet_text();
+    assert!(text.contains("| Disk 1 of 1"));
+    assert!(text.contains("...and 6 more"));
+    // Every line is the same width, borders included.
+    let widths: Vec<usize> = text.lines().filter(|line| !line.is_empty()).map(|line| line.chars().count()).collect();
+    assert!(widths.iter().all(|width| *width == widths[0]));
+
+    let svg = manifest.label_sheet_svg();
+    assert!(svg.starts_with("<svg"));
+    assert!(svg.contains("Family photos &lt;1998&gt;"));
+    assert_eq!(svg.matches("<rect").count(), 2);
+
+    let directory = tempdir().unwrap();
+    manifest.write_files(directory.path()).unwrap();
+    assert!(directory.path().join("manifest.txt").exists());
+    assert!(directory.path().join("labels.svg").exists());
+}
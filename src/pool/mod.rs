@@ -1,3 +1,11 @@
 pub(crate) mod disk;
+pub(crate) mod dedup;
+pub(crate) mod fat12;
+pub(crate) mod format;
+pub(crate) mod manifest;
+pub(crate) mod placement;
+pub(crate) mod scrub;
+pub(crate) mod snapshot;
+pub(crate) mod trash;
 pub mod io;
 pub mod pool_actions;
@@ -0,0 +1,4 @@
+pub(crate) mod placement_struct;
+pub(crate) mod placement_methods;
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,209 @@
+// Finding seats, and remembering who asked for which.
+
+// Imports
+
+use std::{
+    fmt::Display,
+    path::{
+        Component,
+        Path
+    }
+};
+
+use log::debug;
+
+use crate::{
+    error_types::drive::DriveError,
+    pool::{
+        disk::standard_disk::block::{
+            directory::directory_struct::{
+                DirectoryBlock,
+                DirectoryItem
+            },
+            io::directory::types::NamedItem
+        },
+        format::format_struct::Feature,
+        pool_actions::pool_struct::Pool
+    }
+};
+
+use super::placement_struct::{
+    ActivePlacement,
+    Placement,
+    PlacementGuard,
+    ACTIVE_PLACEMENT
+};
+
+// Implementations
+
+impl Placement {
+    /// How many bytes a placement takes up on the end of a DirectoryItem.
+    pub(crate) const SIZE: usize = 5;
+
+    /// Read a placement from text, as set through the extended attribute.
+    ///
+    /// Accepts `together`, a single disk like `4`, or a range of disks like `3-5`.
+    /// Disk 0 is the pool disk, and can't hold anything.
+    pub(crate) fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if text == "together" {
+            return Some(Placement::Together);
+        }
+        let (first, last) = text.split_once('-').unwrap_or((text, text));
+        let first: u16 = first.trim().parse().ok()?;
+        let last: u16 = last.trim().parse().ok()?;
+        if first == 0 || first > last {
+            return None;
+        }
+        Some(Placement::Disks { first, last })
+    }
+
+    /// Turn a placement into bytes.
+    ///
+    /// A kind byte, then the first and last disk. Together placements leave the disks zeroed.
+    pub(crate) fn to_bytes(self) -> [u8; Placement::SIZE] {
+        let mut buffer: [u8; Placement::SIZE] = [0u8; Placement::SIZE];
+        match self {
+            Placement::Disks { first, last } => {
+                buffer[1..3].copy_from_slice(&first.to_le_bytes());
+                buffer[3..5].copy_from_slice(&last.to_le_bytes());
+            },
+            Placement::Together => buffer[0] = 1,
+        }
+        buffer
+    }
+
+    /// Read a placement back out of bytes.
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Self {
+        match bytes[0] {
+            0 => Placement::Disks {
+                first: u16::from_le_bytes(bytes[1..3].try_into().expect("2 = 2")),
+                last: u16::from_le_bytes(bytes[3..5].try_into().expect("2 = 2")),
+            },
+            1 => Placement::Together,
+            _ => panic!("Placements should only have known kinds."),
+        }
+    }
+
+    /// Get the placement set on a directory itself, ignoring anything it would inherit.
+    ///
+    /// Returns None if the directory has no placement, or the path is not a directory.
+    pub(crate) fn of_directory(path: &Path) -> Result<Option<Self>, DriveError> {
+        Ok(find_directory_item(path)?.and_then(|(_, item)| item.placement))
+    }
+
+    /// Set or clear the placement on a directory.
+    ///
+    /// Only new allocations follow the placement, nothing already written gets moved.
+    ///
+    /// Returns false if the path is not a directory. The root directory can't have a placement.
+    pub(crate) fn set_on_directory(path: &Path, placement: Option<Self>) -> Result<bool, DriveError> {
+        go_set_on_directory(path, placement)
+    }
+}
+
+impl Display for Placement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Placement::Disks { first, last } if first == last => write!(f, "{first}"),
+            Placement::Disks { first, last } => write!(f, "{first}-{last}"),
+            Placement::Together => write!(f, "together"),
+        }
+    }
+}
+
+impl ActivePlacement {
+    /// The placement allocations on this thread should follow right now, if any.
+    pub(crate) fn current() -> Option<Self> {
+        ACTIVE_PLACEMENT.get()
+    }
+
+    /// Find the placement that applies to things inside of a directory.
+    ///
+    /// This is the placement of the deepest directory on the path that has one.
+    /// Stops early if the path does not exist, since nothing can go there anyways.
+    pub(crate) fn resolve(path: &Path) -> Result<Option<Self>, DriveError> {
+        go_resolve(path)
+    }
+}
+
+impl PlacementGuard {
+    /// Make allocations on this thread follow the placement of a directory, until the guard is dropped.
+    pub(crate) fn enter(directory: &Path) -> Result<Self, DriveError> {
+        let resolved = ActivePlacement::resolve(directory)?;
+        Ok(PlacementGuard {
+            previous: ACTIVE_PLACEMENT.replace(resolved),
+        })
+    }
+}
+
+impl Drop for PlacementGuard {
+    fn drop(&mut self) {
+        ACTIVE_PLACEMENT.set(self.previous);
+    }
+}
+
+// Functions
+
+fn go_resolve(path: &Path) -> Result<Option<ActivePlacement>, DriveError> {
+    let mut current: DirectoryBlock = Pool::get_root_directory()?;
+    let mut found: Option<ActivePlacement> = None;
+
+    for folder in path.components() {
+        let Component::Normal(name) = folder else {
+            continue;
+        };
+        let name: String = name.to_str().expect("Should be valid utf8").to_string();
+        let Some(item) = current.find_item(&NamedItem::Directory(name))? else {
+            break;
+        };
+        let block: DirectoryBlock = item.get_directory_block()?;
+        if let Some(placement) = item.placement {
+            found = Some(ActivePlacement {
+                placement,
+                home_disk: block.block_origin.disk,
+            });
+        }
+        current = block;
+    }
+
+    Ok(found)
+}
+
+fn go_set_on_directory(path: &Path, placement: Option<Placement>) -> Result<bool, DriveError> {
+    let Some((mut parent, item)) = find_directory_item(path)? else {
+        return Ok(false);
+    };
+    if item.placement == placement {
+        // Nothing to do.
+        return Ok(true);
+    }
+
+    debug!("Setting placement of `{}`...", path.display());
+    // The item changes size, so it has to come out and go back in.
+    let named: NamedItem = NamedItem::Directory(item.name.clone());
+    let mut updated = parent.find_and_extract_item(&named)?.expect("We just found it.");
+    updated.placement = placement;
+    if placement.is_some() {
+        Pool::use_feature(Feature::Placement);
+    }
+    parent.add_item(&updated)?;
+    debug!("Done.");
+    Ok(true)
+}
+
+/// Find a directory's item, along with the directory that holds it.
+fn find_directory_item(path: &Path) -> Result<Option<(DirectoryBlock, DirectoryItem)>, DriveError> {
+    // The root directory has no parent to hold an item for it.
+    let (Some(parent_path), Some(name)) = (path.parent(), path.file_name()) else {
+        return Ok(None);
+    };
+    let Some(parent) = DirectoryBlock::try_find_directory(Some(parent_path))? else {
+        return Ok(None);
+    };
+    let name: String = name.to_str().expect("Should be valid utf8").to_string();
+    let Some(item) = parent.find_item(&NamedItem::Directory(name))? else {
+        return Ok(None);
+    };
+    Ok(Some((parent, item)))
+}
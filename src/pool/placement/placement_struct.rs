@@ -0,0 +1,47 @@
+// You sit here, you sit over there, and you two stay together.
+
+// Imports
+
+use std::cell::Cell;
+
+// Structs, Enums, Flags
+
+/// Extended attribute used to read and set the placement of a directory.
+pub(crate) const PLACEMENT_XATTR: &str = "user.fluster.placement";
+
+/// Where a directory would like its contents to end up.
+///
+/// Placement is stored on the directory's DirectoryItem, and applies to everything underneath that directory,
+/// unless a deeper directory has its own placement.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Placement {
+    /// Only use disks `first` through `last`, inclusive.
+    Disks {
+        first: u16,
+        last: u16,
+    },
+    /// Keep everything on as few disks as possible, starting from the disk the directory lives on.
+    Together,
+}
+
+/// The placement the current operation is working under, along with where the directory that asked for it lives.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) struct ActivePlacement {
+    /// What was asked for.
+    pub(crate) placement: Placement,
+    /// The disk the pinned directory's first DirectoryBlock lives on.
+    pub(crate) home_disk: u16,
+}
+
+/// Holds a placement in place for the current thread, until it is dropped.
+///
+/// Puts back whatever placement was there before when dropped, so these can be nested.
+pub(crate) struct PlacementGuard {
+    pub(super) previous: Option<ActivePlacement>,
+}
+
+// The allocator has no idea what directory it is allocating for, so the FUSE layer tells it ahead of time.
+thread_local! {
+    /// The placement allocations on this thread should follow, if any.
+    pub(super) static ACTIVE_PLACEMENT: Cell<Option<ActivePlacement>> = const { Cell::new(None) };
+}
@@ -0,0 +1,105 @@
+// Musical chairs.
+// Unwrapping is okay here, since we want unexpected outcomes to fail tests.
+#![allow(clippy::unwrap_used)]
+
+use std::path::Path;
+
+use test_log::test;
+
+use crate::pool::{
+    disk::standard_disk::block::io::directory::tests::get_filesystem,
+    pool_actions::pool_struct::Pool
+};
+
+use super::placement_struct::{
+    ActivePlacement,
+    Placement,
+    PlacementGuard
+};
+
+#[test]
+fn placement_text() {
+    assert_eq!(Placement::parse("3-5"), Some(Placement::Disks { first: 3, last: 5 }));
+    assert_eq!(Placement::parse(" 4 "), Some(Placement::Disks { first: 4, last: 4 }));
+    assert_eq!(Placement::parse("together"), Some(Placement::Together));
+    assert_eq!(Placement::parse("0-2"), None);
+    assert_eq!(Placement::parse("5-3"), None);
+    assert_eq!(Placement::parse("wherever"), None);
+
+    for placement in [Placement::Disks { first: 3, last: 5 }, Placement::Disks { first: 7, last: 7 }, Placement::Together] {
+        assert_eq!(Placement::parse(&placement.to_string()), Some(placement));
+        assert_eq!(Placement::from_bytes(&placement.to_bytes()), placement);
+    }
+}
+
+/// Placement sticks to the directory, and everything under it inherits it.
+#[test]
+fn placement_is_inherited() {
+    let _fs = get_filesystem();
+    let mut root = Pool::get_root_directory().unwrap();
+    let mut alpha = root.make_directory("alpha".to_string()).unwrap().get_directory_block().unwrap();
+    let _ = alpha.make_directory("beta".to_string()).unwrap();
+
+    let pinned = Placement::Disks { first: 3, last: 5 };
+    assert!(Placement::set_on_directory(Path::new("/alpha"), Some(pinned)).unwrap());
+    assert_eq!(Placement::of_directory(Path::new("/alpha")).unwrap(), Some(pinned));
+    // Only directories below the root.
+    assert!(!Placement::set_on_directory(Path::new("/"), Some(pinned)).unwrap());
+
+    // Beta has nothing of its own, but inherits from alpha.
+    assert_eq!(Placement::of_directory(Path::new("/alpha/beta")).unwrap(), None);
+    let inherited = ActivePlacement::resolve(Path::new("/alpha/beta")).unwrap().unwrap();
+    assert_eq!(inherited.placement, pinned);
+    assert_eq!(ActivePlacement::resolve(Path::new("/")).unwrap(), None);
+
+    // The deepest one wins.
+    assert!(Placement::set_on_directory(Path::new("/alpha/beta"), Some(Placement::Together)).unwrap());
+    let deeper = ActivePlacement::resolve(Path::new("/alpha/beta")).unwrap().unwrap();
+    assert_eq!(deeper.placement, Placement::Together);
+
+    // And it can be taken away again.
+    assert!(Placement::set_on_directory(Path::new("/alpha"), None).unwrap());
+    assert_eq!(Placement::of_directory(Path::new("/alpha")).unwrap(), None);
+    assert_eq!(Placement::of_directory(Path::new("/alpha/beta")).unwrap(), Some(Placement::Together));
+}
+
+/// Allocations go to the pinned disks while the guard is held.
+#[test]
+fn pinned_allocations() {
+    let _fs = get_filesystem();
+    let mut root = Pool::get_root_directory().unwrap();
+    let _ = root.make_directory("alpha".to_string()).unwrap();
+    assert!(Placement::set_on_directory(Path::new("/alpha"), Some(Placement::Disks { first: 3, last: 3 })).unwrap());
+
+    {
+        let _guard = PlacementGuard::enter(Path::new("/alpha")).unwrap();
+        let blocks = Pool::find_and_allocate_pool_blocks(10, false).unwrap();
+        assert!(blocks.iter().all(|block| block.disk == 3));
+
+        // Files made in there follow along.
+        let mut alpha = Pool::get_root_directory().unwrap().change_directory("alpha".to_string()).unwrap().unwrap();
+        let file = alpha.new_file("pinned.txt".to_string()).unwrap();
+        let _ = file.write_file(&[1u8; 2000], 0).unwrap();
+        let pointers = file.get_inode().unwrap().extract_file().unwrap().as_pointers().unwrap();
+        assert!(pointers.iter().all(|block| block.disk == 3));
+    }
+
+    // Dropping the guard puts things back to normal.
+    assert_eq!(ActivePlacement::current(), None);
+}
+
+/// Full pinned disks still hand out blocks, just from somewhere else.
+#[test]
+fn full_pins_fall_back() {
+    let _fs = get_filesystem();
+    let mut root = Pool::get_root_directory().unwrap();
+    let _ = root.make_directory("alpha".to_string()).unwrap();
+    assert!(Placement::set_on_directory(Path::new("/alpha"), Some(Placement::Disks { first: 2, last: 2 })).unwrap());
+
+    let _guard = PlacementGuard::enter(Path::new("/alpha")).unwrap();
+    // More than a single disk can hold.
+    let blocks = Pool::find_and_allocate_pool_blocks(3000, false).unwrap();
+    assert_eq!(blocks.len(), 3000);
+    assert!(blocks.iter().any(|block| block.disk == 2));
+    assert!(blocks.iter().any(|block| block.disk != 2));
+}
@@ -5,6 +5,9 @@
 use super::pool_struct::GLOBAL_POOL;
 use super::pool_struct::Pool;
 use crate::error_types::drive::DriveError;
+use crate::filesystem::filesystem_struct::FORMAT_POOL;
+use crate::filesystem::filesystem_struct::POOL_NAME;
+use crate::pool::dedup::dedup_struct::DedupIndex;
 use crate::pool::disk::blank_disk::blank_disk_struct::BlankDisk;
 use crate::pool::disk::drive_struct::DiskBootstrap;
 use crate::pool::disk::drive_struct::FloppyDrive;
@@ -16,12 +19,17 @@ use crate::pool::disk::pool_disk::block::header::header_struct::PoolDiskHeader;
 use crate::pool::disk::standard_disk::block::directory::directory_struct::DirectoryBlock;
 use crate::pool::disk::standard_disk::block::directory::directory_struct::DirectoryItemFlags;
 use crate::pool::disk::standard_disk::block::directory::directory_struct::DirectoryItem;
+use crate::pool::disk::standard_disk::block::header::header_struct::StandardDiskHeader;
+use crate::pool::disk::standard_disk::block::inode::inode_struct::Inode;
 use crate::pool::disk::standard_disk::block::inode::inode_struct::InodeLocation;
 use crate::pool::disk::standard_disk::standard_disk_struct::StandardDisk;
+use crate::pool::snapshot::snapshot_struct::Snapshot;
+use crate::pool::snapshot::snapshot_struct::SnapshotRoot;
 use crate::tui::notify::NotifyTui;
 use crate::tui::tasks::TaskType;
 use log::debug;
 use log::error;
+use log::warn;
 use std::sync::Arc;
 use std::sync::Mutex;
 
@@ -56,6 +64,34 @@ impl Pool {
     pub fn get_root_directory_item() -> DirectoryItem {
         pool_get_root_directory_item()
     }
+    /// Count a file or directory that was just given an inode.
+    pub(crate) fn item_added(inode: &Inode) {
+        count_item(inode, true)
+    }
+    /// Stop counting a file or directory, since its inode is gone.
+    pub(crate) fn item_removed(inode: &Inode) {
+        count_item(inode, false)
+    }
+}
+
+fn count_item(inode: &Inode, added: bool) {
+    let mut pool = GLOBAL_POOL
+        .get()
+        .expect("Pool must exist to put things in it.")
+        .lock()
+        .expect("Other mutex holders should not panic.");
+    let items = &mut pool.header.items;
+    let count: &mut u64 = if inode.file.is_some() {
+        &mut items.files
+    } else {
+        &mut items.directories
+    };
+    if added {
+        *count += 1;
+    } else {
+        // Damaged counts start over from zero, so they could be short.
+        *count = count.saturating_sub(1);
+    }
 }
 
 /// Sync information about the pool to disk
@@ -71,13 +107,23 @@ pub(super) fn flush_pool() -> Result<(), DriveError> {
     // flush the pool even if it got poisoned.
     global_pool.clear_poison();
 
-    let pool_header:PoolDiskHeader = 
-        global_pool.try_lock()
-        .expect("Single threaded, already cleared poison.")
+    let mut pool_header:PoolDiskHeader = 
+        global_pool.lock()
+        .expect("Already cleared poison.")
         .header;
 
+    // The dedup index lives on the pool disk too, and the header needs to know how big it is,
+    // so it has to go first.
+    DedupIndex::flush(&mut pool_header)?;
+    Snapshot::flush(&mut pool_header)?;
+    pool_header.add_features_in_use();
+    global_pool.lock()
+        .expect("Already cleared poison.")
+        .header = pool_header;
+
     // Now write that back to disk.
     pool_header.write()?;
+
     debug!("Pool flushed.");
     Ok(())
 }
@@ -119,8 +165,33 @@ pub(super) fn load() -> Arc<Mutex<Pool>> {
         error!("Fluster will now exit.");
         panic!("Failed to get pool header!");
     };
+
+    // Pools from a newer fluster can't be trusted to mean what we think they mean, don't touch anything on them.
+    if let Err(problem) = header.check_format() {
+        error!("This pool can't be mounted.");
+        error!("Reason: {problem}");
+        error!("Fluster will now exit.");
+        panic!("Unsupported pool format! {problem}");
+    }
     
 
+    // Shared blocks need to be known about before anything gets written.
+    if let Err(error) = DedupIndex::load(header.dedup_index_blocks) {
+        // If we dont know which blocks are shared, writing to any of them could clobber other files.
+        error!("Failed to load the dedup index.");
+        error!("Reason: {error}");
+        error!("Fluster will now exit.");
+        panic!("Failed to load dedup index! {error}");
+    }
+
+    // Snapshots hold onto blocks too, and can't be found any other way.
+    if let Err(error) = Snapshot::load(header.snapshots) {
+        error!("Failed to load the snapshot table.");
+        error!("Reason: {error}");
+        error!("Fluster will now exit.");
+        panic!("Failed to load snapshot table! {error}");
+    }
+
     let pool = Pool {
         header,
     };
@@ -143,7 +214,7 @@ pub(super) fn load() -> Arc<Mutex<Pool>> {
     // So if this lock fails, cooked.
     
     let highest_known: u16 = if let Some(global_pool) = GLOBAL_POOL.get() {
-        if let Ok(innards) = global_pool.try_lock() {
+        if let Ok(innards) = global_pool.lock() {
             innards.header.highest_known_disk
         } else {
             panic!("Locking the global pool immediately after creation failed!");
@@ -153,6 +224,22 @@ pub(super) fn load() -> Arc<Mutex<Pool>> {
         panic!("The global pool does not exist, even though we JUST made it?");
     };
 
+    let formatting: bool = *FORMAT_POOL.get().unwrap_or(&false);
+    if formatting && highest_known != 0 {
+        // mkfs doesn't get to clobber an existing pool.
+        error!("There's already a pool on this disk, with {highest_known} disks.");
+        error!("Wipe the pool disk first if you really want to replace it.");
+        error!("Fluster will now exit.");
+        panic!("Tried to make a pool over an existing one!");
+    }
+    if !formatting && highest_known == 0 {
+        // mkfs made the pool disk, but never got to disk 1.
+        error!("This pool was never finished being made.");
+        error!("Run `fluster_fs mkfs` on it again to finish it.");
+        error!("Fluster will now exit.");
+        panic!("Tried to mount an unfinished pool!");
+    }
+
     // Check if this is a brand new pool
     if highest_known == 0 {
         // This is a brand new pool, we need to initialize it.
@@ -168,10 +255,49 @@ pub(super) fn load() -> Arc<Mutex<Pool>> {
         };
     };
 
+    // Older pools need to be brought up to date before anything new gets written to them.
+    if let Err(error) = Pool::migrate() {
+        error!("Failed to upgrade the pool to the current format.");
+        error!("Reason: {error}");
+        error!("Fluster will now exit.");
+        panic!("Failed to upgrade pool! {error}");
+    }
+
+    // Anything the pool already uses stays on, unless it was turned off for this mount.
+    shared_pool
+        .lock()
+        .expect("Other mutex holders should not panic.")
+        .header
+        .turn_on_features_in_use();
+
+    // Every disk knows what pool it's from, so the name only has to be given once.
+    if POOL_NAME.get().is_none() {
+        load_pool_name();
+    }
+
     // All done
     shared_pool
 }
 
+/// Pick up the pool's name from the header of disk 1.
+fn load_pool_name() {
+    let pointer: DiskPointer = DiskPointer {
+        disk: 1,
+        block: 0,
+    };
+    match CachedBlockIO::read_block(pointer) {
+        Ok(block) => {
+            let label: String = StandardDiskHeader::from_block(&block).pool_label;
+            if !label.is_empty() {
+                debug!("The pool is called `{label}`.");
+                let _ = POOL_NAME.set(label);
+            }
+        },
+        // Not the end of the world, the labels just get the default name.
+        Err(error) => warn!("Couldn't read the pool's name from disk 1. Error: {error}"),
+    }
+}
+
 /// Set up stuff for a brand new pool
 fn initalize_pool() -> Result<(), DriveError> {
     debug!("Doing first time pool setup...");
@@ -203,8 +329,8 @@ fn add_disk<T: DiskBootstrap>() -> Result<T, DriveError> {
     let highest_known: u16 = GLOBAL_POOL
         .get()
         .expect("Pool must exist at to add disks to it.")
-        .try_lock()
-        .expect("Cannot add disks to poisoned pool. Also single threaded, so should not block.")
+        .lock()
+        .expect("Cannot add disks to poisoned pool.")
         .header
         .highest_known_disk;
     let next_open_disk = highest_known + 1;
@@ -241,7 +367,7 @@ fn add_disk<T: DiskBootstrap>() -> Result<T, DriveError> {
     
     // The disk has now bootstrapped itself, we are done here.
     // We already locked earlier, so this can't be poisoned, unless maybe making the disks also panicked?
-    if let Ok(mut inner) = GLOBAL_POOL.get().expect("Pool has to be set up before we can make disks.").try_lock() {
+    if let Ok(mut inner) = GLOBAL_POOL.get().expect("Pool has to be set up before we can make disks.").lock() {
         inner.header.highest_known_disk += 1;
     } else {
         // Poisoned again! We're probably in really bad shape. Just give up.
@@ -257,14 +383,19 @@ fn add_disk<T: DiskBootstrap>() -> Result<T, DriveError> {
 }
 
 /// Grabs the root inode block
+///
+/// If this thread is looking at a snapshot, this is the snapshot's root instead.
 fn pool_get_root_directory() -> Result<DirectoryBlock, DriveError> {
     // Root directory should always be at disk 1 block 2. We just assume that to be the case.
     // Why do we have a root inode that points to the root directory when its always in a static location?
     // Beats me, I forgot why I did that.
 
-    let root_pointer: DiskPointer = DiskPointer {
-        disk: 1,
-        block: 2,
+    let root_pointer: DiskPointer = match SnapshotRoot::current() {
+        Some(snapshot) => snapshot.directory,
+        None => DiskPointer {
+            disk: 1,
+            block: 2,
+        },
     };
 
     // Get the root directory block
@@ -276,6 +407,9 @@ fn pool_get_root_directory() -> Result<DirectoryBlock, DriveError> {
 
 /// Grabs the root inode location, duh
 fn pool_get_root_inode_location() -> InodeLocation {
+    if let Some(snapshot) = SnapshotRoot::current() {
+        return snapshot.inode;
+    }
     let pointer = DiskPointer {
         disk: 1,
         block: 1,
@@ -292,5 +426,6 @@ fn pool_get_root_directory_item() -> DirectoryItem {
         name_length: 0,
         name: DELIMITER.into(),
         location: pool_get_root_inode_location(),
+        placement: None,
     }
 }
\ No newline at end of file
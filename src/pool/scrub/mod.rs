@@ -0,0 +1,4 @@
+pub(crate) mod scrub_struct;
+pub(crate) mod scrub_methods;
+#[cfg(test)]
+mod tests;
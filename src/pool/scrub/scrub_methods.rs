@@ -0,0 +1,226 @@
+// Every nook and cranny.
+
+// Imports
+
+use std::{
+    fmt::Write,
+    fs::File
+};
+
+use log::{debug, error, info, warn};
+
+use crate::{
+    error_types::drive::DriveError,
+    filesystem::disk_backup::restore::read_backup_block,
+    pool::{
+        disk::{
+            drive_struct::{
+                DiskType,
+                FloppyDrive
+            },
+            generic::{
+                block::{
+                    allocate::block_allocation::BlockAllocation,
+                    block_structs::RawBlock,
+                    crc::check_crc
+                },
+                disk_trait::GenericDiskMethods,
+                generic_structs::pointer_struct::DiskPointer,
+                io::{
+                    cache::cache_io::CachedBlockIO,
+                    read::read_block_direct,
+                    write::write_block_direct
+                }
+            }
+        },
+        pool_actions::pool_struct::{
+            Pool,
+            GLOBAL_POOL
+        }
+    },
+    tui::{
+        notify::NotifyTui,
+        tasks::TaskType
+    }
+};
+
+use super::scrub_struct::{
+    DiskScrub,
+    ScrubReport,
+    LAST_SCRUB
+};
+
+/// What happened to a single block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Checked {
+    Fine,
+    Corrected,
+    Uncorrectable,
+    Unreadable,
+}
+
+// Implementations
+
+impl ScrubReport {
+    /// Check every allocated block in the pool, and fix whatever is broken from the disk backups.
+    ///
+    /// Flushes everything first, so the disks are up to date. Swaps through every disk in the pool.
+    pub(crate) fn run() -> Result<ScrubReport, DriveError> {
+        go_scrub(&read_backup_block)
+    }
+
+    /// The report from the last scrub, if one has been run since mounting.
+    pub(crate) fn last() -> Option<ScrubReport> {
+        LAST_SCRUB.lock().expect("Other mutex holders should not panic.").clone()
+    }
+
+    /// One line per disk, `disk checked corrected uncorrectable skipped`, then the totals.
+    pub(crate) fn report(&self) -> String {
+        let mut report = String::from("disk checked corrected uncorrectable skipped\n");
+        let mut total = DiskScrub::default();
+        for disk in &self.disks {
+            let _ = writeln!(
+                report,
+                "{} {} {} {} {}",
+                disk.number, disk.checked, disk.corrected, disk.uncorrectable, disk.skipped
+            );
+            total.checked += disk.checked;
+            total.corrected += disk.corrected;
+            total.uncorrectable += disk.uncorrectable;
+            total.skipped += disk.skipped;
+        }
+        let _ = writeln!(
+            report,
+            "total {} {} {} {}",
+            total.checked, total.corrected, total.uncorrectable, total.skipped
+        );
+        report
+    }
+}
+
+// Functions
+
+/// Scrub the whole pool, getting good copies of broken blocks from `good_copy`.
+///
+/// Whatever `good_copy` hands back is only used if its CRC checks out.
+pub(super) fn go_scrub(good_copy: &dyn Fn(DiskPointer) -> Option<RawBlock>) -> Result<ScrubReport, DriveError> {
+    info!("Starting a scrub...");
+    // Dirty blocks haven't made it to the disks yet, so the disks would look out of date.
+    CachedBlockIO::flush()?;
+    Pool::flush()?;
+
+    let highest_disk: u16 = GLOBAL_POOL
+        .get()
+        .expect("Pool must exist to scrub it.")
+        .lock()
+        .expect("Other mutex holders should not panic.")
+        .header
+        .highest_known_disk;
+
+    let mut disks: Vec<DiskScrub> = Vec::with_capacity(highest_disk as usize + 1);
+    for number in 0..=highest_disk {
+        let scrubbed: DiskScrub = scrub_disk(number, good_copy)?;
+        debug!("Disk {number}: {scrubbed:?}");
+        disks.push(scrubbed);
+    }
+
+    let report = ScrubReport { disks };
+    info!("Scrub finished.");
+    *LAST_SCRUB.lock().expect("Other mutex holders should not panic.") = Some(report.clone());
+    Ok(report)
+}
+
+fn scrub_disk(number: u16, good_copy: &dyn Fn(DiskPointer) -> Option<RawBlock>) -> Result<DiskScrub, DriveError> {
+    debug!("Scrubbing disk {number}...");
+    let mut scrubbed = DiskScrub {
+        number,
+        ..Default::default()
+    };
+
+    // The cache would only tell us what the disk was supposed to say, we want what it actually says.
+    #[allow(deprecated)]
+    let disk: DiskType = FloppyDrive::open(number)?;
+    let blocks: Vec<u16> = match &disk {
+        DiskType::Pool(pool_disk) => allocated_blocks(pool_disk),
+        DiskType::Standard(standard_disk) => allocated_blocks(standard_disk),
+        DiskType::Dense(dense_disk) => allocated_blocks(dense_disk),
+        DiskType::Unknown(_) | DiskType::Blank(_) => {
+            warn!("Disk {number} isn't a fluster disk anymore, skipping all of it.");
+            scrubbed.skipped = 2880;
+            return Ok(scrubbed);
+        },
+    };
+    let disk_file: File = disk.disk_file();
+
+    let handle = NotifyTui::start_task(TaskType::ScrubDisk(number), blocks.len() as u64);
+    for block in blocks {
+        let pointer = DiskPointer {
+            disk: number,
+            block,
+        };
+        match scrub_block(&disk_file, pointer, good_copy)? {
+            Checked::Fine => scrubbed.checked += 1,
+            Checked::Corrected => {
+                scrubbed.checked += 1;
+                scrubbed.corrected += 1;
+            },
+            Checked::Uncorrectable => {
+                scrubbed.checked += 1;
+                scrubbed.uncorrectable += 1;
+            },
+            Checked::Unreadable => scrubbed.skipped += 1,
+        }
+        NotifyTui::complete_task_step(&handle);
+    }
+    NotifyTui::finish_task(handle);
+    Ok(scrubbed)
+}
+
+/// Every block that's in use on a disk.
+///
+/// The header is always there, even if the map doesn't say so.
+fn allocated_blocks<T: BlockAllocation>(disk: &T) -> Vec<u16> {
+    (0..2880_u16)
+        .filter(|block| *block == 0 || disk.is_block_allocated(*block))
+        .collect()
+}
+
+fn scrub_block(disk_file: &File, pointer: DiskPointer, good_copy: &dyn Fn(DiskPointer) -> Option<RawBlock>) -> Result<Checked, DriveError> {
+    // We check the CRC ourselves, a bad one would just get retried over and over down there.
+    let Ok(read) = read_block_direct(disk_file, pointer.disk, pointer.block, true, false) else {
+        warn!("Couldn't read block {} of disk {} at all.", pointer.block, pointer.disk);
+        return Ok(Checked::Unreadable);
+    };
+    if check_crc(read.data) {
+        return Ok(Checked::Fine);
+    }
+
+    // Blocks full of zeros were allocated, but never written. Unless the backup says otherwise.
+    let never_written: bool = read.data.iter().all(|byte| *byte == 0);
+    let Some(copy) = good_copy(pointer).filter(|copy| check_crc(copy.data) && copy.data != read.data) else {
+        if never_written {
+            return Ok(Checked::Fine);
+        }
+        error!("Block {} of disk {} is bad, and there's no good copy of it!", pointer.block, pointer.disk);
+        return Ok(Checked::Uncorrectable);
+    };
+
+    warn!("Block {} of disk {} is bad, replacing it from the backup...", pointer.block, pointer.disk);
+    let fixed: RawBlock = RawBlock {
+        block_origin: pointer,
+        data: copy.data,
+    };
+    write_block_direct(disk_file, &fixed, false)?;
+
+    // Make sure it stuck.
+    match read_block_direct(disk_file, pointer.disk, pointer.block, true, false) {
+        Ok(reread) if reread.data == fixed.data => {
+            debug!("Fixed.");
+            Ok(Checked::Corrected)
+        },
+        _ => {
+            error!("Block {} of disk {} didn't take the fix!", pointer.block, pointer.disk);
+            Ok(Checked::Uncorrectable)
+        },
+    }
+}
@@ -0,0 +1,37 @@
+// Scrub a dub dub.
+
+// Imports
+
+use std::sync::Mutex;
+
+// Structs, Enums, Flags
+
+/// How every disk in the pool held up the last time it was checked over.
+///
+/// A scrub reads every allocated block of every disk, in disk order, and checks its CRC. Bad blocks
+/// are replaced one at a time with the same block from the disk's backup, and read back to make
+/// sure the fix stuck. There's no parity to rebuild from, so blocks without a good backup stay broken.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ScrubReport {
+    /// Every disk in the pool, in order, starting with the pool disk.
+    pub(crate) disks: Vec<DiskScrub>,
+}
+
+/// How a single disk held up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct DiskScrub {
+    /// Which disk this is.
+    pub(crate) number: u16,
+    /// Allocated blocks that were read and checked.
+    pub(crate) checked: u16,
+    /// Bad blocks that were replaced from the backup.
+    pub(crate) corrected: u16,
+    /// Bad blocks that couldn't be fixed, since the backup was missing, also bad, or didn't stick.
+    pub(crate) uncorrectable: u16,
+    /// Allocated blocks that couldn't be read at all.
+    pub(crate) skipped: u16,
+}
+
+// Scrubs take a long time, so the result sticks around for whoever wants to look at it later.
+/// The report from the last scrub since mounting, if there was one.
+pub(super) static LAST_SCRUB: Mutex<Option<ScrubReport>> = Mutex::new(None);
@@ -0,0 +1,104 @@
+// Rub a dub dub, three bits in a tub.
+// Unwrapping is okay here, since we want unexpected outcomes to fail tests.
+#![allow(clippy::unwrap_used)]
+
+use std::{
+    fs::OpenOptions,
+    os::unix::fs::FileExt,
+    path::{Path, PathBuf}
+};
+
+use rand::{rngs::ThreadRng, RngCore};
+use test_log::test;
+
+use crate::{
+    filesystem::filesystem_struct::USE_VIRTUAL_DISKS,
+    pool::{
+        disk::{
+            generic::{
+                block::block_structs::RawBlock,
+                generic_structs::pointer_struct::DiskPointer
+            },
+            standard_disk::block::{
+                header::header_struct::StandardDiskHeader,
+                io::directory::tests::get_filesystem
+            }
+        },
+        pool_actions::pool_struct::Pool
+    }
+};
+
+use super::{
+    scrub_methods::go_scrub,
+    scrub_struct::ScrubReport
+};
+
+fn disk_path(disk: u16) -> PathBuf {
+    USE_VIRTUAL_DISKS.lock().unwrap().clone().unwrap().join(format!("disk{disk}.fsr"))
+}
+
+fn read_raw(path: &Path, block: u16) -> [u8; 512] {
+    let mut buffer = [0u8; 512];
+    OpenOptions::new().read(true).open(path).unwrap().read_exact_at(&mut buffer, block as u64 * 512).unwrap();
+    buffer
+}
+
+fn scribble(path: &Path, block: u16) {
+    let file = OpenOptions::new().write(true).open(path).unwrap();
+    file.write_all_at(b"not what was here", block as u64 * 512 + 100).unwrap();
+}
+
+/// Bad blocks with a good backup get fixed, bad blocks without one are reported.
+#[test]
+fn fixes_what_it_can() {
+    let _fs = get_filesystem();
+    let mut root = Pool::get_root_directory().unwrap();
+    let file = root.new_file("data.bin".to_string()).unwrap();
+    let mut random: ThreadRng = rand::rng();
+    let mut bytes = vec![0u8; 20_000];
+    random.fill_bytes(&mut bytes);
+    let _ = file.write_file(&bytes, 0).unwrap();
+
+    // Make sure everything is on the disks before copying them.
+    let clean = go_scrub(&|_| None).unwrap();
+    assert!(clean.disks.iter().all(|disk| disk.corrected == 0 && disk.uncorrectable == 0));
+
+    // Keep a copy of disk 1 to pretend it's the backup.
+    let disk_one = disk_path(1);
+    let backup = disk_one.with_extension("backup");
+    let _ = std::fs::copy(&disk_one, &backup).unwrap();
+
+    let header = StandardDiskHeader::from_block(&RawBlock {
+        block_origin: DiskPointer { disk: 1, block: 0 },
+        data: read_raw(&disk_one, 0),
+    });
+    let used: Vec<u16> = (1..2880_u16)
+        .filter(|block| header.block_usage_map[*block as usize / 8] & (0b10000000 >> (block % 8)) != 0)
+        .collect();
+    let fixable = used[used.len() / 2];
+    let hopeless = used[used.len() - 1];
+
+    scribble(&disk_one, fixable);
+    scribble(&disk_one, hopeless);
+    scribble(&backup, hopeless);
+
+    let from_backup = |pointer: DiskPointer| {
+        (pointer.disk == 1).then(|| RawBlock {
+            block_origin: pointer,
+            data: read_raw(&backup, pointer.block),
+        })
+    };
+
+    let report: ScrubReport = go_scrub(&from_backup).unwrap();
+    let one = report.disks[1];
+    assert_eq!(one.corrected, 1);
+    assert_eq!(one.uncorrectable, 1);
+    assert_eq!(one.checked as usize, used.len() + 1);
+    assert_eq!(read_raw(&disk_one, fixable), read_raw(&backup, fixable));
+    assert_eq!(ScrubReport::last(), Some(report));
+
+    // Once fixed, stays fixed.
+    let again: ScrubReport = go_scrub(&from_backup).unwrap();
+    assert_eq!(again.disks[1].corrected, 0);
+    assert_eq!(again.disks[1].uncorrectable, 1);
+}
@@ -0,0 +1,4 @@
+pub(crate) mod snapshot_struct;
+pub(crate) mod snapshot_methods;
+#[cfg(test)]
+mod tests;
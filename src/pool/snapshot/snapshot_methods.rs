@@ -0,0 +1,375 @@
+// Taking pictures, and throwing them out.
+
+// Imports
+
+use log::debug;
+
+use crate::{
+    error_types::drive::DriveError,
+    pool::{
+        disk::{
+            drive_struct::{
+                DiskType,
+                FloppyDrive
+            },
+            generic::{
+                block::{
+                    block_structs::RawBlock,
+                    crc::add_crc_to_block
+                },
+                disk_trait::GenericDiskMethods,
+                generic_structs::pointer_struct::DiskPointer,
+                io::cache::cache_io::CachedBlockIO
+            },
+            pool_disk::block::header::header_struct::PoolDiskHeader,
+            standard_disk::block::{
+                directory::directory_struct::{
+                    DirectoryBlock,
+                    DirectoryItem,
+                    DirectoryItemFlags
+                },
+                inode::inode_struct::{
+                    Inode,
+                    InodeDirectory,
+                    InodeLocation,
+                    InodeTimestamp
+                }
+            }
+        },
+        pool_actions::pool_struct::Pool,
+        trash::trash_struct::Trash
+    }
+};
+
+use super::snapshot_struct::{
+    Snapshot,
+    SnapshotGuard,
+    SnapshotRoot,
+    SNAPSHOTS,
+    VIEWED_ROOT
+};
+
+// Consts
+
+/// The snapshot table always lives in the final block of the pool disk.
+const TABLE_BLOCK: u16 = 2880 - 1;
+
+/// Each entry is a name length, the name, a pointer to the root directory, then the root inode's pointer and offset.
+const ENTRY_OVERHEAD: usize = 1 + 4 + 4 + 2;
+
+// Grab the snapshots.
+macro_rules! get_snapshots {
+    () => {
+        SNAPSHOTS.lock().expect("Other mutex holders should not panic.")
+    };
+}
+
+// Implementations
+
+impl Snapshot {
+    /// Every snapshot, in the order they were taken.
+    pub(crate) fn list() -> Vec<Snapshot> {
+        get_snapshots!().clone()
+    }
+
+    /// Find a snapshot by name.
+    pub(crate) fn find(name: &str) -> Option<Snapshot> {
+        get_snapshots!().iter().find(|snapshot| snapshot.name == name).cloned()
+    }
+
+    /// Is there room left in the snapshot table for a snapshot with this name?
+    pub(crate) fn has_room_for(name: &str) -> bool {
+        let used: usize = get_snapshots!().iter().map(|snapshot| ENTRY_OVERHEAD + snapshot.name.len()).sum();
+        // Count byte up front, crc on the end.
+        1 + used + ENTRY_OVERHEAD + name.len() <= 508
+    }
+
+    /// Take a snapshot of the whole pool.
+    ///
+    /// Copies every directory, inode and extent block, and adds a reference to every data block.
+    /// No file data is read, but every bit of metadata in the pool is, and a copy of it written out, so
+    /// expect to swap through every disk each time. Every data block the snapshot shares takes a spot in the
+    /// dedup index, which only holds about 103k of them across every snapshot, so big pools can run out
+    /// after a few snapshots.
+    ///
+    /// If this fails partway, everything copied so far is thrown out again.
+    ///
+    /// The name must be unused, and fit in the table. Caller must check.
+    pub(crate) fn take(name: &str) -> Result<Snapshot, DriveError> {
+        go_take(name)
+    }
+
+    /// Delete a snapshot, freeing everything only it was using.
+    ///
+    /// Returns false if there was no snapshot with that name.
+    pub(crate) fn delete(name: &str) -> Result<bool, DriveError> {
+        go_delete(name)
+    }
+
+    /// Read the snapshot table off of the pool disk.
+    ///
+    /// Will swap to the pool disk if there are snapshots to read.
+    pub(crate) fn load(count: u8) -> Result<(), DriveError> {
+        go_load_table(count)
+    }
+
+    /// Write the snapshot table to the pool disk, and update the provided header to match.
+    ///
+    /// Must come after the dedup index is flushed, since that rewrites the header's allocation map.
+    ///
+    /// Does not write the header itself.
+    pub(crate) fn flush(header: &mut PoolDiskHeader) -> Result<(), DriveError> {
+        go_flush_table(header)
+    }
+}
+
+impl SnapshotRoot {
+    /// The snapshot root lookups on this thread are using right now, if any.
+    pub(crate) fn current() -> Option<Self> {
+        VIEWED_ROOT.get()
+    }
+}
+
+impl SnapshotGuard {
+    /// Make lookups on this thread start from a snapshot's root, until the guard is dropped.
+    pub(crate) fn enter(root: SnapshotRoot) -> Self {
+        SnapshotGuard {
+            previous: VIEWED_ROOT.replace(Some(root)),
+        }
+    }
+}
+
+impl Drop for SnapshotGuard {
+    fn drop(&mut self) {
+        VIEWED_ROOT.set(self.previous);
+    }
+}
+
+// Functions
+
+fn go_take(name: &str) -> Result<Snapshot, DriveError> {
+    assert!(Snapshot::find(name).is_none(), "Tried to take a snapshot with a name that is already used!");
+    assert!(Snapshot::has_room_for(name), "Tried to take a snapshot with no room to keep track of it!");
+    debug!("Taking snapshot `{name}`...");
+
+    let live_root: DirectoryBlock = Pool::get_root_directory()?;
+    let live_inode: Inode = Pool::get_root_directory_item().get_inode()?;
+
+    let directory: DiskPointer = new_directory_block()?;
+    if let Err(error) = copy_directory(&live_root, directory) {
+        discard_directory(directory)?;
+        return Err(error);
+    }
+
+    // The root keeps its timestamps, other than being created just now.
+    let inode: Inode = Inode {
+        directory: Some(InodeDirectory::from_disk_pointer(directory)),
+        created: InodeTimestamp::now(),
+        ..live_inode
+    };
+    let inode: InodeLocation = match Pool::fast_add_inode(inode) {
+        Ok(ok) => ok,
+        Err(error) => {
+            discard_directory(directory)?;
+            return Err(error);
+        },
+    };
+
+    let snapshot: Snapshot = Snapshot {
+        name: name.to_string(),
+        root: SnapshotRoot {
+            directory,
+            inode,
+        },
+    };
+    get_snapshots!().push(snapshot.clone());
+    debug!("Snapshot taken.");
+    Ok(snapshot)
+}
+
+/// Copy everything in a directory into an empty one.
+/// 
+/// If this fails, whatever made it into the destination is left there for the caller to throw out.
+fn copy_directory(source: &DirectoryBlock, destination: DiskPointer) -> Result<(), DriveError> {
+    let mut copy: DirectoryBlock = DirectoryBlock::from_block(&CachedBlockIO::read_block(destination)?);
+    for item in source.list()? {
+        // Deleted things stay deleted.
+        if Trash::is_trash(&item) {
+            continue;
+        }
+        if !item.flags.contains(DirectoryItemFlags::IsDirectory) {
+            let _ = copy.reflink_file(&item)?;
+            continue;
+        }
+        let inner_source: DirectoryBlock = item.get_directory_block()?;
+        let inode: Inode = item.get_inode()?;
+        let inner: DiskPointer = new_directory_block()?;
+        if let Err(error) = copy_directory(&inner_source, inner) {
+            discard_directory(inner)?;
+            return Err(error);
+        }
+        let inode: Inode = Inode {
+            directory: Some(InodeDirectory::from_disk_pointer(inner)),
+            ..inode
+        };
+        let location: InodeLocation = match Pool::fast_add_inode(inode) {
+            Ok(ok) => ok,
+            Err(error) => {
+                discard_directory(inner)?;
+                return Err(error);
+            },
+        };
+        let inner_item: DirectoryItem = DirectoryItem {
+            location,
+            ..item
+        };
+        if let Err(error) = copy.add_item(&inner_item) {
+            // Has an inode now, so it goes like any other directory.
+            let mut inner_block: DirectoryBlock = DirectoryBlock::from_block(&CachedBlockIO::read_block(inner)?);
+            inner_block.delete_contents()?;
+            inner_block.delete_self(inner_item)?;
+            return Err(error);
+        }
+    }
+    Ok(())
+}
+
+/// Throw out a copied directory that never got an inode, along with everything in it.
+fn discard_directory(directory: DiskPointer) -> Result<(), DriveError> {
+    debug!("Throwing out a partial copy...");
+    let mut block: DirectoryBlock = DirectoryBlock::from_block(&CachedBlockIO::read_block(directory)?);
+    block.delete_contents()?;
+    // Empty directories are only one block.
+    let _ = Pool::free_pool_block_from_disk(&[directory])?;
+    Ok(())
+}
+
+/// Make a new empty DirectoryBlock somewhere.
+fn new_directory_block() -> Result<DiskPointer, DriveError> {
+    // No crc, we're writing over it.
+    let location: DiskPointer = Pool::find_and_allocate_pool_blocks(1, false)?[0];
+    CachedBlockIO::update_block(&DirectoryBlock::new(location).to_block())?;
+    Ok(location)
+}
+
+fn go_delete(name: &str) -> Result<bool, DriveError> {
+    let Some(snapshot) = Snapshot::find(name) else {
+        return Ok(false);
+    };
+    debug!("Deleting snapshot `{name}`...");
+
+    let mut root: DirectoryBlock = DirectoryBlock::from_block(&CachedBlockIO::read_block(snapshot.root.directory)?);
+    root.delete_contents()?;
+    root.delete_self(DirectoryItem {
+        flags: DirectoryItemFlags::IsDirectory,
+        name_length: 0,
+        name: std::path::MAIN_SEPARATOR.into(),
+        location: snapshot.root.inode,
+        placement: None,
+    })?;
+
+    get_snapshots!().retain(|kept| kept.name != name);
+    debug!("Snapshot deleted.");
+    Ok(true)
+}
+
+/// Turn the snapshots into the table block.
+pub(super) fn table_to_block(snapshots: &[Snapshot]) -> [u8; 512] {
+    let mut buffer: [u8; 512] = [0u8; 512];
+    // Always fits, the table can't hold that many.
+    buffer[0] = snapshots.len() as u8;
+    let mut offset: usize = 1;
+    for snapshot in snapshots {
+        buffer[offset] = snapshot.name.len() as u8;
+        offset += 1;
+        buffer[offset..offset + snapshot.name.len()].copy_from_slice(snapshot.name.as_bytes());
+        offset += snapshot.name.len();
+        buffer[offset..offset + 4].copy_from_slice(&snapshot.root.directory.to_bytes());
+        offset += 4;
+        buffer[offset..offset + 4].copy_from_slice(&snapshot.root.inode.pointer.to_bytes());
+        offset += 4;
+        buffer[offset..offset + 2].copy_from_slice(&snapshot.root.inode.offset.to_le_bytes());
+        offset += 2;
+    }
+    add_crc_to_block(&mut buffer);
+    buffer
+}
+
+/// Read the snapshots back out of the table block.
+pub(super) fn table_from_block(block: &[u8; 512]) -> Vec<Snapshot> {
+    let count = block[0] as usize;
+    let mut snapshots: Vec<Snapshot> = Vec::with_capacity(count);
+    let mut offset: usize = 1;
+    for _ in 0..count {
+        let length = block[offset] as usize;
+        offset += 1;
+        let name: String = String::from_utf8_lossy(&block[offset..offset + length]).to_string();
+        offset += length;
+        let directory = DiskPointer::from_bytes(block[offset..offset + 4].try_into().expect("4 = 4"));
+        offset += 4;
+        let pointer = DiskPointer::from_bytes(block[offset..offset + 4].try_into().expect("4 = 4"));
+        offset += 4;
+        let inode_offset = u16::from_le_bytes(block[offset..offset + 2].try_into().expect("2 = 2"));
+        offset += 2;
+        snapshots.push(Snapshot {
+            name,
+            root: SnapshotRoot {
+                directory,
+                inode: InodeLocation::new(pointer, inode_offset),
+            },
+        });
+    }
+    snapshots
+}
+
+fn go_load_table(count: u8) -> Result<(), DriveError> {
+    if count == 0 {
+        debug!("No snapshots on the pool disk.");
+        return Ok(());
+    }
+    debug!("Loading {count} snapshots from the pool disk...");
+
+    #[allow(deprecated)] // Pool disks cannot use the cache.
+    let disk = match FloppyDrive::open(0)? {
+        DiskType::Pool(pool_disk) => pool_disk,
+        _ => unreachable!("Disk 0 should NEVER be assigned to a non-pool disk!"),
+    };
+
+    *get_snapshots!() = table_from_block(&disk.unchecked_read_block(TABLE_BLOCK)?.data);
+    debug!("Snapshots loaded.");
+    Ok(())
+}
+
+fn go_flush_table(header: &mut PoolDiskHeader) -> Result<(), DriveError> {
+    let snapshots: Vec<Snapshot> = Snapshot::list();
+    let byte = TABLE_BLOCK as usize / 8;
+    let bit = 0b10000000 >> (TABLE_BLOCK % 8);
+
+    if snapshots.is_empty() {
+        // Nothing to write, just make sure the block isn't held onto.
+        header.block_usage_map[byte] &= !bit;
+        header.snapshots = 0;
+        return Ok(());
+    }
+
+    debug!("Writing {} snapshots to the pool disk...", snapshots.len());
+
+    #[allow(deprecated)] // Pool disks cannot use the cache.
+    let mut disk = match FloppyDrive::open(0)? {
+        DiskType::Pool(pool_disk) => pool_disk,
+        _ => unreachable!("Disk 0 should NEVER be assigned to a non-pool disk!"),
+    };
+
+    disk.unchecked_write_block(&RawBlock {
+        block_origin: DiskPointer {
+            disk: 0,
+            block: TABLE_BLOCK,
+        },
+        data: table_to_block(&snapshots),
+    })?;
+
+    header.block_usage_map[byte] |= bit;
+    header.snapshots = snapshots.len() as u8;
+    debug!("Snapshots flushed.");
+    Ok(())
+}
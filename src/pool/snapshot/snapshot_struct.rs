@@ -0,0 +1,59 @@
+// Say cheese.
+
+// Imports
+
+use std::{
+    cell::Cell,
+    sync::Mutex
+};
+
+use lazy_static::lazy_static;
+
+use crate::pool::disk::{
+    generic::generic_structs::pointer_struct::DiskPointer,
+    standard_disk::block::inode::inode_struct::InodeLocation
+};
+
+// Structs, Enums, Flags
+
+lazy_static! {
+    /// Every snapshot in the pool.
+    ///
+    /// Loaded from the pool disk on startup, and written back when the pool is flushed.
+    pub(super) static ref SNAPSHOTS: Mutex<Vec<Snapshot>> = Mutex::new(Vec::new());
+}
+
+/// A read-only copy of the whole pool, as it was when the snapshot was taken.
+///
+/// Directories, inodes and extent blocks are copied, but data blocks are shared with the live files
+/// through the dedup index, so writes to them get copied first and the snapshot never changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Snapshot {
+    /// What the snapshot is called under `/.snapshots`.
+    pub(crate) name: String,
+    /// Where its copy of the root directory is.
+    pub(crate) root: SnapshotRoot,
+}
+
+/// Where a snapshot's root directory lives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SnapshotRoot {
+    /// The first DirectoryBlock of the root.
+    pub(crate) directory: DiskPointer,
+    /// The inode that points at it.
+    pub(crate) inode: InodeLocation,
+}
+
+/// Makes the root directory be a snapshot's root on the current thread, until it is dropped.
+///
+/// Puts back whatever root was there before when dropped, so these can be nested.
+pub(crate) struct SnapshotGuard {
+    pub(super) previous: Option<SnapshotRoot>,
+}
+
+// Paths are always looked up from the root, so the FUSE layer swaps the root out instead of teaching
+// every lookup about snapshots.
+thread_local! {
+    /// The root directory lookups on this thread should start from, if it isn't the real one.
+    pub(super) static VIEWED_ROOT: Cell<Option<SnapshotRoot>> = const { Cell::new(None) };
+}
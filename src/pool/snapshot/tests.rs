@@ -0,0 +1,179 @@
+// Smile for the camera.
+// Unwrapping is okay here, since we want unexpected outcomes to fail tests.
+#![allow(clippy::unwrap_used)]
+
+use rand::{rngs::ThreadRng, RngCore};
+use test_log::test;
+
+use crate::{
+    error_types::drive::DriveError,
+    pool::{
+        dedup::dedup_struct::DedupIndex,
+        disk::{
+            generic::{
+                generic_structs::pointer_struct::DiskPointer,
+                io::cache::cache_io::CachedBlockIO
+            },
+            standard_disk::block::{
+                header::header_struct::StandardDiskHeader,
+                inode::inode_struct::InodeLocation,
+                io::directory::{
+                    tests::get_filesystem,
+                    types::NamedItem
+                }
+            }
+        },
+        manifest::manifest_methods::count_used,
+        pool_actions::pool_struct::Pool
+    }
+};
+
+use super::{
+    snapshot_methods::{
+        table_from_block,
+        table_to_block
+    },
+    snapshot_struct::{
+        Snapshot,
+        SnapshotGuard,
+        SnapshotRoot
+    }
+};
+
+fn used_blocks() -> u32 {
+    let header = StandardDiskHeader::from_block(&CachedBlockIO::read_block(DiskPointer { disk: 1, block: 0 }).unwrap());
+    count_used(&header.block_usage_map)
+}
+
+#[test]
+fn table_ping_pong() {
+    let snapshots: Vec<Snapshot> = (0..10u16).map(|number| Snapshot {
+        name: format!("snapshot number {number}"),
+        root: SnapshotRoot {
+            directory: DiskPointer {
+                disk: number + 1,
+                block: number * 7,
+            },
+            inode: InodeLocation::new(DiskPointer {
+                disk: number + 2,
+                block: 12,
+            }, number * 3),
+        },
+    }).collect();
+    assert_eq!(table_from_block(&table_to_block(&snapshots)), snapshots);
+}
+
+/// Snapshots keep what was there, no matter what happens to the live files.
+#[test]
+fn snapshots_are_frozen() {
+    let _fs = get_filesystem();
+    let mut root = Pool::get_root_directory().unwrap();
+    let mut folder = root.make_directory("folder".to_string()).unwrap().get_directory_block().unwrap();
+    let file = folder.new_file("file.bin".to_string()).unwrap();
+
+    let mut random: ThreadRng = rand::rng();
+    let mut bytes: Vec<u8> = vec![0; 507 * 8];
+    random.fill_bytes(&mut bytes);
+    let _ = file.write_file(&bytes, 0).unwrap();
+
+    let snapshot = Snapshot::take("before").unwrap();
+    assert_eq!(Snapshot::list(), vec![snapshot.clone()]);
+
+    // Change the live file, and delete it.
+    let _ = file.write_file(&[1, 2, 3, 4], 10).unwrap();
+    let mut folder = Pool::get_root_directory().unwrap().change_directory("folder".to_string()).unwrap().unwrap();
+    folder.delete_file(NamedItem::File("file.bin".to_string())).unwrap().unwrap();
+
+    // Still there in the snapshot.
+    {
+        let _guard = SnapshotGuard::enter(snapshot.root);
+        let mut old_folder = Pool::get_root_directory().unwrap().change_directory("folder".to_string()).unwrap().unwrap();
+        let old_file = old_folder.find_item(&NamedItem::File("file.bin".to_string())).unwrap().unwrap();
+        assert_eq!(old_file.read_file(0, bytes.len() as u32).unwrap(), bytes);
+        // Nobody else has the blocks anymore.
+        let pointers = old_file.get_inode().unwrap().extract_file().unwrap().as_pointers().unwrap();
+        assert!(pointers.iter().all(|block| DedupIndex::reference_count(*block) == 1));
+        assert!(old_folder.new_file("new.bin".to_string()).is_ok());
+    }
+
+    // Back to normal outside of the guard.
+    assert_eq!(SnapshotRoot::current(), None);
+    let folder = Pool::get_root_directory().unwrap().change_directory("folder".to_string()).unwrap().unwrap();
+    assert!(folder.find_item(&NamedItem::File("file.bin".to_string())).unwrap().is_none());
+}
+
+/// Deleting a snapshot lets go of everything it held onto, but not what the live files still use.
+#[test]
+fn delete_lets_go() {
+    let _fs = get_filesystem();
+    let mut root = Pool::get_root_directory().unwrap();
+    let _ = root.make_directory("empty".to_string()).unwrap();
+    let file = root.new_file("file.bin".to_string()).unwrap();
+    let bytes: Vec<u8> = vec![7; 507 * 4];
+    let _ = file.write_file(&bytes, 0).unwrap();
+    let pointers = file.get_inode().unwrap().extract_file().unwrap().as_pointers().unwrap();
+
+    let _ = Snapshot::take("first").unwrap();
+    let _ = Snapshot::take("second").unwrap();
+    assert!(pointers.iter().all(|block| DedupIndex::reference_count(*block) == 3));
+
+    assert!(Snapshot::delete("first").unwrap());
+    assert!(!Snapshot::delete("first").unwrap());
+    assert!(Snapshot::delete("second").unwrap());
+    assert!(Snapshot::list().is_empty());
+
+    assert!(pointers.iter().all(|block| DedupIndex::reference_count(*block) == 1));
+    assert_eq!(file.read_file(0, bytes.len() as u32).unwrap(), bytes);
+}
+
+/// A snapshot that runs out of room partway through gives back everything it took.
+#[test]
+fn failed_snapshots_clean_up() {
+    let _fs = get_filesystem();
+    let mut root = Pool::get_root_directory().unwrap();
+    let mut inner = root.make_directory("inner".to_string()).unwrap().get_directory_block().unwrap();
+    let first = inner.new_file("first.bin".to_string()).unwrap();
+    let second = root.new_file("second.bin".to_string()).unwrap();
+    let _ = first.write_file(&[1; 507 * 4], 0).unwrap();
+    let _ = second.write_file(&[2; 507 * 4], 0).unwrap();
+    let mut pointers = first.get_inode().unwrap().extract_file().unwrap().as_pointers().unwrap();
+    pointers.extend(second.get_inode().unwrap().extract_file().unwrap().as_pointers().unwrap());
+    let before = used_blocks();
+
+    // Only enough room left in the index for one of the files.
+    let mut filler: u32 = 0;
+    let mut chunk: u32 = 1 << 16;
+    while chunk > 0 {
+        if !DedupIndex::room_for_shares(chunk as usize + 6) {
+            chunk /= 2;
+            continue;
+        }
+        let fake: Vec<DiskPointer> = (filler..filler + chunk)
+            .map(|next| DiskPointer { disk: 1000 + (next / 2880) as u16, block: (next % 2880) as u16 })
+            .collect();
+        DedupIndex::share(&fake);
+        filler += chunk;
+    }
+    assert!(DedupIndex::room_for_shares(6));
+    assert!(!DedupIndex::room_for_shares(7));
+
+    assert_eq!(Snapshot::take("doomed").err(), Some(DriveError::NoSpace));
+    assert!(Snapshot::list().is_empty());
+    assert!(pointers.iter().all(|block| DedupIndex::reference_count(*block) == 1));
+    assert_eq!(used_blocks(), before);
+}
+
+/// The table only has so much room.
+#[test]
+fn table_fills_up() {
+    let _fs = get_filesystem();
+    let name: String = "a".repeat(100);
+    let mut taken: usize = 0;
+    while Snapshot::has_room_for(&format!("{name}{taken}")) {
+        let _ = Snapshot::take(&format!("{name}{taken}")).unwrap();
+        taken += 1;
+    }
+    assert_eq!(taken, 4);
+    // Everything still fits in the table, and the pool can be flushed with it.
+    Pool::flush().unwrap();
+}
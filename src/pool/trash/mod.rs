@@ -0,0 +1,4 @@
+pub(crate) mod trash_struct;
+pub(crate) mod trash_methods;
+#[cfg(test)]
+mod tests;
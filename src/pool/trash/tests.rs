@@ -0,0 +1,120 @@
+// Taking out the garbage.
+// Unwrapping is okay here, since we want unexpected outcomes to fail tests.
+#![allow(clippy::unwrap_used)]
+
+use std::path::{Path, PathBuf};
+
+use rand::{rngs::ThreadRng, RngCore};
+use test_log::test;
+
+use crate::{
+    filesystem::filesystem_struct::ENABLE_TRASH,
+    pool::{
+        disk::standard_disk::block::{
+            inode::inode_struct::InodeTimestamp,
+            io::directory::{
+                tests::get_filesystem,
+                types::NamedItem
+            }
+        },
+        pool_actions::pool_struct::{
+            Pool,
+            GLOBAL_POOL
+        }
+    }
+};
+
+use super::trash_struct::{
+    Restored,
+    Trash,
+    TrashEntry
+};
+
+fn highest_disk() -> u16 {
+    GLOBAL_POOL.get().unwrap().lock().unwrap().header.highest_known_disk
+}
+
+#[test]
+fn index_ping_pong() {
+    let entries: Vec<TrashEntry> = (0..10u64).map(|number| TrashEntry {
+        id: number + 1,
+        deleted: InodeTimestamp {
+            seconds: number * 1000,
+            nanos: number as u32 * 7,
+        },
+        directory: number % 2 == 0,
+        original: PathBuf::from(format!("/some/where/number {number}")),
+    }).collect();
+    let bytes: Vec<u8> = entries.iter().flat_map(TrashEntry::to_bytes).collect();
+    assert_eq!(TrashEntry::from_index(&bytes), entries);
+}
+
+/// Things come back where they were, even if where they were is gone.
+#[test]
+fn throw_away_and_restore() {
+    let _fs = get_filesystem();
+    let mut root = Pool::get_root_directory().unwrap();
+    let mut folder = root.make_directory("folder".to_string()).unwrap().get_directory_block().unwrap();
+    let file = folder.new_file("file.bin".to_string()).unwrap();
+
+    let mut random: ThreadRng = rand::rng();
+    let mut bytes: Vec<u8> = vec![0; 507 * 6];
+    random.fill_bytes(&mut bytes);
+    let _ = file.write_file(&bytes, 0).unwrap();
+
+    let entry = Trash::throw_away(Path::new("/folder"), NamedItem::File("file.bin".to_string())).unwrap().unwrap();
+    assert_eq!(entry.original, PathBuf::from("/folder/file.bin"));
+    assert!(!entry.directory);
+    assert!(Trash::throw_away(Path::new("/folder"), NamedItem::File("file.bin".to_string())).unwrap().is_none());
+    let folder = Pool::get_root_directory().unwrap().change_directory("folder".to_string()).unwrap().unwrap();
+    assert!(folder.is_empty().unwrap());
+
+    // Now the folder goes too.
+    let folder_entry = Trash::throw_away(Path::new("/"), NamedItem::Directory("folder".to_string())).unwrap().unwrap();
+    assert!(folder_entry.directory);
+    assert_eq!(Trash::list().unwrap(), vec![entry.clone(), folder_entry]);
+
+    // The file comes back, and makes a new folder to live in.
+    assert_eq!(Trash::restore(entry.id).unwrap(), Restored::Done(PathBuf::from("/folder/file.bin")));
+    assert_eq!(Trash::restore(entry.id).unwrap(), Restored::NotInTrash);
+    let folder = Pool::get_root_directory().unwrap().change_directory("folder".to_string()).unwrap().unwrap();
+    let file = folder.find_item(&NamedItem::File("file.bin".to_string())).unwrap().unwrap();
+    assert_eq!(file.read_file(0, bytes.len() as u32).unwrap(), bytes);
+
+    // The old folder is still in the trash, but it can't go back over the new one.
+    assert_eq!(Trash::list().unwrap().len(), 1);
+    let old_folder = Trash::list().unwrap()[0].id;
+    assert_eq!(Trash::restore(old_folder).unwrap(), Restored::PathTaken);
+    assert_eq!(Trash::list().unwrap().len(), 1);
+}
+
+/// The trash is emptied out before the pool grows.
+#[test]
+fn purged_before_new_disks() {
+    let _fs = get_filesystem();
+    let _ = ENABLE_TRASH.set(true);
+    let mut root = Pool::get_root_directory().unwrap();
+    let file = root.new_file("big.bin".to_string()).unwrap();
+    let _ = file.write_file(&vec![7; 507 * 300], 0).unwrap();
+    let _ = Trash::throw_away(Path::new("/"), NamedItem::File("big.bin".to_string())).unwrap().unwrap();
+
+    let disks: u16 = highest_disk();
+    while !Trash::list().unwrap().is_empty() {
+        let _ = Pool::find_and_allocate_pool_blocks(100, false).unwrap();
+    }
+    // Emptying the trash made enough room.
+    assert_eq!(highest_disk(), disks);
+
+    // Nothing left to purge, so now we get a new disk.
+    let _ = Pool::find_and_allocate_pool_blocks(2880, false).unwrap();
+    assert!(highest_disk() > disks);
+}
+
+/// Filling up a pool that never used the trash shouldn't make one.
+#[test]
+fn no_trash_until_used() {
+    let _fs = get_filesystem();
+    let _ = Pool::find_and_allocate_pool_blocks(2880, false).unwrap();
+    let root = Pool::get_root_directory().unwrap();
+    assert!(root.list().unwrap().is_empty());
+}
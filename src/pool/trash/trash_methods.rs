@@ -0,0 +1,318 @@
+// Dumpster diving.
+
+// Imports
+
+use std::path::{
+    Component,
+    Path,
+    PathBuf
+};
+
+use log::{debug, info};
+
+use crate::{
+    error_types::drive::DriveError,
+    filesystem::filesystem_struct::ENABLE_TRASH,
+    pool::{
+        disk::standard_disk::block::{
+            directory::directory_struct::{
+                DirectoryBlock,
+                DirectoryItem,
+                DirectoryItemFlags
+            },
+            inode::inode_struct::InodeTimestamp,
+            io::directory::types::NamedItem
+        },
+        format::format_struct::Feature,
+        pool_actions::pool_struct::Pool
+    }
+};
+
+use super::trash_struct::{
+    Restored,
+    Trash,
+    TrashEntry,
+    RUMMAGING,
+    TRASH_DIR_NAME,
+    TRASH_INDEX_NAME
+};
+
+// Consts
+
+/// Each entry is the id, the deletion time, the directory flag, then the length of the path.
+/// The path itself comes after.
+const ENTRY_OVERHEAD: usize = 8 + 8 + 4 + 1 + 2;
+
+// Implementations
+
+impl Trash {
+    /// Should deletions go to the trash?
+    pub(crate) fn enabled() -> bool {
+        *ENABLE_TRASH.get().unwrap_or(&false)
+    }
+
+    /// Is this the trash directory?
+    pub(crate) fn is_trash(item: &DirectoryItem) -> bool {
+        item.name == TRASH_DIR_NAME
+    }
+
+    /// Move an item into the trash, instead of deleting it.
+    ///
+    /// Directories keep everything inside of them.
+    ///
+    /// Returns None if the item wasn't in that directory.
+    ///
+    /// May swap disks.
+    pub(crate) fn throw_away(parent: &Path, item: NamedItem) -> Result<Option<TrashEntry>, DriveError> {
+        rummage(|| go_throw_away(parent, item))
+    }
+
+    /// Everything in the trash, oldest first.
+    ///
+    /// May swap disks.
+    pub(crate) fn list() -> Result<Vec<TrashEntry>, DriveError> {
+        rummage(go_list)
+    }
+
+    /// Put something from the trash back where it came from.
+    ///
+    /// Any missing directories on the way there are made.
+    ///
+    /// May swap disks.
+    pub(crate) fn restore(id: u64) -> Result<Restored, DriveError> {
+        rummage(|| go_restore(id))
+    }
+
+    /// Delete the oldest thing in the trash for good, to make some room.
+    ///
+    /// Returns false if there was nothing to get rid of, or if this thread is already in the
+    /// middle of messing with the trash.
+    ///
+    /// May swap disks.
+    pub(crate) fn purge_oldest() -> Result<bool, DriveError> {
+        if RUMMAGING.get() {
+            return Ok(false);
+        }
+        rummage(go_purge_oldest)
+    }
+}
+
+impl TrashEntry {
+    /// Turn this entry into bytes for the index.
+    pub(super) fn to_bytes(&self) -> Vec<u8> {
+        let path: &[u8] = self.original.as_os_str().as_encoded_bytes();
+        let mut bytes: Vec<u8> = Vec::with_capacity(ENTRY_OVERHEAD + path.len());
+        bytes.extend_from_slice(&self.id.to_le_bytes());
+        bytes.extend_from_slice(&self.deleted.seconds.to_le_bytes());
+        bytes.extend_from_slice(&self.deleted.nanos.to_le_bytes());
+        bytes.push(self.directory.into());
+        bytes.extend_from_slice(&(path.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(path);
+        bytes
+    }
+
+    /// Read every entry back out of the index.
+    ///
+    /// Stops at anything that doesn't fit, which should only ever be the end.
+    pub(super) fn from_index(bytes: &[u8]) -> Vec<TrashEntry> {
+        let mut entries: Vec<TrashEntry> = Vec::new();
+        let mut offset: usize = 0;
+        while offset + ENTRY_OVERHEAD <= bytes.len() {
+            let id = u64::from_le_bytes(bytes[offset..offset + 8].try_into().expect("8 = 8"));
+            let seconds = u64::from_le_bytes(bytes[offset + 8..offset + 16].try_into().expect("8 = 8"));
+            let nanos = u32::from_le_bytes(bytes[offset + 16..offset + 20].try_into().expect("4 = 4"));
+            let directory = bytes[offset + 20] != 0;
+            let length = u16::from_le_bytes(bytes[offset + 21..offset + 23].try_into().expect("2 = 2")) as usize;
+            offset += ENTRY_OVERHEAD;
+            if offset + length > bytes.len() {
+                break;
+            }
+            let original = PathBuf::from(String::from_utf8_lossy(&bytes[offset..offset + length]).to_string());
+            offset += length;
+            entries.push(TrashEntry {
+                id,
+                deleted: InodeTimestamp {
+                    seconds,
+                    nanos,
+                },
+                directory,
+                original,
+            });
+        }
+        entries
+    }
+
+    /// How this entry is found in the trash directory.
+    fn named_item(&self) -> NamedItem {
+        if self.directory {
+            NamedItem::Directory(self.id.to_string())
+        } else {
+            NamedItem::File(self.id.to_string())
+        }
+    }
+}
+
+// Functions
+
+/// Hold the lid open while working in the trash.
+fn rummage<T>(work: impl FnOnce() -> Result<T, DriveError>) -> Result<T, DriveError> {
+    let previous: bool = RUMMAGING.replace(true);
+    let result = work();
+    RUMMAGING.set(previous);
+    result
+}
+
+/// Open the trash directory, making it if it isn't there yet.
+fn trash_directory() -> Result<DirectoryBlock, DriveError> {
+    let mut root: DirectoryBlock = Pool::get_root_directory()?;
+    if let Some(trash) = root.find_item(&NamedItem::Directory(TRASH_DIR_NAME.to_string()))? {
+        return trash.get_directory_block();
+    }
+    debug!("No trash directory yet, making one...");
+    Pool::use_feature(Feature::Trash);
+    root.make_directory(TRASH_DIR_NAME.to_string())?.get_directory_block()
+}
+
+/// Grab the index file out of the trash, making it if needed.
+fn index_file(trash: &mut DirectoryBlock) -> Result<DirectoryItem, DriveError> {
+    if let Some(index) = trash.find_item(&NamedItem::File(TRASH_INDEX_NAME.to_string()))? {
+        return Ok(index);
+    }
+    trash.new_file(TRASH_INDEX_NAME.to_string())
+}
+
+fn read_index(trash: &mut DirectoryBlock) -> Result<Vec<TrashEntry>, DriveError> {
+    let index: DirectoryItem = index_file(trash)?;
+    let size: u64 = index.get_size()?;
+    Ok(TrashEntry::from_index(&index.read_file(0, size as u32)?))
+}
+
+/// Replace the whole index.
+///
+/// Writes over the old one before cutting it down to size, so it never needs more room than it already has.
+fn write_index(trash: &mut DirectoryBlock, entries: &[TrashEntry]) -> Result<(), DriveError> {
+    let index: DirectoryItem = index_file(trash)?;
+    let bytes: Vec<u8> = entries.iter().flat_map(TrashEntry::to_bytes).collect();
+    if !bytes.is_empty() {
+        let _ = index.write_file(&bytes, 0)?;
+    }
+    index.truncate(bytes.len() as u64)
+}
+
+fn go_throw_away(parent: &Path, item: NamedItem) -> Result<Option<TrashEntry>, DriveError> {
+    let (_, name) = item.debug_strings();
+    let original: PathBuf = parent.join(name);
+    debug!("Throwing `{}` in the trash...", original.display());
+
+    // Make sure the trash is there first, it might need to go in the parent.
+    let mut trash: DirectoryBlock = trash_directory()?;
+    let entries: Vec<TrashEntry> = read_index(&mut trash)?;
+
+    let Some(mut parent) = DirectoryBlock::try_find_directory(Some(parent))? else {
+        return Ok(None);
+    };
+    let Some(mut extracted) = parent.find_and_extract_item(&item)? else {
+        return Ok(None);
+    };
+
+    let entry: TrashEntry = TrashEntry {
+        id: entries.last().map_or(1, |last| last.id + 1),
+        deleted: InodeTimestamp::now(),
+        directory: extracted.flags.contains(DirectoryItemFlags::IsDirectory),
+        original,
+    };
+    extracted.name = entry.id.to_string();
+    extracted.name_length = extracted.name.len() as u8;
+    trash.add_item(&extracted)?;
+
+    let index: DirectoryItem = index_file(&mut trash)?;
+    let _ = index.write_file(&entry.to_bytes(), index.get_size()?)?;
+    debug!("Trashed as entry {}.", entry.id);
+    Ok(Some(entry))
+}
+
+fn go_list() -> Result<Vec<TrashEntry>, DriveError> {
+    let mut trash: DirectoryBlock = trash_directory()?;
+    read_index(&mut trash)
+}
+
+fn go_restore(id: u64) -> Result<Restored, DriveError> {
+    let mut trash: DirectoryBlock = trash_directory()?;
+    let mut entries: Vec<TrashEntry> = read_index(&mut trash)?;
+    let Some(position) = entries.iter().position(|entry| entry.id == id) else {
+        return Ok(Restored::NotInTrash);
+    };
+    let entry: TrashEntry = entries[position].clone();
+    info!("Restoring `{}` from the trash...", entry.original.display());
+
+    let name: String = match entry.original.file_name() {
+        Some(name) => name.to_string_lossy().to_string(),
+        None => return Ok(Restored::PathTaken),
+    };
+
+    // Find our way back, making any directories that have gone missing since.
+    let mut destination: DirectoryBlock = Pool::get_root_directory()?;
+    let parent: &Path = entry.original.parent().unwrap_or(Path::new("/"));
+    for component in parent.components() {
+        let Component::Normal(step) = component else {
+            continue;
+        };
+        let step: String = step.to_string_lossy().to_string();
+        if destination.find_item(&NamedItem::File(step.clone()))?.is_some() {
+            debug!("A file is in the way of `{step}`.");
+            return Ok(Restored::PathTaken);
+        }
+        destination = match destination.find_item(&NamedItem::Directory(step.clone()))? {
+            Some(found) => found.get_directory_block()?,
+            None => destination.make_directory(step)?.get_directory_block()?,
+        };
+    }
+
+    if destination.find_item(&NamedItem::File(name.clone()))?.is_some()
+        || destination.find_item(&NamedItem::Directory(name.clone()))?.is_some() {
+        debug!("Something else is already at `{}`.", entry.original.display());
+        return Ok(Restored::PathTaken);
+    }
+
+    let mut restored: DirectoryItem = trash
+        .find_and_extract_item(&entry.named_item())?
+        .expect("Everything in the index should be in the trash.");
+    restored.name_length = name.len() as u8;
+    restored.name = name;
+    destination.add_item(&restored)?;
+
+    let _ = entries.remove(position);
+    write_index(&mut trash, &entries)?;
+    debug!("Restored.");
+    Ok(Restored::Done(entry.original))
+}
+
+fn go_purge_oldest() -> Result<bool, DriveError> {
+    // This runs whenever the pool fills up, trash or not, so don't make one just to find it empty.
+    let root: DirectoryBlock = Pool::get_root_directory()?;
+    let Some(trash) = root.find_item(&NamedItem::Directory(TRASH_DIR_NAME.to_string()))? else {
+        return Ok(false);
+    };
+    let mut trash: DirectoryBlock = trash.get_directory_block()?;
+    let mut entries: Vec<TrashEntry> = read_index(&mut trash)?;
+    if entries.is_empty() {
+        return Ok(false);
+    }
+    let entry: TrashEntry = entries.remove(0);
+    info!("Purging `{}` from the trash...", entry.original.display());
+
+    if entry.directory {
+        let extracted: DirectoryItem = trash
+            .find_and_extract_item(&entry.named_item())?
+            .expect("Everything in the index should be in the trash.");
+        let mut directory: DirectoryBlock = extracted.get_directory_block()?;
+        directory.delete_contents()?;
+        directory.delete_self(extracted)?;
+    } else {
+        let _ = trash.delete_file(entry.named_item())?;
+    }
+
+    write_index(&mut trash, &entries)?;
+    debug!("Purged.");
+    Ok(true)
+}
@@ -0,0 +1,63 @@
+// One man's trash.
+
+// Imports
+
+use std::{
+    cell::Cell,
+    path::PathBuf
+};
+
+use crate::pool::disk::standard_disk::block::inode::inode_struct::InodeTimestamp;
+
+// Consts
+
+/// What the trash directory is called in the root.
+///
+/// The kernel never hands us names with a slash in them, so nothing outside of the trash can ever
+/// look this up, make something with the same name, or delete it.
+pub(crate) const TRASH_DIR_NAME: &str = "fluster/trash";
+
+/// The file in the trash directory that remembers where everything came from.
+///
+/// Everything else in there is named after its entry number, so this can't clash.
+pub(super) const TRASH_INDEX_NAME: &str = "index";
+
+// Structs, Enums, Flags
+
+/// Deleted items are moved into a hidden directory in the root instead of being freed, so they can
+/// be put back later.
+///
+/// Only used when turned on at mount time. Whatever is in the trash is purged, oldest first, when the
+/// pool runs out of room, before any new disks are asked for.
+pub(crate) struct Trash;
+
+/// Something in the trash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TrashEntry {
+    /// What the item is called in the trash directory. Counts up, so lower is older.
+    pub(crate) id: u64,
+    /// When it was deleted.
+    pub(crate) deleted: InodeTimestamp,
+    /// Is this a directory?
+    pub(crate) directory: bool,
+    /// Where it was before it was deleted.
+    pub(crate) original: PathBuf,
+}
+
+/// What happened when trying to restore something.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Restored {
+    /// Put back where it was.
+    Done(PathBuf),
+    /// Nothing in the trash had that id.
+    NotInTrash,
+    /// Something else is already at the original path, so it was left in the trash.
+    PathTaken,
+}
+
+// Going through the trash moves things around in there, and so can allocating, since that may purge
+// the trash. One at a time please.
+thread_local! {
+    /// Is this thread already digging through the trash?
+    pub(super) static RUMMAGING: Cell<bool> = const { Cell::new(false) };
+}
@@ -3,4 +3,5 @@ pub(crate) mod state;
 pub(crate) mod tui_struct;
 pub mod notify;
 pub(crate) mod tasks;
-pub(crate) mod prompts;
\ No newline at end of file
+pub(crate) mod prompts;
+pub mod prompt_script;
\ No newline at end of file
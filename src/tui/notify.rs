@@ -6,7 +6,7 @@ use std::sync::Mutex;
 use lazy_static::lazy_static;
 use log::error;
 
-use crate::{filesystem::filesystem_struct::USE_TUI, tui::{layout::FlusterTUI, tasks::{ProgressableTask, TaskHandle, TaskType}}};
+use crate::{filesystem::{filesystem_struct::USE_TUI, io_gate::io_gate_struct::IoGate}, tui::{layout::FlusterTUI, tasks::{ProgressableTask, TaskHandle, TaskType}}};
 
 // Global TUI state
 lazy_static! {
@@ -35,6 +35,16 @@ macro_rules! skip_if_tui_disabled {
 // locks and expects, thus we will just abstract that out. If the lock fails, the task state would become
 // desynced, which would almost guarantee a crash. Thus we will exit out if that happens.
 //
+// Tasks are one big chain, so only whoever owns the drive gets to touch them. Lookups answered
+// straight from the cache are over too quickly to be worth showing anyways.
+macro_rules! skip_if_cache_only {
+    () => {
+        if IoGate::cache_only() {
+            return
+        }
+    };
+}
+
 // Karen, because it gets the manager
 macro_rules! karen {
     () => {
@@ -96,7 +106,7 @@ impl NotifyTui {
         manager.state.cache_swaps_saved += 1;
     }
 
-    /// A tier of the cache was flushed to disk.
+    /// The cache was flushed to disk to make room, or all at once.
     pub(crate) fn cache_flushed() {
         skip_if_tui_disabled!();
         let mut manager = karen!();
@@ -131,6 +141,36 @@ impl NotifyTui {
         manager.state.cache_pressure = pressure;
     }
 
+    //
+    // Reading
+    //
+
+    /// Everything the TUI is counting, as `name: value` lines.
+    /// 
+    /// Counters are only kept while the TUI is enabled.
+    pub(crate) fn report() -> String {
+        let manager = karen!();
+        let state = &manager.state;
+        format!(
+            "disk_swap_count: {}\n\
+            disk_blocks_read: {}\n\
+            disk_blocks_written: {}\n\
+            current_disk_in_drive: {}\n\
+            cache_blocks_read: {}\n\
+            cache_blocks_written: {}\n\
+            cache_flushes: {}\n\
+            cache_swaps_saved: {}\n",
+            state.disk_swap_count,
+            state.disk_blocks_read,
+            state.disk_blocks_written,
+            state.current_disk_in_drive,
+            state.cache_blocks_read,
+            state.cache_blocks_written,
+            state.cache_flushes,
+            state.cache_swaps_saved,
+        )
+    }
+
     //
     // Task
     //
@@ -168,6 +208,13 @@ impl NotifyTui {
             return pre_finished;
         }
 
+        // Cache-only lookups don't get tasks, see skip_if_cache_only.
+        if IoGate::cache_only() {
+            let mut pre_finished = TaskHandle::new();
+            pre_finished.task_was_finished_or_canceled = true;
+            return pre_finished;
+        }
+
         // Create the task and make a new handle
         let new_task = ProgressableTask::new(task_type, steps);
         let handle: TaskHandle = TaskHandle::new();
@@ -189,6 +236,7 @@ impl NotifyTui {
     /// Handle required to ensure you actually have a task you're working on
     pub(crate) fn complete_task_step(_handle: &TaskHandle) {
         skip_if_tui_disabled!();
+        skip_if_cache_only!();
         let mut manager = karen!();
         if let Some(task) = manager.state.task.as_mut() {
             task.finish_steps(1);
@@ -205,6 +253,7 @@ impl NotifyTui {
     /// Handle required to ensure you actually have a task you're working on
     pub(crate) fn complete_multiple_task_steps(_handle: &TaskHandle, steps: u64) {
         skip_if_tui_disabled!();
+        skip_if_cache_only!();
         let mut manager = karen!();
         if let Some(task) = manager.state.task.as_mut() {
             task.finish_steps(steps);
@@ -221,6 +270,7 @@ impl NotifyTui {
     /// Handle required to ensure you actually have a task you're working on
     pub(crate) fn add_steps_to_task(_handle: &TaskHandle, steps: u64) {
                 skip_if_tui_disabled!();
+        skip_if_cache_only!();
         let mut manager = karen!();
         if let Some(task) = manager.state.task.as_mut() {
             task.add_work(steps);
@@ -237,6 +287,7 @@ impl NotifyTui {
     /// Handle required to ensure you actually have a task you're working on
     pub(crate) fn finish_task(mut handle: TaskHandle) {
         skip_if_tui_disabled!();
+        skip_if_cache_only!();
         let mut manager = karen!();
         let stored_task = &mut manager
         .state
@@ -259,6 +310,7 @@ impl NotifyTui {
     /// Handle required to ensure you actually have a task you're working on
     pub(crate) fn cancel_task(mut handle: TaskHandle) {
         skip_if_tui_disabled!();
+        skip_if_cache_only!();
         let mut manager = karen!();
         let stored_task = &mut manager
         .state
@@ -280,6 +332,7 @@ impl NotifyTui {
     /// Only used for dropping
     pub(super) fn force_cancel_task() {
         skip_if_tui_disabled!();
+        skip_if_cache_only!();
         let mut manager = karen!();
         let stored_task = &mut manager
         .state
@@ -0,0 +1,4 @@
+pub mod prompt_script_struct;
+pub(crate) mod prompt_script_methods;
+#[cfg(test)]
+mod tests;
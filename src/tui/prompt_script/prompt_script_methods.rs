@@ -0,0 +1,201 @@
+// Robots are very good at pressing enter.
+
+use std::{
+    convert::Infallible,
+    io::{
+        BufRead,
+        BufReader,
+        Read,
+        Write
+    },
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    str::FromStr,
+    sync::{
+        mpsc::{
+            self,
+            RecvTimeoutError
+        },
+        Mutex
+    },
+    thread
+};
+
+use log::{debug, error, warn};
+
+use crate::filesystem::filesystem_struct::PROMPT_TIMEOUT;
+
+use super::prompt_script_struct::{
+    LineProvider,
+    PromptEvent,
+    PromptProvider,
+    PromptSource,
+    ScriptReply,
+    PROMPT_PROVIDER
+};
+
+impl FromStr for PromptSource {
+    type Err = Infallible;
+
+    /// `stdio` (or `-`) means stdin and stdout, anything else is a path to a unix socket.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stdio" | "-" => Ok(PromptSource::Stdio),
+            path => Ok(PromptSource::Socket(PathBuf::from(path))),
+        }
+    }
+}
+
+impl PromptEvent {
+    /// Is a script answering prompts instead of the TUI?
+    pub(crate) fn script_attached() -> bool {
+        PROMPT_PROVIDER.get().is_some()
+    }
+
+    /// Send this event to the script, and wait for it to answer.
+    ///
+    /// Panics if there is no script attached.
+    pub(crate) fn ask_script(&self) -> ScriptReply {
+        let provider = PROMPT_PROVIDER.get().expect("Only ask the script if there is one.");
+        let reply = provider
+            .lock()
+            .expect("Other mutex holders should not panic.")
+            .ask(self, PROMPT_TIMEOUT.get().copied());
+        if reply == ScriptReply::TimedOut {
+            warn!("Prompt script did not answer in time: {}", self.to_line());
+        }
+        reply
+    }
+
+    /// The event as one tab separated line, without the newline.
+    ///
+    /// `enter<TAB>title<TAB>content`, `input<TAB>title<TAB>content`, or `swap<TAB>disk<TAB>title<TAB>content`.
+    pub(crate) fn to_line(&self) -> String {
+        match self {
+            PromptEvent::Enter { title, content } => format!("enter\t{}\t{}", escape(title), escape(content)),
+            PromptEvent::Input { title, content } => format!("input\t{}\t{}", escape(title), escape(content)),
+            PromptEvent::Swap { disk, title, content } => format!("swap\t{disk}\t{}\t{}", escape(title), escape(content)),
+        }
+    }
+}
+
+impl LineProvider {
+    /// Start talking to a script over a pair of streams.
+    ///
+    /// Answers are read on their own thread, so waiting on them can time out.
+    pub(crate) fn new(answers: impl Read + Send + 'static, events: impl Write + Send + 'static) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let _ = thread::spawn(move || {
+            for line in BufReader::new(answers).lines() {
+                let Ok(line) = line else { break };
+                if sender.send(unescape(&line)).is_err() {
+                    // Nobody is listening anymore.
+                    break;
+                }
+            }
+            debug!("Prompt script closed its end.");
+        });
+        LineProvider {
+            events: Box::new(events),
+            answers: receiver,
+        }
+    }
+
+    /// Connect to wherever the prompts should go.
+    pub(crate) fn connect(source: &PromptSource) -> std::io::Result<Self> {
+        match source {
+            PromptSource::Stdio => Ok(LineProvider::new(std::io::stdin(), std::io::stdout())),
+            PromptSource::Socket(path) => {
+                let stream = UnixStream::connect(path)?;
+                Ok(LineProvider::new(stream.try_clone()?, stream))
+            },
+        }
+    }
+}
+
+impl PromptProvider for LineProvider {
+    fn ask(&mut self, event: &PromptEvent, timeout: Option<std::time::Duration>) -> ScriptReply {
+        // Anything still sitting here was a late answer to an earlier prompt that timed out.
+        while let Ok(stale) = self.answers.try_recv() {
+            debug!("Dropping a late answer from the prompt script: {stale}");
+        }
+
+        let line = event.to_line();
+        debug!("Asking the prompt script: {line}");
+        if writeln!(self.events, "{line}").and_then(|_| self.events.flush()).is_err() {
+            error!("Could not send a prompt to the prompt script.");
+            panic!("The prompt script went away, nobody is left to answer prompts!");
+        }
+
+        let answer = match timeout {
+            Some(timeout) => match self.answers.recv_timeout(timeout) {
+                Ok(answer) => Some(answer),
+                Err(RecvTimeoutError::Timeout) => return ScriptReply::TimedOut,
+                Err(RecvTimeoutError::Disconnected) => None,
+            },
+            None => self.answers.recv().ok(),
+        };
+
+        if let Some(answer) = answer {
+            ScriptReply::Answer(answer)
+        } else {
+            error!("The prompt script closed its end while we were waiting on it.");
+            panic!("The prompt script went away, nobody is left to answer prompts!");
+        }
+    }
+}
+
+/// Hand all prompts to a script from now on.
+///
+/// Panics if the script can't be reached, or one is already attached.
+pub(crate) fn attach_prompt_script(source: &PromptSource) {
+    let provider = match LineProvider::connect(source) {
+        Ok(provider) => provider,
+        Err(err) => {
+            error!("Could not reach the prompt script at {source:?}: {err}");
+            panic!("Prompt script is unreachable!");
+        },
+    };
+    if PROMPT_PROVIDER.set(Mutex::new(Box::new(provider))).is_err() {
+        panic!("This should only ever be called once.");
+    }
+}
+
+/// Keep the text of an event on one line, and the tabs out of the way.
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            other => escaped.push(other),
+        }
+    }
+    escaped
+}
+
+/// Undo [escape]. Unknown escapes are left as they are.
+pub(crate) fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut characters = text.chars();
+    while let Some(character) = characters.next() {
+        if character != '\\' {
+            unescaped.push(character);
+            continue;
+        }
+        match characters.next() {
+            Some('\\') => unescaped.push('\\'),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            },
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
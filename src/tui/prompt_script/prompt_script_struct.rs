@@ -0,0 +1,81 @@
+//
+//
+// ======
+// Prompt scripts
+// ======
+//
+//
+
+// Sometimes the person at the keyboard is a robot.
+// Instead of drawing prompts in the TUI, they can be handed to another process as one line events,
+// which answers them with one line of its own.
+
+use std::{
+    io::Write,
+    path::PathBuf,
+    sync::{
+        mpsc::Receiver,
+        Mutex,
+        OnceLock
+    },
+    time::Duration
+};
+
+/// Where prompts get sent when a script is answering them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PromptSource {
+    /// Events go out on stdout, answers come in on stdin.
+    /// The TUI draws on stdout, so it has to be disabled to use this.
+    Stdio,
+    /// Connect to a unix socket that something else is already listening on.
+    Socket(PathBuf),
+}
+
+/// Something Fluster needs a human (or a robot) for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum PromptEvent {
+    /// Read this, then say you're done.
+    Enter {
+        title: String,
+        content: String,
+    },
+    /// Read this, then type something back.
+    Input {
+        title: String,
+        content: String,
+    },
+    /// Put this disk in the drive, then say `ok`.
+    Swap {
+        disk: u16,
+        title: String,
+        content: String,
+    },
+}
+
+/// What came back from the script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ScriptReply {
+    /// The line the script answered with.
+    Answer(String),
+    /// Nothing came back before the timeout.
+    TimedOut,
+}
+
+/// Anything that can answer prompts in place of the TUI.
+pub(crate) trait PromptProvider: Send {
+    /// Hand an event over, and wait for the answer.
+    ///
+    /// Waits forever if there is no timeout.
+    fn ask(&mut self, event: &PromptEvent, timeout: Option<Duration>) -> ScriptReply;
+}
+
+/// Talks to a script one line at a time.
+pub(crate) struct LineProvider {
+    /// Events go here.
+    pub(super) events: Box<dyn Write + Send>,
+    /// Answers come out of here, filled by a thread reading lines from the script.
+    pub(super) answers: Receiver<String>,
+}
+
+/// The script answering prompts, if there is one.
+pub(crate) static PROMPT_PROVIDER: OnceLock<Mutex<Box<dyn PromptProvider>>> = OnceLock::new();
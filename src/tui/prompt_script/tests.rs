@@ -0,0 +1,125 @@
+// Pretend to be the robot.
+// Unwrapping is okay here, since we want unexpected outcomes to fail tests.
+#![allow(clippy::unwrap_used)]
+
+use std::{
+    io::{
+        BufRead,
+        BufReader,
+        Write
+    },
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    thread,
+    time::Duration
+};
+
+use test_log::test;
+
+use super::{
+    prompt_script_methods::{
+        escape,
+        unescape
+    },
+    prompt_script_struct::{
+        LineProvider,
+        PromptEvent,
+        PromptProvider,
+        PromptSource,
+        ScriptReply
+    }
+};
+
+#[test]
+fn events_are_one_line() {
+    let event = PromptEvent::Swap {
+        disk: 4,
+        title: "Please swap disks.".to_string(),
+        content: "Please remove disk 1,\nand insert disk 4".to_string(),
+    };
+    assert_eq!(event.to_line(), "swap\t4\tPlease swap disks.\tPlease remove disk 1,\\nand insert disk 4");
+
+    let event = PromptEvent::Input {
+        title: "Troubleshooting".to_string(),
+        content: "(Y)es/(D)isk restore/(G)ive up".to_string(),
+    };
+    assert_eq!(event.to_line(), "input\tTroubleshooting\t(Y)es/(D)isk restore/(G)ive up");
+}
+
+#[test]
+fn escaping_round_trips() {
+    let text = "tabs\there,\nnew lines,\r\nand a \\n that was already there \\";
+    let escaped = escape(text);
+    assert!(!escaped.contains(['\n', '\t', '\r']));
+    assert_eq!(unescape(&escaped), text);
+}
+
+#[test]
+fn sources_parse() {
+    assert_eq!("stdio".parse::<PromptSource>().unwrap(), PromptSource::Stdio);
+    assert_eq!("-".parse::<PromptSource>().unwrap(), PromptSource::Stdio);
+    assert_eq!(
+        "/run/fluster.sock".parse::<PromptSource>().unwrap(),
+        PromptSource::Socket(PathBuf::from("/run/fluster.sock"))
+    );
+}
+
+#[test]
+fn script_answers_prompts() {
+    let (ours, theirs) = UnixStream::pair().unwrap();
+    let mut provider = LineProvider::new(ours.try_clone().unwrap(), ours);
+
+    // The robot answers every troubleshooter with yes, and every swap with ok.
+    let robot = thread::spawn(move || {
+        let mut writer = theirs.try_clone().unwrap();
+        let mut seen: Vec<String> = Vec::new();
+        for line in BufReader::new(theirs).lines().take(2) {
+            let line = line.unwrap();
+            let answer = if line.starts_with("swap\t") { "ok" } else { "y" };
+            writeln!(writer, "{answer}").unwrap();
+            seen.push(line);
+        }
+        seen
+    });
+
+    let input = PromptEvent::Input {
+        title: "Try again?".to_string(),
+        content: "(Y)es/(D)isk restore/(G)ive up".to_string(),
+    };
+    assert_eq!(provider.ask(&input, None), ScriptReply::Answer("y".to_string()));
+
+    let swap = PromptEvent::Swap {
+        disk: 7,
+        title: "New disk.".to_string(),
+        content: "Insert disk 7".to_string(),
+    };
+    assert_eq!(provider.ask(&swap, Some(Duration::from_secs(10))), ScriptReply::Answer("ok".to_string()));
+
+    let seen = robot.join().unwrap();
+    assert_eq!(seen, vec![input.to_line(), swap.to_line()]);
+}
+
+#[test]
+fn slow_scripts_time_out() {
+    let (ours, theirs) = UnixStream::pair().unwrap();
+    let mut provider = LineProvider::new(ours.try_clone().unwrap(), ours);
+    let event = PromptEvent::Enter {
+        title: "Hello?".to_string(),
+        content: "Anybody home?".to_string(),
+    };
+    assert_eq!(provider.ask(&event, Some(Duration::from_millis(50))), ScriptReply::TimedOut);
+
+    // A late answer to that prompt should not be taken as the answer to the next one.
+    let mut writer = theirs.try_clone().unwrap();
+    writeln!(writer, "late").unwrap();
+    thread::sleep(Duration::from_millis(100));
+    let robot = thread::spawn(move || {
+        let mut lines = BufReader::new(theirs).lines();
+        // Skip the first prompt, answer the second.
+        let _ = lines.next().unwrap().unwrap();
+        let _ = lines.next().unwrap().unwrap();
+        writeln!(writer, "on time").unwrap();
+    });
+    assert_eq!(provider.ask(&event, Some(Duration::from_secs(10))), ScriptReply::Answer("on time".to_string()));
+    robot.join().unwrap();
+}
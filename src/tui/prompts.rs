@@ -12,6 +12,7 @@ use tui_textarea::TextArea;
 
 use crate::error_types::drive::DriveError;
 use crate::filesystem::filesystem_struct::FLOPPY_PATH;
+use crate::tui::prompt_script::prompt_script_struct::{PromptEvent, ScriptReply};
 use crate::{filesystem::filesystem_struct::USE_TUI, tui::notify::TUI_MANAGER};
 
 #[derive(Debug)]
@@ -49,6 +50,13 @@ impl TuiPrompt<'_> {
     /// 
     /// This will block until the user presses enter.
     pub(crate) fn prompt_enter(title: String, content: String, flash: bool) {
+        // If a script is answering prompts, it gets this one instead of the user.
+        // Nothing we can do about a script that doesn't answer, so we just carry on.
+        if PromptEvent::script_attached() {
+            let _ = PromptEvent::Enter { title, content }.ask_script();
+            return;
+        }
+
         // We need the channel even if we arent getting a string back, since we wanna
         // block the caller thread without having to spin in a loop lockin stuff.
         let (response_tx, response_rx) = oneshot::channel();
@@ -92,6 +100,14 @@ impl TuiPrompt<'_> {
     /// 
     /// This will block until the user responds.
     pub(crate) fn prompt_input(title: String, content: String, flash: bool) -> String {
+        // Scripts that don't answer in time get treated like the user hit enter on an empty box.
+        if PromptEvent::script_attached() {
+            return match (PromptEvent::Input { title, content }.ask_script()) {
+                ScriptReply::Answer(answer) => answer,
+                ScriptReply::TimedOut => String::new(),
+            };
+        }
+
         // Get the channel for communicating the result of the prompt
         let (response_tx, response_rx) = oneshot::channel();
 
@@ -141,7 +157,20 @@ impl TuiPrompt<'_> {
     /// This function also assumes that the floppy drive is a block device, and checks agains /sys/ to deduce that.
     /// 
     /// Will this work on MacOS? Probably not. Def wont work on windows.
-    pub(crate) fn prompt_wait_for_disk_swap(title: String, content: String, flash: bool) -> Result<(), DriveError> {
+    /// 
+    /// If a script is answering prompts, we take its word that `disk` was put in the drive instead.
+    pub(crate) fn prompt_wait_for_disk_swap(title: String, content: String, flash: bool, disk: u16) -> Result<(), DriveError> {
+        if PromptEvent::script_attached() {
+            return match (PromptEvent::Swap { disk, title, content }.ask_script()) {
+                ScriptReply::Answer(answer) if answer.trim() == "ok" => Ok(()),
+                ScriptReply::Answer(answer) => {
+                    // Same as nobody swapping the disk, the troubleshooter can sort it out.
+                    debug!("Prompt script did not swap to disk {disk}: {answer}");
+                    Err(DriveError::TakingTooLong)
+                },
+                ScriptReply::TimedOut => Err(DriveError::TakingTooLong),
+            };
+        }
 
         // We dont actually care about this response at all, nothing ever gets sent
         // I'm just too lazy to remove the requirement for it right now.
@@ -29,7 +29,7 @@ pub(super) struct FlusterTUIState {
     /// The current hit rate of the cache (Only needs to be updated on
     /// disk swap.)
     pub(super) cache_hit_rate: f64,
-    /// The current pressure of the cache, aka how full tier 0 is.
+    /// The current pressure of the cache, aka how full the part that gets emptied first is.
     pub(super) cache_pressure: f64,
     /// Number of times we went to read a block, but got it from
     /// the cache instead of hitting the disk
@@ -37,7 +37,7 @@ pub(super) struct FlusterTUIState {
     /// Number of times we went to write a block, but were able to
     /// temporarily store it in the cache.
     pub(super) cache_blocks_written: u64,
-    /// Number of times we've flushed tier 0 of the cache to disk.
+    /// Number of times we've flushed the cache to disk.
     pub(super) cache_flushes: u64,
     /// Number of times we avoided swapping disks by doing a cached operation
     pub(super) cache_swaps_saved: u64,
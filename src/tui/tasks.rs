@@ -54,6 +54,8 @@ pub(crate) enum TaskType {
     FilesystemOpenFile(String),
     /// Includes the name of the file.
     FilesystemTruncateFile(String),
+    /// Includes the name of the file.
+    FilesystemAllocateFile(String),
     /// Includes name of new file duh
     FilesystemCreateFile(String),
     /// Includes the name of the new directory.
@@ -70,7 +72,7 @@ pub(crate) enum TaskType {
     WipeDisk,
     RestoreDisk,
     FlushCurrentDisk,
-    FlushTier,
+    FlushCache,
     FileReadBytes,
     FileWriteBytes,
     /// Includes number of requested blocks.
@@ -85,6 +87,11 @@ pub(crate) enum TaskType {
     /// Includes the name of the item we're looking for
     FindItemInDirectory(String),
     CreateDirectoryItem,
+    /// Includes the number of the disk being scrubbed.
+    ScrubDisk(u16),
+    /// Includes the format version the disks are being upgraded to.
+    MigrateDisks(u16),
+    ImportFiles,
 }
 
 /// When we start a task, we are promising to finish it. We need a way to know
@@ -157,7 +164,7 @@ impl TaskInfo {
             TaskType::ListingDirectory => "Listing a directory...".to_string(),
             TaskType::CreateDirectoryItem => "Creating new directory item...".to_string(),
             TaskType::FlushCurrentDisk => "Flushing current disk...".to_string(),
-            TaskType::FlushTier => "Flushing a tier of cache...".to_string(),
+            TaskType::FlushCache => "Flushing cache...".to_string(),
             TaskType::FilesystemReadFile(name) => {
                 format!("Reading from file \"{name}\"...")
             },
@@ -167,6 +174,9 @@ impl TaskInfo {
             TaskType::FilesystemTruncateFile(name) => {
                 format!("Truncating \"{name}\"...")
             },
+            TaskType::FilesystemAllocateFile(name) => {
+                format!("Allocating space in \"{name}\"...")
+            },
             TaskType::PoolAllocateBlocks(number) => {
                 format!("Allocating {number} blocks across disk pool...")
             },
@@ -203,6 +213,9 @@ impl TaskInfo {
             TaskType::FileReadBytes => "Reading bytes from file...".to_string(),
             TaskType::FileWriteBytes => "Writing bytes to file...".to_string(),
             TaskType::RestoreDisk => "Restoring a disk from backup...".to_string(),
+            TaskType::ScrubDisk(number) => format!("Scrubbing disk {number}..."),
+            TaskType::MigrateDisks(version) => format!("Upgrading disks to format version {version}..."),
+            TaskType::ImportFiles => "Copying files from a FAT12 disk...".to_string(),
         }
     }
 
@@ -34,7 +34,8 @@ pub fn start_filesystem() -> FuseMT<FlusterFS> {
     let floppy_drive: PathBuf = PathBuf::new(); // This is never read since we are using temporary disks.
     // Disable backups, since we don't use those in tests for obvious reasons.
     let fs_options = FilesystemOptions::new(Some(temp_dir.path().to_path_buf()), floppy_drive, Some(false), false);
-    let started = FlusterFS::start(&fs_options);
+    // Nothing to mount on a blank disk, so the pool gets made first.
+    let started = FlusterFS::mkfs(&fs_options, 1);
     // MT thing that is actually used for mounting.
     // Zero threads for fully sync.
     fuse_mt::FuseMT::new(started, 0)